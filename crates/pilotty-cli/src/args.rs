@@ -1,6 +1,7 @@
 //! CLI argument parsing with clap derive macros.
 
 use clap::{Parser, Subcommand, ValueEnum};
+use pilotty_core::protocol::{ScreenRegion, Signal};
 
 const SESSION_HELP: &str = "Target session by name or ID [default: default]";
 
@@ -25,7 +26,12 @@ Examples:
   pilotty spawn vim file.txt            # Command with arguments
   pilotty spawn --name editor vim       # Named session for easy reference
   pilotty spawn --cwd /tmp bash         # Start bash in /tmp directory
-  pilotty spawn bash -c 'echo hello'    # Shell command with args")]
+  pilotty spawn bash -c 'echo hello'    # Shell command with args
+  pilotty spawn --record run.cast bash  # Record output to an asciicast file
+  pilotty spawn -e LANG=C.UTF-8 htop    # Set environment variables
+  pilotty spawn --env-clear -e HOME=/tmp bash  # Start from an empty environment
+  pilotty spawn --term vt100 bash       # Override TERM (default: xterm-256color)
+  pilotty spawn --cols 120 --rows 40 htop  # Start at a specific size")]
     Spawn(SpawnArgs),
 
     /// Kill a session and its child process
@@ -44,7 +50,12 @@ Wait for change:
   pilotty key Enter
   pilotty snapshot --await-change $HASH           # Block until screen changes
   pilotty snapshot --await-change $HASH --settle 100  # Wait for 100ms stability
-  pilotty snapshot --settle 100 --strict  # Exit nonzero on deadline/exit")]
+  pilotty snapshot --settle 100 --strict  # Exit nonzero on deadline/exit
+  pilotty snapshot --settle 100 --ignore 0  # Settle despite a clock on row 0
+  pilotty snapshot --settle 100 --ignore-regex '\\d+s elapsed'  # Ignore a counter
+
+Scrollback:
+  pilotty snapshot --scrollback 200     # Include the last 200 off-screen lines")]
     Snapshot(SnapshotArgs),
 
     /// Type text at the current cursor position
@@ -58,12 +69,29 @@ Examples:
     )]
     Type(TypeArgs),
 
+    /// Paste text, as a terminal would, into the current session
+    #[command(after_help = "\
+Text comes from the argument, --file, or stdin. It is wrapped in bracketed
+paste markers (ESC[200~ ... ESC[201~) when the application enabled bracketed
+paste, so editors and REPLs take it as one block instead of auto-indenting or
+running it line by line; otherwise it is typed as is. Unlike 'type', escapes
+like \\n are not interpreted.
+
+Examples:
+  pilotty paste 'print(\"hi\")'          # Paste literal text
+  pilotty paste --file snippet.py -s repl  # Paste a file into a specific session
+  git diff | pilotty paste -s editor    # Paste from stdin")]
+    Paste(PasteArgs),
+
     /// Send a key, key combination, or key sequence
     #[command(after_long_help = "\
 Supported Keys:
   Navigation:  Enter, Tab, Escape, Backspace, Space, Delete, Insert
   Arrows:      Up, Down, Left, Right, Home, End, PageUp, PageDown
-  Function:    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12
+  Function:    F1 ... F24
+  Keypad:      KP0 ... KP9, KPEnter, KPPlus, KPMinus, KPMultiply, KPDivide,
+               KPDecimal, KPComma, KPEqual
+  System:      Menu, Pause, PrintScreen, ScrollLock
   Modifiers:   Ctrl+<key>, Alt+<key>
 
 Key Sequences:
@@ -80,16 +108,49 @@ Examples:
   pilotty key \"a b c\" --delay 50        # Send a, b, c with 50ms delay between")]
     Key(KeyArgs),
 
-    /// Click at a specific row and column coordinate
+    /// Click at a row and column coordinate, on visible text, or on an element ref
     #[command(after_help = "\
-Click at a specific position in the terminal using 0-indexed coordinates.
+Click at a specific position in the terminal using 0-indexed coordinates,
+let the daemon find visible text and click its center, or click the center
+of an element by the ref a compact snapshot reported for it.
 Use 'pilotty snapshot' to see cursor position and terminal dimensions.
 
 Examples:
   pilotty click 10 5                    # Click at row 10, column 5
-  pilotty click -s editor 5 20          # Click in a specific session")]
+  pilotty click -s editor 5 20          # Click in a specific session
+  pilotty click @e3                     # Click element @e3 from 'snapshot --format compact'
+  pilotty click --text Save             # Click the first 'Save' on screen
+  pilotty click --text OK --nth 2       # Click the second 'OK'
+  pilotty click --text '<\\s*OK\\s*>' --regex  # Click a regex match
+  pilotty click --button right 4 12     # Right-click, e.g. for a context menu
+  pilotty click --count 2 --text main   # Double-click a word
+  pilotty click --ctrl --text README    # Ctrl+click")]
     Click(ClickArgs),
 
+    /// Press a mouse button on one cell, drag to another, and release
+    #[command(after_help = "\
+Sends a press at the first row and column, motion to the second, and a
+release there: select text, resize splits, or drag scrollbars. The motion
+report is only sent when the application tracks mouse motion.
+
+Examples:
+  pilotty drag 2 0 4 30                 # Select from row 2, col 0 to row 4, col 30
+  pilotty drag --shift 2 0 4 30         # Shift+drag, e.g. to extend a selection
+  pilotty drag --button middle 0 40 10 40  # Drag with the middle button")]
+    Drag(DragArgs),
+
+    /// Move the mouse pointer over a cell, visible text, or element ref
+    #[command(after_help = "\
+Sends a motion report with no button held, for hover highlights and tooltips.
+Only applications with any-event mouse tracking (snapshot modes.mouse_tracking
+\"any_motion\") receive it.
+
+Examples:
+  pilotty hover 3 10                    # Hover over row 3, column 10
+  pilotty hover --text Help             # Hover over the first 'Help'
+  pilotty hover @e2                     # Hover over element @e2")]
+    Hover(HoverArgs),
+
     /// Scroll the terminal up or down
     Scroll(ScrollArgs),
 
@@ -109,13 +170,68 @@ Examples:
     /// Resize the terminal
     Resize(ResizeArgs),
 
-    /// Wait for text to appear on screen
+    /// Send a signal to a session's process or foreground process group
+    #[command(after_help = "\
+Signals: SIGHUP SIGINT SIGQUIT SIGKILL SIGUSR1 SIGUSR2 SIGALRM SIGTERM
+         SIGCONT SIGSTOP SIGTSTP SIGTTIN SIGTTOU SIGWINCH
+         (case-insensitive, SIG prefix optional)
+
+Examples:
+  pilotty signal SIGTERM                # Ask the default session's process to exit
+  pilotty signal -s app usr1            # Send SIGUSR1 to a named session
+  pilotty signal --target foreground INT  # Interrupt the foreground job, like Ctrl+C
+  pilotty signal --target foreground TSTP # Suspend the foreground job, like Ctrl+Z")]
+    Signal(SignalArgs),
+
+    /// Start or stop an asciicast v2 recording of a running session
+    #[command(after_help = "\
+Examples:
+  pilotty record start run.cast         # Record the default session
+  pilotty record start run.cast --input # Also record keys and typed text
+  pilotty record stop                   # Close the recording")]
+    Record(RecordArgs),
+
+    /// Stream session events as JSON lines until interrupted
+    #[command(after_help = "\
+Prints one JSON object per line: first 'subscribed', then an 'event' for
+each spawn, screen revision (with changed rows), resize, bell, title change,
+and exit (with exit evidence). Raw output chunks are opt-in.
+
+Examples:
+  pilotty subscribe                     # Every session, including new ones
+  pilotty subscribe -s editor -s build  # Only these sessions; ends when both exit
+  pilotty subscribe -s editor --output  # Include raw output bytes")]
+    Subscribe(SubscribeArgs),
+
+    /// Replay a recording or raw ANSI dump offline into screen snapshots
+    #[command(after_help = "\
+Replays an asciicast v2 recording (from 'spawn --record' or 'record start')
+or a raw 'output --ansi' dump without a daemon. Screens print as ScreenState
+JSON lines with the time and event that produced them.
+
+Examples:
+  pilotty replay run.cast               # Final screen
+  pilotty replay run.cast --at 12.5     # Screen 12.5 seconds in
+  pilotty replay run.cast --markers     # Screen at every resize/input/marker
+  pilotty replay run.cast --frames      # Screen after every output event
+  pilotty replay run.cast --format text # Human-readable screens
+  pilotty replay out.ansi --cols 120 --rows 40  # Raw dump at a known size
+  pilotty replay run.cast --history     # Readable history, like 'output'")]
+    Replay(ReplayArgs),
+
+    /// Wait for text to appear on screen, or for a process condition
     #[command(after_help = "\
 Examples:
   pilotty wait-for 'Ready'              # Wait for literal text
   pilotty wait-for -r 'error|warning'   # Wait for regex pattern
   pilotty wait-for -t 5000 'Done'       # Wait up to 5 seconds
-  pilotty wait-for -s editor '~'        # Wait in specific session")]
+  pilotty wait-for -s editor '~'        # Wait in specific session
+  pilotty wait-for --absent 'Loading'   # Wait for text to disappear
+  pilotty wait-for -p Success -p Error  # Wait for either; result has pattern_index
+  pilotty wait-for --all -p Name -p Email  # Wait until both are on screen
+  pilotty wait-for --exit -t 600000     # Wait for the process to exit
+  pilotty wait-for --idle 2000          # Wait for 2s without output
+  pilotty wait-for --foreground-change  # Wait for a job to start or finish in a shell")]
     WaitFor(WaitForArgs),
 
     /// Show an end-to-end usage example
@@ -124,6 +240,17 @@ Examples:
     /// Start the daemon process (usually auto-started)
     Daemon,
 
+    /// Serve the Model Context Protocol over stdio
+    #[command(after_help = "\
+Speaks MCP (JSON-RPC 2.0, one message per line) on stdin/stdout and exposes
+spawn, snapshot, type, paste, key, click, mouse_click, drag, hover, scroll,
+wait_for, output, status, and kill as tools. Calls go to the daemon, which
+starts automatically.
+
+Example MCP host configuration:
+  { \"mcpServers\": { \"pilotty\": { \"command\": \"pilotty\", \"args\": [\"mcp\"] } } }")]
+    Mcp,
+
     /// Stop the daemon process
     Stop,
 }
@@ -150,6 +277,41 @@ pub struct SpawnArgs {
     /// Maximum raw output bytes retained for this session
     #[arg(long, value_name = "BYTES")]
     pub retain_bytes: Option<u64>,
+
+    /// Record the session to a new asciicast v2 file
+    #[arg(long, value_name = "PATH")]
+    pub record: Option<String>,
+
+    /// Also record input sent to the session
+    #[arg(long, requires = "record")]
+    pub record_input: bool,
+
+    /// Set an environment variable for the process (repeatable)
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = parse_env_var)]
+    pub env: Vec<(String, String)>,
+
+    /// Start from an empty environment instead of the daemon's (PATH and TERM are still set)
+    #[arg(long)]
+    pub env_clear: bool,
+
+    /// Terminal type for the process [default: xterm-256color]
+    #[arg(long, value_name = "NAME")]
+    pub term: Option<String>,
+
+    /// Initial terminal width [default: $PILOTTY_COLS or 80]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub cols: Option<u16>,
+
+    /// Initial terminal height [default: $PILOTTY_ROWS or 24]
+    #[arg(long, value_parser = clap::value_parser!(u16).range(1..))]
+    pub rows: Option<u16>,
+}
+
+fn parse_env_var(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{value}'")),
+    }
 }
 
 #[derive(Debug, clap::Args)]
@@ -201,18 +363,51 @@ pub struct SnapshotArgs {
     /// Exit 3 on deadline or 4 on session exit, after printing capture evidence
     #[arg(long)]
     pub strict: bool,
+
+    /// Return only rows changed since this revision (from a previous snapshot)
+    #[arg(long, value_name = "REVISION")]
+    pub since: Option<u64>,
+
+    /// Capture, hash, and settle only ROWS[,COLS], e.g. -1 or 2:,0:39
+    #[arg(long, value_name = "REGION", allow_hyphen_values = true)]
+    pub region: Option<ScreenRegion>,
+
+    /// Leave ROWS[,COLS] out of content_hash and settle detection (repeatable)
+    #[arg(long, value_name = "REGION", allow_hyphen_values = true)]
+    pub ignore: Vec<ScreenRegion>,
+
+    /// Leave text matching this regex out of content_hash and settle detection (repeatable)
+    #[arg(long, value_name = "REGEX")]
+    pub ignore_regex: Vec<String>,
+
+    /// Include up to N lines that scrolled off the top, numbered -N..-1
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with_all = ["since", "region"])]
+    pub scrollback: u32,
 }
 
 #[derive(Debug, Clone, Copy, ValueEnum)]
 pub enum SnapshotFormat {
     /// Full JSON with all metadata
     Full,
-    /// Compact format with inline refs
+    /// Full JSON plus styled spans (colors, bold, inverse)
+    Styled,
+    /// JSON with detected UI elements and their refs (@e1, ...), no text
     Compact,
     /// Plain text only
     Text,
 }
 
+impl From<SnapshotFormat> for pilotty_core::protocol::SnapshotFormat {
+    fn from(format: SnapshotFormat) -> Self {
+        match format {
+            SnapshotFormat::Full => Self::Full,
+            SnapshotFormat::Styled => Self::Styled,
+            SnapshotFormat::Compact => Self::Compact,
+            SnapshotFormat::Text => Self::Text,
+        }
+    }
+}
+
 #[derive(Debug, clap::Args)]
 pub struct TypeArgs {
     /// Text to type
@@ -222,6 +417,19 @@ pub struct TypeArgs {
     pub session: Option<String>,
 }
 
+#[derive(Debug, clap::Args)]
+pub struct PasteArgs {
+    /// Text to paste [default: read piped stdin]
+    pub text: Option<String>,
+
+    /// Paste the contents of a file
+    #[arg(long, conflicts_with = "text")]
+    pub file: Option<String>,
+
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
 #[derive(Debug, clap::Args)]
 pub struct KeyArgs {
     /// Key, combo, or sequence to send (e.g., Enter, Ctrl+C, "Ctrl+X m")
@@ -237,16 +445,141 @@ pub struct KeyArgs {
 
 #[derive(Debug, clap::Args)]
 pub struct ClickArgs {
-    /// Row coordinate (0-indexed)
-    pub row: u16,
+    /// Row coordinate (0-indexed), or an element ref like @e3 from a compact snapshot
+    #[arg(
+        value_name = "ROW|REF",
+        value_parser = parse_click_position,
+        required_unless_present = "text",
+        conflicts_with = "text"
+    )]
+    pub row: Option<ClickPosition>,
+
+    /// Column coordinate (0-indexed); omitted for an element ref
+    pub col: Option<u16>,
+
+    /// Click the center of this visible text instead of a coordinate
+    #[arg(long)]
+    pub text: Option<String>,
+
+    /// Treat --text as a regex
+    #[arg(short, long, requires = "text")]
+    pub regex: bool,
+
+    /// Which match to click, counting from 1 in reading order
+    #[arg(long, default_value_t = 1, requires = "text")]
+    pub nth: u32,
+
+    /// Mouse button to click with
+    #[arg(long, value_enum, default_value_t = MouseButton::Left)]
+    pub button: MouseButton,
+
+    /// Clicks in a row: 2 for a double click, 3 for a triple click
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u8).range(1..=3))]
+    pub count: u8,
 
-    /// Column coordinate (0-indexed)
-    pub col: u16,
+    #[command(flatten)]
+    pub modifiers: MouseModifierArgs,
 
     #[arg(short, long, help = SESSION_HELP)]
     pub session: Option<String>,
 }
 
+/// First positional argument of `click` and `hover`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClickPosition {
+    Row(u16),
+    /// An element ref such as `@e3`.
+    Ref(String),
+}
+
+fn parse_click_position(value: &str) -> Result<ClickPosition, String> {
+    if value.starts_with('@') {
+        return Ok(ClickPosition::Ref(value.to_string()));
+    }
+    value
+        .parse()
+        .map(ClickPosition::Row)
+        .map_err(|_| format!("expected a row number or an element ref like @e3, got '{value}'"))
+}
+
+#[derive(Debug, clap::Args)]
+pub struct DragArgs {
+    /// Row to press at (0-indexed)
+    pub from_row: u16,
+
+    /// Column to press at (0-indexed)
+    pub from_col: u16,
+
+    /// Row to release at (0-indexed)
+    pub to_row: u16,
+
+    /// Column to release at (0-indexed)
+    pub to_col: u16,
+
+    /// Mouse button to drag with
+    #[arg(long, value_enum, default_value_t = MouseButton::Left)]
+    pub button: MouseButton,
+
+    #[command(flatten)]
+    pub modifiers: MouseModifierArgs,
+
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HoverArgs {
+    /// Row coordinate (0-indexed), or an element ref like @e3 from a compact snapshot
+    #[arg(
+        value_name = "ROW|REF",
+        value_parser = parse_click_position,
+        required_unless_present = "text",
+        conflicts_with = "text"
+    )]
+    pub row: Option<ClickPosition>,
+
+    /// Column coordinate (0-indexed); omitted for an element ref
+    pub col: Option<u16>,
+
+    /// Hover over the center of this visible text instead of a coordinate
+    #[arg(long)]
+    pub text: Option<String>,
+
+    /// Treat --text as a regex
+    #[arg(short, long, requires = "text")]
+    pub regex: bool,
+
+    /// Which match to hover over, counting from 1 in reading order
+    #[arg(long, default_value_t = 1, requires = "text")]
+    pub nth: u32,
+
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
+/// Modifier keys to hold during a click or drag.
+#[derive(Debug, clap::Args)]
+pub struct MouseModifierArgs {
+    /// Hold Shift
+    #[arg(long)]
+    pub shift: bool,
+
+    /// Hold Ctrl
+    #[arg(long)]
+    pub ctrl: bool,
+
+    /// Hold Alt
+    #[arg(long)]
+    pub alt: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
 #[derive(Debug, clap::Args)]
 pub struct ScrollArgs {
     /// Direction to scroll
@@ -279,10 +612,133 @@ pub struct ResizeArgs {
     pub session: Option<String>,
 }
 
+#[derive(Debug, clap::Args)]
+pub struct SignalArgs {
+    /// Signal name, e.g. SIGINT, TERM, or usr1
+    pub signal: Signal,
+
+    /// Deliver to the spawned process or the PTY's foreground process group
+    #[arg(short, long, value_enum, default_value = "child")]
+    pub target: SignalTarget,
+
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum SignalTarget {
+    /// The process the session spawned
+    Child,
+    /// Whatever job currently owns the terminal, like a keyboard signal
+    Foreground,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RecordArgs {
+    #[command(subcommand)]
+    pub action: RecordAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum RecordAction {
+    /// Start recording to a new file (never overwrites)
+    Start(RecordStartArgs),
+
+    /// Stop recording and close the file
+    Stop(RecordStopArgs),
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RecordStartArgs {
+    /// Path of the asciicast v2 file to create
+    pub path: String,
+
+    /// Also record input sent to the session
+    #[arg(long)]
+    pub input: bool,
+
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct RecordStopArgs {
+    #[arg(short, long, help = SESSION_HELP)]
+    pub session: Option<String>,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct SubscribeArgs {
+    /// Follow only these sessions by name or ID (repeatable) [default: all]
+    #[arg(short, long = "session", value_name = "SESSION")]
+    pub sessions: Vec<String>,
+
+    /// Also stream raw output chunks
+    #[arg(long)]
+    pub output: bool,
+}
+
+#[derive(Debug, clap::Args)]
+pub struct ReplayArgs {
+    /// Asciicast v2 recording or raw ANSI dump to replay
+    pub file: String,
+
+    /// Emit the screen as it was this many seconds into the recording
+    #[arg(long, value_name = "SECONDS", conflicts_with_all = ["markers", "frames"])]
+    pub at: Option<f64>,
+
+    /// Emit the screen at every resize, input, and marker event
+    #[arg(long, conflicts_with = "frames")]
+    pub markers: bool,
+
+    /// Emit the screen after every output event
+    #[arg(long)]
+    pub frames: bool,
+
+    /// Screen format
+    #[arg(short, long, value_enum, default_value_t = SnapshotFormat::Full)]
+    pub format: SnapshotFormat,
+
+    /// Print readable output history instead of screens (honors --at)
+    #[arg(long, conflicts_with_all = ["markers", "frames", "format"])]
+    pub history: bool,
+
+    /// Terminal width for raw dumps (recordings carry their own size)
+    #[arg(long, default_value_t = 80, value_parser = clap::value_parser!(u16).range(1..))]
+    pub cols: u16,
+
+    /// Terminal height for raw dumps (recordings carry their own size)
+    #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(u16).range(1..))]
+    pub rows: u16,
+}
+
+/// Screen-text options that process conditions cannot be combined with.
+const TEXT_WAIT_ARGS: [&str; 6] = [
+    "pattern",
+    "patterns",
+    "all",
+    "absent",
+    "region",
+    "scrollback",
+];
+
 #[derive(Debug, clap::Args)]
 pub struct WaitForArgs {
     /// Text or regex pattern to wait for
-    pub pattern: String,
+    #[arg(required_unless_present_any = ["patterns", "exit", "idle", "foreground_change"])]
+    pub pattern: Option<String>,
+
+    /// Another pattern to wait for (repeatable); any one matches unless --all
+    #[arg(short = 'p', long = "pattern", id = "patterns", value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /// Require every pattern to match (or, with --absent, to be gone) at once
+    #[arg(long)]
+    pub all: bool,
+
+    /// Wait for the pattern to disappear from the screen instead
+    #[arg(long)]
+    pub absent: bool,
 
     /// Timeout in milliseconds
     #[arg(short, long, default_value_t = 30000)]
@@ -292,6 +748,31 @@ pub struct WaitForArgs {
     #[arg(short, long)]
     pub regex: bool,
 
+    /// Match only inside ROWS[,COLS] of the screen, e.g. -1 for the last row
+    #[arg(long, value_name = "REGION", allow_hyphen_values = true)]
+    pub region: Option<ScreenRegion>,
+
+    /// Also search up to N lines that scrolled off the top of the screen
+    #[arg(long, value_name = "N", default_value_t = 0, conflicts_with = "region")]
+    pub scrollback: u32,
+
+    /// Wait until the session's process exits, reporting how it ended
+    #[arg(long, group = "process_condition", conflicts_with_all = TEXT_WAIT_ARGS)]
+    pub exit: bool,
+
+    /// Wait until the session has produced no output for this many milliseconds
+    #[arg(
+        long,
+        value_name = "MS",
+        group = "process_condition",
+        conflicts_with_all = TEXT_WAIT_ARGS
+    )]
+    pub idle: Option<u64>,
+
+    /// Wait until a different process takes over the terminal foreground
+    #[arg(long, group = "process_condition", conflicts_with_all = TEXT_WAIT_ARGS)]
+    pub foreground_change: bool,
+
     #[arg(short, long, help = SESSION_HELP)]
     pub session: Option<String>,
 }
@@ -328,9 +809,11 @@ pilotty list-sessions
 
 #[cfg(test)]
 mod tests {
+    use crate::args::{
+        Cli, ClickPosition, Commands, MouseButton, RecordAction, RecordArgs, SignalTarget,
+    };
     use clap::Parser;
-
-    use crate::args::{Cli, Commands};
+    use pilotty_core::protocol::Signal;
 
     #[test]
     fn test_spawn_parses_hyphenated_args() {
@@ -377,6 +860,350 @@ mod tests {
         }
     }
 
+    #[test]
+    fn click_parses_coordinates_ref_or_text_target() {
+        let cli = Cli::parse_from(["pilotty", "click", "3", "7"]);
+        match cli.command {
+            Commands::Click(args) => {
+                assert_eq!(
+                    (args.row, args.col, args.text),
+                    (Some(ClickPosition::Row(3)), Some(7), None)
+                );
+            }
+            _ => panic!("Expected click command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "click", "--text", "Save", "--nth", "2"]);
+        match cli.command {
+            Commands::Click(args) => {
+                assert_eq!(args.text.as_deref(), Some("Save"));
+                assert_eq!(args.nth, 2);
+                assert!(args.row.is_none());
+            }
+            _ => panic!("Expected click command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "click", "@e3"]);
+        match cli.command {
+            Commands::Click(args) => {
+                assert_eq!(args.row, Some(ClickPosition::Ref("@e3".to_string())));
+                assert!(args.col.is_none());
+            }
+            _ => panic!("Expected click command"),
+        }
+
+        assert!(Cli::try_parse_from(["pilotty", "click"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "click", "e3"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "click", "3", "7", "--text", "Save"]).is_err());
+    }
+
+    #[test]
+    fn click_drag_and_hover_take_buttons_counts_and_modifiers() {
+        let cli = Cli::parse_from([
+            "pilotty", "click", "--button", "right", "--count", "2", "--ctrl", "3", "7",
+        ]);
+        match cli.command {
+            Commands::Click(args) => {
+                assert_eq!(args.button, MouseButton::Right);
+                assert_eq!(args.count, 2);
+                assert!(args.modifiers.ctrl && !args.modifiers.shift);
+            }
+            _ => panic!("Expected click command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "click", "--count", "4", "3", "7"]).is_err());
+
+        let cli = Cli::parse_from(["pilotty", "drag", "--shift", "2", "0", "4", "30"]);
+        match cli.command {
+            Commands::Drag(args) => {
+                assert_eq!(
+                    (args.from_row, args.from_col, args.to_row, args.to_col),
+                    (2, 0, 4, 30)
+                );
+                assert_eq!(args.button, MouseButton::Left);
+                assert!(args.modifiers.shift);
+            }
+            _ => panic!("Expected drag command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "drag", "2", "0"]).is_err());
+
+        let cli = Cli::parse_from(["pilotty", "hover", "--text", "Help"]);
+        match cli.command {
+            Commands::Hover(args) => assert_eq!(args.text.as_deref(), Some("Help")),
+            _ => panic!("Expected hover command"),
+        }
+    }
+
+    #[test]
+    fn snapshot_parses_diff_baseline() {
+        let cli = Cli::parse_from(["pilotty", "snapshot", "--since", "42"]);
+
+        match cli.command {
+            Commands::Snapshot(args) => assert_eq!(args.since, Some(42)),
+            _ => panic!("Expected snapshot command"),
+        }
+    }
+
+    #[test]
+    fn wait_for_takes_a_pattern_or_one_process_condition() {
+        let cli = Cli::parse_from(["pilotty", "wait-for", "--idle", "500"]);
+        match cli.command {
+            Commands::WaitFor(args) => {
+                assert_eq!(args.pattern, None);
+                assert_eq!(args.idle, Some(500));
+            }
+            _ => panic!("Expected wait-for command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "wait-for", "-p", "Success", "--pattern", "Error"]);
+        match cli.command {
+            Commands::WaitFor(args) => {
+                assert_eq!(args.pattern, None);
+                assert_eq!(args.patterns, vec!["Success", "Error"]);
+                assert!(!args.all && !args.absent);
+            }
+            _ => panic!("Expected wait-for command"),
+        }
+
+        assert!(Cli::try_parse_from(["pilotty", "wait-for"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "wait-for", "--idle", "5", "--absent"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "wait-for", "Ready", "--exit"]).is_err());
+        assert!(
+            Cli::try_parse_from(["pilotty", "wait-for", "--exit", "--foreground-change"]).is_err()
+        );
+    }
+
+    #[test]
+    fn signal_parses_names_and_target() {
+        let cli = Cli::parse_from(["pilotty", "signal", "-s", "app", "-t", "foreground", "int"]);
+        match cli.command {
+            Commands::Signal(args) => {
+                assert_eq!(args.signal, Signal::Sigint);
+                assert!(matches!(args.target, SignalTarget::Foreground));
+                assert_eq!(args.session.as_deref(), Some("app"));
+            }
+            _ => panic!("Expected signal command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "signal", "SIGWINCH"]);
+        match cli.command {
+            Commands::Signal(args) => assert!(matches!(args.target, SignalTarget::Child)),
+            _ => panic!("Expected signal command"),
+        }
+
+        assert!(Cli::try_parse_from(["pilotty", "signal", "SIGFOO"]).is_err());
+    }
+
+    #[test]
+    fn snapshot_and_wait_for_parse_negative_regions() {
+        let cli = Cli::parse_from(["pilotty", "wait-for", "--region", "-1", "Saved"]);
+        match cli.command {
+            Commands::WaitFor(args) => {
+                let region = args.region.expect("region");
+                assert_eq!((region.rows, region.cols), (Some((-1, -1)), None));
+                assert_eq!(args.pattern.as_deref(), Some("Saved"));
+            }
+            _ => panic!("Expected wait-for command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "snapshot", "--region", "2:,-20:"]);
+        match cli.command {
+            Commands::Snapshot(args) => {
+                let region = args.region.expect("region");
+                assert_eq!((region.rows, region.cols), (Some((2, -1)), Some((-20, -1))));
+            }
+            _ => panic!("Expected snapshot command"),
+        }
+
+        let cli = Cli::parse_from([
+            "pilotty",
+            "snapshot",
+            "--ignore",
+            "0",
+            "--ignore",
+            "-1,-8:",
+            "--ignore-regex",
+            "[0-9]+s",
+        ]);
+        match cli.command {
+            Commands::Snapshot(args) => {
+                let ignored: Vec<_> = args.ignore.iter().map(|r| (r.rows, r.cols)).collect();
+                assert_eq!(
+                    ignored,
+                    vec![(Some((0, 0)), None), (Some((-1, -1)), Some((-8, -1)))]
+                );
+                assert_eq!(args.ignore_regex, vec!["[0-9]+s"]);
+            }
+            _ => panic!("Expected snapshot command"),
+        }
+
+        assert!(Cli::try_parse_from(["pilotty", "wait-for", "--exit", "--region", "0"]).is_err());
+    }
+
+    #[test]
+    fn snapshot_and_wait_for_parse_scrollback() {
+        let cli = Cli::parse_from(["pilotty", "snapshot", "--scrollback", "200"]);
+        match cli.command {
+            Commands::Snapshot(args) => assert_eq!(args.scrollback, 200),
+            _ => panic!("Expected snapshot command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "wait-for", "--scrollback", "500", "PASS"]);
+        match cli.command {
+            Commands::WaitFor(args) => {
+                assert_eq!(args.scrollback, 500);
+                assert_eq!(args.pattern.as_deref(), Some("PASS"));
+            }
+            _ => panic!("Expected wait-for command"),
+        }
+
+        assert!(
+            Cli::try_parse_from(["pilotty", "snapshot", "--scrollback", "5", "--since", "3"])
+                .is_err()
+        );
+        assert!(Cli::try_parse_from([
+            "pilotty",
+            "wait-for",
+            "--scrollback",
+            "5",
+            "--region",
+            "-1",
+            "x"
+        ])
+        .is_err());
+        assert!(
+            Cli::try_parse_from(["pilotty", "wait-for", "--exit", "--scrollback", "5"]).is_err()
+        );
+    }
+
+    #[test]
+    fn spawn_parses_environment_flags() {
+        let cli = Cli::parse_from([
+            "pilotty",
+            "spawn",
+            "-e",
+            "A=1",
+            "--env",
+            "B=x=y",
+            "--env-clear",
+            "--term",
+            "vt100",
+            "env",
+            "-e",
+        ]);
+        match cli.command {
+            Commands::Spawn(args) => {
+                assert_eq!(
+                    args.env,
+                    vec![
+                        ("A".to_string(), "1".to_string()),
+                        ("B".to_string(), "x=y".to_string())
+                    ]
+                );
+                assert!(args.env_clear);
+                assert_eq!(args.term.as_deref(), Some("vt100"));
+                assert_eq!((args.cols, args.rows), (None, None));
+                // Flags after the command belong to the command.
+                assert_eq!(args.command, vec!["env", "-e"]);
+            }
+            _ => panic!("Expected spawn command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "spawn", "--env", "NOVALUE", "sh"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "spawn", "--env", "=x", "sh"]).is_err());
+
+        let cli = Cli::parse_from(["pilotty", "spawn", "--cols", "120", "--rows", "40", "sh"]);
+        match cli.command {
+            Commands::Spawn(args) => assert_eq!((args.cols, args.rows), (Some(120), Some(40))),
+            _ => panic!("Expected spawn command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "spawn", "--cols", "0", "sh"]).is_err());
+    }
+
+    #[test]
+    fn record_parses_spawn_flags_and_subcommands() {
+        let cli = Cli::parse_from([
+            "pilotty",
+            "spawn",
+            "--record",
+            "run.cast",
+            "--record-input",
+            "sh",
+        ]);
+        match cli.command {
+            Commands::Spawn(args) => {
+                assert_eq!(args.record.as_deref(), Some("run.cast"));
+                assert!(args.record_input);
+            }
+            _ => panic!("Expected spawn command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "spawn", "--record-input", "sh"]).is_err());
+
+        let cli = Cli::parse_from(["pilotty", "record", "start", "run.cast", "--input"]);
+        match cli.command {
+            Commands::Record(RecordArgs {
+                action: RecordAction::Start(args),
+            }) => {
+                assert_eq!(args.path, "run.cast");
+                assert!(args.input);
+            }
+            _ => panic!("Expected record start command"),
+        }
+
+        let cli = Cli::parse_from(["pilotty", "record", "stop", "-s", "editor"]);
+        match cli.command {
+            Commands::Record(RecordArgs {
+                action: RecordAction::Stop(args),
+            }) => assert_eq!(args.session.as_deref(), Some("editor")),
+            _ => panic!("Expected record stop command"),
+        }
+    }
+
+    #[test]
+    fn replay_sizes_must_be_positive() {
+        let cli = Cli::parse_from(["pilotty", "replay", "out.ansi", "--cols", "120"]);
+        match cli.command {
+            Commands::Replay(args) => assert_eq!((args.cols, args.rows), (120, 24)),
+            _ => panic!("Expected replay command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "replay", "out.ansi", "--cols", "0"]).is_err());
+        assert!(Cli::try_parse_from(["pilotty", "replay", "out.ansi", "--rows", "0"]).is_err());
+    }
+
+    #[test]
+    fn subscribe_parses_repeated_sessions() {
+        let cli = Cli::parse_from([
+            "pilotty",
+            "subscribe",
+            "-s",
+            "editor",
+            "--session",
+            "build",
+            "--output",
+        ]);
+
+        match cli.command {
+            Commands::Subscribe(args) => {
+                assert_eq!(args.sessions, vec!["editor", "build"]);
+                assert!(args.output);
+            }
+            _ => panic!("Expected subscribe command"),
+        }
+    }
+
+    #[test]
+    fn paste_takes_text_or_a_file() {
+        let cli = Cli::parse_from(["pilotty", "paste", "--file", "snippet.py", "-s", "repl"]);
+
+        match cli.command {
+            Commands::Paste(args) => {
+                assert_eq!(args.text, None);
+                assert_eq!(args.file.as_deref(), Some("snippet.py"));
+                assert_eq!(args.session.as_deref(), Some("repl"));
+            }
+            _ => panic!("Expected paste command"),
+        }
+        assert!(Cli::try_parse_from(["pilotty", "paste", "text", "--file", "x"]).is_err());
+    }
+
     #[test]
     fn snapshot_parses_strict_mode() {
         let cli = Cli::parse_from(["pilotty", "snapshot", "--settle", "100", "--strict"]);
@@ -193,21 +193,19 @@ impl DaemonClient {
         }
     }
 
-    async fn exchange(&mut self, request: Request, timeout_duration: Duration) -> Result<Response> {
-        let request_json =
-            serde_json::to_string(&request).context("Failed to serialize request")?;
-        debug!("Sending: {}", request_json);
+    /// Open a subscription, handing the connection over to its stream.
+    pub async fn subscribe(mut self, request: Request) -> Result<EventStream> {
+        let required = request.command.minimum_protocol();
+        self.ensure_protocol(&request.id, required, Duration::from_secs(30))
+            .await?;
+        self.send(&request).await?;
+        Ok(EventStream {
+            reader: BufReader::new(self.stream),
+        })
+    }
 
-        // Send request
-        self.stream
-            .write_all(request_json.as_bytes())
-            .await
-            .context("Failed to write request")?;
-        self.stream
-            .write_all(b"\n")
-            .await
-            .context("Failed to write newline")?;
-        self.stream.flush().await.context("Failed to flush")?;
+    async fn exchange(&mut self, request: Request, timeout_duration: Duration) -> Result<Response> {
+        self.send(&request).await?;
 
         // Read response with timeout
         let (reader, _writer) = self.stream.split();
@@ -229,6 +227,43 @@ impl DaemonClient {
             serde_json::from_str(&response_line).context("Failed to parse response")?;
         Ok(response)
     }
+
+    async fn send(&mut self, request: &Request) -> Result<()> {
+        let request_json = serde_json::to_string(request).context("Failed to serialize request")?;
+        debug!("Sending: {}", request_json);
+
+        self.stream
+            .write_all(request_json.as_bytes())
+            .await
+            .context("Failed to write request")?;
+        self.stream
+            .write_all(b"\n")
+            .await
+            .context("Failed to write newline")?;
+        self.stream.flush().await.context("Failed to flush")
+    }
+}
+
+/// Responses streamed over a subscription connection.
+pub struct EventStream {
+    reader: BufReader<UnixStream>,
+}
+
+impl EventStream {
+    /// Wait for the next streamed response; `None` once the daemon ends the stream.
+    pub async fn next(&mut self) -> Result<Option<Response>> {
+        let mut line = String::new();
+        let bytes_read = self
+            .reader
+            .read_line(&mut line)
+            .await
+            .context("Failed to read event")?;
+        if bytes_read == 0 {
+            return Ok(None);
+        }
+        let response = serde_json::from_str(&line).context("Failed to parse event")?;
+        Ok(Some(response))
+    }
 }
 
 #[cfg(test)]
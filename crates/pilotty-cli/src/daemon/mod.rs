@@ -2,7 +2,10 @@
 
 pub mod client;
 pub mod paths;
+pub mod processes;
 pub mod pty;
+pub mod queries;
+pub mod recording;
 pub mod retention;
 pub mod server;
 pub mod session;
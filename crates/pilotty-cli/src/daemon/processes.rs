@@ -0,0 +1,144 @@
+//! Process introspection for session status.
+//!
+//! A session's spawned command is often a shell, so its `command` says little
+//! about what the user would see. These helpers read `/proc` to report what
+//! owns the terminal now, the full process tree under the session, and where
+//! the spawned process currently is. Other platforms have no `/proc`, so the
+//! helpers return `None` there and status omits the fields.
+
+use pilotty_core::protocol::{ProcessInfo, ProcessNode};
+
+/// Name and argument vector of a running process.
+pub(crate) fn process_info(pid: u32) -> Option<ProcessInfo> {
+    imp::process_info(pid)
+}
+
+/// A process and every process descended from it.
+pub(crate) fn process_tree(root: u32) -> Option<ProcessNode> {
+    imp::process_tree(root)
+}
+
+/// Current working directory of a process.
+pub(crate) fn process_cwd(pid: u32) -> Option<String> {
+    imp::process_cwd(pid)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use super::{ProcessInfo, ProcessNode};
+
+    pub(super) fn process_info(pid: u32) -> Option<ProcessInfo> {
+        let name = fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+        // Zombies and kernel threads have an empty command line.
+        let cmdline = fs::read(format!("/proc/{pid}/cmdline")).unwrap_or_default();
+        let argv = cmdline
+            .split(|&byte| byte == 0)
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| String::from_utf8_lossy(arg).into_owned())
+            .collect();
+        Some(ProcessInfo {
+            pid,
+            name: name.trim_end_matches('\n').to_string(),
+            argv,
+        })
+    }
+
+    pub(super) fn process_tree(root: u32) -> Option<ProcessNode> {
+        let mut children: HashMap<u32, Vec<u32>> = HashMap::new();
+        for entry in fs::read_dir("/proc").ok()?.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            if let Some(ppid) = parent_pid(pid) {
+                children.entry(ppid).or_default().push(pid);
+            }
+        }
+        for pids in children.values_mut() {
+            pids.sort_unstable();
+        }
+        node(root, &children)
+    }
+
+    fn node(pid: u32, children: &HashMap<u32, Vec<u32>>) -> Option<ProcessNode> {
+        Some(ProcessNode {
+            process: process_info(pid)?,
+            // A child that exits between the scan and this read is skipped.
+            children: children
+                .get(&pid)
+                .into_iter()
+                .flatten()
+                .filter_map(|&child| node(child, children))
+                .collect(),
+        })
+    }
+
+    /// Parent PID from `/proc/<pid>/stat`.
+    ///
+    /// The command name field may contain spaces and parentheses, so fields
+    /// are counted from the last `)`.
+    fn parent_pid(pid: u32) -> Option<u32> {
+        let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+        let (_, fields) = stat.rsplit_once(')')?;
+        fields.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    pub(super) fn process_cwd(pid: u32) -> Option<String> {
+        let cwd = fs::read_link(format!("/proc/{pid}/cwd")).ok()?;
+        Some(cwd.to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::{ProcessInfo, ProcessNode};
+
+    pub(super) fn process_info(_pid: u32) -> Option<ProcessInfo> {
+        None
+    }
+
+    pub(super) fn process_tree(_root: u32) -> Option<ProcessNode> {
+        None
+    }
+
+    pub(super) fn process_cwd(_pid: u32) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_process_details_and_descendants_from_proc() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("5")
+            .current_dir("/")
+            .spawn()
+            .expect("spawn sleep");
+        let pid = child.id();
+
+        // The parent resumes before exec has published the new argv.
+        let info = (0..100)
+            .filter_map(|_| {
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                process_info(pid)
+            })
+            .find(|info| !info.argv.is_empty())
+            .expect("child info");
+        assert_eq!(info.name, "sleep");
+        assert_eq!(info.argv, vec!["sleep", "5"]);
+        assert_eq!(process_cwd(pid).as_deref(), Some("/"));
+
+        let tree = process_tree(std::process::id()).expect("own tree");
+        assert_eq!(tree.process.pid, std::process::id());
+        assert!(tree.children.iter().any(|node| node.process == info));
+
+        child.kill().expect("kill sleep");
+        child.wait().expect("reap sleep");
+        assert!(process_info(pid).is_none());
+    }
+}
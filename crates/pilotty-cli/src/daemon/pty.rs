@@ -1,5 +1,6 @@
 //! PTY session management using portable-pty.
 
+use std::collections::BTreeMap;
 use std::io::{Read, Write};
 use std::sync::Arc;
 
@@ -32,6 +33,46 @@ impl From<TermSize> for PtySize {
     }
 }
 
+/// TERM for sessions that do not choose one: the xterm feature set the
+/// vt100 emulator implements, including 256 colors.
+pub const DEFAULT_TERM: &str = "xterm-256color";
+
+/// PATH for sessions spawned with a cleared environment, so bare command
+/// names still resolve.
+const CLEARED_ENV_PATH: &str = "/usr/local/bin:/usr/bin:/bin";
+
+/// Environment for a spawned process.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpawnEnv {
+    /// Variables to set, applied after clearing or inheriting.
+    pub vars: BTreeMap<String, String>,
+    /// Start from an empty environment instead of the daemon's.
+    pub clear: bool,
+    /// TERM for the process; [`DEFAULT_TERM`] unless set here or in `vars`.
+    pub term: Option<String>,
+}
+
+impl SpawnEnv {
+    /// TERM the process gets: `term`, else a TERM in `vars`, else [`DEFAULT_TERM`].
+    pub fn term(&self) -> &str {
+        self.term
+            .as_deref()
+            .or_else(|| self.vars.get("TERM").map(String::as_str))
+            .unwrap_or(DEFAULT_TERM)
+    }
+
+    fn apply(&self, cmd: &mut CommandBuilder) {
+        if self.clear {
+            cmd.env_clear();
+            cmd.env("PATH", CLEARED_ENV_PATH);
+        }
+        for (name, value) in &self.vars {
+            cmd.env(name, value);
+        }
+        cmd.env("TERM", self.term());
+    }
+}
+
 /// A PTY session wrapping a master PTY and child process.
 pub struct PtySession {
     master: Box<dyn MasterPty + Send>,
@@ -43,7 +84,12 @@ impl PtySession {
     ///
     /// If `cwd` is provided, the command will run in that directory.
     /// Otherwise, it inherits the daemon's current directory.
-    pub fn spawn(command: &[String], size: TermSize, cwd: Option<&str>) -> Result<Self> {
+    pub fn spawn(
+        command: &[String],
+        size: TermSize,
+        cwd: Option<&str>,
+        env: &SpawnEnv,
+    ) -> Result<Self> {
         if command.is_empty() {
             anyhow::bail!("Command cannot be empty");
         }
@@ -60,6 +106,7 @@ impl PtySession {
         if let Some(dir) = cwd {
             cmd.cwd(dir);
         }
+        env.apply(&mut cmd);
 
         let child = pair
             .slave
@@ -171,6 +218,14 @@ impl AsyncPtyHandle {
             .context("Failed to resize PTY")?;
         Ok(())
     }
+    /// A cloneable writer for queueing input without holding the handle.
+    pub fn writer(&self) -> PtyWriter {
+        PtyWriter {
+            write_tx: self.write_tx.clone(),
+            shutdown: self.write_shutdown.subscribe(),
+        }
+    }
+
     /// Send bytes to the PTY stdin.
     pub async fn write(&self, data: &[u8]) -> Result<()> {
         let mut shutdown = self.write_shutdown.subscribe();
@@ -187,6 +242,43 @@ impl AsyncPtyHandle {
         }
     }
 
+    /// Process ID the child was spawned with.
+    ///
+    /// This is still returned after the child has been reaped, when the PID
+    /// may already belong to an unrelated process.
+    pub fn child_pid(&self) -> Option<u32> {
+        self.child.lock().ok()?.process_id()
+    }
+
+    /// Send `signal` to the child unless it has already exited.
+    ///
+    /// Returns the child's PID, or `None` once it has exited. The child is
+    /// only ever reaped under the child lock, which is held from the exit
+    /// check through the signal, so the PID still names the child.
+    pub fn signal_child(&self, signal: libc::c_int) -> std::io::Result<Option<i32>> {
+        let mut child = self
+            .child
+            .lock()
+            .map_err(|_| std::io::Error::other("Child process mutex poisoned"))?;
+        if child.try_wait()?.is_some() {
+            return Ok(None);
+        }
+        let Some(pid) = child.process_id().and_then(|pid| i32::try_from(pid).ok()) else {
+            return Ok(None);
+        };
+        // SAFETY: kill(2) only reads its integer arguments. The pid is a
+        // positive PID of this unreaped child, so the signal reaches only it.
+        if unsafe { libc::kill(pid, signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Some(pid))
+    }
+
+    /// Process group currently in the PTY foreground, as `tcgetpgrp` reports it.
+    pub fn foreground_process_group(&self) -> Option<i32> {
+        self.master.lock().ok()?.process_group_leader()
+    }
+
     /// Return the child process exit status when it has exited.
     pub fn exit_status(&self) -> Result<Option<ExitStatus>> {
         self.child
@@ -277,6 +369,27 @@ impl AsyncPtyHandle {
     }
 }
 
+/// Queues input for a PTY from tasks that do not own its [`AsyncPtyHandle`].
+#[derive(Clone)]
+pub struct PtyWriter {
+    write_tx: mpsc::Sender<Vec<u8>>,
+    shutdown: watch::Receiver<bool>,
+}
+
+impl PtyWriter {
+    /// Queue bytes for the PTY stdin without waiting for queue space.
+    ///
+    /// Fails when the input queue is full or the PTY is shutting down.
+    pub fn try_write(&self, data: &[u8]) -> Result<()> {
+        if *self.shutdown.borrow() {
+            anyhow::bail!("PTY input is shutting down");
+        }
+        self.write_tx
+            .try_send(data.to_vec())
+            .context("Failed to queue PTY input")
+    }
+}
+
 impl Drop for AsyncPtyHandle {
     fn drop(&mut self) {
         self.write_shutdown.send_replace(true);
@@ -338,6 +451,7 @@ mod tests {
             &["echo".to_string(), "hello".to_string()],
             TermSize::default(),
             None,
+            &SpawnEnv::default(),
         )
         .expect("Failed to spawn echo");
 
@@ -383,8 +497,13 @@ mod tests {
     #[test]
     fn test_spawn_and_write_input() {
         // Spawn cat which echoes input
-        let session = PtySession::spawn(&["cat".to_string()], TermSize::default(), None)
-            .expect("Failed to spawn cat");
+        let session = PtySession::spawn(
+            &["cat".to_string()],
+            TermSize::default(),
+            None,
+            &SpawnEnv::default(),
+        )
+        .expect("Failed to spawn cat");
 
         let mut writer = session.writer().expect("Failed to get writer");
         let mut reader = session.reader().expect("Failed to get reader");
@@ -428,8 +547,13 @@ mod tests {
     #[tokio::test]
     async fn test_async_pty_bash_exit() {
         // Spawn bash
-        let session = PtySession::spawn(&["bash".to_string()], TermSize::default(), None)
-            .expect("Failed to spawn bash");
+        let session = PtySession::spawn(
+            &["bash".to_string()],
+            TermSize::default(),
+            None,
+            &SpawnEnv::default(),
+        )
+        .expect("Failed to spawn bash");
 
         let (handle, mut read_rx) =
             AsyncPtyHandle::new(session).expect("Failed to create async handle");
@@ -468,8 +592,13 @@ mod tests {
 
     #[tokio::test]
     async fn write_is_rejected_after_terminate() {
-        let session =
-            PtySession::spawn(&["cat".to_string()], TermSize::default(), None).expect("spawn cat");
+        let session = PtySession::spawn(
+            &["cat".to_string()],
+            TermSize::default(),
+            None,
+            &SpawnEnv::default(),
+        )
+        .expect("spawn cat");
         let (handle, _read_rx) = AsyncPtyHandle::new(session).expect("create async handle");
 
         handle.terminate();
@@ -484,8 +613,13 @@ mod tests {
     #[tokio::test]
     async fn test_async_pty_handle_resize() {
         // Spawn a shell
-        let session = PtySession::spawn(&["sh".to_string()], TermSize { cols: 80, rows: 24 }, None)
-            .expect("spawn");
+        let session = PtySession::spawn(
+            &["sh".to_string()],
+            TermSize { cols: 80, rows: 24 },
+            None,
+            &SpawnEnv::default(),
+        )
+        .expect("spawn");
 
         let (handle, _read_rx) = AsyncPtyHandle::new(session).expect("async handle");
 
@@ -507,8 +641,13 @@ mod tests {
     fn test_spawn_with_cwd() {
         // Spawn pwd in /tmp and verify it outputs a path containing "tmp"
         // Note: On macOS, /tmp is a symlink to /private/tmp
-        let session = PtySession::spawn(&["pwd".to_string()], TermSize::default(), Some("/tmp"))
-            .expect("Failed to spawn pwd with cwd");
+        let session = PtySession::spawn(
+            &["pwd".to_string()],
+            TermSize::default(),
+            Some("/tmp"),
+            &SpawnEnv::default(),
+        )
+        .expect("Failed to spawn pwd with cwd");
 
         let mut reader = session.reader().expect("Failed to get reader");
 
@@ -543,4 +682,85 @@ mod tests {
             output_str
         );
     }
+
+    fn spawned_output(env: &SpawnEnv, script: &str) -> String {
+        let session = PtySession::spawn(
+            &["sh".to_string(), "-c".to_string(), script.to_string()],
+            TermSize::default(),
+            None,
+            env,
+        )
+        .expect("Failed to spawn sh");
+        let mut reader = session.reader().expect("Failed to get reader");
+        let (_master, mut child) = session.into_parts();
+        child.wait().expect("wait for sh");
+
+        let mut output = Vec::new();
+        let mut buf = [0u8; 256];
+        // The master reports EIO instead of EOF once the child has exited.
+        while let Ok(n @ 1..) = reader.read(&mut buf) {
+            output.extend_from_slice(&buf[..n]);
+        }
+        String::from_utf8_lossy(&output).trim_end().to_string()
+    }
+
+    #[test]
+    fn spawn_env_sets_term_and_variables() {
+        let script = r#"printf '%s|%s' "$TERM" "$GREETING""#;
+        let env = SpawnEnv {
+            vars: BTreeMap::from([("GREETING".to_string(), "hello".to_string())]),
+            ..SpawnEnv::default()
+        };
+        assert_eq!(spawned_output(&env, script), "xterm-256color|hello");
+        assert_eq!(env.term(), "xterm-256color");
+
+        let from_vars = SpawnEnv {
+            vars: BTreeMap::from([("TERM".to_string(), "screen".to_string())]),
+            ..SpawnEnv::default()
+        };
+        assert_eq!(spawned_output(&from_vars, script), "screen|");
+        assert_eq!(from_vars.term(), "screen");
+
+        let env = SpawnEnv {
+            term: Some("vt100".to_string()),
+            ..env
+        };
+        assert_eq!(spawned_output(&env, script), "vt100|hello");
+        assert_eq!(env.term(), "vt100");
+    }
+
+    #[test]
+    fn default_env_passes_inherited_variables_through() {
+        let names = ["PATH", "HOME", "COLORTERM", "TERMINFO", "TMUX", "COLUMNS"];
+        let script = names
+            .iter()
+            .map(|name| format!("printf '%s|' \"${{{name}-unset}}\""))
+            .collect::<Vec<_>>()
+            .join("; ");
+        let expected: String = names
+            .iter()
+            .map(|name| {
+                let value = std::env::var(name).unwrap_or_else(|_| "unset".to_string());
+                format!("{value}|")
+            })
+            .collect();
+        assert_eq!(spawned_output(&SpawnEnv::default(), &script), expected);
+    }
+
+    #[test]
+    fn cleared_env_keeps_only_path_term_and_requested_variables() {
+        let env = SpawnEnv {
+            vars: BTreeMap::from([("ONLY".to_string(), "this".to_string())]),
+            clear: true,
+            term: None,
+        };
+        let output = spawned_output(
+            &env,
+            r#"printf '%s|%s|%s|%s' "$PATH" "$TERM" "$ONLY" "${HOME-unset}""#,
+        );
+        assert_eq!(
+            output,
+            format!("{CLEARED_ENV_PATH}|xterm-256color|this|unset")
+        );
+    }
 }
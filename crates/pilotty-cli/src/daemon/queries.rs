@@ -0,0 +1,315 @@
+//! Replies to terminal queries applications send through their output.
+//!
+//! Prompt toolkits and TUI libraries ask the terminal where the cursor is
+//! (DSR/CPR), what it is (DA1, DA2, XTVERSION), and which colors it uses
+//! (OSC 4/10/11), then block until a reply arrives on their input. A real
+//! terminal answers; without one the application stalls or degrades. The
+//! emulator hands unhandled sequences here and queues the replies for the
+//! output pump to write back to the PTY.
+
+use std::fmt::Write as _;
+
+use anyhow::{bail, Context, Result};
+
+/// An RGB color as reported in OSC color replies.
+pub type Rgb = [u8; 3];
+
+/// How the emulator identifies itself when applications query the terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalIdentity {
+    /// XTVERSION name and version, e.g. `pilotty(0.1.0)`.
+    pub version: String,
+    /// Primary device attributes (DA1) reply parameters, e.g. `?62;22`.
+    pub primary_attributes: String,
+    /// Secondary device attributes (DA2) reply parameters, e.g. `>1;10;0`.
+    pub secondary_attributes: String,
+    /// Colors reported to OSC 4/10/11 queries.
+    pub palette: TerminalPalette,
+}
+
+impl Default for TerminalIdentity {
+    fn default() -> Self {
+        Self {
+            version: format!("pilotty({})", env!("CARGO_PKG_VERSION")),
+            primary_attributes: "?62;22".to_string(),
+            secondary_attributes: ">1;10;0".to_string(),
+            palette: TerminalPalette::default(),
+        }
+    }
+}
+
+impl TerminalIdentity {
+    /// Replace the XTVERSION string.
+    ///
+    /// Rejects control characters, which would end the reply early.
+    pub fn set_version(&mut self, version: &str) -> Result<()> {
+        if version.is_empty() || version.chars().any(char::is_control) {
+            bail!("terminal version must be non-empty printable text, got {version:?}");
+        }
+        self.version = version.to_string();
+        Ok(())
+    }
+
+    /// Replace the DA1 reply parameters, which must start with `?`.
+    pub fn set_primary_attributes(&mut self, params: &str) -> Result<()> {
+        self.primary_attributes = attribute_params(params, '?')?;
+        Ok(())
+    }
+
+    /// Replace the DA2 reply parameters, which must start with `>`.
+    pub fn set_secondary_attributes(&mut self, params: &str) -> Result<()> {
+        self.secondary_attributes = attribute_params(params, '>')?;
+        Ok(())
+    }
+}
+
+fn attribute_params(params: &str, prefix: char) -> Result<String> {
+    let valid = params.strip_prefix(prefix).is_some_and(|rest| {
+        !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit() || c == ';')
+    });
+    if !valid {
+        bail!("device attributes must look like '{prefix}62;22', got {params:?}");
+    }
+    Ok(params.to_string())
+}
+
+/// Default foreground, background, and 256-color palette.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalPalette {
+    pub foreground: Rgb,
+    pub background: Rgb,
+    pub colors: [Rgb; 256],
+}
+
+/// xterm's default 16 ANSI colors.
+const ANSI_COLORS: [Rgb; 16] = [
+    [0x00, 0x00, 0x00],
+    [0xcd, 0x00, 0x00],
+    [0x00, 0xcd, 0x00],
+    [0xcd, 0xcd, 0x00],
+    [0x00, 0x00, 0xee],
+    [0xcd, 0x00, 0xcd],
+    [0x00, 0xcd, 0xcd],
+    [0xe5, 0xe5, 0xe5],
+    [0x7f, 0x7f, 0x7f],
+    [0xff, 0x00, 0x00],
+    [0x00, 0xff, 0x00],
+    [0xff, 0xff, 0x00],
+    [0x5c, 0x5c, 0xff],
+    [0xff, 0x00, 0xff],
+    [0x00, 0xff, 0xff],
+    [0xff, 0xff, 0xff],
+];
+
+impl Default for TerminalPalette {
+    fn default() -> Self {
+        let mut colors = [[0; 3]; 256];
+        colors[..16].copy_from_slice(&ANSI_COLORS);
+        // 6x6x6 color cube, then a 24-step grayscale ramp.
+        let level = |n: usize| if n == 0 { 0 } else { (55 + n * 40) as u8 };
+        for (i, color) in colors[16..232].iter_mut().enumerate() {
+            *color = [level(i / 36), level(i / 6 % 6), level(i % 6)];
+        }
+        for (i, color) in colors[232..].iter_mut().enumerate() {
+            let gray = (8 + i * 10) as u8;
+            *color = [gray; 3];
+        }
+        Self {
+            // A dark theme, so light/dark detection picks dark-background styles.
+            foreground: ANSI_COLORS[7],
+            background: ANSI_COLORS[0],
+            colors,
+        }
+    }
+}
+
+impl TerminalPalette {
+    /// Apply comma-separated overrides such as `fg=#d0d0d0,bg=#1e1e1e,1=#ff5555`.
+    ///
+    /// Keys are `fg`, `bg`, or a palette index from 0 to 255.
+    pub fn apply_overrides(&mut self, spec: &str) -> Result<()> {
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (key, value) = entry
+                .split_once('=')
+                .with_context(|| format!("color override {entry:?} must look like 'bg=#000000'"))?;
+            let color = parse_hex_color(value.trim())?;
+            match key.trim() {
+                "fg" => self.foreground = color,
+                "bg" => self.background = color,
+                index => {
+                    let index: u8 = index.parse().with_context(|| {
+                        format!("color key {index:?} must be 'fg', 'bg', or 0-255")
+                    })?;
+                    self.colors[usize::from(index)] = color;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_hex_color(value: &str) -> Result<Rgb> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        bail!("color {value:?} must be #rrggbb");
+    }
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16);
+    Ok([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Reply to a CSI query the emulator did not handle, if it is one.
+///
+/// `cursor` is the 1-based (row, col) at the moment the query was parsed.
+pub(crate) fn answer_csi(
+    identity: &TerminalIdentity,
+    cursor: (u16, u16),
+    intermediate: Option<u8>,
+    params: &[&[u16]],
+    action: char,
+) -> Option<String> {
+    let first = params.first().and_then(|param| param.first()).copied();
+    let (row, col) = cursor;
+    match (intermediate, action, first.unwrap_or(0)) {
+        // DSR: operating status, then cursor position report.
+        (None, 'n', 5) => Some("\x1b[0n".to_string()),
+        (None, 'n', 6) => Some(format!("\x1b[{row};{col}R")),
+        // DECXCPR: extended cursor position report, always page 1.
+        (Some(b'?'), 'n', 6) => Some(format!("\x1b[?{row};{col};1R")),
+        (None, 'c', 0) => Some(format!("\x1b[{}c", identity.primary_attributes)),
+        (Some(b'>'), 'c', 0) => Some(format!("\x1b[{}c", identity.secondary_attributes)),
+        // XTVERSION
+        (Some(b'>'), 'q', 0) => Some(format!("\x1bP>|{}\x1b\\", identity.version)),
+        _ => None,
+    }
+}
+
+/// Reply to the color queries in an OSC sequence the emulator did not handle.
+///
+/// Handles `OSC 4;index;?` pairs and `OSC 10/11;?`, including xterm's form
+/// where each further `?` asks for the next color (`OSC 10;?;?`). Replies end
+/// with BEL when the query did, as xterm's do, and with ST otherwise.
+pub(crate) fn answer_osc(
+    palette: &TerminalPalette,
+    params: &[&[u8]],
+    bel_terminated: bool,
+) -> Option<String> {
+    let (code, rest) = params.split_first()?;
+    let end = if bel_terminated { "\x07" } else { "\x1b\\" };
+    let mut reply = String::new();
+    match *code {
+        b"4" => {
+            for pair in rest.chunks(2) {
+                let [index, b"?"] = pair else { continue };
+                let Some(index) = std::str::from_utf8(index)
+                    .ok()
+                    .and_then(|index| index.parse::<u8>().ok())
+                else {
+                    continue;
+                };
+                let color = palette.colors[usize::from(index)];
+                let _ = write!(reply, "\x1b]4;{index};{}{end}", color_spec(color));
+            }
+        }
+        b"10" | b"11" => {
+            let first = if *code == b"10" { 10 } else { 11 };
+            let colors = [(10, palette.foreground), (11, palette.background)];
+            let queried = colors.iter().skip_while(|(code, _)| *code < first);
+            for ((code, color), query) in queried.zip(rest) {
+                if *query == b"?" {
+                    let _ = write!(reply, "\x1b]{code};{}{end}", color_spec(*color));
+                }
+            }
+        }
+        _ => {}
+    }
+    (!reply.is_empty()).then_some(reply)
+}
+
+/// X11 color spec with 16-bit channels, as xterm reports it.
+fn color_spec([r, g, b]: Rgb) -> String {
+    let wide = |c: u8| u16::from(c) * 0x101;
+    format!("rgb:{:04x}/{:04x}/{:04x}", wide(r), wide(g), wide(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csi_queries_report_cursor_and_identity() {
+        let identity = TerminalIdentity::default();
+        let answer = |intermediate, params: &[&[u16]], action| {
+            answer_csi(&identity, (3, 7), intermediate, params, action)
+        };
+
+        assert_eq!(answer(None, &[&[6]], 'n').as_deref(), Some("\x1b[3;7R"));
+        assert_eq!(
+            answer(Some(b'?'), &[&[6]], 'n').as_deref(),
+            Some("\x1b[?3;7;1R")
+        );
+        assert_eq!(answer(None, &[&[5]], 'n').as_deref(), Some("\x1b[0n"));
+        assert_eq!(answer(None, &[], 'c').as_deref(), Some("\x1b[?62;22c"));
+        assert_eq!(answer(None, &[&[0]], 'c').as_deref(), Some("\x1b[?62;22c"));
+        assert_eq!(
+            answer(Some(b'>'), &[], 'c').as_deref(),
+            Some("\x1b[>1;10;0c")
+        );
+        assert_eq!(
+            answer(Some(b'>'), &[&[0]], 'q'),
+            Some(format!(
+                "\x1bP>|pilotty({})\x1b\\",
+                env!("CARGO_PKG_VERSION")
+            ))
+        );
+        // Not queries: window ops and DA with unknown parameters.
+        assert_eq!(answer(None, &[&[1]], 'c'), None);
+        assert_eq!(answer(None, &[&[22]], 't'), None);
+    }
+
+    #[test]
+    fn osc_color_queries_use_the_palette() {
+        let mut palette = TerminalPalette::default();
+        palette
+            .apply_overrides("fg=#d0d0d0, bg=#1e1e1e, 1=#ff5555")
+            .unwrap();
+
+        assert_eq!(
+            answer_osc(&palette, &[b"11", b"?"], false).as_deref(),
+            Some("\x1b]11;rgb:1e1e/1e1e/1e1e\x1b\\")
+        );
+        assert_eq!(
+            answer_osc(&palette, &[b"10", b"?", b"?"], false).as_deref(),
+            Some("\x1b]10;rgb:d0d0/d0d0/d0d0\x1b\\\x1b]11;rgb:1e1e/1e1e/1e1e\x1b\\")
+        );
+        assert_eq!(
+            answer_osc(&palette, &[b"4", b"1", b"?", b"196", b"?"], false).as_deref(),
+            Some("\x1b]4;1;rgb:ffff/5555/5555\x1b\\\x1b]4;196;rgb:ffff/0000/0000\x1b\\")
+        );
+        assert_eq!(
+            answer_osc(&palette, &[b"11", b"?"], true).as_deref(),
+            Some("\x1b]11;rgb:1e1e/1e1e/1e1e\x07")
+        );
+        // Setting a color is not a query.
+        assert_eq!(answer_osc(&palette, &[b"11", b"#000000"], false), None);
+    }
+
+    #[test]
+    fn identity_and_palette_overrides_are_validated() {
+        let mut identity = TerminalIdentity::default();
+        identity.set_version("xterm(390)").unwrap();
+        identity.set_primary_attributes("?1;2").unwrap();
+        identity.set_secondary_attributes(">41;390;0").unwrap();
+        assert_eq!(identity.version, "xterm(390)");
+
+        assert!(identity.set_version("bad\x1b\\").is_err());
+        assert!(identity.set_primary_attributes("62;22").is_err());
+        assert!(identity.set_secondary_attributes(">1;x").is_err());
+
+        let mut palette = TerminalPalette::default();
+        assert_eq!(palette.colors[231], [0xff; 3]);
+        assert_eq!(palette.colors[232], [0x08; 3]);
+        assert!(palette.apply_overrides("bg=black").is_err());
+        assert!(palette.apply_overrides("256=#000000").is_err());
+        assert!(palette.apply_overrides("fg").is_err());
+    }
+}
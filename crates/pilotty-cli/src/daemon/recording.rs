@@ -0,0 +1,461 @@
+//! Opt-in session recordings in asciicast v2 format.
+//!
+//! A recording is an append-only file: one JSON header line followed by one
+//! `[time, code, data]` event line per output batch, resize, or (when
+//! requested) input write. Times are seconds since the recording started.
+//! Events are appended one whole line at a time, so a recording cut short by
+//! a crash is still valid up to its last complete line.
+//!
+//! Events are timestamped when they happen and handed to a blocking writer
+//! task through a bounded queue, so a slow disk delays the file, never the
+//! session's output pump or input writes. When the queue is full, events are
+//! dropped and a `"m"` marker line records how many went missing.
+
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use pilotty_core::error::ApiError;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::daemon::pty::TermSize;
+
+/// Events that may wait for the writer task before new ones are dropped.
+const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// Where and what to record for a session.
+#[derive(Debug, Clone)]
+pub struct RecordingOptions {
+    /// Absolute path of the cast file to create.
+    pub path: PathBuf,
+    /// Also record bytes written to the session.
+    pub input: bool,
+}
+
+impl RecordingOptions {
+    /// Create the recording file, mapping failures to client-facing errors.
+    ///
+    /// `term` is the TERM the session's process runs with.
+    pub(crate) fn open(
+        &self,
+        size: TermSize,
+        command: &[String],
+        term: &str,
+    ) -> Result<Recorder, ApiError> {
+        if !self.path.is_absolute() {
+            return Err(ApiError::invalid_input_with_suggestion(
+                format!("Recording path '{}' is not absolute", self.path.display()),
+                "The daemon may run in a different directory; pass an absolute path.",
+            ));
+        }
+        Recorder::create(&self.path, size, command, term, self.input).map_err(|error| {
+            let suggestion = match error.kind() {
+                std::io::ErrorKind::AlreadyExists => {
+                    "Recordings never overwrite files; choose a new path or remove the old one."
+                }
+                _ => "Check that the parent directory exists and is writable.",
+            };
+            ApiError::command_failed_with_suggestion(
+                format!(
+                    "Failed to create recording '{}': {}",
+                    self.path.display(),
+                    error
+                ),
+                suggestion,
+            )
+        })
+    }
+}
+
+/// An open asciicast v2 recording for one session.
+pub(crate) struct Recorder {
+    path: PathBuf,
+    started: Instant,
+    record_input: bool,
+    /// Trailing bytes of an incomplete UTF-8 sequence, held until the next
+    /// output batch completes it.
+    pending_utf8: Vec<u8>,
+    events: mpsc::Sender<CastEvent>,
+    /// Events dropped on a full queue since the last marker was queued.
+    dropped: u64,
+    /// Writer task; resolves to the number of events written once every
+    /// sender is gone.
+    writer: Option<JoinHandle<u64>>,
+}
+
+/// One timestamped event waiting for the writer task.
+struct CastEvent {
+    time: f64,
+    code: &'static str,
+    data: String,
+}
+
+impl Recorder {
+    /// Create a new recording file, write its header, and start its writer
+    /// task.
+    ///
+    /// Refuses to overwrite an existing file. Must run inside a Tokio runtime.
+    pub(crate) fn create(
+        path: &Path,
+        size: TermSize,
+        command: &[String],
+        term: &str,
+        record_input: bool,
+    ) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let header = json!({
+            "version": 2,
+            "width": size.cols,
+            "height": size.rows,
+            "timestamp": chrono::Utc::now().timestamp(),
+            "command": command.join(" "),
+            "env": { "TERM": term },
+        });
+        file.write_all(format!("{header}\n").as_bytes())?;
+
+        let (events, events_rx) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        let writer_path = path.to_path_buf();
+        let writer =
+            tokio::task::spawn_blocking(move || write_events(file, &writer_path, events_rx));
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            started: Instant::now(),
+            record_input,
+            pending_utf8: Vec::new(),
+            events,
+            dropped: 0,
+            writer: Some(writer),
+        })
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Close the recording once every queued event is on disk, returning its
+    /// path and event count.
+    pub(crate) async fn finish(mut self) -> (PathBuf, u64) {
+        self.flush_pending();
+        if self.dropped > 0 {
+            // Wait for room so the final count is never lost.
+            let marker = self.dropped_marker();
+            let _ = self.events.send(marker).await;
+        }
+        let path = self.path.clone();
+        let writer = self.writer.take();
+        // Dropping the sender lets the writer drain the queue and exit.
+        drop(self);
+        let events = match writer {
+            Some(writer) => writer.await.unwrap_or_else(|error| {
+                warn!("Recording writer for {} failed: {}", path.display(), error);
+                0
+            }),
+            None => 0,
+        };
+        (path, events)
+    }
+
+    /// Close and delete a recording whose session never started.
+    pub(crate) fn discard(self) {
+        let path = self.path.clone();
+        drop(self);
+        if let Err(error) = std::fs::remove_file(&path) {
+            warn!(
+                "Failed to remove unused recording {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+
+    /// Record bytes the session emitted.
+    pub(crate) fn output(&mut self, bytes: &[u8]) {
+        self.pending_utf8.extend_from_slice(bytes);
+        let complete = complete_utf8_prefix(&self.pending_utf8);
+        if complete == 0 {
+            return;
+        }
+        let data = String::from_utf8_lossy(&self.pending_utf8[..complete]).into_owned();
+        self.pending_utf8.drain(..complete);
+        self.event("o", &data);
+    }
+
+    /// Record bytes written to the session, if input recording is enabled.
+    pub(crate) fn input(&mut self, bytes: &[u8]) {
+        if self.record_input {
+            self.event("i", &String::from_utf8_lossy(bytes));
+        }
+    }
+
+    /// Record a terminal resize.
+    pub(crate) fn resize(&mut self, size: TermSize) {
+        self.event("r", &format!("{}x{}", size.cols, size.rows));
+    }
+
+    fn event(&mut self, code: &'static str, data: &str) {
+        if self.dropped > 0 {
+            // Note the gap as soon as the writer has room for it.
+            let Ok(permit) = self.events.clone().try_reserve_owned() else {
+                self.dropped += 1;
+                return;
+            };
+            permit.send(self.dropped_marker());
+        }
+        let event = CastEvent {
+            time: self.elapsed(),
+            code,
+            data: data.to_string(),
+        };
+        match self.events.try_send(event) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => self.dropped += 1,
+            // The writer has stopped after a write error and already logged it.
+            Err(mpsc::error::TrySendError::Closed(_)) => {}
+        }
+    }
+
+    /// Take the dropped-event count as a marker event for the file.
+    fn dropped_marker(&mut self) -> CastEvent {
+        let dropped = std::mem::take(&mut self.dropped);
+        warn!(
+            "Recording to {} dropped {} events",
+            self.path.display(),
+            dropped
+        );
+        CastEvent {
+            time: self.elapsed(),
+            code: "m",
+            data: format!("{dropped} events dropped"),
+        }
+    }
+
+    /// Seconds since the recording started, to the microsecond.
+    fn elapsed(&self) -> f64 {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        (elapsed * 1_000_000.0).round() / 1_000_000.0
+    }
+
+    fn flush_pending(&mut self) {
+        if !self.pending_utf8.is_empty() {
+            let data = String::from_utf8_lossy(&self.pending_utf8).into_owned();
+            self.pending_utf8.clear();
+            self.event("o", &data);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.flush_pending();
+    }
+}
+
+/// Append queued events to `file` until every sender is gone.
+///
+/// Returns the number of events written.
+fn write_events(mut file: File, path: &Path, mut events: mpsc::Receiver<CastEvent>) -> u64 {
+    let mut written = 0;
+    while let Some(event) = events.blocking_recv() {
+        let line = format!("{}\n", json!([event.time, event.code, event.data]));
+        if let Err(error) = file.write_all(line.as_bytes()) {
+            // A full disk must not take the session down with it.
+            warn!(
+                "Recording to {} stopped after write failure: {}",
+                path.display(),
+                error
+            );
+            break;
+        }
+        written += 1;
+    }
+    written
+}
+
+/// Length of the longest prefix that does not end inside a UTF-8 sequence.
+///
+/// Invalid bytes count as complete; only a truncated trailing sequence is
+/// held back.
+fn complete_utf8_prefix(bytes: &[u8]) -> usize {
+    for back in 1..=bytes.len().min(3) {
+        let byte = bytes[bytes.len() - back];
+        if byte & 0xC0 == 0x80 {
+            continue;
+        }
+        let needed = match byte {
+            0xC0..=0xDF => 2,
+            0xE0..=0xEF => 3,
+            0xF0..=0xF7 => 4,
+            _ => 1,
+        };
+        return if needed > back {
+            bytes.len() - back
+        } else {
+            bytes.len()
+        };
+    }
+    bytes.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_cast(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("pilotty-{}-{}.cast", name, uuid::Uuid::new_v4()))
+    }
+
+    fn read_lines(path: &Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn writes_header_and_timed_events() {
+        let path = temp_cast("events");
+        let size = TermSize { cols: 80, rows: 24 };
+        {
+            let mut recorder =
+                Recorder::create(&path, size, &["bash".into(), "-l".into()], "vt220", true)
+                    .unwrap();
+            recorder.output(b"hello\r\n");
+            recorder.input(b"ls\r");
+            recorder.resize(TermSize {
+                cols: 100,
+                rows: 30,
+            });
+            assert_eq!(recorder.finish().await, (path.clone(), 3));
+        }
+
+        let lines = read_lines(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert_eq!(lines[0]["command"], "bash -l");
+        assert_eq!(lines[0]["env"]["TERM"], "vt220");
+        assert_eq!(lines[1][1], "o");
+        assert_eq!(lines[1][2], "hello\r\n");
+        assert_eq!(lines[2][1], "i");
+        assert_eq!(lines[2][2], "ls\r");
+        assert_eq!(lines[3][1], "r");
+        assert_eq!(lines[3][2], "100x30");
+        assert!(lines[1][0].as_f64().unwrap() <= lines[3][0].as_f64().unwrap());
+    }
+
+    #[tokio::test]
+    async fn input_is_skipped_unless_requested() {
+        let path = temp_cast("no-input");
+        {
+            let mut recorder = Recorder::create(
+                &path,
+                TermSize::default(),
+                &["sh".into()],
+                "xterm-256color",
+                false,
+            )
+            .unwrap();
+            recorder.input(b"secret\r");
+            recorder.output(b"$ ");
+            assert_eq!(recorder.finish().await.1, 1);
+        }
+
+        let lines = read_lines(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[1][1], "o");
+    }
+
+    #[tokio::test]
+    async fn utf8_split_across_batches_is_joined() {
+        let path = temp_cast("utf8");
+        let snowman = "☃".as_bytes();
+        {
+            let mut recorder = Recorder::create(
+                &path,
+                TermSize::default(),
+                &["sh".into()],
+                "xterm-256color",
+                false,
+            )
+            .unwrap();
+            recorder.output(&[b'a', snowman[0]]);
+            recorder.output(&snowman[1..]);
+            recorder.finish().await;
+        }
+
+        let lines = read_lines(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(lines[1][2], "a");
+        assert_eq!(lines[2][2], "☃");
+    }
+
+    #[tokio::test]
+    async fn dropped_events_are_counted_in_a_marker() {
+        let path = temp_cast("burst");
+        let sent = EVENT_QUEUE_CAPACITY * 4;
+        let written = {
+            let mut recorder = Recorder::create(
+                &path,
+                TermSize::default(),
+                &["sh".into()],
+                "xterm-256color",
+                false,
+            )
+            .unwrap();
+            for _ in 0..sent {
+                recorder.output(b"x");
+            }
+            recorder.finish().await.1
+        };
+
+        let lines = read_lines(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(written as usize, lines.len() - 1);
+        // Whether or not the writer kept up, every event is either in the
+        // file or counted by a marker.
+        let accounted: usize = lines[1..]
+            .iter()
+            .map(|line| match line[1].as_str().unwrap() {
+                "o" => 1,
+                "m" => line[2]
+                    .as_str()
+                    .unwrap()
+                    .strip_suffix(" events dropped")
+                    .unwrap()
+                    .parse::<usize>()
+                    .unwrap(),
+                other => panic!("unexpected event code {other}"),
+            })
+            .sum();
+        assert_eq!(accounted, sent);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_overwrite_existing_file() {
+        let path = temp_cast("exists");
+        std::fs::write(&path, "keep me").unwrap();
+        let result = Recorder::create(
+            &path,
+            TermSize::default(),
+            &["sh".into()],
+            "xterm-256color",
+            false,
+        );
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            result.err().map(|error| error.kind()),
+            Some(std::io::ErrorKind::AlreadyExists)
+        );
+        assert_eq!(contents, "keep me");
+    }
+}
@@ -6,29 +6,44 @@ use std::time::{Duration, Instant};
 
 use anyhow::{Context, Result};
 use pilotty_core::error::ApiError;
-use pilotty_core::input::encode_mouse_click_combined;
+use pilotty_core::input::{encode_mouse_click_combined, encode_mouse_drag, encode_mouse_hover};
 use pilotty_core::protocol::{
-    supports_protocol, CaptureExit, CaptureOutcome, Command, OutputFormat, Request, Response,
-    ResponseData, ScreenCapture, SnapshotFormat,
+    supports_protocol, CaptureExit, CaptureOutcome, ClickTarget, Command, DiffCapture, MatchMode,
+    MouseButton, MouseModifiers, OutputFormat, ProcessCondition, Request, Response, ResponseData,
+    ScreenCapture, ScreenMask, ScreenRegion, SessionEvent, SessionEventKind, Signal, SignalTarget,
+    SnapshotFormat, PROTOCOL_V4,
+};
+use pilotty_core::snapshot::{
+    CursorState, MouseEncoding, MouseTracking, ScreenState, ScrollbackLine, TerminalModes,
+    TerminalSize,
 };
-use pilotty_core::snapshot::{CursorState, ScreenState, TerminalSize};
 use tokio::io::{AsyncWriteExt, BufReader};
 use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::{Notify, Semaphore};
+use tokio::sync::{broadcast, Notify, Semaphore};
 use tokio::task::JoinSet;
 use tracing::{debug, error, info, warn};
 
 use crate::daemon::paths;
-use crate::daemon::pty::TermSize;
+use crate::daemon::processes;
+use crate::daemon::pty::{SpawnEnv, TermSize};
+use crate::daemon::queries::TerminalIdentity;
+use crate::daemon::recording::RecordingOptions;
 use crate::daemon::retention::{RetentionSnapshot, DEFAULT_RETAIN_BYTES};
 use crate::daemon::session::{
-    ObservationEvent, OutputEvidence, SessionEvidence, SessionId, SessionManager, SessionObserver,
-    SnapshotData,
+    BusEventKind, CaptureScope, ObservationEvent, OutputEvidence, ScreenBaseline, SessionEvidence,
+    SessionId, SessionManager, SessionObserver, SessionOptions, SnapshotData,
 };
-use crate::daemon::terminal::render_retained_output;
+use crate::daemon::terminal::{render_retained_output, TerminalEmulator, DEFAULT_SCROLLBACK_LINES};
 use crate::daemon::tombstone::{ExitMetadata, Tombstone};
 
 const RETAIN_BYTES_ENV: &str = "PILOTTY_RETAIN_BYTES";
+const SCROLLBACK_ENV: &str = "PILOTTY_SCROLLBACK";
+const COLS_ENV: &str = "PILOTTY_COLS";
+const ROWS_ENV: &str = "PILOTTY_ROWS";
+const TERM_VERSION_ENV: &str = "PILOTTY_TERM_VERSION";
+const TERM_DA1_ENV: &str = "PILOTTY_TERM_DA1";
+const TERM_DA2_ENV: &str = "PILOTTY_TERM_DA2";
+const TERM_COLORS_ENV: &str = "PILOTTY_TERM_COLORS";
 
 /// Maximum number of concurrent client connections to prevent resource exhaustion.
 const MAX_CONNECTIONS: usize = 100;
@@ -63,8 +78,11 @@ impl DaemonServer {
         paths::ensure_socket_dir().context("Failed to create socket directory")?;
         let socket_path = paths::get_socket_path(None);
         let pid_path = paths::get_pid_path(None);
-        let retain_bytes = retain_bytes_from_env()?;
-        Self::bind_to_with_retain_bytes(socket_path, pid_path, retain_bytes).await
+        let sessions = SessionManager::with_default_retain_bytes(retain_bytes_from_env()?)
+            .with_default_size(default_size_from_env()?)
+            .with_scrollback_lines(scrollback_lines_from_env()?)
+            .with_terminal_identity(terminal_identity_from_env()?);
+        Self::bind_to_with_sessions(socket_path, pid_path, sessions).await
     }
 
     /// Create a new daemon server bound to a specific socket path.
@@ -76,13 +94,13 @@ impl DaemonServer {
     /// 4. If daemon alive, return error
     #[cfg(test)]
     pub async fn bind_to(socket_path: PathBuf, pid_path: PathBuf) -> Result<Self> {
-        Self::bind_to_with_retain_bytes(socket_path, pid_path, DEFAULT_RETAIN_BYTES).await
+        Self::bind_to_with_sessions(socket_path, pid_path, SessionManager::new()).await
     }
 
-    pub(crate) async fn bind_to_with_retain_bytes(
+    pub(crate) async fn bind_to_with_sessions(
         socket_path: PathBuf,
         pid_path: PathBuf,
-        retain_bytes: usize,
+        sessions: SessionManager,
     ) -> Result<Self> {
         if let Some(parent) = socket_path.parent() {
             std::fs::create_dir_all(parent).with_context(|| {
@@ -162,7 +180,7 @@ impl DaemonServer {
             listener,
             socket_path,
             pid_path,
-            sessions: Arc::new(SessionManager::with_default_retain_bytes(retain_bytes)),
+            sessions: Arc::new(sessions),
             connection_semaphore: Arc::new(Semaphore::new(MAX_CONNECTIONS)),
             shutdown: Arc::new(Notify::new()),
         })
@@ -352,6 +370,80 @@ fn parse_retain_bytes(value: Option<&std::ffi::OsStr>) -> Result<usize> {
     })
 }
 
+fn scrollback_lines_from_env() -> Result<usize> {
+    parse_scrollback_lines(std::env::var_os(SCROLLBACK_ENV).as_deref())
+}
+
+fn parse_scrollback_lines(value: Option<&std::ffi::OsStr>) -> Result<usize> {
+    let Some(value) = value else {
+        return Ok(DEFAULT_SCROLLBACK_LINES);
+    };
+    let value = value
+        .to_str()
+        .with_context(|| format!("{SCROLLBACK_ENV} must contain a non-negative integer"))?;
+    value.parse::<usize>().with_context(|| {
+        format!("{SCROLLBACK_ENV} must be a non-negative integer number of lines, got '{value}'")
+    })
+}
+
+fn default_size_from_env() -> Result<TermSize> {
+    parse_default_size(
+        std::env::var_os(COLS_ENV).as_deref(),
+        std::env::var_os(ROWS_ENV).as_deref(),
+    )
+}
+
+fn parse_default_size(
+    cols: Option<&std::ffi::OsStr>,
+    rows: Option<&std::ffi::OsStr>,
+) -> Result<TermSize> {
+    let parse = |name: &str, value: Option<&std::ffi::OsStr>, default: u16| -> Result<u16> {
+        let Some(value) = value else {
+            return Ok(default);
+        };
+        value
+            .to_str()
+            .and_then(|value| value.parse::<u16>().ok())
+            .filter(|value| *value > 0)
+            .with_context(|| format!("{name} must be a positive integer, got {value:?}"))
+    };
+    let default = TermSize::default();
+    Ok(TermSize {
+        cols: parse(COLS_ENV, cols, default.cols)?,
+        rows: parse(ROWS_ENV, rows, default.rows)?,
+    })
+}
+
+fn terminal_identity_from_env() -> Result<TerminalIdentity> {
+    parse_terminal_identity(|name| std::env::var(name).ok())
+}
+
+fn parse_terminal_identity(var: impl Fn(&str) -> Option<String>) -> Result<TerminalIdentity> {
+    let mut identity = TerminalIdentity::default();
+    if let Some(version) = var(TERM_VERSION_ENV) {
+        identity
+            .set_version(&version)
+            .with_context(|| format!("Invalid {TERM_VERSION_ENV}"))?;
+    }
+    if let Some(params) = var(TERM_DA1_ENV) {
+        identity
+            .set_primary_attributes(&params)
+            .with_context(|| format!("Invalid {TERM_DA1_ENV}"))?;
+    }
+    if let Some(params) = var(TERM_DA2_ENV) {
+        identity
+            .set_secondary_attributes(&params)
+            .with_context(|| format!("Invalid {TERM_DA2_ENV}"))?;
+    }
+    if let Some(colors) = var(TERM_COLORS_ENV) {
+        identity
+            .palette
+            .apply_overrides(&colors)
+            .with_context(|| format!("Invalid {TERM_COLORS_ENV}"))?;
+    }
+    Ok(identity)
+}
+
 /// Kill all active sessions during shutdown.
 ///
 /// Used by both the shutdown command handler and the idle shutdown task.
@@ -416,6 +508,9 @@ const MAX_REQUEST_SIZE: usize = 1024 * 1024;
 /// Maximum scroll amount to prevent long-running requests.
 const MAX_SCROLL_AMOUNT: u32 = 1000;
 
+/// Maximum clicks in a row (a triple click).
+const MAX_CLICK_COUNT: u8 = 3;
+
 /// Maximum delay between keys in a sequence (10 seconds).
 /// Allows time for slow TUI animations while preventing DoS.
 const MAX_KEY_DELAY_MS: u32 = 10_000;
@@ -507,6 +602,11 @@ async fn handle_connection(
         debug!("Received: {} bytes", trimmed.len());
 
         let response = match serde_json::from_str::<Request>(trimmed) {
+            // A subscription takes over the connection until it ends.
+            Ok(request) if matches!(request.command, Command::Subscribe { .. }) => {
+                return stream_events(request, &sessions, &mut reader, &mut writer, &shutdown)
+                    .await;
+            }
             Ok(request) => handle_request(request, sessions.clone(), shutdown.clone()).await,
             Err(e) => Response::error(
                 "unknown",
@@ -517,24 +617,216 @@ async fn handle_connection(
             ),
         };
 
-        let response_json =
-            serde_json::to_string(&response).context("Failed to serialize response")?;
-        debug!("Sending: {}", response_json);
+        write_response(&mut writer, &response).await?;
+    }
 
-        writer
-            .write_all(response_json.as_bytes())
-            .await
-            .context("Failed to write response")?;
-        writer
-            .write_all(b"\n")
-            .await
-            .context("Failed to write newline")?;
-        writer.flush().await.context("Failed to flush")?;
+    Ok(())
+}
+
+/// Write one newline-delimited response to a client.
+async fn write_response<W: tokio::io::AsyncWrite + Unpin>(
+    writer: &mut W,
+    response: &Response,
+) -> Result<()> {
+    let response_json = serde_json::to_string(response).context("Failed to serialize response")?;
+    debug!("Sending: {}", response_json);
+
+    writer
+        .write_all(response_json.as_bytes())
+        .await
+        .context("Failed to write response")?;
+    writer
+        .write_all(b"\n")
+        .await
+        .context("Failed to write newline")?;
+    writer.flush().await.context("Failed to flush")?;
+    Ok(())
+}
+
+/// Stream subscribed session events until the client disconnects, every
+/// followed session exits, or the daemon shuts down.
+async fn stream_events<R, W>(
+    request: Request,
+    sessions: &SessionManager,
+    reader: &mut R,
+    writer: &mut W,
+    shutdown: &Notify,
+) -> Result<()>
+where
+    R: tokio::io::AsyncBufRead + Unpin,
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    use tokio::io::AsyncBufReadExt;
+
+    let request_id = request.id;
+    if let Some(response) = protocol_mismatch_response(
+        &request_id,
+        request.protocol,
+        request.command.minimum_protocol(),
+    ) {
+        return write_response(writer, &response).await;
+    }
+    let Command::Subscribe {
+        sessions: targets,
+        output,
+    } = request.command
+    else {
+        unreachable!("only subscribe requests open a stream");
+    };
+
+    // Join the bus before resolving targets so nothing published in between is lost.
+    let mut events = sessions.subscribe_events();
+    let mut followed = None;
+    if !targets.is_empty() {
+        let mut ids = std::collections::HashSet::new();
+        for target in &targets {
+            match sessions.resolve_session(Some(target)).await {
+                Ok(id) => ids.insert(id),
+                Err(e) => return write_response(writer, &Response::error(&request_id, e)).await,
+            };
+        }
+        followed = Some(ids);
+    }
+
+    let mut subscription = Subscription {
+        baselines: std::collections::HashMap::new(),
+        output,
+    };
+    let mut infos = Vec::new();
+    for info in sessions.list_sessions().await {
+        let id = SessionId::from(info.id.as_str());
+        if followed.as_ref().is_some_and(|ids| !ids.contains(&id)) {
+            continue;
+        }
+        if let Ok(mut observer) = sessions.observe_session(&id).await {
+            let snapshot = observer.current().await;
+            subscription
+                .baselines
+                .insert(id, ScreenBaseline::new(&snapshot));
+        }
+        infos.push(info);
+    }
+    let subscribed = Response::success(&request_id, ResponseData::Subscribed { sessions: infos });
+    write_response(writer, &subscribed).await?;
+
+    loop {
+        tokio::select! {
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        let lagged = Response::success(&request_id, ResponseData::Lagged { skipped });
+                        write_response(writer, &lagged).await?;
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if followed.as_ref().is_some_and(|ids| !ids.contains(&event.session)) {
+                    continue;
+                }
+                let exited = matches!(event.kind, BusEventKind::Exited { .. });
+                let session = event.session.clone();
+                for kind in subscription.translate(sessions, event.session.clone(), event.kind).await {
+                    let event = SessionEvent {
+                        session_id: event.session.to_string(),
+                        session_name: event.name.clone(),
+                        kind,
+                    };
+                    write_response(writer, &Response::success(&request_id, ResponseData::Event(event))).await?;
+                }
+                if exited {
+                    if let Some(ids) = followed.as_mut() {
+                        ids.remove(&session);
+                        if ids.is_empty() {
+                            break;
+                        }
+                    }
+                }
+            }
+            read = reader.fill_buf() => {
+                // Requests are not accepted mid-stream; EOF or an error ends it.
+                match read {
+                    Ok([]) | Err(_) => {
+                        debug!("Subscriber disconnected");
+                        break;
+                    }
+                    Ok(buf) => {
+                        let len = buf.len();
+                        reader.consume(len);
+                    }
+                }
+            }
+            _ = shutdown.notified() => break,
+        }
     }
 
     Ok(())
 }
 
+/// Per-connection state turning bus events into wire events.
+struct Subscription {
+    /// Last screen delivered for each session, so revision events carry diffs.
+    baselines: std::collections::HashMap<SessionId, ScreenBaseline>,
+    output: bool,
+}
+
+impl Subscription {
+    async fn translate(
+        &mut self,
+        sessions: &SessionManager,
+        id: SessionId,
+        kind: BusEventKind,
+    ) -> Vec<SessionEventKind> {
+        match kind {
+            BusEventKind::Spawned { command, size } => {
+                self.baselines.insert(id, ScreenBaseline::blank(size));
+                vec![SessionEventKind::Spawned { command }]
+            }
+            BusEventKind::Output(bytes) => {
+                let mut kinds = Vec::new();
+                if self.output {
+                    kinds.push(SessionEventKind::Output {
+                        bytes: bytes.to_vec(),
+                    });
+                }
+                // Several batches may be queued; one capture covers them all.
+                let Ok(mut observer) = sessions.observe_session(&id).await else {
+                    return kinds;
+                };
+                let snapshot = observer.current().await;
+                let baseline = self.baselines.get(&id);
+                if baseline.is_some_and(|baseline| baseline.revision() >= snapshot.revision) {
+                    return kinds;
+                }
+                let diff = match baseline {
+                    Some(baseline) => baseline.diff(&snapshot),
+                    None => ScreenBaseline::blank(snapshot.size).diff(&snapshot),
+                };
+                self.baselines.insert(id, ScreenBaseline::new(&snapshot));
+                kinds.push(SessionEventKind::Revision(diff));
+                kinds
+            }
+            BusEventKind::Resize(size) => vec![SessionEventKind::Resize {
+                size: TerminalSize {
+                    cols: size.cols,
+                    rows: size.rows,
+                },
+            }],
+            BusEventKind::Bell => vec![SessionEventKind::Bell],
+            BusEventKind::Title(title) => vec![SessionEventKind::Title { title }],
+            BusEventKind::Exited {
+                exit,
+                output_complete,
+            } => {
+                self.baselines.remove(&id);
+                vec![SessionEventKind::Exited {
+                    exit: capture_exit(exit, output_complete),
+                }]
+            }
+        }
+    }
+}
+
 /// Handle a single request and return a response.
 async fn handle_request(
     request: Request,
@@ -546,8 +838,9 @@ async fn handle_request(
     let command = request.command;
     debug!("Handling command: {:?}", command);
 
+    let minimum_protocol = command.minimum_protocol();
     if let Some(response) =
-        protocol_mismatch_response(&request_id, request_protocol, command.minimum_protocol())
+        protocol_mismatch_response(&request_id, request_protocol, minimum_protocol)
     {
         return response;
     }
@@ -558,7 +851,33 @@ async fn handle_request(
             session_name,
             cwd,
             retain_bytes,
+            record,
+            record_input,
+            env,
+            env_clear,
+            term,
+            cols,
+            rows,
         } => {
+            // A single dimension keeps the daemon default for the other.
+            let default_size = sessions.default_size();
+            let size = (cols.is_some() || rows.is_some()).then(|| TermSize {
+                cols: cols.unwrap_or(default_size.cols),
+                rows: rows.unwrap_or(default_size.rows),
+            });
+            let options = SessionOptions {
+                size,
+                retain_bytes: None,
+                recording: record.map(|path| RecordingOptions {
+                    path: path.into(),
+                    input: record_input,
+                }),
+                env: SpawnEnv {
+                    vars: env,
+                    clear: env_clear,
+                    term,
+                },
+            };
             handle_spawn(
                 &request_id,
                 &sessions,
@@ -566,6 +885,7 @@ async fn handle_request(
                 session_name,
                 cwd,
                 retain_bytes,
+                options,
             )
             .await
         }
@@ -576,6 +896,10 @@ async fn handle_request(
             await_change,
             settle_ms,
             timeout_ms,
+            since,
+            region,
+            ignore,
+            scrollback,
         } => {
             handle_snapshot(
                 &request_id,
@@ -586,6 +910,10 @@ async fn handle_request(
                     await_change,
                     settle_ms,
                     timeout_ms,
+                    since,
+                    region,
+                    ignore,
+                    scrollback,
                 },
             )
             .await
@@ -603,14 +931,45 @@ async fn handle_request(
 
         Command::Type { text, session } => handle_type(&request_id, &sessions, text, session).await,
 
+        Command::Paste { text, session } => {
+            handle_paste(&request_id, &sessions, text, session).await
+        }
+
         Command::Key {
             key,
             delay_ms,
             session,
         } => handle_key(&request_id, &sessions, key, delay_ms, session).await,
 
-        Command::Click { row, col, session } => {
-            handle_click(&request_id, &sessions, row, col, session).await
+        Command::Click { target, session } => {
+            handle_click(&request_id, &sessions, target, session).await
+        }
+
+        Command::MouseClick {
+            target,
+            button,
+            count,
+            modifiers,
+            session,
+        } => {
+            let click = MouseClick {
+                button,
+                count,
+                modifiers,
+            };
+            handle_mouse_click(&request_id, &sessions, target, click, session).await
+        }
+
+        Command::Drag {
+            from,
+            to,
+            button,
+            modifiers,
+            session,
+        } => handle_drag(&request_id, &sessions, from, to, button, modifiers, session).await,
+
+        Command::Hover { target, session } => {
+            handle_hover(&request_id, &sessions, target, session).await
         }
 
         Command::Scroll {
@@ -621,10 +980,84 @@ async fn handle_request(
 
         Command::WaitFor {
             pattern,
+            patterns,
+            match_mode,
+            absent,
             timeout_ms,
             regex,
+            until,
+            region,
+            scrollback,
             session,
-        } => handle_wait_for(&request_id, &sessions, pattern, timeout_ms, regex, session).await,
+        } => {
+            // Unlike `pattern`, which legacy clients leave empty, an empty
+            // entry here is a mistake: it matches any screen, and with
+            // `absent` it can never be satisfied.
+            let empty_entry = patterns.iter().any(String::is_empty);
+            // `pattern` comes first, unless a client sends only `patterns`.
+            let patterns: Vec<String> = if pattern.is_empty() && !patterns.is_empty() {
+                patterns
+            } else {
+                std::iter::once(pattern).chain(patterns).collect()
+            };
+            // An empty pattern matches at once, so it would "find" nothing.
+            // Legacy clients still get that immediate match; only requests
+            // using the newer wait options are rejected.
+            let nothing_to_find =
+                minimum_protocol >= PROTOCOL_V4 && patterns.iter().all(String::is_empty);
+            match until {
+                _ if empty_entry => Response::error(
+                    &request_id,
+                    ApiError::invalid_input_with_suggestion(
+                        "wait-for patterns cannot be empty",
+                        "Remove the empty pattern, or pass the text it should match.",
+                    ),
+                ),
+                Some(_)
+                    if absent
+                        || match_mode == MatchMode::All
+                        || region.is_some()
+                        || scrollback > 0
+                        || patterns.iter().any(|p| !p.is_empty()) =>
+                {
+                    Response::error(
+                        &request_id,
+                        ApiError::invalid_input_with_suggestion(
+                            "Wait for either screen text or a process condition, not both",
+                            "Drop the pattern options, or run two wait-for commands in sequence.",
+                        ),
+                    )
+                }
+                Some(condition) => {
+                    handle_wait_until(&request_id, &sessions, condition, timeout_ms, session).await
+                }
+                None if nothing_to_find => Response::error(
+                    &request_id,
+                    ApiError::invalid_input_with_suggestion(
+                        "wait-for needs a pattern or a process condition",
+                        "Pass the text to wait for, or one of --exit, --idle, or --foreground-change.",
+                    ),
+                ),
+                None if region.is_some() && scrollback > 0 => Response::error(
+                    &request_id,
+                    ApiError::invalid_input_with_suggestion(
+                        "--scrollback searches whole lines, so it cannot be combined with --region",
+                        "Drop --region to search scrollback, or drop --scrollback.",
+                    ),
+                ),
+                None => {
+                    let wait = TextWait {
+                        patterns,
+                        regex: regex.unwrap_or(false),
+                        match_mode,
+                        absent,
+                        region,
+                        scrollback,
+                    };
+                    handle_wait_for(&request_id, &sessions, wait, timeout_ms, session).await
+                }
+            }
+        }
 
         Command::Resize {
             cols,
@@ -632,6 +1065,36 @@ async fn handle_request(
             session,
         } => handle_resize(&request_id, &sessions, cols, rows, session).await,
 
+        Command::Signal {
+            signal,
+            target,
+            session,
+        } => handle_signal(&request_id, &sessions, signal, target, session).await,
+
+        Command::RecordStart {
+            path,
+            input,
+            session,
+        } => {
+            let options = RecordingOptions {
+                path: path.into(),
+                input,
+            };
+            handle_record_start(&request_id, &sessions, options, session).await
+        }
+
+        Command::RecordStop { session } => {
+            handle_record_stop(&request_id, &sessions, session).await
+        }
+
+        Command::Subscribe { .. } => Response::error(
+            &request_id,
+            ApiError::invalid_input_with_suggestion(
+                "Subscriptions stream over their own connection",
+                "Send subscribe as the first request on a new connection.",
+            ),
+        ),
+
         Command::Shutdown => handle_shutdown(&request_id, sessions, shutdown).await,
     };
 
@@ -658,6 +1121,7 @@ async fn handle_spawn(
     session_name: Option<String>,
     cwd: Option<String>,
     retain_bytes: Option<u64>,
+    mut options: SessionOptions,
 ) -> Response {
     if command.is_empty() {
         return Response::error(
@@ -692,7 +1156,18 @@ async fn handle_spawn(
         }
     }
 
-    let retain_bytes = match retain_bytes.map(usize::try_from).transpose() {
+    if matches!(options.size, Some(size) if size.cols == 0 || size.rows == 0) {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input("Terminal dimensions must be greater than 0"),
+        );
+    }
+
+    if let Err(error) = validate_spawn_env(&options.env) {
+        return Response::error(request_id, error);
+    }
+
+    options.retain_bytes = match retain_bytes.map(usize::try_from).transpose() {
         Ok(retain_bytes) => retain_bytes,
         Err(_) => {
             return Response::error(
@@ -706,7 +1181,7 @@ async fn handle_spawn(
     };
 
     match sessions
-        .create_session_with_retention(command.clone(), session_name, None, cwd, retain_bytes)
+        .create_session_with_options(command.clone(), session_name, cwd, options)
         .await
     {
         Ok(id) => {
@@ -723,6 +1198,32 @@ async fn handle_spawn(
     }
 }
 
+/// Reject variables the OS cannot pass to a child process.
+fn validate_spawn_env(env: &SpawnEnv) -> Result<(), ApiError> {
+    for (name, value) in &env.vars {
+        if name.is_empty() || name.contains(['=', '\0']) {
+            return Err(ApiError::invalid_input_with_suggestion(
+                format!("Invalid environment variable name '{name}'"),
+                "Pass variables as --env KEY=VALUE with a non-empty KEY that contains no '='.",
+            ));
+        }
+        if value.contains('\0') {
+            return Err(ApiError::invalid_input(format!(
+                "Environment variable '{name}' contains a NUL byte"
+            )));
+        }
+    }
+    if let Some(term) = &env.term {
+        if term.is_empty() || term.contains(|c: char| c.is_whitespace() || c.is_control()) {
+            return Err(ApiError::invalid_input_with_suggestion(
+                format!("Invalid terminal type '{term}'"),
+                "Use a terminfo name such as xterm-256color, or omit --term for the default.",
+            ));
+        }
+    }
+    Ok(())
+}
+
 /// Handle retained output command.
 async fn handle_output(
     request_id: &str,
@@ -808,6 +1309,10 @@ struct SnapshotRequestOptions {
     await_change: Option<u64>,
     settle_ms: u64,
     timeout_ms: u64,
+    since: Option<u64>,
+    region: Option<ScreenRegion>,
+    ignore: Vec<ScreenMask>,
+    scrollback: u32,
 }
 
 /// Handle immediate, wait-for-change, and settle captures.
@@ -824,7 +1329,53 @@ async fn handle_snapshot(
         await_change,
         settle_ms,
         timeout_ms,
+        since,
+        region,
+        ignore,
+        scrollback,
     } = options;
+    if since.is_some() && format != SnapshotFormat::Full {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input_with_suggestion(
+                "--since only applies to the full snapshot format",
+                "Drop --format to diff screen text, or drop --since for a complete capture.",
+            ),
+        );
+    }
+    let reports_text = matches!(format, SnapshotFormat::Full | SnapshotFormat::Text);
+    if region.is_some() && (!reports_text || since.is_some()) {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input_with_suggestion(
+                "--region only applies to full and text snapshots without --since",
+                "Use --format full or text without --since to capture a region, or drop --region for the whole screen.",
+            ),
+        );
+    }
+    if scrollback > 0 && (!reports_text || since.is_some() || region.is_some()) {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input_with_suggestion(
+                "--scrollback only applies to full and text snapshots without --since or --region",
+                "Use --format full or text without --since and --region to include scrollback, or drop --scrollback.",
+            ),
+        );
+    }
+    let mut scope = CaptureScope {
+        region,
+        scrollback: scrollback as usize,
+        ..CaptureScope::default()
+    };
+    for mask in ignore {
+        match mask {
+            ScreenMask::Region(region) => scope.masked_regions.push(region),
+            ScreenMask::Regex(pattern) => match compile_pattern(&pattern) {
+                Ok(re) => scope.masked_patterns.push(re),
+                Err(e) => return Response::error(request_id, e),
+            },
+        }
+    }
     let evidence = match sessions.resolve_evidence(session.as_deref()).await {
         Ok(evidence) => evidence,
         Err(e) => return Response::error(request_id, e),
@@ -832,7 +1383,7 @@ async fn handle_snapshot(
     let session_id = match evidence {
         SessionEvidence::Live(id) => id,
         SessionEvidence::Exited(tombstone) => {
-            return exited_snapshot_response(request_id, *tombstone, format)
+            return exited_snapshot_response(request_id, *tombstone, format, &scope, since)
         }
     };
 
@@ -842,22 +1393,36 @@ async fn handle_snapshot(
     let mut observer = match sessions.observe_session(&session_id).await {
         Ok(observer) => observer,
         Err(error) => {
-            if let Some(response) =
-                finalized_snapshot_response(request_id, sessions, &session_id, format).await
+            if let Some(response) = finalized_snapshot_response(
+                request_id,
+                sessions,
+                &session_id,
+                format,
+                &scope,
+                since,
+            )
+            .await
             {
                 return response;
             }
             return Response::error(request_id, error);
         }
     };
+    observer.scope(scope.clone());
     let mut output_closed = false;
 
     // Phase 1: If await_change is set, wait until content_hash differs
     if let Some(baseline_hash) = await_change {
         loop {
             if output_closed {
-                match exited_live_snapshot_response(request_id, &session_id, &mut observer, format)
-                    .await
+                match exited_live_snapshot_response(
+                    request_id,
+                    &session_id,
+                    &mut observer,
+                    format,
+                    since,
+                )
+                .await
                 {
                     Ok(Some(response)) => return response,
                     Ok(None) if start.elapsed() < timeout => {
@@ -878,6 +1443,7 @@ async fn handle_snapshot(
                     &mut observer,
                     SnapshotDeadline {
                         format,
+                        since,
                         output_closed,
                         note: format!(
                             "Timeout after {}ms waiting for screen to change from hash {}",
@@ -908,8 +1474,15 @@ async fn handle_snapshot(
                 }
                 ObservationEvent::Deadline => {}
                 ObservationEvent::PumpFailed => {
-                    if let Some(response) =
-                        finalized_snapshot_response(request_id, sessions, &session_id, format).await
+                    if let Some(response) = finalized_snapshot_response(
+                        request_id,
+                        sessions,
+                        &session_id,
+                        format,
+                        &scope,
+                        since,
+                    )
+                    .await
                     {
                         return response;
                     }
@@ -928,8 +1501,14 @@ async fn handle_snapshot(
 
         loop {
             if output_closed {
-                match exited_live_snapshot_response(request_id, &session_id, &mut observer, format)
-                    .await
+                match exited_live_snapshot_response(
+                    request_id,
+                    &session_id,
+                    &mut observer,
+                    format,
+                    since,
+                )
+                .await
                 {
                     Ok(Some(response)) => return response,
                     Ok(None) if start.elapsed() < timeout => {
@@ -950,6 +1529,7 @@ async fn handle_snapshot(
                     &mut observer,
                     SnapshotDeadline {
                         format,
+                        since,
                         output_closed,
                         note: format!(
                             "Timeout after {}ms waiting for screen to stabilize for {}ms (last hash: {})",
@@ -985,8 +1565,15 @@ async fn handle_snapshot(
                 }
                 ObservationEvent::Deadline => {}
                 ObservationEvent::PumpFailed => {
-                    if let Some(response) =
-                        finalized_snapshot_response(request_id, sessions, &session_id, format).await
+                    if let Some(response) = finalized_snapshot_response(
+                        request_id,
+                        sessions,
+                        &session_id,
+                        format,
+                        &scope,
+                        since,
+                    )
+                    .await
                     {
                         return response;
                     }
@@ -1008,6 +1595,7 @@ async fn handle_snapshot(
         &session_id,
         &mut observer,
         format,
+        since,
         CaptureDetails {
             outcome,
             exit: None,
@@ -1028,28 +1616,72 @@ async fn live_snapshot_response(
     session_id: &SessionId,
     observer: &mut SessionObserver,
     format: SnapshotFormat,
-    details: CaptureDetails,
+    since: Option<u64>,
+    mut details: CaptureDetails,
 ) -> Response {
-    let snapshot = observer.current().await;
+    let snapshot = observer.capture(format).await;
     debug!(
         "Captured session {} at revision {}",
         session_id, snapshot.revision
     );
-    snapshot_response(request_id, snapshot, format, details)
+    let region = observer.capture_scope().region;
+    if format == SnapshotFormat::Text {
+        return snapshot_response(request_id, snapshot, format, region, details);
+    }
+
+    let diff = since.and_then(|since| observer.diff_since(since, &snapshot));
+    observer.remember(&snapshot);
+    if let Some(diff) = diff {
+        return Response::success(
+            request_id,
+            ResponseData::ScreenDiff(DiffCapture {
+                diff,
+                outcome: details.outcome,
+                exit: details.exit,
+                note: details.note,
+            }),
+        );
+    }
+    if let Some(since) = since {
+        let fallback = baseline_unavailable_note(since);
+        details.note = Some(match details.note {
+            Some(note) => format!("{} {}", note, fallback),
+            None => fallback,
+        });
+    }
+    snapshot_response(request_id, snapshot, format, region, details)
+}
+
+/// Note for a `--since` request on an exited session.
+const EXITED_SINCE_NOTE: &str =
+    "Session has exited; returning the final screen as a full snapshot.";
+
+/// Note for a `--since` request answered with a full snapshot.
+fn baseline_unavailable_note(since: u64) -> String {
+    format!(
+        "Revision {} is no longer retained; returned a full snapshot instead of a diff.",
+        since
+    )
 }
 
 fn snapshot_response(
     request_id: &str,
     snapshot: SnapshotData,
     format: SnapshotFormat,
+    region: Option<ScreenRegion>,
     details: CaptureDetails,
 ) -> Response {
     let (cursor_row, cursor_col) = snapshot.cursor_pos;
 
     match format {
         SnapshotFormat::Text => {
-            let output =
-                format_text_snapshot(&snapshot.text, cursor_row, cursor_col, snapshot.size);
+            let output = format_scoped_text_snapshot(
+                &snapshot.text,
+                snapshot.cursor_pos,
+                snapshot.size,
+                region,
+                snapshot.scrollback.as_deref(),
+            );
             Response::success(
                 request_id,
                 ResponseData::Snapshot {
@@ -1061,31 +1693,8 @@ fn snapshot_response(
                 },
             )
         }
-        SnapshotFormat::Full => {
-            let screen_state = ScreenState {
-                size: TerminalSize {
-                    cols: snapshot.size.cols,
-                    rows: snapshot.size.rows,
-                },
-                cursor: CursorState {
-                    row: cursor_row,
-                    col: cursor_col,
-                    visible: snapshot.cursor_visible,
-                },
-                text: Some(snapshot.text),
-                content_hash: Some(snapshot.content_hash),
-            };
-            Response::success(
-                request_id,
-                ResponseData::ScreenState(ScreenCapture {
-                    screen: screen_state,
-                    outcome: details.outcome,
-                    exit: details.exit,
-                    note: details.note,
-                }),
-            )
-        }
-        SnapshotFormat::Compact => {
+        SnapshotFormat::Full | SnapshotFormat::Styled | SnapshotFormat::Compact => {
+            let include_text = format != SnapshotFormat::Compact;
             let screen_state = ScreenState {
                 size: TerminalSize {
                     cols: snapshot.size.cols,
@@ -1096,8 +1705,13 @@ fn snapshot_response(
                     col: cursor_col,
                     visible: snapshot.cursor_visible,
                 },
-                text: None,
-                content_hash: None,
+                text: include_text.then_some(snapshot.text),
+                content_hash: include_text.then_some(snapshot.content_hash),
+                styled: snapshot.styled,
+                revision: Some(snapshot.revision),
+                elements: snapshot.elements,
+                scrollback: snapshot.scrollback.map(ScrollbackLine::number),
+                modes: Some(snapshot.modes),
             };
             Response::success(
                 request_id,
@@ -1121,18 +1735,26 @@ async fn snapshot_deadline_response(
 ) -> Response {
     let SnapshotDeadline {
         format,
+        since,
         output_closed,
         note,
     } = deadline;
 
-    if let Some(response) =
-        finalized_snapshot_response(request_id, sessions, session_id, format).await
+    if let Some(response) = finalized_snapshot_response(
+        request_id,
+        sessions,
+        session_id,
+        format,
+        observer.capture_scope(),
+        since,
+    )
+    .await
     {
         return response;
     }
 
     if output_closed {
-        match exited_live_snapshot_response(request_id, session_id, observer, format).await {
+        match exited_live_snapshot_response(request_id, session_id, observer, format, since).await {
             Ok(Some(response)) => return response,
             Ok(None) => {}
             Err(response) => return response,
@@ -1149,6 +1771,7 @@ async fn snapshot_deadline_response(
         session_id,
         observer,
         format,
+        since,
         CaptureDetails {
             outcome: CaptureOutcome::Deadline,
             exit: None,
@@ -1160,6 +1783,7 @@ async fn snapshot_deadline_response(
 
 struct SnapshotDeadline {
     format: SnapshotFormat,
+    since: Option<u64>,
     output_closed: bool,
     note: String,
 }
@@ -1169,6 +1793,7 @@ async fn exited_live_snapshot_response(
     session_id: &SessionId,
     observer: &mut SessionObserver,
     format: SnapshotFormat,
+    since: Option<u64>,
 ) -> Result<Option<Response>, Response> {
     let exit = observer
         .exit_metadata()
@@ -1183,6 +1808,7 @@ async fn exited_live_snapshot_response(
             session_id,
             observer,
             format,
+            since,
             CaptureDetails {
                 outcome: CaptureOutcome::Exited,
                 exit: Some(capture_exit(exit, true)),
@@ -1198,11 +1824,13 @@ async fn finalized_snapshot_response(
     sessions: &SessionManager,
     session_id: &SessionId,
     format: SnapshotFormat,
+    scope: &CaptureScope,
+    since: Option<u64>,
 ) -> Option<Response> {
     match sessions.resolve_evidence(Some(&session_id.0)).await {
-        Ok(SessionEvidence::Exited(tombstone)) => {
-            Some(exited_snapshot_response(request_id, *tombstone, format))
-        }
+        Ok(SessionEvidence::Exited(tombstone)) => Some(exited_snapshot_response(
+            request_id, *tombstone, format, scope, since,
+        )),
         Ok(SessionEvidence::Live(_)) | Err(_) => None,
     }
 }
@@ -1217,51 +1845,75 @@ fn capture_exit(exit: ExitMetadata, output_complete: bool) -> CaptureExit {
     }
 }
 
+/// Answer from a finalized session's tombstone.
+///
+/// Tombstones keep no diff baselines, so `since` always falls back to a
+/// full snapshot with [`EXITED_SINCE_NOTE`].
 fn exited_snapshot_response(
     request_id: &str,
-    tombstone: Tombstone,
+    mut tombstone: Tombstone,
     format: SnapshotFormat,
+    scope: &CaptureScope,
+    since: Option<u64>,
 ) -> Response {
+    if !scope.is_whole_screen() {
+        let (text, content_hash) = tombstone_text_and_hash(&tombstone, scope);
+        tombstone.final_screen.content_hash = Some(content_hash);
+        tombstone.final_screen.text = Some(text);
+    }
+    if scope.scrollback > 0 {
+        let lines = std::mem::take(&mut tombstone.final_scrollback);
+        let skip = lines.len().saturating_sub(scope.scrollback);
+        tombstone.final_screen.scrollback = Some(ScrollbackLine::number(
+            lines.into_iter().skip(skip).collect(),
+        ));
+    }
     let details = CaptureDetails {
         outcome: CaptureOutcome::Exited,
         exit: Some(capture_exit(tombstone.exit, tombstone.output_complete)),
-        note: None,
+        note: since.map(|_| EXITED_SINCE_NOTE.to_string()),
     };
 
     match format {
-        SnapshotFormat::Full => Response::success(
-            request_id,
-            ResponseData::ScreenState(ScreenCapture {
-                screen: tombstone.final_screen,
-                outcome: details.outcome,
-                exit: details.exit,
-                note: details.note,
-            }),
-        ),
-        SnapshotFormat::Compact => Response::success(
-            request_id,
-            ResponseData::ScreenState(ScreenCapture {
-                screen: ScreenState {
-                    size: tombstone.final_screen.size,
-                    cursor: tombstone.final_screen.cursor,
-                    text: None,
-                    content_hash: None,
-                },
-                outcome: details.outcome,
-                exit: details.exit,
-                note: details.note,
-            }),
-        ),
+        SnapshotFormat::Full | SnapshotFormat::Styled | SnapshotFormat::Compact => {
+            // The tombstone keeps every detail; report only what the format asks for.
+            let final_screen = tombstone.final_screen;
+            let include_text = format != SnapshotFormat::Compact;
+            let screen = ScreenState {
+                text: final_screen.text.filter(|_| include_text),
+                content_hash: final_screen.content_hash.filter(|_| include_text),
+                styled: final_screen
+                    .styled
+                    .filter(|_| format == SnapshotFormat::Styled),
+                elements: final_screen
+                    .elements
+                    .filter(|_| format == SnapshotFormat::Compact),
+                ..final_screen
+            };
+            Response::success(
+                request_id,
+                ResponseData::ScreenState(ScreenCapture {
+                    screen,
+                    outcome: details.outcome,
+                    exit: details.exit,
+                    note: details.note,
+                }),
+            )
+        }
         SnapshotFormat::Text => {
-            let text = tombstone.final_screen.text.unwrap_or_default();
-            let content = format_text_snapshot(
-                &text,
-                tombstone.final_screen.cursor.row,
-                tombstone.final_screen.cursor.col,
+            let final_screen = tombstone.final_screen;
+            let scrollback: Option<Vec<String>> = final_screen
+                .scrollback
+                .map(|lines| lines.into_iter().map(|line| line.text).collect());
+            let content = format_scoped_text_snapshot(
+                &final_screen.text.unwrap_or_default(),
+                (final_screen.cursor.row, final_screen.cursor.col),
                 TermSize {
-                    cols: tombstone.final_screen.size.cols,
-                    rows: tombstone.final_screen.size.rows,
+                    cols: final_screen.size.cols,
+                    rows: final_screen.size.rows,
                 },
+                scope.region,
+                scrollback.as_deref(),
             );
             Response::success(
                 request_id,
@@ -1277,8 +1929,19 @@ fn exited_snapshot_response(
     }
 }
 
+/// Text and content hash of an exited session's final screen under `scope`.
+fn tombstone_text_and_hash(tombstone: &Tombstone, scope: &CaptureScope) -> (String, u64) {
+    let size = tombstone.final_screen.size;
+    let mut replay = TerminalEmulator::new(TermSize {
+        cols: size.cols,
+        rows: size.rows,
+    });
+    replay.feed(&tombstone.final_contents);
+    scope.text_and_hash(&replay)
+}
+
 /// Format a plain text snapshot with cursor position indicator.
-fn format_text_snapshot(
+pub(crate) fn format_text_snapshot(
     text: &str,
     cursor_row: u16,
     cursor_col: u16,
@@ -1291,8 +1954,65 @@ fn format_text_snapshot(
         "--- Terminal {}x{} | Cursor: ({}, {}) ---\n",
         size.cols, size.rows, cursor_row, cursor_col
     ));
+    push_screen_lines(&mut output, text, Some((cursor_row, cursor_col)));
+    output
+}
+
+/// Format a text snapshot of a capture limited to `region` or extended with
+/// `scrollback` lines.
+///
+/// A region's text holds only its own rows and columns, so the header names
+/// the region and the cursor is marked only when it falls inside. Scrollback
+/// lines come first, oldest first, above a `--- Screen ---` rule.
+fn format_scoped_text_snapshot(
+    text: &str,
+    cursor: (u16, u16),
+    size: TermSize,
+    region: Option<ScreenRegion>,
+    scrollback: Option<&[String]>,
+) -> String {
+    let (cursor_row, cursor_col) = cursor;
+    let mut header = format!(
+        "--- Terminal {}x{} | Cursor: ({}, {})",
+        size.cols, size.rows, cursor_row, cursor_col
+    );
+    let mut marked = Some(cursor);
+    if let Some(region) = region {
+        let (rows, cols) = region.resolve(TerminalSize {
+            cols: size.cols,
+            rows: size.rows,
+        });
+        header.push_str(&format!(
+            " | Region: rows {}..{}, cols {}..{}",
+            rows.start, rows.end, cols.start, cols.end
+        ));
+        marked = (rows.contains(&cursor_row) && cols.contains(&cursor_col))
+            .then(|| (cursor_row - rows.start, cursor_col - cols.start));
+    }
+
+    let mut output = format!("{} ---\n", header);
+    if let Some(lines) = scrollback {
+        output.push_str(&format!("--- Scrollback: {} lines ---\n", lines.len()));
+        for line in lines {
+            output.push_str(line);
+            output.push('\n');
+        }
+        output.push_str("--- Screen ---\n");
+    }
+    push_screen_lines(&mut output, text, marked);
+    output
+}
+
+/// Append screen rows to `output`, marking the cursor cell if given.
+fn push_screen_lines(output: &mut String, text: &str, cursor: Option<(u16, u16)>) {
+    let Some((cursor_row, cursor_col)) = cursor else {
+        for line in text.lines() {
+            output.push_str(line);
+            output.push('\n');
+        }
+        return;
+    };
 
-    // Screen content
     for (row_idx, line) in text.lines().enumerate() {
         if row_idx == cursor_row as usize {
             // Mark cursor position in this line using char_indices to avoid Vec<char> allocation
@@ -1337,8 +2057,6 @@ fn format_text_snapshot(
         output.push_str(&" ".repeat(cursor_col as usize));
         output.push_str("[_]\n");
     }
-
-    output
 }
 
 /// Handle list-sessions command.
@@ -1411,6 +2129,49 @@ async fn handle_type(
     }
 }
 
+/// Handle paste command - write text the way a terminal pastes it.
+///
+/// Bracketed when the application enabled bracketed paste (`?2004`), so
+/// editors and REPLs take the text as a whole instead of auto-indenting or
+/// running it line by line; typed as is otherwise.
+async fn handle_paste(
+    request_id: &str,
+    sessions: &SessionManager,
+    text: String,
+    session: Option<String>,
+) -> Response {
+    use pilotty_core::input::encode_paste;
+
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+    let bracketed = match sessions.get_terminal_modes(&session_id).await {
+        Ok(modes) => modes.bracketed_paste,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    let bytes = encode_paste(&text, bracketed);
+    match sessions.write_to_session(&session_id, &bytes).await {
+        Ok(()) => {
+            debug!(
+                "Pasted {} bytes to session {} (bracketed: {})",
+                bytes.len(),
+                session_id,
+                bracketed
+            );
+            Response::success(
+                request_id,
+                ResponseData::Pasted {
+                    bytes: bytes.len(),
+                    bracketed,
+                },
+            )
+        }
+        Err(e) => Response::error(request_id, e),
+    }
+}
+
 /// Handle key command - send key, key combo, or key sequence to PTY.
 ///
 /// Supports space-separated key sequences like "Ctrl+X m" for chords.
@@ -1422,7 +2183,7 @@ async fn handle_key(
     delay_ms: u32,
     session: Option<String>,
 ) -> Response {
-    use pilotty_core::input::parse_key_sequence;
+    use pilotty_core::input::{parse_key_sequence, unsupported_key};
 
     // Validate delay_ms to prevent DoS
     if delay_ms > MAX_KEY_DELAY_MS {
@@ -1444,27 +2205,30 @@ async fn handle_key(
         Err(e) => return Response::error(request_id, e),
     };
 
-    // Get application cursor mode for proper arrow key encoding.
-    // TUI apps like dialog, vim, htop enable this mode (DECCKM) and expect
-    // arrow keys to send SS3 sequences (\x1bO) instead of CSI (\x1b[).
-    let app_cursor = sessions
-        .get_application_cursor_mode(&session_id)
+    // Keys are encoded for the modes the application switched on.
+    // TUI apps like dialog, vim, htop enable application cursor mode (DECCKM)
+    // and expect arrow keys to send SS3 sequences (\x1bO) instead of CSI
+    // (\x1b[); apps like helix and neovim negotiate the kitty keyboard
+    // protocol or modifyOtherKeys to tell Ctrl+I from Tab.
+    let modes = sessions
+        .get_terminal_modes(&session_id)
         .await
-        .unwrap_or(false);
+        .unwrap_or_default();
 
     // Parse key sequence (handles single keys, combos, and space-separated sequences)
-    let sequence = match parse_key_sequence(&key, app_cursor) {
-        Some(seq) => seq,
-        None => {
-            return Response::error(
-                request_id,
-                ApiError::invalid_input_with_suggestion(
-                    format!("Invalid key: '{}'", key),
-                    "Use named keys (Enter, Tab, Escape, F1), combos (Ctrl+C, Alt+F), \
-                     or space-separated sequences (\"Ctrl+X m\"). Run 'pilotty key --help' for examples.",
-                ),
-            );
-        }
+    let Some(sequence) = parse_key_sequence(&key, modes) else {
+        let error = match unsupported_key(&key, modes) {
+            Some(unsupported) => ApiError::invalid_input_with_suggestion(
+                format!("Unsupported key in this keyboard mode: '{}'", unsupported),
+                "Pause, PrintScreen, and ScrollLock only reach applications that enabled the kitty keyboard protocol (modes.kitty_keyboard in a snapshot).",
+            ),
+            None => ApiError::invalid_input_with_suggestion(
+                format!("Invalid key: '{}'", key),
+                "Use named keys (Enter, Tab, Escape, F1), combos (Ctrl+C, Alt+F), \
+                 or space-separated sequences (\"Ctrl+X m\"). Run 'pilotty key --help' for examples.",
+            ),
+        };
+        return Response::error(request_id, error);
     };
 
     // Validate sequence length to prevent DoS
@@ -1513,21 +2277,116 @@ async fn handle_key(
 async fn handle_click(
     request_id: &str,
     sessions: &SessionManager,
-    row: u16,
-    col: u16,
+    target: ClickTarget,
+    session: Option<String>,
+) -> Response {
+    let click = MouseClick {
+        button: MouseButton::Left,
+        count: 1,
+        modifiers: MouseModifiers::default(),
+    };
+    handle_mouse_click(request_id, sessions, target, click, session).await
+}
+
+/// Button, click count, and modifier keys of a mouse click.
+struct MouseClick {
+    button: MouseButton,
+    count: u8,
+    modifiers: MouseModifiers,
+}
+
+impl MouseClick {
+    /// Describe the click for a response message, e.g. "Double-clicked (right, Ctrl)".
+    fn describe(&self) -> String {
+        let verb = match self.count {
+            1 => "Clicked",
+            2 => "Double-clicked",
+            _ => "Triple-clicked",
+        };
+        let mut details = Vec::new();
+        match self.button {
+            MouseButton::Left => {}
+            MouseButton::Middle => details.push("middle"),
+            MouseButton::Right => details.push("right"),
+        }
+        details.extend(modifier_names(self.modifiers));
+        if details.is_empty() {
+            verb.to_string()
+        } else {
+            format!("{} ({})", verb, details.join(", "))
+        }
+    }
+}
+
+/// Names of the held modifier keys, in Shift, Ctrl, Alt order.
+fn modifier_names(modifiers: MouseModifiers) -> impl Iterator<Item = &'static str> {
+    [
+        (modifiers.shift, "Shift"),
+        (modifiers.ctrl, "Ctrl"),
+        (modifiers.alt, "Alt"),
+    ]
+    .into_iter()
+    .filter_map(|(held, name)| held.then_some(name))
+}
+
+/// Handle mouse click command - click with any button, count, and modifiers.
+async fn handle_mouse_click(
+    request_id: &str,
+    sessions: &SessionManager,
+    target: ClickTarget,
+    click: MouseClick,
     session: Option<String>,
 ) -> Response {
+    if !(1..=MAX_CLICK_COUNT).contains(&click.count) {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input_with_suggestion(
+                format!(
+                    "Click count {} is outside 1 to {}",
+                    click.count, MAX_CLICK_COUNT
+                ),
+                "Use --count 2 for a double click or --count 3 for a triple click.",
+            ),
+        );
+    }
+
     // Resolve session
     let session_id = match sessions.resolve_session(session.as_deref()).await {
         Ok(id) => id,
         Err(e) => return Response::error(request_id, e),
     };
 
+    let modes = match mouse_modes(sessions, &session_id).await {
+        Ok(modes) => modes,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    let (row, col) = match locate_click_target(sessions, &session_id, target).await {
+        Ok(position) => position,
+        Err(e) => return Response::error(request_id, e),
+    };
+
     // Generate mouse click sequence (encode_mouse_click_combined takes col, row)
-    let click_bytes = encode_mouse_click_combined(col, row);
+    let Some(click_bytes) = encode_mouse_click_combined(
+        col,
+        row,
+        click.button,
+        click.modifiers,
+        modes.mouse_tracking,
+        modes.mouse_encoding,
+    ) else {
+        return Response::error(
+            request_id,
+            mouse_position_unsupported(modes.mouse_encoding, row, col),
+        );
+    };
 
-    // Send the click
-    if let Err(e) = sessions.write_to_session(&session_id, &click_bytes).await {
+    // Send every click in one write so the application sees them as a
+    // double or triple click rather than separate clicks.
+    if let Err(e) = sessions
+        .write_to_session(&session_id, &click_bytes.repeat(usize::from(click.count)))
+        .await
+    {
         return Response::error(request_id, e);
     }
 
@@ -1536,156 +2395,567 @@ async fn handle_click(
     Response::success(
         request_id,
         ResponseData::Ok {
-            message: format!("Clicked at row {}, col {}", row, col),
+            message: format!("{} at row {}, col {}", click.describe(), row, col),
         },
     )
 }
 
-/// Handle scroll command.
-async fn handle_scroll(
+/// Handle drag command - press at one position, move to another, release.
+async fn handle_drag(
     request_id: &str,
     sessions: &SessionManager,
-    direction: pilotty_core::protocol::ScrollDirection,
-    amount: u32,
+    from: ClickTarget,
+    to: ClickTarget,
+    button: MouseButton,
+    modifiers: MouseModifiers,
     session: Option<String>,
 ) -> Response {
-    use pilotty_core::input::encode_scroll;
+    // Resolve session
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
 
-    if amount > MAX_SCROLL_AMOUNT {
+    let modes = match mouse_modes(sessions, &session_id).await {
+        Ok(modes) => modes,
+        Err(e) => return Response::error(request_id, e),
+    };
+    if modes.mouse_tracking == MouseTracking::Press {
         return Response::error(
             request_id,
-            ApiError::invalid_input_with_suggestion(
+            ApiError::command_failed_with_suggestion(
                 format!(
-                    "Scroll amount {} exceeds maximum {}",
-                    amount, MAX_SCROLL_AMOUNT
+                    "The application in session '{}' reports mouse presses only, so it cannot see a drag",
+                    session_id
                 ),
-                "Use a smaller scroll amount (<= 1000).",
+                "Click the start and end points instead, or use the application's keyboard selection.",
             ),
         );
     }
 
-    // Resolve session
-    let session_id = match sessions.resolve_session(session.as_deref()).await {
-        Ok(id) => id,
+    // Both ends are located on the screen as it is before the drag starts.
+    let (from_row, from_col) = match locate_click_target(sessions, &session_id, from).await {
+        Ok(position) => position,
+        Err(e) => return Response::error(request_id, e),
+    };
+    let (to_row, to_col) = match locate_click_target(sessions, &session_id, to).await {
+        Ok(position) => position,
         Err(e) => return Response::error(request_id, e),
     };
 
-    // Get terminal size and scroll at center
-    let (scroll_x, scroll_y) = match sessions.get_terminal_size(&session_id).await {
-        Ok(size) => (size.cols / 2, size.rows / 2),
-        Err(_) => (40, 12), // Fallback if session gone mid-request
+    let Some(drag_bytes) = encode_mouse_drag(
+        (from_col, from_row),
+        (to_col, to_row),
+        button,
+        modifiers,
+        modes.mouse_tracking,
+        modes.mouse_encoding,
+    ) else {
+        let (row, col) = (from_row.max(to_row), from_col.max(to_col));
+        return Response::error(
+            request_id,
+            mouse_position_unsupported(modes.mouse_encoding, row, col),
+        );
     };
 
-    // Send scroll events
-    for _ in 0..amount {
-        let scroll_bytes = encode_scroll(direction, scroll_x, scroll_y);
-        if let Err(e) = sessions.write_to_session(&session_id, &scroll_bytes).await {
-            return Response::error(request_id, e);
-        }
+    if let Err(e) = sessions.write_to_session(&session_id, &drag_bytes).await {
+        return Response::error(request_id, e);
     }
 
-    let dir_str = match direction {
-        pilotty_core::protocol::ScrollDirection::Up => "up",
-        pilotty_core::protocol::ScrollDirection::Down => "down",
-    };
-
     debug!(
-        "Scrolled {} {} times in session {}",
-        dir_str, amount, session_id
+        "Dragged from ({}, {}) to ({}, {}) in session {}",
+        from_row, from_col, to_row, to_col, session_id
     );
 
     Response::success(
         request_id,
         ResponseData::Ok {
-            message: format!("Scrolled {} {} times", dir_str, amount),
+            message: format!(
+                "Dragged from row {}, col {} to row {}, col {}",
+                from_row, from_col, to_row, to_col
+            ),
         },
     )
 }
 
-/// Handle resize command.
-async fn handle_resize(
+/// Handle hover command - move the pointer without pressing a button.
+async fn handle_hover(
     request_id: &str,
     sessions: &SessionManager,
-    cols: u16,
-    rows: u16,
+    target: ClickTarget,
     session: Option<String>,
 ) -> Response {
-    // Validate dimensions
-    if cols == 0 || rows == 0 {
+    // Resolve session
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    let modes = match mouse_modes(sessions, &session_id).await {
+        Ok(modes) => modes,
+        Err(e) => return Response::error(request_id, e),
+    };
+    if modes.mouse_tracking != MouseTracking::AnyMotion {
         return Response::error(
             request_id,
-            ApiError::invalid_input("Terminal dimensions must be greater than 0"),
+            ApiError::command_failed_with_suggestion(
+                format!(
+                    "The application in session '{}' does not report mouse motion without a button held",
+                    session_id
+                ),
+                "Hover needs any-event tracking (modes.mouse_tracking \"any_motion\" in a snapshot). Click the element instead.",
+            ),
         );
     }
 
-    // Resolve session
-    let session_id = match sessions.resolve_session(session.as_deref()).await {
-        Ok(id) => id,
+    let (row, col) = match locate_click_target(sessions, &session_id, target).await {
+        Ok(position) => position,
         Err(e) => return Response::error(request_id, e),
     };
 
-    // Resize the session
-    match sessions.resize_session(&session_id, cols, rows).await {
-        Ok(()) => {
-            debug!("Resized session {} to {}x{}", session_id, cols, rows);
-            Response::success(
-                request_id,
-                ResponseData::Ok {
-                    message: format!("Resized terminal to {}x{}", cols, rows),
-                },
-            )
-        }
-        Err(e) => Response::error(request_id, e),
+    let Some(hover_bytes) =
+        encode_mouse_hover(col, row, modes.mouse_tracking, modes.mouse_encoding)
+    else {
+        return Response::error(
+            request_id,
+            mouse_position_unsupported(modes.mouse_encoding, row, col),
+        );
+    };
+
+    if let Err(e) = sessions.write_to_session(&session_id, &hover_bytes).await {
+        return Response::error(request_id, e);
     }
+
+    debug!("Hovered at ({}, {}) in session {}", row, col, session_id);
+
+    Response::success(
+        request_id,
+        ResponseData::Ok {
+            message: format!("Hovered at row {}, col {}", row, col),
+        },
+    )
 }
 
-/// Handle wait-for command - poll for text pattern.
-async fn handle_wait_for(
-    request_id: &str,
+/// Resolve a click target to a (row, col) cell.
+async fn locate_click_target(
     sessions: &SessionManager,
-    pattern: String,
-    timeout_ms: Option<u64>,
-    regex: Option<bool>,
-    session: Option<String>,
-) -> Response {
-    use std::time::{Duration, Instant};
+    session_id: &SessionId,
+    target: ClickTarget,
+) -> Result<(u16, u16), ApiError> {
+    match target {
+        ClickTarget::Cell { row, col } => Ok((row, col)),
+        ClickTarget::Text { text, regex, nth } => {
+            locate_click_text(sessions, session_id, &text, regex, nth).await
+        }
+        ClickTarget::Ref { r#ref } => locate_click_ref(sessions, session_id, &r#ref).await,
+    }
+}
 
-    let timeout = Duration::from_millis(timeout_ms.unwrap_or(30000));
-    let use_regex = regex.unwrap_or(false);
+/// Mouse modes of a session whose application reports mouse events.
+async fn mouse_modes(
+    sessions: &SessionManager,
+    session_id: &SessionId,
+) -> Result<TerminalModes, ApiError> {
+    let modes = sessions.get_terminal_modes(session_id).await?;
+    if modes.mouse_tracking == MouseTracking::None {
+        return Err(ApiError::mouse_not_enabled(&session_id.0));
+    }
+    Ok(modes)
+}
 
-    // Resolve session first
-    let session_id = match sessions.resolve_session(session.as_deref()).await {
-        Ok(id) => id,
-        Err(e) => return Response::error(request_id, e),
+/// Error for a mouse position the application's encoding cannot report.
+fn mouse_position_unsupported(encoding: MouseEncoding, row: u16, col: u16) -> ApiError {
+    let name = match encoding {
+        MouseEncoding::X10 => "X10",
+        MouseEncoding::Utf8 => "UTF-8",
+        MouseEncoding::Sgr => "SGR",
+        MouseEncoding::Urxvt => "urxvt",
     };
+    ApiError::invalid_input_with_suggestion(
+        format!(
+            "Row {}, col {} is beyond what the application's {} mouse encoding can report",
+            row, col, name
+        ),
+        "X10 mouse reports reach row and column 222, and UTF-8 reports 2014. Click closer to the top-left, or resize the session smaller.",
+    )
+}
 
-    // Compile regex if needed.
-    // Limit compiled pattern size to prevent slow compilation.
-    let compiled_regex = if use_regex {
-        match regex::RegexBuilder::new(&pattern)
-            .size_limit(256 * 1024) // 256KB compiled size limit
-            .build()
-        {
-            Ok(r) => Some(r),
-            Err(e) => {
-                return Response::error(
-                    request_id,
-                    ApiError::invalid_input_with_suggestion(
-                        format!("Invalid regex pattern: {}", e),
-                        "Check your regex syntax. Common issues: unescaped special chars, unbalanced parentheses, or pattern too complex.",
-                    ),
-                );
-            }
-        }
+/// Find the center cell of the `nth` on-screen match of `text`.
+///
+/// Matches are searched row by row in reading order and never span rows.
+async fn locate_click_text(
+    sessions: &SessionManager,
+    session_id: &SessionId,
+    text: &str,
+    regex: bool,
+    nth: u32,
+) -> Result<(u16, u16), ApiError> {
+    if text.is_empty() {
+        return Err(ApiError::invalid_input_with_suggestion(
+            "Click text cannot be empty",
+            "Pass the visible label to click, e.g., 'pilotty click --text Save'.",
+        ));
+    }
+    if nth == 0 {
+        return Err(ApiError::invalid_input_with_suggestion(
+            "--nth must be at least 1",
+            "Matches are numbered from 1 in reading order; use --nth 1 for the first match.",
+        ));
+    }
+
+    let pattern = if regex {
+        compile_pattern(text)?
     } else {
-        None
+        compile_pattern(&regex::escape(text))?
     };
+    let lines = sessions.screen_lines(session_id).await?;
+    let matches: Vec<(u16, u16)> = lines
+        .iter()
+        .flat_map(|line| {
+            pattern
+                .find_iter(&line.text)
+                .filter(|found| !found.as_str().trim().is_empty())
+                .map(move |found| {
+                    let (col, width) = line.columns(found.start(), found.end());
+                    (line.row, col + width.saturating_sub(1) / 2)
+                })
+        })
+        .collect();
+
+    let count = matches.len();
+    matches
+        .get(nth as usize - 1)
+        .copied()
+        .ok_or_else(|| match count {
+            0 => ApiError::command_failed_with_suggestion(
+                format!("No text matching '{}' on screen", text),
+                "Take a snapshot to check the visible text, or wait for it first with 'pilotty wait-for'.",
+            ),
+            _ => ApiError::command_failed_with_suggestion(
+                format!(
+                    "Only {} match(es) for '{}' on screen, but --nth {} was requested",
+                    count, text, nth
+                ),
+                "Use a smaller --nth, or a more specific pattern.",
+            ),
+        })
+}
+
+/// Find the center cell of the element labeled `element_ref` on the current
+/// screen.
+async fn locate_click_ref(
+    sessions: &SessionManager,
+    session_id: &SessionId,
+    element_ref: &str,
+) -> Result<(u16, u16), ApiError> {
+    let elements = sessions.elements(session_id).await?;
+    elements
+        .iter()
+        .find(|element| element.id == element_ref)
+        .map(|element| {
+            (
+                element.row + element.height.saturating_sub(1) / 2,
+                element.col + element.width.saturating_sub(1) / 2,
+            )
+        })
+        .ok_or_else(|| {
+            ApiError::command_failed_with_suggestion(
+                format!("No element '{}' on the current screen", element_ref),
+                "Refs are renumbered as the screen changes. Take a fresh snapshot with 'pilotty snapshot --format compact' and use a ref from it.",
+            )
+        })
+}
+
+/// Compile a user-supplied pattern with a bounded compiled size.
+fn compile_pattern(pattern: &str) -> Result<regex::Regex, ApiError> {
+    regex::RegexBuilder::new(pattern)
+        .size_limit(256 * 1024) // 256KB compiled size limit
+        .build()
+        .map_err(|e| {
+            ApiError::invalid_input_with_suggestion(
+                format!("Invalid regex pattern: {}", e),
+                "Check your regex syntax. Common issues: unescaped special chars, unbalanced parentheses, or pattern too complex.",
+            )
+        })
+}
+
+/// Handle scroll command.
+async fn handle_scroll(
+    request_id: &str,
+    sessions: &SessionManager,
+    direction: pilotty_core::protocol::ScrollDirection,
+    amount: u32,
+    session: Option<String>,
+) -> Response {
+    use pilotty_core::input::encode_scroll;
+
+    if amount > MAX_SCROLL_AMOUNT {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input_with_suggestion(
+                format!(
+                    "Scroll amount {} exceeds maximum {}",
+                    amount, MAX_SCROLL_AMOUNT
+                ),
+                "Use a smaller scroll amount (<= 1000).",
+            ),
+        );
+    }
+
+    // Resolve session
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    let modes = match mouse_modes(sessions, &session_id).await {
+        Ok(modes) => modes,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    // Get terminal size and scroll at center
+    let (scroll_x, scroll_y) = match sessions.get_terminal_size(&session_id).await {
+        Ok(size) => (size.cols / 2, size.rows / 2),
+        Err(_) => (40, 12), // Fallback if session gone mid-request
+    };
+
+    // Send scroll events
+    for _ in 0..amount {
+        let Some(scroll_bytes) = encode_scroll(direction, scroll_x, scroll_y, modes.mouse_encoding)
+        else {
+            return Response::error(
+                request_id,
+                mouse_position_unsupported(modes.mouse_encoding, scroll_y, scroll_x),
+            );
+        };
+        if let Err(e) = sessions.write_to_session(&session_id, &scroll_bytes).await {
+            return Response::error(request_id, e);
+        }
+    }
+
+    let dir_str = match direction {
+        pilotty_core::protocol::ScrollDirection::Up => "up",
+        pilotty_core::protocol::ScrollDirection::Down => "down",
+    };
+
+    debug!(
+        "Scrolled {} {} times in session {}",
+        dir_str, amount, session_id
+    );
+
+    Response::success(
+        request_id,
+        ResponseData::Ok {
+            message: format!("Scrolled {} {} times", dir_str, amount),
+        },
+    )
+}
+
+/// Handle resize command.
+async fn handle_resize(
+    request_id: &str,
+    sessions: &SessionManager,
+    cols: u16,
+    rows: u16,
+    session: Option<String>,
+) -> Response {
+    // Validate dimensions
+    if cols == 0 || rows == 0 {
+        return Response::error(
+            request_id,
+            ApiError::invalid_input("Terminal dimensions must be greater than 0"),
+        );
+    }
+
+    // Resolve session
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    // Resize the session
+    match sessions.resize_session(&session_id, cols, rows).await {
+        Ok(()) => {
+            debug!("Resized session {} to {}x{}", session_id, cols, rows);
+            Response::success(
+                request_id,
+                ResponseData::Ok {
+                    message: format!("Resized terminal to {}x{}", cols, rows),
+                },
+            )
+        }
+        Err(e) => Response::error(request_id, e),
+    }
+}
+
+/// Handle signal command.
+async fn handle_signal(
+    request_id: &str,
+    sessions: &SessionManager,
+    signal: Signal,
+    target: SignalTarget,
+    session: Option<String>,
+) -> Response {
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    match sessions.signal_session(&session_id, signal, target).await {
+        Ok(pid) => {
+            debug!(
+                "Sent {} to {:?} {} of session {}",
+                signal, target, pid, session_id
+            );
+            let recipient = match target {
+                SignalTarget::Child => "process",
+                SignalTarget::Foreground => "foreground process group",
+            };
+            Response::success(
+                request_id,
+                ResponseData::Ok {
+                    message: format!("Sent {signal} to {recipient} {pid}"),
+                },
+            )
+        }
+        Err(e) => Response::error(request_id, e),
+    }
+}
+
+/// Handle record start command.
+async fn handle_record_start(
+    request_id: &str,
+    sessions: &SessionManager,
+    options: RecordingOptions,
+    session: Option<String>,
+) -> Response {
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    let path = options.path.display().to_string();
+    match sessions.start_recording(&session_id, options).await {
+        Ok(()) => {
+            info!("Recording session {} to {}", session_id, path);
+            Response::success(
+                request_id,
+                ResponseData::Ok {
+                    message: format!("Recording to {}", path),
+                },
+            )
+        }
+        Err(e) => Response::error(request_id, e),
+    }
+}
+
+/// Handle record stop command.
+async fn handle_record_stop(
+    request_id: &str,
+    sessions: &SessionManager,
+    session: Option<String>,
+) -> Response {
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    match sessions.stop_recording(&session_id).await {
+        Ok((path, events)) => {
+            info!("Stopped recording session {}", session_id);
+            Response::success(
+                request_id,
+                ResponseData::Ok {
+                    message: format!("Saved {} events to {}", events, path.display()),
+                },
+            )
+        }
+        Err(e) => Response::error(request_id, e),
+    }
+}
+
+/// Screen text a wait-for command blocks on.
+struct TextWait {
+    /// Patterns in request order; `pattern_index` refers to positions here.
+    patterns: Vec<String>,
+    regex: bool,
+    match_mode: MatchMode,
+    absent: bool,
+    /// Screen region the patterns are matched against, if not the whole screen.
+    region: Option<ScreenRegion>,
+    /// Off-screen lines searched above the screen.
+    scrollback: u32,
+}
+
+impl TextWait {
+    /// Describe the awaited condition for timeout errors.
+    fn describe(&self) -> String {
+        let quoted: Vec<String> = self.patterns.iter().map(|p| format!("'{}'", p)).collect();
+        let joiner = match self.match_mode {
+            MatchMode::Any => " or ",
+            MatchMode::All => " and ",
+        };
+        let mut patterns = quoted.join(joiner);
+        if let Some(region) = &self.region {
+            patterns = format!("{} in {}", patterns, region);
+        }
+        if self.scrollback > 0 {
+            patterns = format!(
+                "{} (with {} lines of scrollback)",
+                patterns, self.scrollback
+            );
+        }
+        if self.absent {
+            format!("{} to disappear", patterns)
+        } else {
+            patterns
+        }
+    }
+}
+
+/// Handle wait-for command - poll for text patterns to appear or disappear.
+async fn handle_wait_for(
+    request_id: &str,
+    sessions: &SessionManager,
+    wait: TextWait,
+    timeout_ms: Option<u64>,
+    session: Option<String>,
+) -> Response {
+    use std::time::{Duration, Instant};
+
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(30000));
+
+    // Resolve session first
+    let session_id = match sessions.resolve_session(session.as_deref()).await {
+        Ok(id) => id,
+        Err(e) => return Response::error(request_id, e),
+    };
+
+    // Literal patterns are escaped so every pattern matches the same way.
+    // Limit compiled pattern size to prevent slow compilation.
+    let mut compiled = Vec::with_capacity(wait.patterns.len());
+    for pattern in &wait.patterns {
+        let source = if wait.regex {
+            pattern.clone()
+        } else {
+            regex::escape(pattern)
+        };
+        match compile_pattern(&source) {
+            Ok(re) => compiled.push(re),
+            Err(e) => return Response::error(request_id, e),
+        }
+    }
 
     let start = Instant::now();
     let mut observer = match sessions.observe_session(&session_id).await {
         Ok(observer) => observer,
         Err(error) => return Response::error(request_id, error),
     };
+    observer.scope(CaptureScope {
+        region: wait.region,
+        scrollback: wait.scrollback as usize,
+        ..CaptureScope::default()
+    });
 
     loop {
         // Check timeout first
@@ -1695,42 +2965,65 @@ async fn handle_wait_for(
                 request_id,
                 ApiError::command_failed_with_suggestion(
                     format!(
-                        "Timeout waiting for '{}' after {}ms",
-                        pattern,
+                        "Timeout waiting for {} after {}ms",
+                        wait.describe(),
                         elapsed.as_millis()
                     ),
-                    "The pattern was not found within the timeout. Try increasing --timeout or check if the expected text actually appears.",
+                    if wait.absent {
+                        "The text was still on screen when the timeout expired. Try increasing --timeout, or take a snapshot to see what is showing."
+                    } else {
+                        "The pattern was not found within the timeout. Try increasing --timeout or check if the expected text actually appears."
+                    },
                 ),
             );
         }
 
-        // Get current screen text.
+        // Get current screen text, below any requested scrollback.
         let snapshot = observer.current().await;
+        let text = match &snapshot.scrollback {
+            Some(lines) if !lines.is_empty() => format!("{}\n{}", lines.join("\n"), snapshot.text),
+            _ => snapshot.text,
+        };
 
-        // Check for match
-        let matched = if let Some(ref re) = compiled_regex {
-            re.find(&snapshot.text).map(|m| m.as_str().to_string())
-        } else {
-            // Plain text search
-            if snapshot.text.contains(&pattern) {
-                Some(pattern.clone())
-            } else {
-                None
+        // A pattern satisfies the wait when it is present, or gone for --absent.
+        let found: Vec<Option<String>> = compiled
+            .iter()
+            .map(|re| re.find(&text).map(|m| m.as_str().to_string()))
+            .collect();
+        let satisfied = |found: &Option<String>| found.is_some() != wait.absent;
+        let outcome = match wait.match_mode {
+            MatchMode::Any => found
+                .iter()
+                .position(satisfied)
+                .map(|index| (Some(index), found[index].clone())),
+            MatchMode::All if found.iter().all(satisfied) => {
+                // Report text only when it is unambiguous.
+                let matched_text = match found.as_slice() {
+                    [only] => only.clone(),
+                    _ => None,
+                };
+                Some((None, matched_text))
             }
+            MatchMode::All => None,
         };
 
-        if let Some(matched_text) = matched {
+        if let Some((pattern_index, matched_text)) = outcome {
             let elapsed_ms = start.elapsed().as_millis() as u64;
             debug!(
-                "Found '{}' after {}ms in session {}",
-                matched_text, elapsed_ms, session_id
+                "Wait for {} satisfied after {}ms in session {}",
+                wait.describe(),
+                elapsed_ms,
+                session_id
             );
             return Response::success(
                 request_id,
                 ResponseData::WaitForResult {
                     found: true,
-                    matched_text: Some(matched_text),
+                    matched_text,
                     elapsed_ms,
+                    pattern_index,
+                    exit: None,
+                    foreground: None,
                 },
             );
         }
@@ -1749,31 +3042,163 @@ async fn handle_wait_for(
     }
 }
 
-fn pump_failure_response(request_id: &str) -> Response {
-    Response::error(
-        request_id,
-        ApiError::command_failed_with_suggestion(
-            "Session output pump stopped unexpectedly",
-            "Inspect daemon logs, then stop and restart the daemon before retrying.",
-        ),
-    )
-}
-
-/// Handle shutdown command - gracefully stop the daemon.
+/// How often process conditions are re-checked when no output arrives.
 ///
-/// Kills all sessions and signals the main run loop to exit.
-/// The DaemonServer's Drop impl cleans up the socket and PID files.
-async fn handle_shutdown(
+/// Neither process exit nor a foreground change necessarily produces output,
+/// so those waits poll between observation wakeups.
+const PROCESS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Handle wait-for with a process condition instead of screen text.
+async fn handle_wait_until(
     request_id: &str,
-    sessions: Arc<SessionManager>,
-    shutdown: Arc<Notify>,
+    sessions: &SessionManager,
+    condition: ProcessCondition,
+    timeout_ms: Option<u64>,
+    session: Option<String>,
 ) -> Response {
-    info!("Received shutdown command, stopping daemon");
+    let timeout = Duration::from_millis(timeout_ms.unwrap_or(30000));
+    let start = Instant::now();
+    let met = |exit, foreground| {
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        Response::success(
+            request_id,
+            ResponseData::WaitForResult {
+                found: true,
+                matched_text: None,
+                elapsed_ms,
+                pattern_index: None,
+                exit,
+                foreground,
+            },
+        )
+    };
 
-    // Spawn shutdown task to run after we return the response
-    tokio::spawn(async move {
-        // Kill all sessions first
-        kill_all_sessions(&sessions).await;
+    // A session that already ended satisfies an exit wait with its tombstone.
+    let session_id = match sessions.resolve_evidence(session.as_deref()).await {
+        Ok(SessionEvidence::Live(id)) => id,
+        Ok(SessionEvidence::Exited(tombstone)) if condition == ProcessCondition::Exit => {
+            return met(
+                Some(capture_exit(tombstone.exit, tombstone.output_complete)),
+                None,
+            );
+        }
+        Ok(SessionEvidence::Exited(tombstone)) => {
+            let identifier = session.as_deref().unwrap_or("default");
+            return Response::error(
+                request_id,
+                ApiError::session_exited(identifier, &tombstone.exit.description()),
+            );
+        }
+        Err(error) => return Response::error(request_id, error),
+    };
+    let mut observer = match sessions.observe_session(&session_id).await {
+        Ok(observer) => observer,
+        Err(error) => return Response::error(request_id, error),
+    };
+    let initial_foreground = observer.foreground_process_group();
+
+    loop {
+        let mut poll = PROCESS_POLL_INTERVAL;
+        match condition {
+            ProcessCondition::Exit => match observed_exit(sessions, &session_id, &observer).await {
+                Ok(Some(exit)) => return met(Some(exit), None),
+                Ok(None) => {}
+                Err(error) => return Response::error(request_id, error),
+            },
+            ProcessCondition::Idle { idle_ms } => {
+                let idle_for = observer.idle_for();
+                let threshold = Duration::from_millis(idle_ms);
+                if idle_for >= threshold {
+                    return met(None, None);
+                }
+                poll = threshold - idle_for;
+            }
+            ProcessCondition::ForegroundChange => {
+                let foreground = observer.foreground_process_group();
+                if foreground != initial_foreground {
+                    let process =
+                        foreground.and_then(|pgid| processes::process_info(pgid.unsigned_abs()));
+                    return met(None, process);
+                }
+            }
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            let waited_for = match condition {
+                ProcessCondition::Exit => "process exit".to_string(),
+                ProcessCondition::Idle { idle_ms } => format!("{idle_ms}ms without output"),
+                ProcessCondition::ForegroundChange => "a foreground process change".to_string(),
+            };
+            return Response::error(
+                request_id,
+                ApiError::command_failed_with_suggestion(
+                    format!(
+                        "Timeout waiting for {} after {}ms",
+                        waited_for,
+                        elapsed.as_millis()
+                    ),
+                    "The condition was not met within the timeout. Try increasing --timeout, or run 'pilotty status' to see what the session is doing.",
+                ),
+            );
+        }
+
+        let poll = poll.min(timeout - elapsed);
+        match observer.wait_for_update(poll).await {
+            ObservationEvent::Updated | ObservationEvent::Deadline => {}
+            ObservationEvent::OutputClosed => tokio::time::sleep(poll).await,
+            ObservationEvent::PumpFailed => return pump_failure_response(request_id),
+        }
+    }
+}
+
+/// Exit evidence for a session whose direct child has exited, if it has.
+///
+/// Prefers the tombstone once the session is finalized, since only it records
+/// an explicit kill and whether output drained completely.
+async fn observed_exit(
+    sessions: &SessionManager,
+    session_id: &SessionId,
+    observer: &SessionObserver,
+) -> Result<Option<CaptureExit>, ApiError> {
+    if let Ok(SessionEvidence::Exited(tombstone)) =
+        sessions.resolve_evidence(Some(&session_id.0)).await
+    {
+        return Ok(Some(capture_exit(
+            tombstone.exit,
+            tombstone.output_complete,
+        )));
+    }
+    Ok(observer
+        .exit_metadata()?
+        .map(|exit| capture_exit(exit, observer.output_closed())))
+}
+
+fn pump_failure_response(request_id: &str) -> Response {
+    Response::error(
+        request_id,
+        ApiError::command_failed_with_suggestion(
+            "Session output pump stopped unexpectedly",
+            "Inspect daemon logs, then stop and restart the daemon before retrying.",
+        ),
+    )
+}
+
+/// Handle shutdown command - gracefully stop the daemon.
+///
+/// Kills all sessions and signals the main run loop to exit.
+/// The DaemonServer's Drop impl cleans up the socket and PID files.
+async fn handle_shutdown(
+    request_id: &str,
+    sessions: Arc<SessionManager>,
+    shutdown: Arc<Notify>,
+) -> Response {
+    info!("Received shutdown command, stopping daemon");
+
+    // Spawn shutdown task to run after we return the response
+    tokio::spawn(async move {
+        // Kill all sessions first
+        kill_all_sessions(&sessions).await;
 
         // Brief delay to allow response to flush before signaling shutdown
         tokio::time::sleep(Duration::from_millis(50)).await;
@@ -1794,6 +3219,9 @@ async fn handle_shutdown(
 mod tests {
     use pilotty_core::error::ErrorCode;
     use pilotty_core::protocol::{Command, SessionStatus, PROTOCOL_VERSION};
+    use pilotty_core::snapshot::{
+        compute_content_hash, Color, MouseEncoding, MouseTracking, RowChange, TerminalModes,
+    };
     use std::time::Duration;
     use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
     use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
@@ -1834,6 +3262,60 @@ mod tests {
         assert!(parse_retain_bytes(Some(std::ffi::OsStr::new("many"))).is_err());
     }
 
+    #[test]
+    fn scrollback_environment_value_is_validated() {
+        assert_eq!(parse_scrollback_lines(None).expect("default"), 1_000);
+        assert_eq!(
+            parse_scrollback_lines(Some(std::ffi::OsStr::new("0"))).expect("disabled"),
+            0
+        );
+        assert!(parse_scrollback_lines(Some(std::ffi::OsStr::new("-1"))).is_err());
+    }
+
+    #[test]
+    fn default_size_environment_values_are_validated() {
+        use std::ffi::OsStr;
+
+        assert_eq!(
+            parse_default_size(None, None).expect("default"),
+            TermSize { cols: 80, rows: 24 }
+        );
+        assert_eq!(
+            parse_default_size(Some(OsStr::new("120")), None).expect("configured cols"),
+            TermSize {
+                cols: 120,
+                rows: 24
+            }
+        );
+        assert!(parse_default_size(Some(OsStr::new("0")), None).is_err());
+        assert!(parse_default_size(None, Some(OsStr::new("tall"))).is_err());
+    }
+
+    #[test]
+    fn terminal_identity_environment_values_are_validated() {
+        assert_eq!(
+            parse_terminal_identity(|_| None).expect("default"),
+            TerminalIdentity::default()
+        );
+
+        let identity = parse_terminal_identity(|name| match name {
+            TERM_VERSION_ENV => Some("xterm(390)".to_string()),
+            TERM_DA1_ENV => Some("?1;2".to_string()),
+            TERM_COLORS_ENV => Some("bg=#ffffff".to_string()),
+            _ => None,
+        })
+        .expect("configured identity");
+        assert_eq!(identity.version, "xterm(390)");
+        assert_eq!(identity.primary_attributes, "?1;2");
+        assert_eq!(identity.palette.background, [0xff; 3]);
+
+        let error = parse_terminal_identity(|name| {
+            (name == TERM_COLORS_ENV).then(|| "bg=white".to_string())
+        })
+        .expect_err("invalid color");
+        assert!(format!("{error:#}").contains(TERM_COLORS_ENV));
+    }
+
     #[tokio::test]
     async fn output_renders_text_by_default_and_preserves_ansi_on_request() {
         let raw = b"\x1b[31mred\x1b[0m".to_vec();
@@ -1926,6 +3408,13 @@ mod tests {
                     session_name: Some("socket-status".to_string()),
                     cwd: Some("/tmp".to_string()),
                     retain_bytes: Some(8),
+                    record: None,
+                    record_input: false,
+                    env: Default::default(),
+                    env_clear: false,
+                    term: None,
+                    cols: None,
+                    rows: None,
                 },
             ),
         )
@@ -1998,6 +3487,146 @@ mod tests {
         let _ = std::fs::remove_file(&socket_path);
     }
 
+    #[tokio::test]
+    async fn subscription_streams_session_events_over_the_socket() {
+        let socket_path = std::path::PathBuf::from(format!(
+            "/tmp/pilotty-subscribe-{}.sock",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&socket_path);
+        let pid_path = socket_path.with_extension("pid");
+        let server = DaemonServer::bind_to(socket_path.clone(), pid_path)
+            .await
+            .expect("bind server");
+        let server_handle = tokio::spawn(async move {
+            let _ = timeout(Duration::from_secs(6), server.run()).await;
+        });
+
+        let connect = || async {
+            let stream = UnixStream::connect(&socket_path)
+                .await
+                .expect("connect to server");
+            let (reader, writer) = stream.into_split();
+            (BufReader::new(reader), writer)
+        };
+        let (mut events, mut subscriber) = connect().await;
+        let subscribed = socket_request(
+            &mut events,
+            &mut subscriber,
+            Request::new(
+                "subscribe",
+                Command::Subscribe {
+                    sessions: vec![],
+                    output: true,
+                },
+            ),
+        )
+        .await;
+        assert!(matches!(
+            subscribed.data,
+            Some(ResponseData::Subscribed { ref sessions }) if sessions.is_empty()
+        ));
+
+        let (mut reader, mut writer) = connect().await;
+        let spawn = socket_request(
+            &mut reader,
+            &mut writer,
+            Request::new(
+                "spawn-streamed",
+                Command::Spawn {
+                    command: vec![
+                        "sh".to_string(),
+                        "-c".to_string(),
+                        "printf 'hello\\a\\033]2;Build\\007'; sleep 0.5; exit 3".to_string(),
+                    ],
+                    session_name: Some("streamed".to_string()),
+                    cwd: None,
+                    retain_bytes: None,
+                    record: None,
+                    record_input: false,
+                    env: Default::default(),
+                    env_clear: false,
+                    term: None,
+                    cols: None,
+                    rows: None,
+                },
+            ),
+        )
+        .await;
+        assert!(spawn.success, "spawn failed: {spawn:?}");
+        let resize = socket_request(
+            &mut reader,
+            &mut writer,
+            Request::new(
+                "resize-streamed",
+                Command::Resize {
+                    cols: 100,
+                    rows: 30,
+                    session: Some("streamed".to_string()),
+                },
+            ),
+        )
+        .await;
+        assert!(resize.success, "resize failed: {resize:?}");
+
+        let kinds = timeout(Duration::from_secs(5), async {
+            let mut kinds = Vec::new();
+            loop {
+                let mut line = String::new();
+                events.read_line(&mut line).await.expect("read event");
+                let response: Response = serde_json::from_str(&line).expect("parse event");
+                let Some(ResponseData::Event(event)) = response.data else {
+                    panic!("expected an event, got: {line}");
+                };
+                assert_eq!(event.session_name.as_deref(), Some("streamed"));
+                let exited = matches!(event.kind, SessionEventKind::Exited { .. });
+                kinds.push(event.kind);
+                if exited {
+                    break kinds;
+                }
+            }
+        })
+        .await
+        .expect("session exit streamed");
+
+        assert!(matches!(
+            kinds.first(),
+            Some(SessionEventKind::Spawned { command }) if command[0] == "sh"
+        ));
+        assert!(kinds.iter().any(|kind| matches!(
+            kind,
+            SessionEventKind::Output { bytes } if bytes.starts_with(b"hello")
+        )));
+        assert!(kinds.iter().any(|kind| matches!(
+            kind,
+            SessionEventKind::Revision(diff)
+                if diff.rows.iter().any(|row| row.text.starts_with("hello"))
+        )));
+        assert!(kinds.contains(&SessionEventKind::Bell));
+        assert!(kinds.contains(&SessionEventKind::Title {
+            title: "Build".to_string()
+        }));
+        assert!(kinds.contains(&SessionEventKind::Resize {
+            size: TerminalSize {
+                cols: 100,
+                rows: 30
+            }
+        }));
+        assert!(matches!(
+            kinds.last(),
+            Some(SessionEventKind::Exited {
+                exit: CaptureExit {
+                    exit_code: Some(3),
+                    killed_by_client: false,
+                    ..
+                }
+            })
+        ));
+
+        server_handle.abort();
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
     #[tokio::test]
     async fn finalized_session_serves_evidence_and_rejects_input() {
         let sessions = Arc::new(SessionManager::new());
@@ -2068,6 +3697,10 @@ mod tests {
                     await_change: None,
                     settle_ms: 0,
                     timeout_ms: 1000,
+                    since: None,
+                    region: None,
+                    ignore: Vec::new(),
+                    scrollback: 0,
                 },
             ),
             sessions.clone(),
@@ -2128,90 +3761,328 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn snapshot_wait_returns_exit_evidence_before_reaper_runs() {
+    async fn finalized_session_answers_styled_and_compact_formats() {
         let sessions = Arc::new(SessionManager::new());
         sessions
             .create_session(
                 vec![
                     "sh".to_string(),
                     "-c".to_string(),
-                    "printf ready; sleep 0.2; exit 7".to_string(),
+                    "printf 'ok \\033[1;31mfail\\033[0m  < Retry >'; exit 1".to_string(),
                 ],
-                Some("exits-during-wait".to_string()),
+                Some("styled".to_string()),
                 None,
                 None,
             )
             .await
             .expect("create exiting session");
-        tokio::time::sleep(Duration::from_millis(75)).await;
+        sessions.spawn_cleaner();
+        timeout(Duration::from_secs(3), async {
+            while !sessions.is_empty().await {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("finalize session");
 
-        let baseline = handle_request(
+        let shutdown = Arc::new(Notify::new());
+        let snapshot = |format| {
+            handle_request(
+                Request::new(
+                    "snapshot",
+                    Command::Snapshot {
+                        session: Some("styled".to_string()),
+                        format,
+                        await_change: None,
+                        settle_ms: 0,
+                        timeout_ms: 1000,
+                        since: None,
+                        region: None,
+                        ignore: Vec::new(),
+                        scrollback: 0,
+                    },
+                ),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+
+        let Some(ResponseData::ScreenState(styled)) = snapshot(SnapshotFormat::Styled).await.data
+        else {
+            panic!("expected styled screen state");
+        };
+        let rows = styled.screen.styled.expect("styled spans");
+        let fail = rows[0]
+            .spans
+            .iter()
+            .find(|span| span.text == "fail")
+            .expect("highlighted span");
+        assert_eq!(fail.col, 3);
+        assert!(fail.style.bold);
+        assert_eq!(fail.style.fg, Some(Color::Idx(1)));
+
+        let Some(ResponseData::ScreenState(full)) = snapshot(SnapshotFormat::Full).await.data
+        else {
+            panic!("expected full screen state");
+        };
+        assert!(full.screen.styled.is_none());
+        assert!(full.screen.elements.is_none());
+        assert!(full.screen.text.is_some());
+
+        let Some(ResponseData::ScreenState(compact)) = snapshot(SnapshotFormat::Compact).await.data
+        else {
+            panic!("expected compact screen state");
+        };
+        assert!(compact.screen.text.is_none());
+        assert!(compact.screen.styled.is_none());
+        let elements = compact.screen.elements.expect("detected elements");
+        assert_eq!(elements.len(), 1, "got: {elements:?}");
+        assert_eq!(elements[0].id, "@e1");
+        assert_eq!(elements[0].label, "Retry");
+        assert_eq!((elements[0].row, elements[0].col), (0, 9));
+    }
+
+    #[tokio::test]
+    async fn click_by_ref_targets_detected_element_center() {
+        let sessions = Arc::new(SessionManager::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[?1000hSave?  < OK >  < No >'; sleep 5".to_string(),
+                ],
+                Some("click-ref".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let shutdown = Arc::new(Notify::new());
+        let ready = handle_request(
             Request::new(
-                "baseline",
-                Command::Snapshot {
-                    session: Some("exits-during-wait".to_string()),
-                    format: SnapshotFormat::Full,
-                    await_change: None,
-                    settle_ms: 0,
-                    timeout_ms: 1000,
+                "ready",
+                Command::WaitFor {
+                    pattern: "< No >".to_string(),
+                    patterns: vec![],
+                    match_mode: MatchMode::Any,
+                    absent: false,
+                    timeout_ms: Some(3000),
+                    regex: None,
+                    until: None,
+                    region: None,
+                    scrollback: 0,
+                    session: Some("click-ref".to_string()),
                 },
             ),
             sessions.clone(),
-            Arc::new(Notify::new()),
+            shutdown.clone(),
         )
         .await;
-        let baseline_hash = match baseline.data {
-            Some(ResponseData::ScreenState(capture)) => {
-                capture.screen.content_hash.expect("baseline hash")
-            }
-            _ => panic!("expected baseline screen capture"),
+        assert!(ready.success, "screen never rendered: {ready:?}");
+
+        let click = |element_ref: &str| {
+            handle_request(
+                Request::new(
+                    "click",
+                    Command::Click {
+                        target: ClickTarget::Ref {
+                            r#ref: element_ref.to_string(),
+                        },
+                        session: Some("click-ref".to_string()),
+                    },
+                ),
+                sessions.clone(),
+                shutdown.clone(),
+            )
         };
 
-        let waited = handle_request(
+        let ok = click("@e1").await;
+        assert!(
+            matches!(
+                ok.data,
+                Some(ResponseData::Ok { ref message }) if message.contains("row 0, col 9")
+            ),
+            "{ok:?}"
+        );
+
+        let missing = click("@e9").await;
+        let error = missing.error.expect("unknown ref");
+        assert_eq!(error.code, ErrorCode::CommandFailed);
+        assert!(error
+            .suggestion
+            .as_deref()
+            .is_some_and(|suggestion| suggestion.contains("--format compact")));
+
+        handle_request(
             Request::new(
-                "wait-for-exit",
-                Command::Snapshot {
-                    session: Some("exits-during-wait".to_string()),
-                    format: SnapshotFormat::Full,
-                    await_change: Some(baseline_hash),
-                    settle_ms: 2000,
-                    timeout_ms: 3000,
+                "kill",
+                Command::Kill {
+                    session: Some("click-ref".to_string()),
                 },
             ),
             sessions,
-            Arc::new(Notify::new()),
+            shutdown,
         )
         .await;
-
-        assert!(matches!(
-            waited.data,
-            Some(ResponseData::ScreenState(ScreenCapture {
-                screen: ScreenState { text: Some(text), .. },
-                outcome: CaptureOutcome::Exited,
-                exit: Some(CaptureExit {
-                    exit_code: Some(7),
-                    success: false,
-                    output_complete: true,
-                    ..
-                }),
-                ..
-            })) if text.contains("ready")
-        ));
     }
 
     #[tokio::test]
-    async fn deadline_does_not_treat_output_eof_as_process_exit() {
+    async fn finalized_session_snapshot_since_notes_full_fallback() {
         let sessions = Arc::new(SessionManager::new());
-        let session_id = sessions
+        sessions
             .create_session(
-                vec!["sleep".to_string(), "10".to_string()],
-                Some("closed-output".to_string()),
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "printf 'finished'; exit 0".to_string(),
+                ],
+                Some("exited-diff".to_string()),
                 None,
                 None,
             )
             .await
-            .expect("create live session");
-        let mut observer = sessions
+            .expect("create exiting session");
+        sessions.spawn_cleaner();
+        timeout(Duration::from_secs(3), async {
+            while !sessions.is_empty().await {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .expect("finalize session");
+
+        let response = handle_request(
+            Request::new(
+                "snapshot",
+                Command::Snapshot {
+                    session: Some("exited-diff".to_string()),
+                    format: SnapshotFormat::Full,
+                    await_change: None,
+                    settle_ms: 0,
+                    timeout_ms: 1000,
+                    since: Some(1),
+                    region: None,
+                    ignore: Vec::new(),
+                    scrollback: 0,
+                },
+            ),
+            sessions,
+            Arc::new(Notify::new()),
+        )
+        .await;
+
+        let Some(ResponseData::ScreenState(capture)) = response.data else {
+            panic!("expected full screen state, got: {response:?}");
+        };
+        assert_eq!(capture.outcome, CaptureOutcome::Exited);
+        assert!(capture
+            .screen
+            .text
+            .is_some_and(|text| text.contains("finished")));
+        assert!(
+            capture
+                .note
+                .as_deref()
+                .is_some_and(|note| note.contains("Session has exited")),
+            "got: {:?}",
+            capture.note
+        );
+    }
+
+    #[tokio::test]
+    async fn snapshot_wait_returns_exit_evidence_before_reaper_runs() {
+        let sessions = Arc::new(SessionManager::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "printf ready; sleep 0.2; exit 7".to_string(),
+                ],
+                Some("exits-during-wait".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create exiting session");
+        tokio::time::sleep(Duration::from_millis(75)).await;
+
+        let baseline = handle_request(
+            Request::new(
+                "baseline",
+                Command::Snapshot {
+                    session: Some("exits-during-wait".to_string()),
+                    format: SnapshotFormat::Full,
+                    await_change: None,
+                    settle_ms: 0,
+                    timeout_ms: 1000,
+                    since: None,
+                    region: None,
+                    ignore: Vec::new(),
+                    scrollback: 0,
+                },
+            ),
+            sessions.clone(),
+            Arc::new(Notify::new()),
+        )
+        .await;
+        let baseline_hash = match baseline.data {
+            Some(ResponseData::ScreenState(capture)) => {
+                capture.screen.content_hash.expect("baseline hash")
+            }
+            _ => panic!("expected baseline screen capture"),
+        };
+
+        let waited = handle_request(
+            Request::new(
+                "wait-for-exit",
+                Command::Snapshot {
+                    session: Some("exits-during-wait".to_string()),
+                    format: SnapshotFormat::Full,
+                    await_change: Some(baseline_hash),
+                    settle_ms: 2000,
+                    timeout_ms: 3000,
+                    since: None,
+                    region: None,
+                    ignore: Vec::new(),
+                    scrollback: 0,
+                },
+            ),
+            sessions,
+            Arc::new(Notify::new()),
+        )
+        .await;
+
+        assert!(matches!(
+            waited.data,
+            Some(ResponseData::ScreenState(ScreenCapture {
+                screen: ScreenState { text: Some(text), .. },
+                outcome: CaptureOutcome::Exited,
+                exit: Some(CaptureExit {
+                    exit_code: Some(7),
+                    success: false,
+                    output_complete: true,
+                    ..
+                }),
+                ..
+            })) if text.contains("ready")
+        ));
+    }
+
+    #[tokio::test]
+    async fn deadline_does_not_treat_output_eof_as_process_exit() {
+        let sessions = Arc::new(SessionManager::new());
+        let session_id = sessions
+            .create_session(
+                vec!["sleep".to_string(), "10".to_string()],
+                Some("closed-output".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create live session");
+        let mut observer = sessions
             .observe_session(&session_id)
             .await
             .expect("observe live session");
@@ -2223,6 +4094,7 @@ mod tests {
             &mut observer,
             SnapshotDeadline {
                 format: SnapshotFormat::Full,
+                since: None,
                 output_closed: true,
                 note: "deadline".to_string(),
             },
@@ -2252,9 +4124,13 @@ mod tests {
         let temp_dir = std::env::temp_dir();
         let socket_path = temp_dir.join(format!("pilotty-output-{}.sock", std::process::id()));
         let pid_path = socket_path.with_extension("pid");
-        let server = DaemonServer::bind_to_with_retain_bytes(socket_path.clone(), pid_path, 4)
-            .await
-            .expect("bind server");
+        let server = DaemonServer::bind_to_with_sessions(
+            socket_path.clone(),
+            pid_path,
+            SessionManager::with_default_retain_bytes(4),
+        )
+        .await
+        .expect("bind server");
         let server_handle = tokio::spawn(async move {
             let _ = timeout(Duration::from_secs(3), server.run()).await;
         });
@@ -2275,6 +4151,13 @@ mod tests {
                 session_name: Some("output-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         );
         writer
@@ -2587,6 +4470,13 @@ mod tests {
                 session_name: Some("test-snap".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -2621,6 +4511,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 0,
                 timeout_ms: 30000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let snap_json = serde_json::to_string(&snap_request).unwrap();
@@ -2699,6 +4593,13 @@ mod tests {
                 session_name: Some("full-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -2727,6 +4628,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 0,
                 timeout_ms: 30000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let snap_json = serde_json::to_string(&snap_request).unwrap();
@@ -2819,6 +4724,13 @@ mod tests {
                 session_name: Some("type-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -2878,6 +4790,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 0,
                 timeout_ms: 30000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let snap_json = serde_json::to_string(&snap_request).unwrap();
@@ -2941,6 +4857,13 @@ mod tests {
                 session_name: Some("key-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3008,166 +4931,1859 @@ mod tests {
             .expect("timeout")
             .expect("read");
 
-        let ctrlc_response: Response =
-            serde_json::from_str(&response_line).expect("parse ctrl+c response");
-        assert!(ctrlc_response.success, "Ctrl+C should succeed");
+        let ctrlc_response: Response =
+            serde_json::from_str(&response_line).expect("parse ctrl+c response");
+        assert!(ctrlc_response.success, "Ctrl+C should succeed");
+
+        server_handle.abort();
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    #[tokio::test]
+    async fn test_key_rejects_large_delay() {
+        let short_id = Uuid::new_v4().simple().to_string();
+        let socket_path = std::path::PathBuf::from("/tmp")
+            .join(format!("pilotty-keydelay-{}.sock", &short_id[..8]));
+        let pid_path = socket_path.with_extension("pid");
+
+        let server = DaemonServer::bind_to(socket_path.clone(), pid_path.clone())
+            .await
+            .expect("Failed to bind server");
+
+        let server_handle = tokio::spawn(async move {
+            let _ = timeout(Duration::from_secs(2), server.run()).await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let stream = UnixStream::connect(&socket_path)
+            .await
+            .expect("Failed to connect");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        // Try to send a key with delay exceeding MAX_KEY_DELAY_MS (10000)
+        let request = Request {
+            protocol: PROTOCOL_VERSION,
+            id: "key-delay-1".to_string(),
+            command: Command::Key {
+                key: "a b".to_string(),
+                delay_ms: MAX_KEY_DELAY_MS + 1,
+                session: None,
+            },
+        };
+        let request_json = serde_json::to_string(&request).unwrap();
+        writer
+            .write_all(request_json.as_bytes())
+            .await
+            .expect("write");
+        writer.write_all(b"\n").await.expect("newline");
+        writer.flush().await.expect("flush");
+
+        let mut response_line = String::new();
+        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
+            .await
+            .expect("timeout")
+            .expect("read");
+
+        let response: Response = serde_json::from_str(&response_line).expect("parse response");
+        assert!(!response.success);
+        let error = response.error.expect("error response");
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+        assert!(error.message.contains("exceeds maximum"));
+
+        server_handle.abort();
+        let _ = std::fs::remove_file(&socket_path);
+        let _ = std::fs::remove_file(&pid_path);
+    }
+
+    #[tokio::test]
+    async fn test_click_command() {
+        let temp_dir = std::env::temp_dir();
+        let socket_path = temp_dir.join(format!("pilotty-click-{}.sock", std::process::id()));
+        let pid_path = socket_path.with_extension("pid");
+
+        let server = DaemonServer::bind_to(socket_path.clone(), pid_path.clone())
+            .await
+            .expect("Failed to bind server");
+
+        let server_handle = tokio::spawn(async move {
+            let _ = timeout(Duration::from_secs(5), server.run()).await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let stream = UnixStream::connect(&socket_path)
+            .await
+            .expect("Failed to connect");
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        // Spawn a session
+        let spawn_request = Request {
+            protocol: PROTOCOL_VERSION,
+            id: "spawn-1".to_string(),
+            command: Command::Spawn {
+                command: vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[?1000;1006hmouse on'; exec cat".to_string(),
+                ],
+                session_name: Some("click-test".to_string()),
+                cwd: None,
+                retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
+            },
+        };
+        let request_json = serde_json::to_string(&spawn_request).unwrap();
+        writer
+            .write_all(request_json.as_bytes())
+            .await
+            .expect("write");
+        writer.write_all(b"\n").await.expect("newline");
+        writer.flush().await.expect("flush");
+
+        let mut response_line = String::new();
+        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
+            .await
+            .expect("timeout")
+            .expect("read");
+
+        // Mouse input needs the application to have enabled reporting.
+        let ready = socket_request(
+            &mut reader,
+            &mut writer,
+            Request::new(
+                "ready",
+                Command::WaitFor {
+                    pattern: "mouse on".to_string(),
+                    patterns: vec![],
+                    match_mode: MatchMode::Any,
+                    absent: false,
+                    timeout_ms: Some(3000),
+                    regex: None,
+                    until: None,
+                    region: None,
+                    scrollback: 0,
+                    session: Some("click-test".to_string()),
+                },
+            ),
+        )
+        .await;
+        assert!(ready.success, "mouse never enabled: {ready:?}");
+
+        // Click at coordinates (row 5, col 10)
+        let click_request = Request {
+            protocol: PROTOCOL_VERSION,
+            id: "click-1".to_string(),
+            command: Command::Click {
+                target: ClickTarget::Cell { row: 5, col: 10 },
+                session: Some("click-test".to_string()),
+            },
+        };
+        let click_json = serde_json::to_string(&click_request).unwrap();
+        writer
+            .write_all(click_json.as_bytes())
+            .await
+            .expect("write");
+        writer.write_all(b"\n").await.expect("newline");
+        writer.flush().await.expect("flush");
+
+        response_line.clear();
+        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
+            .await
+            .expect("timeout")
+            .expect("read");
+
+        let click_response: Response =
+            serde_json::from_str(&response_line).expect("parse click response");
+        assert!(
+            click_response.success,
+            "Click should succeed, got: {:?}",
+            click_response
+        );
+
+        // Verify the response message contains coordinates
+        if let Some(ResponseData::Ok { message }) = &click_response.data {
+            assert!(
+                message.contains("row 5") && message.contains("col 10"),
+                "Response should contain coordinates: {}",
+                message
+            );
+        } else {
+            panic!("Expected Ok response, got: {:?}", click_response.data);
+        }
+
+        server_handle.abort();
+        let _ = std::fs::remove_file(&socket_path);
+    }
+
+    fn read_cast(path: &std::path::Path) -> Vec<serde_json::Value> {
+        std::fs::read_to_string(path)
+            .expect("read recording")
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("cast line is JSON"))
+            .collect()
+    }
+
+    fn cast_events<'a>(lines: &'a [serde_json::Value], code: &str) -> Vec<&'a str> {
+        lines[1..]
+            .iter()
+            .filter(|event| event[1] == code)
+            .filter_map(|event| event[2].as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn spawn_with_record_writes_asciicast_output() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        let path =
+            std::env::temp_dir().join(format!("pilotty-spawn-{}.cast", uuid::Uuid::new_v4()));
+
+        let spawn = handle_request(
+            Request::new(
+                "spawn",
+                Command::Spawn {
+                    command: vec![
+                        "sh".to_string(),
+                        "-c".to_string(),
+                        "printf 'recorded ready'; sleep 5".to_string(),
+                    ],
+                    session_name: Some("spawn-record".to_string()),
+                    cwd: None,
+                    retain_bytes: None,
+                    record: Some(path.to_string_lossy().into_owned()),
+                    record_input: false,
+                    env: Default::default(),
+                    env_clear: false,
+                    term: None,
+                    cols: None,
+                    rows: None,
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        assert!(spawn.success, "spawn failed: {spawn:?}");
+
+        let ready = handle_request(
+            Request::new(
+                "ready",
+                Command::WaitFor {
+                    pattern: "recorded ready".to_string(),
+                    patterns: vec![],
+                    match_mode: MatchMode::Any,
+                    absent: false,
+                    timeout_ms: Some(3000),
+                    regex: None,
+                    until: None,
+                    region: None,
+                    scrollback: 0,
+                    session: Some("spawn-record".to_string()),
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        assert!(ready.success, "screen never rendered: {ready:?}");
+
+        // Stopping waits for the writer task to drain queued events.
+        let stopped = handle_request(
+            Request::new(
+                "stop",
+                Command::RecordStop {
+                    session: Some("spawn-record".to_string()),
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        assert!(stopped.success, "record stop failed: {stopped:?}");
+
+        let lines = read_cast(&path);
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["width"], 80);
+        assert_eq!(lines[0]["height"], 24);
+        assert!(cast_events(&lines, "o").concat().contains("recorded ready"));
+
+        let again = handle_request(
+            Request::new(
+                "spawn-again",
+                Command::Spawn {
+                    command: vec!["true".to_string()],
+                    session_name: Some("spawn-record-again".to_string()),
+                    cwd: None,
+                    retain_bytes: None,
+                    record: Some(path.to_string_lossy().into_owned()),
+                    record_input: false,
+                    env: Default::default(),
+                    env_clear: false,
+                    term: None,
+                    cols: None,
+                    rows: None,
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        let error = again
+            .error
+            .expect("existing recording is never overwritten");
+        assert_eq!(error.code, ErrorCode::CommandFailed);
+        assert_eq!(sessions.session_count().await, 1);
+
+        handle_request(
+            Request::new(
+                "kill",
+                Command::Kill {
+                    session: Some("spawn-record".to_string()),
+                },
+            ),
+            sessions,
+            shutdown,
+        )
+        .await;
+        std::fs::remove_file(&path).expect("remove recording");
+    }
+
+    #[tokio::test]
+    async fn spawn_starts_at_requested_size_over_daemon_default() {
+        let sessions =
+            Arc::new(SessionManager::new().with_default_size(TermSize { cols: 90, rows: 30 }));
+        let shutdown = Arc::new(Notify::new());
+        let spawn = |name: &str, cols: Option<u16>, rows: Option<u16>| Command::Spawn {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                "stty size; sleep 5".to_string(),
+            ],
+            session_name: Some(name.to_string()),
+            cwd: None,
+            retain_bytes: None,
+            record: None,
+            record_input: false,
+            env: Default::default(),
+            env_clear: false,
+            term: None,
+            cols,
+            rows,
+        };
+        let request = |command: Command| {
+            handle_request(
+                Request::new("spawn-size", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+
+        let zero = request(spawn("zero", Some(0), None)).await;
+        assert_eq!(zero.error.map(|e| e.code), Some(ErrorCode::InvalidInput));
+
+        // The application sees its size from the first byte, so `stty size`
+        // reports it without any resize.
+        for (name, cols, rows, expected) in [
+            ("sized-default", None, None, "30 90"),
+            ("sized-wide", Some(132), None, "30 132"),
+        ] {
+            let spawned = request(spawn(name, cols, rows)).await;
+            assert!(spawned.success, "spawn failed: {spawned:?}");
+            let waited = request(Command::WaitFor {
+                pattern: expected.to_string(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
+                timeout_ms: Some(2000),
+                regex: None,
+                until: None,
+                region: None,
+                scrollback: 0,
+                session: Some(name.to_string()),
+            })
+            .await;
+            assert!(
+                matches!(
+                    waited.data,
+                    Some(ResponseData::WaitForResult { found: true, .. })
+                ),
+                "{name}: {waited:?}"
+            );
+            request(Command::Kill {
+                session: Some(name.to_string()),
+            })
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn spawn_applies_environment_and_rejects_invalid_variables() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        let spawn = |env: &[(&str, &str)], term: Option<&str>| Command::Spawn {
+            command: vec![
+                "sh".to_string(),
+                "-c".to_string(),
+                r#"printf 'env:%s:%s' "$TERM" "$PILOTTY_GREETING"; sleep 5"#.to_string(),
+            ],
+            session_name: Some("spawn-env".to_string()),
+            cwd: None,
+            retain_bytes: None,
+            record: None,
+            record_input: false,
+            env: env
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            env_clear: false,
+            term: term.map(str::to_string),
+            cols: None,
+            rows: None,
+        };
+        let request = |command: Command| {
+            handle_request(
+                Request::new("spawn-env", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+
+        for invalid in [spawn(&[("A=B", "x")], None), spawn(&[], Some("two words"))] {
+            let response = request(invalid).await;
+            assert_eq!(
+                response.error.map(|error| error.code),
+                Some(ErrorCode::InvalidInput)
+            );
+        }
+
+        let spawned = request(spawn(&[("PILOTTY_GREETING", "hello")], Some("screen"))).await;
+        assert!(spawned.success, "spawn failed: {spawned:?}");
+        let waited = request(Command::WaitFor {
+            pattern: "env:screen:hello".to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(2000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("spawn-env".to_string()),
+        })
+        .await;
+        assert!(
+            matches!(
+                waited.data,
+                Some(ResponseData::WaitForResult { found: true, .. })
+            ),
+            "{waited:?}"
+        );
+
+        request(Command::Kill {
+            session: Some("spawn-env".to_string()),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn wait_for_absent_text_and_pattern_sets() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf Loading; sleep 0.4; printf '\r\033[KError: boom'; sleep 30"
+                        .to_string(),
+                ],
+                Some("patterns".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("wait-patterns", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |patterns: &[&str], match_mode, absent, timeout_ms| Command::WaitFor {
+            pattern: String::new(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            match_mode,
+            absent,
+            timeout_ms: Some(timeout_ms),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("patterns".to_string()),
+        };
+        let result = |response: Response| match response.data {
+            Some(ResponseData::WaitForResult {
+                found: true,
+                matched_text,
+                pattern_index,
+                ..
+            }) => (matched_text, pattern_index),
+            _ => panic!("wait not satisfied: {response:?}"),
+        };
+
+        let loading = request(wait(&["Loading"], MatchMode::Any, false, 5000)).await;
+        assert_eq!(result(loading), (Some("Loading".to_string()), Some(0)));
+        let gone = request(wait(&["Loading"], MatchMode::Any, true, 5000)).await;
+        assert_eq!(result(gone), (None, Some(0)));
+
+        let either = request(wait(&["Success", "Error"], MatchMode::Any, false, 5000)).await;
+        assert_eq!(result(either), (Some("Error".to_string()), Some(1)));
+        let both = request(wait(&["Error", "boom"], MatchMode::All, false, 5000)).await;
+        assert_eq!(result(both), (None, None));
+        let all_gone = request(wait(&["Loading", "Success"], MatchMode::All, true, 5000)).await;
+        assert_eq!(result(all_gone), (None, None));
+
+        let timed_out = request(wait(&["Success", "Error"], MatchMode::All, false, 100)).await;
+        let error = timed_out.error.expect("wait should time out");
+        assert!(error.message.contains("'Success' and 'Error'"), "{error:?}");
+
+        let mut mixed = wait(&["Error"], MatchMode::Any, true, 100);
+        if let Command::WaitFor { until, .. } = &mut mixed {
+            *until = Some(ProcessCondition::Exit);
+        }
+        for invalid in [mixed, wait(&[], MatchMode::Any, true, 100)] {
+            let response = request(invalid).await;
+            assert_eq!(
+                response.error.map(|error| error.code),
+                Some(ErrorCode::InvalidInput)
+            );
+        }
+
+        request(Command::Kill {
+            session: Some("patterns".to_string()),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn region_scopes_wait_for_and_snapshot_settle() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // A ticking counter in the top row, like a clock in a status bar.
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[4;1HSaved'; i=0; while :; do i=$((i+1)); printf '\033[1;1H%s' $i; sleep 0.05; done"
+                        .to_string(),
+                ],
+                Some("ticking".to_string()),
+                Some(TermSize { cols: 20, rows: 4 }),
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("region", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let region = |spec: &str| Some(spec.parse::<ScreenRegion>().unwrap());
+        let wait = |spec: &str, timeout_ms| Command::WaitFor {
+            pattern: "Saved".to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(timeout_ms),
+            regex: None,
+            until: None,
+            region: region(spec),
+            scrollback: 0,
+            session: Some("ticking".to_string()),
+        };
+        let snapshot = |format, region, timeout_ms| Command::Snapshot {
+            session: Some("ticking".to_string()),
+            format,
+            await_change: None,
+            settle_ms: 200,
+            timeout_ms,
+            since: None,
+            region,
+            ignore: Vec::new(),
+            scrollback: 0,
+        };
+
+        let found = request(wait("-1", 5000)).await;
+        assert!(
+            matches!(
+                found.data,
+                Some(ResponseData::WaitForResult { found: true, .. })
+            ),
+            "{found:?}"
+        );
+        let outside = request(wait("0:-2", 200)).await;
+        let error = outside.error.expect("Saved is only on the last row");
+        assert!(error.message.contains("'Saved' in rows 0:-2"), "{error:?}");
+
+        let settled = request(snapshot(SnapshotFormat::Full, region("1:"), 3000)).await;
+        let Some(ResponseData::ScreenState(capture)) = settled.data else {
+            panic!("expected a screen capture: {settled:?}");
+        };
+        assert_eq!(capture.outcome, CaptureOutcome::Settled);
+        assert_eq!(capture.screen.text.as_deref(), Some("\n\nSaved"));
+        assert_eq!(
+            capture.screen.content_hash,
+            Some(compute_content_hash("\n\nSaved"))
+        );
+
+        let whole = request(snapshot(SnapshotFormat::Full, None, 500)).await;
+        let Some(ResponseData::ScreenState(capture)) = whole.data else {
+            panic!("expected a screen capture: {whole:?}");
+        };
+        assert_eq!(capture.outcome, CaptureOutcome::Deadline);
+
+        let text = request(snapshot(SnapshotFormat::Text, region("1:"), 3000)).await;
+        let Some(ResponseData::Snapshot {
+            content, outcome, ..
+        }) = text.data
+        else {
+            panic!("expected a text snapshot: {text:?}");
+        };
+        assert_eq!(outcome, CaptureOutcome::Settled);
+        // The cursor sits on the ticking top row, outside the region.
+        assert!(
+            content.ends_with(" | Region: rows 1..4, cols 0..20 ---\n\n\nSaved\n"),
+            "{content:?}"
+        );
+
+        let compact = request(snapshot(SnapshotFormat::Compact, region("1:"), 500)).await;
+        assert_eq!(
+            compact.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        request(Command::Kill {
+            session: Some("ticking".to_string()),
+        })
+        .await;
+        // Exited sessions answer regions from their final screen.
+        let exited = request(snapshot(SnapshotFormat::Full, region("-1"), 500)).await;
+        let Some(ResponseData::ScreenState(capture)) = exited.data else {
+            panic!("expected a screen capture: {exited:?}");
+        };
+        assert_eq!(capture.outcome, CaptureOutcome::Exited);
+        assert_eq!(capture.screen.text.as_deref(), Some("Saved"));
+    }
+
+    #[tokio::test]
+    async fn ignore_masks_let_ticking_screens_settle() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[3;1HReady'; i=0; while :; do i=$((i+1)); printf '\033[1;1H%ss elapsed' $i; sleep 0.05; done"
+                        .to_string(),
+                ],
+                Some("clock".to_string()),
+                Some(TermSize { cols: 20, rows: 3 }),
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("masks", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let snapshot = |ignore, await_change, settle_ms, timeout_ms| Command::Snapshot {
+            session: Some("clock".to_string()),
+            format: SnapshotFormat::Full,
+            await_change,
+            settle_ms,
+            timeout_ms,
+            since: None,
+            region: None,
+            ignore,
+            scrollback: 0,
+        };
+        let capture = |response: Response| match response.data {
+            Some(ResponseData::ScreenState(capture)) => capture,
+            _ => panic!("expected a screen capture: {response:?}"),
+        };
+        let top_row = || vec![ScreenMask::Region("0".parse().unwrap())];
+        let counter = || vec![ScreenMask::Regex(r"\d+s elapsed".to_string())];
+
+        let unmasked = capture(request(snapshot(vec![], None, 200, 500)).await);
+        assert_eq!(unmasked.outcome, CaptureOutcome::Deadline);
+
+        for ignore in [top_row(), counter()] {
+            let settled = capture(request(snapshot(ignore.clone(), None, 200, 3000)).await);
+            assert_eq!(settled.outcome, CaptureOutcome::Settled, "{ignore:?}");
+            // Masks only change the hash; the text still shows the counter.
+            let text = settled.screen.text.expect("text");
+            assert!(
+                text.contains("s elapsed") && text.contains("Ready"),
+                "{text}"
+            );
+
+            let hash = settled.screen.content_hash.expect("hash");
+            let unchanged = request(snapshot(ignore, Some(hash), 0, 300)).await;
+            assert_eq!(capture(unchanged).outcome, CaptureOutcome::Deadline);
+        }
+
+        let invalid = request(snapshot(
+            vec![ScreenMask::Regex("(".to_string())],
+            None,
+            0,
+            300,
+        ))
+        .await;
+        assert_eq!(
+            invalid.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        request(Command::Kill {
+            session: Some("clock".to_string()),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn snapshots_report_terminal_modes() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[?1049h\033[?1000;1006;2004hmenu'; sleep 30".to_string(),
+                ],
+                Some("tui".to_string()),
+                Some(TermSize { cols: 20, rows: 4 }),
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("modes", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let modes_of = |response: Response| {
+            let Some(ResponseData::ScreenState(capture)) = response.data else {
+                panic!("expected a screen capture: {response:?}");
+            };
+            capture.screen.modes.expect("modes")
+        };
+        let snapshot = |format| Command::Snapshot {
+            session: Some("tui".to_string()),
+            format,
+            await_change: None,
+            settle_ms: 0,
+            timeout_ms: 0,
+            since: None,
+            region: None,
+            ignore: Vec::new(),
+            scrollback: 0,
+        };
+        let expected = TerminalModes {
+            alternate_screen: true,
+            mouse_tracking: MouseTracking::PressRelease,
+            mouse_encoding: MouseEncoding::Sgr,
+            bracketed_paste: true,
+            ..TerminalModes::default()
+        };
+
+        let ready = request(Command::WaitFor {
+            pattern: "menu".to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(5000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("tui".to_string()),
+        })
+        .await;
+        assert!(ready.error.is_none(), "{ready:?}");
+        assert_eq!(
+            modes_of(request(snapshot(SnapshotFormat::Full)).await),
+            expected
+        );
+        assert_eq!(
+            modes_of(request(snapshot(SnapshotFormat::Compact)).await),
+            expected
+        );
+
+        request(Command::Kill {
+            session: Some("tui".to_string()),
+        })
+        .await;
+        // The final screen keeps the modes the application left behind.
+        assert_eq!(
+            modes_of(request(snapshot(SnapshotFormat::Full)).await),
+            expected
+        );
+    }
+
+    #[tokio::test]
+    async fn scrollback_reaches_lines_that_scrolled_off_screen() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    "seq 1 50; printf done; sleep 30".to_string(),
+                ],
+                Some("scroller".to_string()),
+                Some(TermSize { cols: 20, rows: 5 }),
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("scrollback", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |pattern: &str, scrollback, timeout_ms| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(timeout_ms),
+            regex: Some(true),
+            until: None,
+            region: None,
+            scrollback,
+            session: Some("scroller".to_string()),
+        };
+        let snapshot = |format, scrollback| Command::Snapshot {
+            session: Some("scroller".to_string()),
+            format,
+            await_change: None,
+            settle_ms: 0,
+            timeout_ms: 0,
+            since: None,
+            region: None,
+            ignore: Vec::new(),
+            scrollback,
+        };
+        let scrollback_of = |response: Response| {
+            let Some(ResponseData::ScreenState(capture)) = response.data else {
+                panic!("expected a screen capture: {response:?}");
+            };
+            capture
+                .screen
+                .scrollback
+                .expect("scrollback")
+                .into_iter()
+                .map(|line| (line.row, line.text))
+                .collect::<Vec<_>>()
+        };
+
+        let ready = request(wait("done", 0, 5000)).await;
+        assert!(ready.error.is_none(), "{ready:?}");
+
+        // Line 3 scrolled away long ago; only scrollback still has it.
+        let missed = request(wait("(?m)^3$", 0, 200)).await;
+        assert!(missed.error.is_some(), "{missed:?}");
+        let found = request(wait("(?m)^3$", 100, 1000)).await;
+        assert!(
+            matches!(
+                found.data,
+                Some(ResponseData::WaitForResult { found: true, .. })
+            ),
+            "{found:?}"
+        );
+
+        let expected = vec![
+            (-3, "44".to_string()),
+            (-2, "45".to_string()),
+            (-1, "46".to_string()),
+        ];
+        assert_eq!(
+            scrollback_of(request(snapshot(SnapshotFormat::Full, 3)).await),
+            expected
+        );
+        let plain = request(snapshot(SnapshotFormat::Full, 0)).await;
+        let Some(ResponseData::ScreenState(capture)) = plain.data else {
+            panic!("expected a screen capture: {plain:?}");
+        };
+        assert!(capture.screen.scrollback.is_none());
+
+        let text_scrollback = |response: Response| {
+            let Some(ResponseData::Snapshot { content, .. }) = response.data else {
+                panic!("expected a text snapshot: {response:?}");
+            };
+            assert!(
+                content.contains("---\n--- Scrollback: 3 lines ---\n44\n45\n46\n--- Screen ---\n"),
+                "{content:?}"
+            );
+        };
+        text_scrollback(request(snapshot(SnapshotFormat::Text, 3)).await);
+        let compact = request(snapshot(SnapshotFormat::Compact, 3)).await;
+        assert_eq!(
+            compact.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        request(Command::Kill {
+            session: Some("scroller".to_string()),
+        })
+        .await;
+        // Exited sessions keep their scrollback with the final screen.
+        assert_eq!(
+            scrollback_of(request(snapshot(SnapshotFormat::Full, 3)).await),
+            expected
+        );
+        text_scrollback(request(snapshot(SnapshotFormat::Text, 3)).await);
+    }
+
+    #[tokio::test]
+    async fn wait_for_process_conditions() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // With job control the inner job owns the terminal until it ends,
+        // then the outer shell's group takes it back.
+        let job_shell = "sh -c 'printf ready; exec sleep 0.5'; exec sleep 30";
+        for (name, script) in [("jobs", job_shell), ("exits", "sleep 0.3; exit 4")] {
+            sessions
+                .create_session(
+                    vec![
+                        "sh".to_string(),
+                        "-m".to_string(),
+                        "-c".to_string(),
+                        script.to_string(),
+                    ],
+                    Some(name.to_string()),
+                    None,
+                    None,
+                )
+                .await
+                .expect("create session");
+        }
+        let request = |command: Command| {
+            handle_request(
+                Request::new("wait-until", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |session: &str, pattern: &str, until, timeout_ms| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(timeout_ms),
+            regex: None,
+            until,
+            region: None,
+            scrollback: 0,
+            session: Some(session.to_string()),
+        };
+
+        // A legacy empty pattern still matches at once.
+        let bare: Command =
+            serde_json::from_str(r#"{"action":"wait_for","session":"jobs"}"#).expect("decode");
+        let bare = request(bare).await;
+        assert!(bare.success, "{bare:?}");
+        // With the newer options there is nothing to wait for.
+        let absent: Command =
+            serde_json::from_str(r#"{"action":"wait_for","absent":true,"session":"jobs"}"#)
+                .expect("decode");
+        let error = request(absent)
+            .await
+            .error
+            .expect("absent without a pattern");
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+        assert!(error.message.contains("pattern or a process condition"));
+        // An empty entry among several patterns would match any screen.
+        let empty_entry: Command = serde_json::from_str(
+            r#"{"action":"wait_for","patterns":["ready",""],"absent":true,"session":"jobs"}"#,
+        )
+        .expect("decode");
+        let error = request(empty_entry).await.error.expect("empty entry");
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+        assert!(error.message.contains("cannot be empty"));
+
+        let ready = request(wait("jobs", "ready", None, 5000)).await;
+        assert!(ready.success, "{ready:?}");
+        let changed = request(wait(
+            "jobs",
+            "",
+            Some(ProcessCondition::ForegroundChange),
+            5000,
+        ))
+        .await;
+        match changed.data {
+            Some(ResponseData::WaitForResult {
+                found: true,
+                foreground: Some(foreground),
+                exit: None,
+                ..
+            }) => assert_eq!(foreground.argv, vec!["sleep", "30"]),
+            other => panic!("unexpected foreground wait: {other:?}"),
+        }
+
+        let idle = request(wait(
+            "jobs",
+            "",
+            Some(ProcessCondition::Idle { idle_ms: 200 }),
+            5000,
+        ))
+        .await;
+        assert!(
+            matches!(
+                idle.data,
+                Some(ResponseData::WaitForResult { found: true, .. })
+            ),
+            "{idle:?}"
+        );
+
+        let timed_out = request(wait("jobs", "", Some(ProcessCondition::Exit), 200)).await;
+        let error = timed_out.error.expect("exit wait should time out");
+        assert_eq!(error.code, ErrorCode::CommandFailed);
+        assert!(error.message.contains("process exit"), "{error:?}");
+
+        let exited = request(wait("exits", "", Some(ProcessCondition::Exit), 5000)).await;
+        assert!(
+            matches!(
+                exited.data,
+                Some(ResponseData::WaitForResult {
+                    found: true,
+                    exit: Some(CaptureExit {
+                        exit_code: Some(4),
+                        success: false,
+                        ..
+                    }),
+                    ..
+                })
+            ),
+            "{exited:?}"
+        );
+
+        request(Command::Kill {
+            session: Some("jobs".to_string()),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn signal_reaches_child_or_foreground_group() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // The foreground job reports readiness itself, so the interrupt
+        // cannot land before it starts.
+        let script = "trap 'echo got-int' INT; trap 'echo got-usr1' USR1; \
+                      sh -c 'echo ready; exec sleep 30'; echo woke; \
+                      while :; do sleep 0.1; done";
+        sessions
+            .create_session(
+                vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                Some("signals".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("signal", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let signal = |signal: Signal, target: SignalTarget| Command::Signal {
+            signal,
+            target,
+            session: Some("signals".to_string()),
+        };
+        let wait_for = |pattern: &str| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(5000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("signals".to_string()),
+        };
+        let found = |response: &Response| {
+            matches!(
+                response.data,
+                Some(ResponseData::WaitForResult { found: true, .. })
+            )
+        };
+
+        let ready = request(wait_for("ready")).await;
+        assert!(found(&ready), "{ready:?}");
+
+        // Like Ctrl+C: the foreground sleep dies and the shell runs its trap.
+        let sent = request(signal(Signal::Sigint, SignalTarget::Foreground)).await;
+        match sent.data {
+            Some(ResponseData::Ok { message }) => {
+                assert!(message.starts_with("Sent SIGINT to foreground process group"))
+            }
+            other => panic!("unexpected signal response: {other:?}"),
+        }
+        let woke = request(wait_for("woke")).await;
+        assert!(found(&woke), "{woke:?}");
+
+        let sent = request(signal(Signal::Sigusr1, SignalTarget::Child)).await;
+        assert!(sent.success, "{sent:?}");
+        let trapped = request(wait_for("got-usr1")).await;
+        assert!(found(&trapped), "{trapped:?}");
+
+        request(Command::Kill {
+            session: Some("signals".to_string()),
+        })
+        .await;
+        let after_exit = request(signal(Signal::Sigterm, SignalTarget::Child)).await;
+        assert_eq!(
+            after_exit.error.map(|error| error.code),
+            Some(ErrorCode::SessionExited)
+        );
+
+        // An exited child stays listed until the cleaner finalizes it, but
+        // its reaped PID must not be signaled.
+        sessions
+            .create_session(
+                vec!["sh".to_string(), "-c".to_string(), "exit 3".to_string()],
+                Some("exited".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let exited = request(Command::WaitFor {
+            pattern: String::new(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(5000),
+            regex: None,
+            until: Some(ProcessCondition::Exit),
+            region: None,
+            scrollback: 0,
+            session: Some("exited".to_string()),
+        })
+        .await;
+        assert!(exited.success, "{exited:?}");
+        for target in [SignalTarget::Child, SignalTarget::Foreground] {
+            let refused = request(Command::Signal {
+                signal: Signal::Sigterm,
+                target,
+                session: Some("exited".to_string()),
+            })
+            .await;
+            let error = refused.error.expect("signal after exit");
+            assert_eq!(error.code, ErrorCode::SessionExited);
+            assert!(error.message.contains('3'), "{}", error.message);
+        }
+    }
+
+    #[tokio::test]
+    async fn record_start_and_stop_capture_input_output_and_resize() {
+        let sessions = Arc::new(SessionManager::new());
+        sessions
+            .create_session(
+                vec!["cat".to_string()],
+                Some("live-record".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let shutdown = Arc::new(Notify::new());
+        let path = std::env::temp_dir().join(format!("pilotty-live-{}.cast", uuid::Uuid::new_v4()));
+        let request = |command: Command| {
+            handle_request(
+                Request::new("record", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let start = |path: String| Command::RecordStart {
+            path,
+            input: true,
+            session: Some("live-record".to_string()),
+        };
+        let stop = || Command::RecordStop {
+            session: Some("live-record".to_string()),
+        };
+
+        let idle = request(stop()).await;
+        assert_eq!(
+            idle.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+        let relative = request(start("run.cast".to_string())).await;
+        assert_eq!(
+            relative.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        let started = request(start(path.to_string_lossy().into_owned())).await;
+        assert!(started.success, "record start failed: {started:?}");
+        let twice = request(start(path.to_string_lossy().into_owned())).await;
+        assert_eq!(
+            twice.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        let typed = request(Command::Type {
+            text: "echoed\n".to_string(),
+            session: Some("live-record".to_string()),
+        })
+        .await;
+        assert!(typed.success, "type failed: {typed:?}");
+        let echoed = request(Command::WaitFor {
+            pattern: "echoed".to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(3000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("live-record".to_string()),
+        })
+        .await;
+        assert!(echoed.success, "echo never rendered: {echoed:?}");
+        let resized = request(Command::Resize {
+            cols: 100,
+            rows: 30,
+            session: Some("live-record".to_string()),
+        })
+        .await;
+        assert!(resized.success, "resize failed: {resized:?}");
+
+        let stopped = request(stop()).await;
+        assert!(matches!(
+            stopped.data,
+            Some(ResponseData::Ok { ref message }) if message.contains(&*path.to_string_lossy())
+        ));
+
+        let lines = read_cast(&path);
+        std::fs::remove_file(&path).expect("remove recording");
+        assert_eq!(cast_events(&lines, "i"), vec!["echoed\n"]);
+        assert!(cast_events(&lines, "o").concat().contains("echoed"));
+        assert_eq!(cast_events(&lines, "r"), vec!["100x30"]);
+
+        request(Command::Kill {
+            session: Some("live-record".to_string()),
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn mouse_input_follows_the_application_mouse_mode() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // `cat -v` echoes the reports it reads, once a line ends.
+        for (name, script) in [
+            ("no-mouse", "stty -echo; cat -v"),
+            (
+                "x10-mouse",
+                r"printf '\033[?1000h'; stty -echo; echo ready; cat -v",
+            ),
+        ] {
+            sessions
+                .create_session(
+                    vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                    Some(name.to_string()),
+                    None,
+                    None,
+                )
+                .await
+                .expect("create session");
+        }
+        let request = |command: Command| {
+            handle_request(
+                Request::new("mouse", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |pattern: &str| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(3000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("x10-mouse".to_string()),
+        };
+        let click = |session: &str| Command::Click {
+            target: ClickTarget::Cell { row: 5, col: 10 },
+            session: Some(session.to_string()),
+        };
+
+        let refused = request(click("no-mouse")).await;
+        let error = refused.error.expect("no mouse reporting");
+        assert_eq!(error.code, ErrorCode::MouseNotEnabled);
+        assert!(error.suggestion.is_some());
+        let scroll = request(Command::Scroll {
+            direction: pilotty_core::protocol::ScrollDirection::Up,
+            amount: 1,
+            session: Some("no-mouse".to_string()),
+        })
+        .await;
+        assert_eq!(
+            scroll.error.map(|error| error.code),
+            Some(ErrorCode::MouseNotEnabled)
+        );
+
+        let ready = request(wait("ready")).await;
+        assert!(ready.success, "{ready:?}");
+        let clicked = request(click("x10-mouse")).await;
+        assert!(clicked.success, "{clicked:?}");
+        request(Command::Key {
+            key: "Enter".to_string(),
+            delay_ms: 0,
+            session: Some("x10-mouse".to_string()),
+        })
+        .await;
+        // Press then release (button 3), each offset by 32: no SGR bytes.
+        let echoed = request(wait("^[[M +&^[[M#+&")).await;
+        assert!(echoed.success, "{echoed:?}");
+
+        for name in ["no-mouse", "x10-mouse"] {
+            request(Command::Kill {
+                session: Some(name.to_string()),
+            })
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn mouse_buttons_drags_and_hovers_reach_the_application() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // `cat -v` echoes the reports it reads, once a line ends.
+        for (name, script) in [
+            (
+                "press-release",
+                r"printf '\033[?1000h\033[?1006h'; stty -echo; echo ready; cat -v",
+            ),
+            (
+                "any-motion",
+                r"printf '\033[?1003h\033[?1006h'; stty -echo; echo ready; cat -v",
+            ),
+        ] {
+            sessions
+                .create_session(
+                    vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                    Some(name.to_string()),
+                    None,
+                    None,
+                )
+                .await
+                .expect("create session");
+        }
+        let request = |command: Command| {
+            handle_request(
+                Request::new("mouse", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |pattern: &str, session: &str| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(3000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some(session.to_string()),
+        };
+        let cell = |row, col| ClickTarget::Cell { row, col };
+        let hover = |session: &str| Command::Hover {
+            target: cell(1, 1),
+            session: Some(session.to_string()),
+        };
+
+        for name in ["press-release", "any-motion"] {
+            let ready = request(wait("ready", name)).await;
+            assert!(ready.success, "{ready:?}");
+        }
+        let refused = request(hover("press-release")).await;
+        let error = refused.error.expect("no motion reporting");
+        assert_eq!(error.code, ErrorCode::CommandFailed);
+        assert!(error.suggestion.is_some());
+        let quadruple = request(Command::MouseClick {
+            target: cell(0, 0),
+            button: MouseButton::Left,
+            count: 4,
+            modifiers: MouseModifiers::default(),
+            session: Some("any-motion".to_string()),
+        })
+        .await;
+        assert_eq!(
+            quadruple.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
+
+        let clicked = request(Command::MouseClick {
+            target: cell(1, 2),
+            button: MouseButton::Right,
+            count: 2,
+            modifiers: MouseModifiers {
+                ctrl: true,
+                ..MouseModifiers::default()
+            },
+            session: Some("any-motion".to_string()),
+        })
+        .await;
+        assert_eq!(
+            clicked.data,
+            Some(ResponseData::Ok {
+                message: "Double-clicked (right, Ctrl) at row 1, col 2".to_string(),
+            })
+        );
+        let dragged = request(Command::Drag {
+            from: cell(0, 0),
+            to: cell(2, 4),
+            button: MouseButton::Left,
+            modifiers: MouseModifiers::default(),
+            session: Some("any-motion".to_string()),
+        })
+        .await;
+        assert!(dragged.success, "{dragged:?}");
+        let hovered = request(hover("any-motion")).await;
+        assert!(hovered.success, "{hovered:?}");
+        request(Command::Key {
+            key: "Enter".to_string(),
+            delay_ms: 0,
+            session: Some("any-motion".to_string()),
+        })
+        .await;
+        // Ctrl adds 16 to the right button (2); motion adds 32.
+        let echoed = request(wait(
+            "^[[<18;3;2M^[[<18;3;2m^[[<18;3;2M^[[<18;3;2m\
+             ^[[<0;1;1M^[[<32;5;3M^[[<0;5;3m^[[<35;2;2M",
+            "any-motion",
+        ))
+        .await;
+        assert!(echoed.success, "{echoed:?}");
+
+        for name in ["press-release", "any-motion"] {
+            request(Command::Kill {
+                session: Some(name.to_string()),
+            })
+            .await;
+        }
+    }
+
+    #[tokio::test]
+    async fn pastes_are_bracketed_only_when_the_app_asks() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // `cat -v` echoes what it reads, once a line ends.
+        for (name, script) in [
+            ("plain", "stty -echo; echo ready; cat -v"),
+            (
+                "bracketed",
+                r"printf '\033[?2004h'; stty -echo; echo ready; cat -v",
+            ),
+        ] {
+            sessions
+                .create_session(
+                    vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                    Some(name.to_string()),
+                    None,
+                    None,
+                )
+                .await
+                .expect("create session");
+        }
+        let request = |command: Command| {
+            handle_request(
+                Request::new("paste", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |session: &str, pattern: &str| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(3000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some(session.to_string()),
+        };
+        let paste = |session: &str| Command::Paste {
+            text: "one\\n\ntwo\n".to_string(),
+            session: Some(session.to_string()),
+        };
+
+        // The count covers the markers: 12 bytes for `ESC[200~` and `ESC[201~`.
+        for (session, bracketed, written, echoed) in [
+            ("plain", false, 10, "one\\n\ntwo"),
+            ("bracketed", true, 22, "^[[200~one\\n\ntwo\n^[[201~"),
+        ] {
+            let ready = request(wait(session, "ready")).await;
+            assert!(ready.success, "{ready:?}");
+            let pasted = request(paste(session)).await;
+            assert_eq!(
+                pasted.data,
+                Some(ResponseData::Pasted {
+                    bytes: written,
+                    bracketed
+                }),
+                "{session}"
+            );
+            request(Command::Key {
+                key: "Enter".to_string(),
+                delay_ms: 0,
+                session: Some(session.to_string()),
+            })
+            .await;
+            let seen = request(wait(session, echoed)).await;
+            assert!(seen.success, "{session}: {seen:?}");
+        }
 
-        server_handle.abort();
-        let _ = std::fs::remove_file(&socket_path);
+        for name in ["plain", "bracketed"] {
+            request(Command::Kill {
+                session: Some(name.to_string()),
+            })
+            .await;
+        }
     }
 
     #[tokio::test]
-    async fn test_key_rejects_large_delay() {
-        let short_id = Uuid::new_v4().simple().to_string();
-        let socket_path = std::path::PathBuf::from("/tmp")
-            .join(format!("pilotty-keydelay-{}.sock", &short_id[..8]));
-        let pid_path = socket_path.with_extension("pid");
-
-        let server = DaemonServer::bind_to(socket_path.clone(), pid_path.clone())
-            .await
-            .expect("Failed to bind server");
-
-        let server_handle = tokio::spawn(async move {
-            let _ = timeout(Duration::from_secs(2), server.run()).await;
-        });
-
-        tokio::time::sleep(Duration::from_millis(50)).await;
-
-        let stream = UnixStream::connect(&socket_path)
+    async fn keys_follow_the_negotiated_keyboard_protocol() {
+        let sessions = Arc::new(SessionManager::new());
+        let shutdown = Arc::new(Notify::new());
+        // The app pushes kitty flags and queries them; `cat -v` echoes both
+        // the query reply and the keys it reads, once a line ends.
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"stty -echo; printf '\033[>1u\033[?u'; echo ready; cat -v".to_string(),
+                ],
+                Some("kitty".to_string()),
+                None,
+                None,
+            )
             .await
-            .expect("Failed to connect");
-        let (reader, mut writer) = stream.into_split();
-        let mut reader = BufReader::new(reader);
+            .expect("create session");
+        let request = |command: Command| {
+            handle_request(
+                Request::new("keys", command),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
+        let wait = |pattern: &str| Command::WaitFor {
+            pattern: pattern.to_string(),
+            patterns: vec![],
+            match_mode: MatchMode::Any,
+            absent: false,
+            timeout_ms: Some(3000),
+            regex: None,
+            until: None,
+            region: None,
+            scrollback: 0,
+            session: Some("kitty".to_string()),
+        };
 
-        // Try to send a key with delay exceeding MAX_KEY_DELAY_MS (10000)
-        let request = Request {
-            protocol: PROTOCOL_VERSION,
-            id: "key-delay-1".to_string(),
-            command: Command::Key {
-                key: "a b".to_string(),
-                delay_ms: MAX_KEY_DELAY_MS + 1,
-                session: None,
-            },
+        let ready = request(wait("ready")).await;
+        assert!(ready.success, "{ready:?}");
+        let sent = request(Command::Key {
+            key: "Ctrl+I Ctrl+Enter Enter".to_string(),
+            delay_ms: 0,
+            session: Some("kitty".to_string()),
+        })
+        .await;
+        assert!(sent.success, "{sent:?}");
+        let echoed = request(wait("^[[?1u^[[105;5u^[[13;5u")).await;
+        assert!(echoed.success, "{echoed:?}");
+        let pause = |session: &str| Command::Key {
+            key: "Pause".to_string(),
+            delay_ms: 0,
+            session: Some(session.to_string()),
         };
-        let request_json = serde_json::to_string(&request).unwrap();
-        writer
-            .write_all(request_json.as_bytes())
-            .await
-            .expect("write");
-        writer.write_all(b"\n").await.expect("newline");
-        writer.flush().await.expect("flush");
+        assert!(request(pause("kitty")).await.success);
 
-        let mut response_line = String::new();
-        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
+        // Without the kitty protocol Pause has no encoding at all.
+        sessions
+            .create_session(
+                vec!["cat".to_string()],
+                Some("legacy".to_string()),
+                None,
+                None,
+            )
             .await
-            .expect("timeout")
-            .expect("read");
-
-        let response: Response = serde_json::from_str(&response_line).expect("parse response");
-        assert!(!response.success);
-        let error = response.error.expect("error response");
+            .expect("create session");
+        let refused = request(pause("legacy")).await;
+        let error = refused.error.expect("pause without kitty keyboard");
         assert_eq!(error.code, ErrorCode::InvalidInput);
-        assert!(error.message.contains("exceeds maximum"));
+        assert!(error
+            .message
+            .starts_with("Unsupported key in this keyboard mode"));
 
-        server_handle.abort();
-        let _ = std::fs::remove_file(&socket_path);
-        let _ = std::fs::remove_file(&pid_path);
+        for name in ["kitty", "legacy"] {
+            request(Command::Kill {
+                session: Some(name.to_string()),
+            })
+            .await;
+        }
     }
 
     #[tokio::test]
-    async fn test_click_command() {
-        let temp_dir = std::env::temp_dir();
-        let socket_path = temp_dir.join(format!("pilotty-click-{}.sock", std::process::id()));
-        let pid_path = socket_path.with_extension("pid");
-
-        let server = DaemonServer::bind_to(socket_path.clone(), pid_path.clone())
+    async fn click_by_text_targets_nth_match_center() {
+        let sessions = Arc::new(SessionManager::new());
+        sessions
+            .create_session(
+                vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[?1000h  [ OK ]  Cancel  OK'; sleep 5".to_string(),
+                ],
+                Some("click-text".to_string()),
+                None,
+                None,
+            )
             .await
-            .expect("Failed to bind server");
+            .expect("create session");
+        let shutdown = Arc::new(Notify::new());
+        let ready = handle_request(
+            Request::new(
+                "ready",
+                Command::WaitFor {
+                    pattern: "Cancel  OK".to_string(),
+                    patterns: vec![],
+                    match_mode: MatchMode::Any,
+                    absent: false,
+                    timeout_ms: Some(3000),
+                    regex: None,
+                    until: None,
+                    region: None,
+                    scrollback: 0,
+                    session: Some("click-text".to_string()),
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        assert!(ready.success, "screen never rendered: {ready:?}");
+
+        let click = |text: &str, regex: bool, nth: u32| {
+            handle_request(
+                Request::new(
+                    "click",
+                    Command::Click {
+                        target: ClickTarget::Text {
+                            text: text.to_string(),
+                            regex,
+                            nth,
+                        },
+                        session: Some("click-text".to_string()),
+                    },
+                ),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
 
-        let server_handle = tokio::spawn(async move {
-            let _ = timeout(Duration::from_secs(5), server.run()).await;
-        });
+        let second = click("OK", false, 2).await;
+        assert!(matches!(
+            second.data,
+            Some(ResponseData::Ok { ref message }) if message.contains("row 0, col 18")
+        ));
 
-        tokio::time::sleep(Duration::from_millis(50)).await;
+        let button = click(r"\[ *OK *\]", true, 1).await;
+        assert!(matches!(
+            button.data,
+            Some(ResponseData::Ok { ref message }) if message.contains("row 0, col 4")
+        ));
 
-        let stream = UnixStream::connect(&socket_path)
-            .await
-            .expect("Failed to connect");
-        let (reader, mut writer) = stream.into_split();
-        let mut reader = BufReader::new(reader);
+        let missing = click("OK", false, 3).await;
+        let error = missing.error.expect("out of range nth");
+        assert_eq!(error.code, ErrorCode::CommandFailed);
+        assert!(error.message.contains("Only 2"), "got: {}", error.message);
 
-        // Spawn a session
-        let spawn_request = Request {
-            protocol: PROTOCOL_VERSION,
-            id: "spawn-1".to_string(),
-            command: Command::Spawn {
-                command: vec!["cat".to_string()],
-                session_name: Some("click-test".to_string()),
-                cwd: None,
-                retain_bytes: None,
-            },
-        };
-        let request_json = serde_json::to_string(&spawn_request).unwrap();
-        writer
-            .write_all(request_json.as_bytes())
-            .await
-            .expect("write");
-        writer.write_all(b"\n").await.expect("newline");
-        writer.flush().await.expect("flush");
+        let absent = click("Delete", false, 1).await;
+        assert_eq!(
+            absent.error.map(|error| error.code),
+            Some(ErrorCode::CommandFailed)
+        );
 
-        let mut response_line = String::new();
-        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
-            .await
-            .expect("timeout")
-            .expect("read");
+        handle_request(
+            Request::new(
+                "kill",
+                Command::Kill {
+                    session: Some("click-text".to_string()),
+                },
+            ),
+            sessions,
+            shutdown,
+        )
+        .await;
+    }
 
-        tokio::time::sleep(Duration::from_millis(100)).await;
+    #[tokio::test]
+    async fn snapshot_since_returns_changed_rows_or_falls_back() {
+        let sessions = Arc::new(SessionManager::new());
+        sessions
+            .create_session(
+                vec!["cat".to_string()],
+                Some("diff".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create session");
+        let shutdown = Arc::new(Notify::new());
+        let snapshot = |format, await_change, since| {
+            handle_request(
+                Request::new(
+                    "snapshot",
+                    Command::Snapshot {
+                        session: Some("diff".to_string()),
+                        format,
+                        await_change,
+                        settle_ms: 0,
+                        timeout_ms: 3000,
+                        since,
+                        region: None,
+                        ignore: Vec::new(),
+                        scrollback: 0,
+                    },
+                ),
+                sessions.clone(),
+                shutdown.clone(),
+            )
+        };
 
-        // Click at coordinates (row 5, col 10)
-        let click_request = Request {
-            protocol: PROTOCOL_VERSION,
-            id: "click-1".to_string(),
-            command: Command::Click {
-                row: 5,
-                col: 10,
-                session: Some("click-test".to_string()),
-            },
+        let Some(ResponseData::ScreenState(baseline)) =
+            snapshot(SnapshotFormat::Full, None, None).await.data
+        else {
+            panic!("expected baseline screen state");
         };
-        let click_json = serde_json::to_string(&click_request).unwrap();
-        writer
-            .write_all(click_json.as_bytes())
-            .await
-            .expect("write");
-        writer.write_all(b"\n").await.expect("newline");
-        writer.flush().await.expect("flush");
+        let revision = baseline.screen.revision.expect("live revision");
 
-        response_line.clear();
-        timeout(Duration::from_secs(2), reader.read_line(&mut response_line))
-            .await
-            .expect("timeout")
-            .expect("read");
+        let typed = handle_request(
+            Request::new(
+                "type",
+                Command::Type {
+                    text: "hi".to_string(),
+                    session: Some("diff".to_string()),
+                },
+            ),
+            sessions.clone(),
+            shutdown.clone(),
+        )
+        .await;
+        assert!(typed.success);
 
-        let click_response: Response =
-            serde_json::from_str(&response_line).expect("parse click response");
-        assert!(
-            click_response.success,
-            "Click should succeed, got: {:?}",
-            click_response
+        let Some(ResponseData::ScreenDiff(changed)) = snapshot(
+            SnapshotFormat::Full,
+            baseline.screen.content_hash,
+            Some(revision),
+        )
+        .await
+        .data
+        else {
+            panic!("expected a diff against a remembered revision");
+        };
+        assert_eq!(changed.diff.since, revision);
+        assert!(changed.diff.revision > revision);
+        assert_eq!(
+            changed.diff.rows,
+            vec![RowChange {
+                row: 0,
+                text: "hi".to_string(),
+            }]
         );
+        assert_eq!(changed.diff.cursor.map(|cursor| cursor.col), Some(2));
+        assert!(changed.diff.size.is_none());
+
+        let Some(ResponseData::ScreenDiff(unchanged)) =
+            snapshot(SnapshotFormat::Full, None, Some(changed.diff.revision))
+                .await
+                .data
+        else {
+            panic!("expected a diff against the previous diff's revision");
+        };
+        assert!(unchanged.diff.rows.is_empty());
+        assert!(unchanged.diff.cursor.is_none());
+
+        let Some(ResponseData::ScreenState(fallback)) =
+            snapshot(SnapshotFormat::Full, None, Some(u64::MAX))
+                .await
+                .data
+        else {
+            panic!("expected a full snapshot for a forgotten revision");
+        };
+        assert!(fallback.screen.text.is_some_and(|text| text.contains("hi")));
+        assert!(fallback
+            .note
+            .is_some_and(|note| note.contains("no longer retained")));
 
-        // Verify the response message contains coordinates
-        if let Some(ResponseData::Ok { message }) = &click_response.data {
-            assert!(
-                message.contains("row 5") && message.contains("col 10"),
-                "Response should contain coordinates: {}",
-                message
-            );
-        } else {
-            panic!("Expected Ok response, got: {:?}", click_response.data);
-        }
+        let text = snapshot(SnapshotFormat::Text, None, Some(revision)).await;
+        assert_eq!(
+            text.error.map(|error| error.code),
+            Some(ErrorCode::InvalidInput)
+        );
 
-        server_handle.abort();
-        let _ = std::fs::remove_file(&socket_path);
+        handle_request(
+            Request::new(
+                "kill",
+                Command::Kill {
+                    session: Some("diff".to_string()),
+                },
+            ),
+            sessions,
+            shutdown,
+        )
+        .await;
     }
 
     #[tokio::test]
@@ -3199,10 +6815,21 @@ mod tests {
             protocol: PROTOCOL_VERSION,
             id: "spawn-1".to_string(),
             command: Command::Spawn {
-                command: vec!["cat".to_string()],
+                command: vec![
+                    "sh".to_string(),
+                    "-c".to_string(),
+                    r"printf '\033[?1000;1006hmouse on'; exec cat".to_string(),
+                ],
                 session_name: Some("scroll-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3219,7 +6846,28 @@ mod tests {
             .expect("timeout")
             .expect("read");
 
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        // Mouse input needs the application to have enabled reporting.
+        let ready = socket_request(
+            &mut reader,
+            &mut writer,
+            Request::new(
+                "ready",
+                Command::WaitFor {
+                    pattern: "mouse on".to_string(),
+                    patterns: vec![],
+                    match_mode: MatchMode::Any,
+                    absent: false,
+                    timeout_ms: Some(3000),
+                    regex: None,
+                    until: None,
+                    region: None,
+                    scrollback: 0,
+                    session: Some("scroll-test".to_string()),
+                },
+            ),
+        )
+        .await;
+        assert!(ready.success, "mouse never enabled: {ready:?}");
 
         // Scroll up
         let scroll_up_request = Request {
@@ -3312,6 +6960,13 @@ mod tests {
                 session_name: Some("waitfor-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3337,8 +6992,14 @@ mod tests {
             id: "wait-1".to_string(),
             command: Command::WaitFor {
                 pattern: "marker".to_string(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
                 timeout_ms: Some(5000),
                 regex: Some(false),
+                until: None,
+                region: None,
+                scrollback: 0,
                 session: Some("waitfor-test".to_string()),
             },
         };
@@ -3362,6 +7023,7 @@ mod tests {
             found,
             matched_text,
             elapsed_ms,
+            ..
         }) = wait_response.data
         {
             assert!(found, "Should have found the pattern");
@@ -3406,6 +7068,13 @@ mod tests {
                 session_name: Some("waitfor-re-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3430,8 +7099,14 @@ mod tests {
             id: "wait-re".to_string(),
             command: Command::WaitFor {
                 pattern: r"version \d+\.\d+\.\d+".to_string(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
                 timeout_ms: Some(5000),
                 regex: Some(true),
+                until: None,
+                region: None,
+                scrollback: 0,
                 session: Some("waitfor-re-test".to_string()),
             },
         };
@@ -3499,6 +7174,13 @@ mod tests {
                 session_name: Some("waitfor-to-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3523,8 +7205,14 @@ mod tests {
             id: "wait-to".to_string(),
             command: Command::WaitFor {
                 pattern: "nonexistent pattern xyz".to_string(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
                 timeout_ms: Some(500), // Short timeout
                 regex: Some(false),
+                until: None,
+                region: None,
+                scrollback: 0,
                 session: Some("waitfor-to-test".to_string()),
             },
         };
@@ -3590,6 +7278,13 @@ mod tests {
                 session_name: Some("waitfor-bad-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3613,7 +7308,13 @@ mod tests {
             command: Command::WaitFor {
                 pattern: "[invalid(regex".to_string(), // Unbalanced brackets
                 timeout_ms: Some(1000),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
                 regex: Some(true),
+                until: None,
+                region: None,
+                scrollback: 0,
                 session: Some("waitfor-bad-test".to_string()),
             },
         };
@@ -3682,6 +7383,13 @@ mod tests {
                 session_name: Some("await-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3710,6 +7418,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 0,
                 timeout_ms: 30000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let snap_json = serde_json::to_string(&snap_request).unwrap();
@@ -3768,6 +7480,10 @@ mod tests {
                 await_change: Some(baseline_hash),
                 settle_ms: 50,
                 timeout_ms: 5000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let await_json = serde_json::to_string(&await_request).unwrap();
@@ -3854,6 +7570,13 @@ mod tests {
                 session_name: Some("await-static-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -3882,6 +7605,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 0,
                 timeout_ms: 30000,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let snap_json = serde_json::to_string(&snap_request).unwrap();
@@ -3915,6 +7642,10 @@ mod tests {
                 await_change: Some(baseline_hash),
                 settle_ms: 0,
                 timeout_ms: 500,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let await_json = serde_json::to_string(&await_request).unwrap();
@@ -3996,6 +7727,13 @@ mod tests {
                 session_name: Some("settle-busy-test".to_string()),
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -4025,6 +7763,10 @@ mod tests {
                 await_change: None,
                 settle_ms: 200,
                 timeout_ms: 800,
+                since: None,
+                region: None,
+                ignore: Vec::new(),
+                scrollback: 0,
             },
         };
         let settle_json = serde_json::to_string(&settle_request).unwrap();
@@ -4095,6 +7837,13 @@ mod tests {
                 session_name: Some("bad-cwd-test".to_string()),
                 cwd: Some("/nonexistent/path/that/does/not/exist".to_string()),
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             },
         };
         let request_json = serde_json::to_string(&spawn_request).unwrap();
@@ -1,27 +1,44 @@
 //! Session manager for tracking active PTY sessions.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
 use chrono::{DateTime, Utc};
 use portable_pty::ExitStatus;
-use tokio::sync::{mpsc, watch, Mutex, RwLock};
+use regex::Regex;
+use tokio::sync::{broadcast, mpsc, watch, Mutex, RwLock};
 use tokio::task::JoinHandle;
 use tracing::{debug, info};
 
+use pilotty_core::elements::Element;
 use pilotty_core::error::ApiError;
-use pilotty_core::protocol::{RetentionAccounting, SessionInfo, SessionStatus};
-use pilotty_core::snapshot::{compute_content_hash, CursorState, ScreenState, TerminalSize};
+use pilotty_core::protocol::{
+    RetentionAccounting, ScreenRegion, SessionInfo, SessionStatus, Signal, SignalTarget,
+    SnapshotFormat,
+};
+use pilotty_core::snapshot::{
+    changed_rows, compute_content_hash, CursorState, ScreenDiff, ScreenState, StyledRow,
+    TerminalModes, TerminalSize,
+};
 
-use crate::daemon::pty::{AsyncPtyHandle, PtySession, TermSize};
+use crate::daemon::processes;
+use crate::daemon::pty::{AsyncPtyHandle, PtySession, PtyWriter, SpawnEnv, TermSize};
+use crate::daemon::queries::TerminalIdentity;
+use crate::daemon::recording::{Recorder, RecordingOptions};
 use crate::daemon::retention::{RetentionRing, RetentionSnapshot, DEFAULT_RETAIN_BYTES};
-use crate::daemon::terminal::TerminalEmulator;
+use crate::daemon::terminal::{ScreenLine, TerminalEmulator, DEFAULT_SCROLLBACK_LINES};
 use crate::daemon::tombstone::{
     ExitMetadata, Tombstone, TombstoneStore, TOMBSTONE_CAPACITY, TOMBSTONE_OUTPUT_BYTES,
-    TOMBSTONE_TTL,
+    TOMBSTONE_SCROLLBACK_LINES, TOMBSTONE_TTL,
 };
 
+/// Screens served to clients that later snapshots can still diff against.
+const MAX_DIFF_BASELINES: usize = 16;
+
+/// Events buffered per subscriber before the slowest one starts lagging.
+const EVENT_BUS_CAPACITY: usize = 1024;
+
 /// Unique identifier for a session.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct SessionId(pub String);
@@ -57,6 +74,44 @@ impl From<&str> for SessionId {
     }
 }
 
+/// Which part of the screen a capture reports and hashes.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CaptureScope {
+    /// Text and content hash cover only this region.
+    pub(crate) region: Option<ScreenRegion>,
+    /// Cells blanked before hashing.
+    pub(crate) masked_regions: Vec<ScreenRegion>,
+    /// Text removed before hashing.
+    pub(crate) masked_patterns: Vec<Regex>,
+    /// Off-screen lines captured alongside the screen.
+    pub(crate) scrollback: usize,
+}
+
+impl CaptureScope {
+    /// Whether captures report and hash the whole, unmasked screen.
+    pub(crate) fn is_whole_screen(&self) -> bool {
+        self.region.is_none() && self.masked_regions.is_empty() && self.masked_patterns.is_empty()
+    }
+
+    /// Screen text within the region, and its content hash with masks applied.
+    pub(crate) fn text_and_hash(&self, emulator: &TerminalEmulator) -> (String, u64) {
+        let text = match &self.region {
+            Some(region) => emulator.region_text(region),
+            None => emulator.get_text(),
+        };
+        if self.masked_regions.is_empty() && self.masked_patterns.is_empty() {
+            let hash = compute_content_hash(&text);
+            return (text, hash);
+        }
+        let region = self.region.unwrap_or_default();
+        let masked = emulator.masked_text(&region, &self.masked_regions);
+        let masked = self.masked_patterns.iter().fold(masked, |masked, pattern| {
+            pattern.replace_all(&masked, "").into_owned()
+        });
+        (text, compute_content_hash(&masked))
+    }
+}
+
 /// Atomically observed screen data for a session.
 pub(crate) struct SnapshotData {
     pub(crate) text: String,
@@ -67,6 +122,127 @@ pub(crate) struct SnapshotData {
     pub(crate) content_hash: u64,
     /// Revision matching this exact screen state.
     pub(crate) revision: u64,
+    /// Styled spans, captured only for formats that report them.
+    pub(crate) styled: Option<Vec<StyledRow>>,
+    /// Detected UI elements, captured only for formats that report them.
+    pub(crate) elements: Option<Vec<Element>>,
+    /// Most recent off-screen lines, oldest first, when the scope asks for them.
+    pub(crate) scrollback: Option<Vec<String>>,
+    pub(crate) modes: TerminalModes,
+}
+
+impl SnapshotData {
+    fn cursor(&self) -> CursorState {
+        CursorState {
+            row: self.cursor_pos.0,
+            col: self.cursor_pos.1,
+            visible: self.cursor_visible,
+        }
+    }
+
+    fn terminal_size(&self) -> TerminalSize {
+        TerminalSize {
+            cols: self.size.cols,
+            rows: self.size.rows,
+        }
+    }
+}
+
+/// A screen previously returned to a client, kept as a diff baseline.
+pub(crate) struct ScreenBaseline {
+    revision: u64,
+    text: String,
+    cursor: CursorState,
+    size: TerminalSize,
+}
+
+impl ScreenBaseline {
+    pub(crate) fn new(snapshot: &SnapshotData) -> Self {
+        Self {
+            revision: snapshot.revision,
+            text: snapshot.text.clone(),
+            cursor: snapshot.cursor(),
+            size: snapshot.terminal_size(),
+        }
+    }
+
+    /// The screen a session starts with, before any output.
+    pub(crate) fn blank(size: TermSize) -> Self {
+        Self {
+            revision: 0,
+            text: String::new(),
+            cursor: CursorState {
+                row: 0,
+                col: 0,
+                visible: true,
+            },
+            size: TerminalSize {
+                cols: size.cols,
+                rows: size.rows,
+            },
+        }
+    }
+
+    pub(crate) fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// What changed between this baseline and `snapshot`.
+    pub(crate) fn diff(&self, snapshot: &SnapshotData) -> ScreenDiff {
+        let cursor = snapshot.cursor();
+        let size = snapshot.terminal_size();
+        ScreenDiff {
+            since: self.revision,
+            revision: snapshot.revision,
+            content_hash: snapshot.content_hash,
+            size: (size != self.size).then_some(size),
+            cursor: (cursor != self.cursor).then_some(cursor),
+            rows: changed_rows(&self.text, &snapshot.text),
+        }
+    }
+}
+
+/// Something that happened to a session, published on the daemon event bus.
+#[derive(Debug, Clone)]
+pub(crate) struct BusEvent {
+    pub(crate) session: SessionId,
+    pub(crate) name: Option<String>,
+    pub(crate) kind: BusEventKind,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum BusEventKind {
+    Spawned {
+        command: Vec<String>,
+        size: TermSize,
+    },
+    /// The pump applied a batch, advancing the revision.
+    Output(Arc<[u8]>),
+    Resize(TermSize),
+    Bell,
+    Title(String),
+    Exited {
+        exit: ExitMetadata,
+        output_complete: bool,
+    },
+}
+
+/// Publishes one session's events; sending with no subscribers is free.
+#[derive(Clone)]
+struct EventPublisher {
+    session: SessionId,
+    name: Option<String>,
+    tx: broadcast::Sender<BusEvent>,
+}
+
+impl EventPublisher {
+    fn publish(&self, kind: BusEventKind) {
+        let _no_subscribers = self.tx.send(BusEvent {
+            session: self.session.clone(),
+            name: self.name.clone(),
+            kind,
+        });
+    }
 }
 
 pub(crate) struct OutputEvidence {
@@ -87,6 +263,9 @@ struct ObservedTerminal {
     emulator: TerminalEmulator,
     revision: u64,
     size: TermSize,
+    /// Active recording, fed under the same lock as the emulator so it sees
+    /// exactly the bytes and resizes the screen does.
+    recorder: Option<Recorder>,
 }
 
 /// Owned pump task that cannot detach when its session is dropped.
@@ -139,6 +318,8 @@ struct Session {
     name: Option<String>,
     /// Command that was spawned.
     command: Vec<String>,
+    /// TERM the command was spawned with.
+    term: String,
     cwd: Option<String>,
     /// When the session was created.
     created_at: DateTime<Utc>,
@@ -149,6 +330,9 @@ struct Session {
     pump_state: watch::Receiver<PumpState>,
     pump_task: Mutex<PumpTask>,
     process_exit: std::sync::Mutex<Option<ProcessExit>>,
+    /// Most recent screens served to clients, oldest first.
+    baselines: std::sync::Mutex<VecDeque<ScreenBaseline>>,
+    events: EventPublisher,
 }
 
 #[derive(Clone)]
@@ -172,6 +356,7 @@ impl Session {
         let pump_state = self.pump_state();
         let size = self.observed_terminal.lock().await.size;
         let retention = self.retention.lock().await.snapshot();
+        let child_pid = self.pty.child_pid();
 
         SessionStatus::Running {
             id: self.id.0.clone(),
@@ -185,16 +370,18 @@ impl Session {
             },
             idle_ms: duration_millis(pump_state.last_output_at.elapsed()),
             retention: retention_accounting(&retention),
+            foreground: self
+                .pty
+                .foreground_process_group()
+                .and_then(|pgid| processes::process_info(pgid.unsigned_abs())),
+            processes: child_pid.and_then(processes::process_tree),
+            current_cwd: child_pid.and_then(processes::process_cwd),
         }
     }
 
-    /// Check if terminal is in application cursor mode.
-    async fn application_cursor(&self) -> bool {
-        self.observed_terminal
-            .lock()
-            .await
-            .emulator
-            .application_cursor()
+    /// Input and display modes the application has switched on.
+    async fn modes(&self) -> TerminalModes {
+        self.observed_terminal.lock().await.emulator.modes()
     }
 
     /// Write bytes to the PTY (send input to the terminal).
@@ -202,19 +389,35 @@ impl Session {
         self.pty.write(data).await
     }
 
-    async fn snapshot(&self) -> SnapshotData {
-        let terminal = self.observed_terminal.lock().await;
-        let text = terminal.emulator.get_text();
+    /// Capture the screen plus any detail reported by one of `formats`.
+    ///
+    /// The text and content hash follow `scope`.
+    async fn snapshot(&self, formats: &[SnapshotFormat], scope: &CaptureScope) -> SnapshotData {
+        let mut terminal = self.observed_terminal.lock().await;
+        let (text, content_hash) = scope.text_and_hash(&terminal.emulator);
         let cursor_pos = terminal.emulator.cursor_position();
         let cursor_visible = terminal.emulator.cursor_visible();
+        let modes = terminal.emulator.modes();
+        let styled = formats
+            .contains(&SnapshotFormat::Styled)
+            .then(|| terminal.emulator.styled_rows());
+        let elements = formats
+            .contains(&SnapshotFormat::Compact)
+            .then(|| terminal.emulator.elements());
+        let scrollback =
+            (scope.scrollback > 0).then(|| terminal.emulator.scrollback(scope.scrollback));
 
         SnapshotData {
-            content_hash: compute_content_hash(&text),
+            content_hash,
             text,
             cursor_pos,
             cursor_visible,
             size: terminal.size,
             revision: terminal.revision,
+            styled,
+            elements,
+            scrollback,
+            modes,
         }
     }
 
@@ -280,7 +483,20 @@ impl Session {
     }
 
     async fn final_tombstone(&self, output_complete: bool, killed_by_client: bool) -> Tombstone {
-        let snapshot = self.snapshot().await;
+        // Keep every detail so exited sessions can still answer any format.
+        let snapshot = self
+            .snapshot(
+                &[SnapshotFormat::Styled, SnapshotFormat::Compact],
+                &CaptureScope::default(),
+            )
+            .await;
+        let (final_contents, final_scrollback) = {
+            let mut terminal = self.observed_terminal.lock().await;
+            (
+                terminal.emulator.formatted_contents(),
+                terminal.emulator.scrollback(TOMBSTONE_SCROLLBACK_LINES),
+            )
+        };
         let output = self
             .retention
             .lock()
@@ -329,7 +545,14 @@ impl Session {
                 },
                 text: Some(snapshot.text),
                 content_hash: Some(snapshot.content_hash),
+                styled: snapshot.styled,
+                revision: Some(snapshot.revision),
+                elements: snapshot.elements,
+                scrollback: None,
+                modes: Some(snapshot.modes),
             },
+            final_contents,
+            final_scrollback,
             output,
         }
     }
@@ -348,6 +571,7 @@ pub(crate) enum ObservationEvent {
 pub(crate) struct SessionObserver {
     session: Arc<Session>,
     pump_state: watch::Receiver<PumpState>,
+    scope: CaptureScope,
 }
 
 #[derive(Clone)]
@@ -360,10 +584,57 @@ impl SessionObserver {
     /// Capture the current screen after marking the current pump state as observed.
     /// Output arriving after that mark remains visible to `wait_for_update`.
     pub(crate) async fn current(&mut self) -> SnapshotData {
+        self.capture(SnapshotFormat::Full).await
+    }
+
+    /// Like [`Self::current`], additionally capturing what `format` reports.
+    pub(crate) async fn capture(&mut self, format: SnapshotFormat) -> SnapshotData {
         {
             let _state = self.pump_state.borrow_and_update();
         }
-        self.session.snapshot().await
+        self.session.snapshot(&[format], &self.scope).await
+    }
+
+    /// Apply `scope` to the text and content hash of later captures.
+    pub(crate) fn scope(&mut self, scope: CaptureScope) {
+        self.scope = scope;
+    }
+
+    /// The scope later captures are taken with.
+    pub(crate) fn capture_scope(&self) -> &CaptureScope {
+        &self.scope
+    }
+
+    /// Keep a served screen so later snapshots can diff against its revision.
+    ///
+    /// Region captures are not whole screens, so they are never kept.
+    pub(crate) fn remember(&self, snapshot: &SnapshotData) {
+        if self.scope.region.is_some() {
+            return;
+        }
+        let mut baselines = self
+            .session
+            .baselines
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if baselines.iter().any(|b| b.revision == snapshot.revision) {
+            return;
+        }
+        if baselines.len() == MAX_DIFF_BASELINES {
+            baselines.pop_front();
+        }
+        baselines.push_back(ScreenBaseline::new(snapshot));
+    }
+
+    /// Diff `snapshot` against a remembered revision, if still retained.
+    pub(crate) fn diff_since(&self, since: u64, snapshot: &SnapshotData) -> Option<ScreenDiff> {
+        let baselines = self
+            .session
+            .baselines
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let baseline = baselines.iter().find(|b| b.revision == since)?;
+        Some(baseline.diff(snapshot))
     }
 
     /// Inspect the direct child without treating PTY EOF as process exit.
@@ -381,6 +652,21 @@ impl SessionObserver {
             .map_err(|error| ApiError::internal(format!("Failed to inspect session exit: {error}")))
     }
 
+    /// Time since the session last produced output.
+    pub(crate) fn idle_for(&self) -> Duration {
+        self.pump_state.borrow().last_output_at.elapsed()
+    }
+
+    /// Whether the pump has seen PTY EOF.
+    pub(crate) fn output_closed(&self) -> bool {
+        self.pump_state.borrow().output_closed
+    }
+
+    /// Process group currently in the PTY foreground.
+    pub(crate) fn foreground_process_group(&self) -> Option<i32> {
+        self.session.pty.foreground_process_group()
+    }
+
     /// Wait for output, EOF, pump failure, or the supplied duration.
     pub(crate) async fn wait_for_update(&mut self, duration: Duration) -> ObservationEvent {
         match tokio::time::timeout(duration, self.pump_state.changed()).await {
@@ -411,6 +697,8 @@ async fn run_output_pump(
     retention: Arc<Mutex<RetentionRing>>,
     observed_terminal: Arc<Mutex<ObservedTerminal>>,
     state_tx: watch::Sender<PumpState>,
+    events: EventPublisher,
+    replies: PtyWriter,
 ) {
     let mut pending = None;
     let mut last_output_at = Instant::now();
@@ -448,11 +736,14 @@ async fn run_output_pump(
         }
 
         retention.lock().await.append(&batch);
-        let revision = {
+        let (revision, signals) = {
             let mut terminal = observed_terminal.lock().await;
             terminal.emulator.feed(&batch);
+            if let Some(recorder) = terminal.recorder.as_mut() {
+                recorder.output(&batch);
+            }
             terminal.revision = terminal.revision.saturating_add(1);
-            terminal.revision
+            (terminal.revision, terminal.emulator.take_signals())
         };
         last_output_at = Instant::now();
         state_tx.send_replace(PumpState {
@@ -460,9 +751,36 @@ async fn run_output_pump(
             last_output_at,
             output_closed: false,
         });
+        if !signals.replies.is_empty() {
+            // Never wait on the child's input here: an application that stops
+            // reading stdin must not stall its own output.
+            if let Err(error) = replies.try_write(&signals.replies) {
+                debug!("Dropped terminal query reply: {:#}", error);
+            }
+        }
+        events.publish(BusEventKind::Output(batch.into()));
+        if signals.bells > 0 {
+            events.publish(BusEventKind::Bell);
+        }
+        if let Some(title) = signals.title {
+            events.publish(BusEventKind::Title(title));
+        }
     }
 }
 
+/// Per-session settings beyond the command, name, and working directory.
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions {
+    /// Initial terminal size; the manager default when `None`.
+    pub size: Option<TermSize>,
+    /// Raw output bytes to retain; the manager default when `None`.
+    pub retain_bytes: Option<usize>,
+    /// Record the session from its first byte.
+    pub recording: Option<RecordingOptions>,
+    /// Environment for the spawned process.
+    pub env: SpawnEnv,
+}
+
 /// Maximum number of concurrent sessions to prevent resource exhaustion.
 const MAX_SESSIONS: usize = 100;
 
@@ -473,6 +791,14 @@ pub struct SessionManager {
     sessions: RwLock<HashMap<SessionId, Arc<Session>>>,
     tombstones: Mutex<TombstoneStore>,
     default_retain_bytes: usize,
+    /// Off-screen lines each session emulator keeps.
+    scrollback_lines: usize,
+    /// Initial size for sessions that do not request one.
+    default_size: TermSize,
+    /// How session emulators answer terminal queries.
+    terminal_identity: Arc<TerminalIdentity>,
+    /// Daemon-wide event bus feeding subscription streams.
+    events: broadcast::Sender<BusEvent>,
 }
 
 impl Default for SessionManager {
@@ -493,9 +819,36 @@ impl SessionManager {
             sessions: RwLock::new(HashMap::new()),
             tombstones: Mutex::new(TombstoneStore::new(TOMBSTONE_CAPACITY, TOMBSTONE_TTL)),
             default_retain_bytes,
+            scrollback_lines: DEFAULT_SCROLLBACK_LINES,
+            default_size: TermSize::default(),
+            terminal_identity: Arc::default(),
+            events: broadcast::channel(EVENT_BUS_CAPACITY).0,
         }
     }
 
+    /// Start sessions that do not request a size at `size`.
+    pub(crate) fn with_default_size(mut self, size: TermSize) -> Self {
+        self.default_size = size;
+        self
+    }
+
+    /// Initial size for sessions that do not request one.
+    pub(crate) fn default_size(&self) -> TermSize {
+        self.default_size
+    }
+
+    /// Keep up to `lines` off-screen lines in each new session's emulator.
+    pub(crate) fn with_scrollback_lines(mut self, lines: usize) -> Self {
+        self.scrollback_lines = lines;
+        self
+    }
+
+    /// Answer terminal queries from new sessions as `identity`.
+    pub(crate) fn with_terminal_identity(mut self, identity: TerminalIdentity) -> Self {
+        self.terminal_identity = Arc::new(identity);
+        self
+    }
+
     /// Create a new session by spawning a PTY.
     ///
     /// Returns the session ID on success.
@@ -513,18 +866,28 @@ impl SessionManager {
         size: Option<TermSize>,
         cwd: Option<String>,
     ) -> Result<SessionId, ApiError> {
-        self.create_session_with_retention(command, name, size, cwd, None)
+        let options = SessionOptions {
+            size,
+            ..SessionOptions::default()
+        };
+        self.create_session_with_options(command, name, cwd, options)
             .await
     }
 
-    pub async fn create_session_with_retention(
+    /// Create a session with size, retention, recording, and environment options.
+    pub async fn create_session_with_options(
         &self,
         command: Vec<String>,
         name: Option<String>,
-        size: Option<TermSize>,
         cwd: Option<String>,
-        retain_bytes: Option<usize>,
+        options: SessionOptions,
     ) -> Result<SessionId, ApiError> {
+        let SessionOptions {
+            size,
+            retain_bytes,
+            recording,
+            env,
+        } = options;
         let name = name.or_else(|| Some("default".to_string()));
 
         // Check session limit and name uniqueness before spawning to prevent
@@ -541,11 +904,24 @@ impl SessionManager {
             }
         }
 
-        let size = size.unwrap_or_default();
+        let size = size.unwrap_or(self.default_size);
+
+        // Open the recording first so a bad path fails before anything runs.
+        let recorder = recording
+            .as_ref()
+            .map(|options| options.open(size, &command, env.term()))
+            .transpose()?;
 
         // Spawn the PTY session
-        let pty_session = PtySession::spawn(&command, size, cwd.as_deref())
-            .map_err(|error| ApiError::spawn_failed(&command, &format!("{error:#}")))?;
+        let pty_session = match PtySession::spawn(&command, size, cwd.as_deref(), &env) {
+            Ok(pty_session) => pty_session,
+            Err(error) => {
+                if let Some(recorder) = recorder {
+                    recorder.discard();
+                }
+                return Err(ApiError::spawn_failed(&command, &format!("{error:#}")));
+            }
+        };
 
         // Wrap in async handle and transfer sole output ownership to the pump.
         let (pty, read_rx) = AsyncPtyHandle::new(pty_session)
@@ -555,9 +931,14 @@ impl SessionManager {
             retain_bytes.unwrap_or(self.default_retain_bytes),
         )));
         let observed_terminal = Arc::new(Mutex::new(ObservedTerminal {
-            emulator: TerminalEmulator::new(size),
+            emulator: TerminalEmulator::with_identity(
+                size,
+                self.terminal_identity.clone(),
+                self.scrollback_lines,
+            ),
             revision: 0,
             size,
+            recorder,
         }));
         let initial_pump_state = PumpState {
             revision: 0,
@@ -565,18 +946,26 @@ impl SessionManager {
             output_closed: false,
         };
         let (pump_state_tx, pump_state) = watch::channel(initial_pump_state);
+        let id = SessionId::new();
+        let events = EventPublisher {
+            session: id.clone(),
+            name: name.clone(),
+            tx: self.events.clone(),
+        };
         let pump_handle = tokio::spawn(run_output_pump(
             read_rx,
             retention.clone(),
             observed_terminal.clone(),
             pump_state_tx,
+            events.clone(),
+            pty.writer(),
         ));
 
-        let id = SessionId::new();
         let session = Arc::new(Session {
             id: id.clone(),
             name,
             command,
+            term: env.term().to_string(),
             cwd,
             created_at: Utc::now(),
             pty,
@@ -585,6 +974,8 @@ impl SessionManager {
             pump_state,
             pump_task: Mutex::new(PumpTask::new(pump_handle)),
             process_exit: std::sync::Mutex::new(None),
+            baselines: std::sync::Mutex::new(VecDeque::with_capacity(MAX_DIFF_BASELINES)),
+            events,
         });
 
         let mut sessions = self.sessions.write().await;
@@ -600,6 +991,10 @@ impl SessionManager {
                 return Err(ApiError::duplicate_session_name(n));
             }
         }
+        session.events.publish(BusEventKind::Spawned {
+            command: session.command.clone(),
+            size,
+        });
         sessions.insert(id.clone(), session);
 
         Ok(id)
@@ -632,6 +1027,10 @@ impl SessionManager {
             .ok_or_else(|| ApiError::session_not_found(&id.0))?;
         let output_complete = session.shutdown().await;
         let tombstone = session.final_tombstone(output_complete, true).await;
+        session.events.publish(BusEventKind::Exited {
+            exit: tombstone.exit.clone(),
+            output_complete,
+        });
         self.tombstones
             .lock()
             .await
@@ -764,7 +1163,11 @@ impl SessionManager {
         session
             .write(data)
             .await
-            .map_err(|e| ApiError::write_failed(&e.to_string()))
+            .map_err(|e| ApiError::write_failed(&e.to_string()))?;
+        if let Some(recorder) = session.observed_terminal.lock().await.recorder.as_mut() {
+            recorder.input(data);
+        }
+        Ok(())
     }
 
     /// Capture retained output accounting and the geometry needed to render it.
@@ -797,10 +1200,118 @@ impl SessionManager {
         let mut terminal = session.observed_terminal.lock().await;
         terminal.size = new_size;
         terminal.emulator.resize(new_size);
+        if let Some(recorder) = terminal.recorder.as_mut() {
+            recorder.resize(new_size);
+        }
+        drop(terminal);
+        session.events.publish(BusEventKind::Resize(new_size));
 
         Ok(())
     }
 
+    /// Send a signal to a session's child process or PTY foreground group.
+    ///
+    /// Returns the process ID, or process group ID, the signal was sent to.
+    pub(crate) async fn signal_session(
+        &self,
+        id: &SessionId,
+        signal: Signal,
+        target: SignalTarget,
+    ) -> Result<i32, ApiError> {
+        let session = self.session(id).await?;
+        let exited = || {
+            // Record the exit status, if this request was the one to reap it.
+            let _ = session.observe_process_exit();
+            ApiError::session_exited(&id.0, &exit_status_description(&session))
+        };
+        // An exited child stays in the session map until the cleaner
+        // finalizes it, and its PID may already name another process.
+        if session
+            .observe_process_exit()
+            .map_err(|e| ApiError::command_failed(e.to_string()))?
+            .is_some()
+        {
+            return Err(exited());
+        }
+        let failed = |error: std::io::Error| {
+            if error.raw_os_error() == Some(libc::ESRCH) {
+                exited()
+            } else {
+                ApiError::command_failed(format!("Failed to send {signal}: {error}"))
+            }
+        };
+
+        match target {
+            SignalTarget::Child => session
+                .pty
+                .signal_child(signal_number(signal))
+                .map_err(failed)?
+                .ok_or_else(exited),
+            SignalTarget::Foreground => {
+                let group = session
+                    .pty
+                    .foreground_process_group()
+                    .filter(|&group| group > 0)
+                    .ok_or_else(|| {
+                        ApiError::command_failed_with_suggestion(
+                            "Session has no foreground process group",
+                            "Send the signal to the child process with '--target child'.",
+                        )
+                    })?;
+                // SAFETY: kill(2) only reads its integer arguments. tcgetpgrp
+                // reported a positive group ID, so the negated pid addresses
+                // that one process group, never 0 (the daemon's own group) or
+                // -1 (every process the daemon may signal).
+                if unsafe { libc::kill(-group, signal_number(signal)) } != 0 {
+                    return Err(failed(std::io::Error::last_os_error()));
+                }
+                Ok(group)
+            }
+        }
+    }
+
+    /// Start recording a running session to a new asciicast file.
+    ///
+    /// The recording opens with the current screen so playback starts from
+    /// what the session shows now, not from a blank terminal.
+    pub(crate) async fn start_recording(
+        &self,
+        id: &SessionId,
+        options: RecordingOptions,
+    ) -> Result<(), ApiError> {
+        let session = self.session(id).await?;
+        let mut terminal = session.observed_terminal.lock().await;
+        if let Some(recorder) = terminal.recorder.as_ref() {
+            return Err(ApiError::invalid_input_with_suggestion(
+                format!(
+                    "Session is already recording to {}",
+                    recorder.path().display()
+                ),
+                "Run 'pilotty record stop' first, then start a new recording.",
+            ));
+        }
+        let mut recorder = options.open(terminal.size, &session.command, &session.term)?;
+        recorder.output(&terminal.emulator.formatted_contents());
+        terminal.recorder = Some(recorder);
+        Ok(())
+    }
+
+    /// Stop a session's recording, returning the file path and event count.
+    pub(crate) async fn stop_recording(
+        &self,
+        id: &SessionId,
+    ) -> Result<(std::path::PathBuf, u64), ApiError> {
+        let session = self.session(id).await?;
+        let recorder = session.observed_terminal.lock().await.recorder.take();
+        let Some(recorder) = recorder else {
+            return Err(ApiError::invalid_input_with_suggestion(
+                "Session is not recording",
+                "Start one with 'pilotty record start <PATH>' or 'pilotty spawn --record <PATH>'.",
+            ));
+        };
+        Ok(recorder.finish().await)
+    }
+
     /// Get terminal size for a session.
     pub async fn get_terminal_size(&self, id: &SessionId) -> Result<TermSize, ApiError> {
         let session = self.session(id).await?;
@@ -808,13 +1319,27 @@ impl SessionManager {
         Ok(size)
     }
 
-    /// Get the application cursor mode for a session.
+    /// Get the text of every screen row, for locating on-screen targets.
+    pub(crate) async fn screen_lines(&self, id: &SessionId) -> Result<Vec<ScreenLine>, ApiError> {
+        let session = self.session(id).await?;
+        let terminal = session.observed_terminal.lock().await;
+        Ok(terminal.emulator.screen_lines())
+    }
+
+    /// Detect the UI elements on the current screen, for resolving refs.
+    pub(crate) async fn elements(&self, id: &SessionId) -> Result<Vec<Element>, ApiError> {
+        let session = self.session(id).await?;
+        let terminal = session.observed_terminal.lock().await;
+        Ok(terminal.emulator.elements())
+    }
+
+    /// Get the terminal modes the application has switched on.
     ///
-    /// The output pump keeps this mode current.
-    /// When true, arrow keys should send SS3 sequences instead of CSI.
-    pub async fn get_application_cursor_mode(&self, id: &SessionId) -> Result<bool, ApiError> {
+    /// The output pump keeps these modes current. Keys and mouse input must
+    /// be encoded the way they ask.
+    pub async fn get_terminal_modes(&self, id: &SessionId) -> Result<TerminalModes, ApiError> {
         let session = self.session(id).await?;
-        Ok(session.application_cursor().await)
+        Ok(session.modes().await)
     }
 
     async fn session(&self, id: &SessionId) -> Result<Arc<Session>, ApiError> {
@@ -826,6 +1351,11 @@ impl SessionManager {
             .ok_or_else(|| ApiError::session_not_found(&id.0))
     }
 
+    /// Subscribe to events from every session on the daemon.
+    pub(crate) fn subscribe_events(&self) -> broadcast::Receiver<BusEvent> {
+        self.events.subscribe()
+    }
+
     /// Subscribe to screen observations for a live session.
     pub(crate) async fn observe_session(
         &self,
@@ -835,6 +1365,7 @@ impl SessionManager {
         Ok(SessionObserver {
             pump_state: session.pump_state.clone(),
             session,
+            scope: CaptureScope::default(),
         })
     }
 
@@ -899,6 +1430,10 @@ impl SessionManager {
                         .get(&id)
                         .is_some_and(|current| Arc::ptr_eq(current, &session));
                     if is_same_session {
+                        session.events.publish(BusEventKind::Exited {
+                            exit: tombstone.exit.clone(),
+                            output_complete,
+                        });
                         tombstones.insert(tombstone, Instant::now());
                         sessions.remove(&id);
                         info!(
@@ -931,6 +1466,25 @@ fn exit_status_description(session: &Session) -> String {
     }
 }
 
+fn signal_number(signal: Signal) -> libc::c_int {
+    match signal {
+        Signal::Sighup => libc::SIGHUP,
+        Signal::Sigint => libc::SIGINT,
+        Signal::Sigquit => libc::SIGQUIT,
+        Signal::Sigkill => libc::SIGKILL,
+        Signal::Sigusr1 => libc::SIGUSR1,
+        Signal::Sigusr2 => libc::SIGUSR2,
+        Signal::Sigalrm => libc::SIGALRM,
+        Signal::Sigterm => libc::SIGTERM,
+        Signal::Sigcont => libc::SIGCONT,
+        Signal::Sigstop => libc::SIGSTOP,
+        Signal::Sigtstp => libc::SIGTSTP,
+        Signal::Sigttin => libc::SIGTTIN,
+        Signal::Sigttou => libc::SIGTTOU,
+        Signal::Sigwinch => libc::SIGWINCH,
+    }
+}
+
 fn duration_millis(duration: Duration) -> u64 {
     u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
 }
@@ -1352,6 +1906,42 @@ mod tests {
         assert!(changed.text.contains("observer-marker"));
     }
 
+    #[tokio::test]
+    async fn cursor_position_queries_are_answered_on_the_pty() {
+        let manager = SessionManager::new();
+        // Raw mode so the six-byte reply is readable without a newline; the
+        // shell prints it with ESC replaced so it shows up on screen.
+        let script = r#"stty -icanon -echo; printf '\033[6n'; r=$(dd bs=1 count=6 2>/dev/null); echo "got:$r" | tr '\033' E; sleep 5"#;
+        let id = manager
+            .create_session(
+                vec!["sh".to_string(), "-c".to_string(), script.to_string()],
+                Some("queried-session".to_string()),
+                None,
+                None,
+            )
+            .await
+            .expect("create querying session");
+        let mut observer = manager
+            .observe_session(&id)
+            .await
+            .expect("subscribe to session observations");
+
+        let answered = tokio::time::timeout(Duration::from_secs(3), async {
+            loop {
+                let snapshot = observer.current().await;
+                if snapshot.text.contains("got:") {
+                    return snapshot.text;
+                }
+                observer.wait_for_update(Duration::from_millis(200)).await;
+            }
+        })
+        .await
+        .expect("query reply reached the application");
+        assert!(answered.contains("got:E[1;1R"), "{answered}");
+
+        manager.kill_session(&id).await.expect("kill session");
+    }
+
     #[tokio::test]
     async fn invisible_output_advances_revision_without_changing_screen_hash() {
         let manager = SessionManager::new();
@@ -1596,12 +2186,11 @@ mod tests {
     async fn configured_default_is_injected_into_new_sessions() {
         let manager = SessionManager::with_default_retain_bytes(4);
         let id = manager
-            .create_session_with_retention(
+            .create_session_with_options(
                 vec!["printf".to_string(), "abcdef".to_string()],
                 Some("default-retention".to_string()),
                 None,
-                None,
-                None,
+                SessionOptions::default(),
             )
             .await
             .expect("create session");
@@ -1631,15 +2220,17 @@ mod tests {
         let manager = SessionManager::with_default_retain_bytes(2);
         let expected = b"\x1b[31mred\x1b[0m";
         let id = manager
-            .create_session_with_retention(
+            .create_session_with_options(
                 vec![
                     "printf".to_string(),
                     String::from_utf8(expected.to_vec()).expect("valid test bytes"),
                 ],
                 Some("retention-override".to_string()),
                 None,
-                None,
-                Some(expected.len()),
+                SessionOptions {
+                    retain_bytes: Some(expected.len()),
+                    ..SessionOptions::default()
+                },
             )
             .await
             .expect("create session");
@@ -1790,6 +2381,62 @@ mod tests {
         }
     }
 
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    async fn running_status_reports_foreground_process_tree_and_cwd() {
+        let manager = SessionManager::new();
+        let shell = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "cd /; sleep 30".to_string(),
+        ];
+        let id = manager
+            .create_session(
+                shell.clone(),
+                Some("processes".to_string()),
+                None,
+                Some("/tmp".to_string()),
+            )
+            .await
+            .expect("create session");
+
+        let (foreground, tree, current_cwd) = tokio::time::timeout(Duration::from_secs(2), async {
+            loop {
+                if let SessionStatus::Running {
+                    foreground: Some(foreground),
+                    processes: Some(tree),
+                    current_cwd,
+                    ..
+                } = manager
+                    .session_status(Some("processes"))
+                    .await
+                    .expect("status")
+                {
+                    if tree
+                        .children
+                        .first()
+                        // Until the fork execs, the child still looks like the shell.
+                        .is_some_and(|sleep| sleep.process.name == "sleep")
+                    {
+                        break (foreground, tree, current_cwd);
+                    }
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("sleep started under the shell");
+
+        // A non-interactive shell keeps its children in its own process group.
+        assert_eq!(foreground.name, "sh");
+        assert_eq!(foreground.argv, shell);
+        assert_eq!(tree.process, foreground);
+        assert_eq!(tree.children[0].process.argv, vec!["sleep", "30"]);
+        assert_eq!(current_cwd.as_deref(), Some("/"));
+
+        manager.kill_session(&id).await.expect("kill session");
+    }
+
     #[tokio::test]
     async fn status_reports_explicit_kill_and_live_name_precedence() {
         let manager = SessionManager::new();
@@ -3,30 +3,322 @@
 //! Wraps vt100::Parser to provide an in-memory terminal screen buffer
 //! that can parse ANSI escape sequences from PTY output.
 
+use pilotty_core::elements::{detect_elements, Element, GridCell, WIDE_CONTINUATION};
+use pilotty_core::protocol::ScreenRegion;
+use pilotty_core::snapshot::{
+    encode_styled_row, CellStyle, Color, MouseEncoding, MouseTracking, StyledRow, TerminalModes,
+    TerminalSize,
+};
+
+use std::sync::Arc;
+
 use crate::daemon::pty::TermSize;
+use crate::daemon::queries::{answer_csi, answer_osc, TerminalIdentity};
 
 const READABLE_LOG_SCROLLBACK_ROWS: usize = 10_000;
 
+/// DEC private mode that turns on focus in/out reports.
+const FOCUS_REPORTING_MODE: u16 = 1004;
+/// DEC private mode that selects urxvt-style decimal mouse reports.
+const URXVT_MOUSE_MODE: u16 = 1015;
+
+/// xterm resource that modifyOtherKeys (`CSI > 4 ; level m`) sets.
+const MODIFY_OTHER_KEYS_RESOURCE: u16 = 4;
+/// Kitty keyboard flags an application can set (bits 0-4).
+const KITTY_KEYBOARD_FLAGS: u16 = 0b1_1111;
+/// Pushes kept per screen before the oldest entries are evicted.
+const KITTY_KEYBOARD_STACK_LIMIT: usize = 16;
+
+/// Lines a live session keeps after they scroll off the top of the screen.
+pub(crate) const DEFAULT_SCROLLBACK_LINES: usize = 1_000;
+
 /// Terminal emulator that parses ANSI escape sequences.
 ///
 /// Wraps vt100::Parser to maintain an in-memory representation
 /// of the terminal screen state.
 pub struct TerminalEmulator {
-    parser: vt100::Parser,
+    parser: vt100::Parser<TerminalCallbacks>,
+    scanner: SequenceScanner,
+}
+
+/// Side effects of parsed output that do not show up on the screen.
+///
+/// Collected while feeding and drained with [`TerminalEmulator::take_signals`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct TerminalSignals {
+    /// Audible or visual bells rung since the last drain.
+    pub bells: u32,
+    /// Latest window title set since the last drain.
+    pub title: Option<String>,
+    /// Replies to terminal queries, to be written back to the application.
+    pub replies: Vec<u8>,
+}
+
+/// Parser callbacks: answers queries and collects [`TerminalSignals`].
+struct TerminalCallbacks {
+    identity: Arc<TerminalIdentity>,
+    signals: TerminalSignals,
+    /// Focus event reporting (`?1004`), which vt100 does not track itself.
+    focus_reporting: bool,
+    /// urxvt mouse encoding (`?1015`), likewise untracked by vt100.
+    urxvt_mouse: bool,
+    /// Negotiated keyboard protocols, likewise untracked by vt100.
+    keyboard: KeyboardProtocols,
+    /// Whether the byte being parsed is a BEL ending a string, so OSC
+    /// replies can use the terminator the query did.
+    bel_terminated: bool,
+}
+
+/// Keyboard enhancements an application negotiated.
+#[derive(Debug, Default)]
+struct KeyboardProtocols {
+    /// Kitty keyboard flag stacks for the main and alternate screens; the
+    /// top entry of the active screen's stack is in effect.
+    kitty: [Vec<u8>; 2],
+    /// xterm modifyOtherKeys level.
+    modify_other_keys: u8,
+}
+
+impl TerminalCallbacks {
+    /// Forget the modes vt100 does not track, as a full reset (RIS) does.
+    fn reset_modes(&mut self) {
+        self.focus_reporting = false;
+        self.urxvt_mouse = false;
+        self.keyboard = KeyboardProtocols::default();
+    }
+}
+
+/// Finds full resets (RIS, `ESC c`) and BEL-terminated strings in PTY output.
+///
+/// vt100 resets its own screen state on RIS but has no callback for it, so
+/// the modes tracked in [`TerminalCallbacks`] would outlive the reset. It
+/// also drops whether an OSC ended with BEL or ST, which replies should
+/// echo. The scanner follows just enough of the escape sequence grammar to
+/// not mistake a `c` inside a CSI, string, or intermediate sequence for RIS,
+/// and keeps its state across chunks.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum SequenceScanner {
+    #[default]
+    Ground,
+    Escape,
+    /// An escape sequence with intermediate bytes, e.g. `ESC ( B`.
+    EscapeIntermediate,
+    Csi,
+    /// OSC, DCS, SOS, PM, or APC payload, ended by BEL or ST.
+    String,
+    /// ESC inside a string: ST if `\` follows, otherwise a new sequence.
+    StringEscape,
+}
+
+/// A byte the emulator must treat specially, found by [`SequenceScanner`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Boundary {
+    /// The final byte of a RIS.
+    Reset,
+    /// A BEL ending an OSC, DCS, SOS, PM, or APC string.
+    StringBel,
+}
+
+impl SequenceScanner {
+    /// Scan `bytes` up to and including the next boundary, returning its
+    /// index, or `None` after scanning all of `bytes` without one.
+    fn next_boundary(&mut self, bytes: &[u8]) -> Option<(usize, Boundary)> {
+        use SequenceScanner::*;
+
+        for (index, &byte) in bytes.iter().enumerate() {
+            *self = match (*self, byte) {
+                // CAN and SUB abort any sequence.
+                (_, 0x18 | 0x1a) => Ground,
+                (String, 0x07) => {
+                    *self = Ground;
+                    return Some((index, Boundary::StringBel));
+                }
+                (String, 0x1b) => StringEscape,
+                (String, _) => String,
+                (StringEscape, b'\\') => Ground,
+                (_, 0x1b) => Escape,
+                (Escape | StringEscape, b'c') => {
+                    *self = Ground;
+                    return Some((index, Boundary::Reset));
+                }
+                (Escape | StringEscape, b'[') => Csi,
+                (Escape | StringEscape, b']' | b'P' | b'X' | b'^' | b'_') => String,
+                (Escape | StringEscape | EscapeIntermediate, 0x20..=0x2f) => EscapeIntermediate,
+                (Escape | StringEscape | EscapeIntermediate, _) => Ground,
+                (Csi, 0x40..=0x7e) => Ground,
+                (state, _) => state,
+            };
+        }
+        None
+    }
+}
+
+impl KeyboardProtocols {
+    fn kitty_flags(&self, alternate_screen: bool) -> u8 {
+        let stack = &self.kitty[usize::from(alternate_screen)];
+        stack.last().copied().unwrap_or(0)
+    }
+
+    /// Apply a kitty keyboard or modifyOtherKeys sequence, queueing any
+    /// query reply. Returns false for sequences that are neither.
+    fn apply(
+        &mut self,
+        alternate_screen: bool,
+        i1: Option<u8>,
+        params: &[&[u16]],
+        c: char,
+        replies: &mut Vec<u8>,
+    ) -> bool {
+        let param = |index: usize| params.get(index).and_then(|param| param.first()).copied();
+        let stack = &mut self.kitty[usize::from(alternate_screen)];
+        // Flags come from the application, so anything past bit 4 is dropped.
+        let flags = |value: Option<u16>| (value.unwrap_or(0) & KITTY_KEYBOARD_FLAGS) as u8;
+        match (i1, c) {
+            (Some(b'?'), 'u') => {
+                let current = stack.last().copied().unwrap_or(0);
+                replies.extend_from_slice(format!("\x1b[?{current}u").as_bytes());
+            }
+            (Some(b'>'), 'u') => {
+                if stack.len() == KITTY_KEYBOARD_STACK_LIMIT {
+                    stack.remove(0);
+                }
+                stack.push(flags(param(0)));
+            }
+            (Some(b'<'), 'u') => {
+                let count = usize::from(param(0).unwrap_or(1).max(1));
+                stack.truncate(stack.len().saturating_sub(count));
+            }
+            (Some(b'='), 'u') => {
+                let value = flags(param(0));
+                let current = stack.last().copied().unwrap_or(0);
+                let updated = match param(1).unwrap_or(1) {
+                    2 => current | value,
+                    3 => current & !value,
+                    _ => value,
+                };
+                match stack.last_mut() {
+                    Some(top) => *top = updated,
+                    None => stack.push(updated),
+                }
+            }
+            (Some(b'>'), 'm') if param(0) == Some(MODIFY_OTHER_KEYS_RESOURCE) => {
+                self.modify_other_keys = param(1).unwrap_or(0).min(2) as u8;
+            }
+            (Some(b'>'), 'n') if param(0) == Some(MODIFY_OTHER_KEYS_RESOURCE) => {
+                self.modify_other_keys = 0;
+            }
+            // XTQMODKEYS
+            (Some(b'?'), 'm') if param(0) == Some(MODIFY_OTHER_KEYS_RESOURCE) => {
+                let level = self.modify_other_keys;
+                replies.extend_from_slice(format!("\x1b[>4;{level}m").as_bytes());
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl vt100::Callbacks for TerminalCallbacks {
+    fn audible_bell(&mut self, _: &mut vt100::Screen) {
+        self.signals.bells = self.signals.bells.saturating_add(1);
+    }
+
+    fn visual_bell(&mut self, _: &mut vt100::Screen) {
+        self.signals.bells = self.signals.bells.saturating_add(1);
+    }
+
+    fn set_window_title(&mut self, _: &mut vt100::Screen, title: &[u8]) {
+        self.signals.title = Some(String::from_utf8_lossy(title).into_owned());
+    }
+
+    fn unhandled_csi(
+        &mut self,
+        screen: &mut vt100::Screen,
+        i1: Option<u8>,
+        _i2: Option<u8>,
+        params: &[&[u16]],
+        c: char,
+    ) {
+        if i1 == Some(b'?') && matches!(c, 'h' | 'l') {
+            if params.contains(&[FOCUS_REPORTING_MODE].as_slice()) {
+                self.focus_reporting = c == 'h';
+            }
+            if params.contains(&[URXVT_MOUSE_MODE].as_slice()) {
+                self.urxvt_mouse = c == 'h';
+            }
+            return;
+        }
+        let alternate_screen = screen.alternate_screen();
+        if self
+            .keyboard
+            .apply(alternate_screen, i1, params, c, &mut self.signals.replies)
+        {
+            return;
+        }
+        // Report 1-based positions; a cursor parked past the last column
+        // (pending wrap) reports the last column, as xterm does.
+        let (row, col) = screen.cursor_position();
+        let (_, cols) = screen.size();
+        let cursor = (row + 1, col.min(cols.saturating_sub(1)) + 1);
+        if let Some(reply) = answer_csi(&self.identity, cursor, i1, params, c) {
+            self.signals.replies.extend_from_slice(reply.as_bytes());
+        }
+    }
+
+    fn unhandled_osc(&mut self, _: &mut vt100::Screen, params: &[&[u8]]) {
+        if let Some(reply) = answer_osc(&self.identity.palette, params, self.bel_terminated) {
+            self.signals.replies.extend_from_slice(reply.as_bytes());
+        }
+    }
 }
 
 impl TerminalEmulator {
     /// Create a new terminal emulator with the given size.
     pub fn new(size: TermSize) -> Self {
-        // vt100::Parser::new(rows, cols, scrollback_len)
-        let parser = vt100::Parser::new(size.rows, size.cols, 0);
-        Self { parser }
+        Self::with_identity(size, Arc::default(), 0)
+    }
+
+    /// Create a terminal emulator that answers queries as `identity` and
+    /// keeps up to `scrollback_lines` lines that scroll off the top.
+    pub fn with_identity(
+        size: TermSize,
+        identity: Arc<TerminalIdentity>,
+        scrollback_lines: usize,
+    ) -> Self {
+        let callbacks = TerminalCallbacks {
+            identity,
+            signals: TerminalSignals::default(),
+            focus_reporting: false,
+            urxvt_mouse: false,
+            keyboard: KeyboardProtocols::default(),
+            bel_terminated: false,
+        };
+        // vt100::Parser::new_with_callbacks(rows, cols, scrollback_len, callbacks)
+        let parser =
+            vt100::Parser::new_with_callbacks(size.rows, size.cols, scrollback_lines, callbacks);
+        Self {
+            parser,
+            scanner: SequenceScanner::default(),
+        }
     }
 
     /// Feed bytes from PTY output into the terminal emulator.
     ///
     /// Parses ANSI escape sequences and updates the screen state.
-    pub fn feed(&mut self, bytes: &[u8]) {
+    pub fn feed(&mut self, mut bytes: &[u8]) {
+        // Split at each RIS so modes set after it in the same chunk survive,
+        // and feed each string-ending BEL alone so its OSC knows it.
+        while let Some((index, boundary)) = self.scanner.next_boundary(bytes) {
+            self.parser.process(&bytes[..index]);
+            self.parser.callbacks_mut().bel_terminated = boundary == Boundary::StringBel;
+            self.parser.process(&bytes[index..=index]);
+            let callbacks = self.parser.callbacks_mut();
+            callbacks.bel_terminated = false;
+            if boundary == Boundary::Reset {
+                callbacks.reset_modes();
+            }
+            bytes = &bytes[index + 1..];
+        }
         self.parser.process(bytes);
     }
 
@@ -59,6 +351,111 @@ impl TerminalEmulator {
         self.parser.screen().application_cursor()
     }
 
+    /// Input and display modes the application has switched on.
+    pub fn modes(&self) -> TerminalModes {
+        let screen = self.parser.screen();
+        let keyboard = &self.parser.callbacks().keyboard;
+        TerminalModes {
+            alternate_screen: screen.alternate_screen(),
+            mouse_tracking: match screen.mouse_protocol_mode() {
+                vt100::MouseProtocolMode::None => MouseTracking::None,
+                vt100::MouseProtocolMode::Press => MouseTracking::Press,
+                vt100::MouseProtocolMode::PressRelease => MouseTracking::PressRelease,
+                vt100::MouseProtocolMode::ButtonMotion => MouseTracking::ButtonMotion,
+                vt100::MouseProtocolMode::AnyMotion => MouseTracking::AnyMotion,
+            },
+            // Applications enabling several encodings get the most capable one.
+            mouse_encoding: match screen.mouse_protocol_encoding() {
+                vt100::MouseProtocolEncoding::Sgr => MouseEncoding::Sgr,
+                _ if self.parser.callbacks().urxvt_mouse => MouseEncoding::Urxvt,
+                vt100::MouseProtocolEncoding::Utf8 => MouseEncoding::Utf8,
+                vt100::MouseProtocolEncoding::Default => MouseEncoding::X10,
+            },
+            bracketed_paste: screen.bracketed_paste(),
+            focus_reporting: self.parser.callbacks().focus_reporting,
+            application_keypad: screen.application_keypad(),
+            application_cursor: self.application_cursor(),
+            kitty_keyboard: keyboard.kitty_flags(screen.alternate_screen()),
+            modify_other_keys: keyboard.modify_other_keys,
+        }
+    }
+
+    /// Get run-length-encoded styled spans for every non-blank row.
+    ///
+    /// Wide-character continuation cells are folded into the preceding
+    /// character, so span columns always match screen columns.
+    pub fn styled_rows(&self) -> Vec<StyledRow> {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        (0..rows)
+            .filter_map(|row| {
+                let cells = (0..cols).filter_map(|col| {
+                    let cell = screen.cell(row, col)?;
+                    (!cell.is_wide_continuation()).then(|| (col, cell.contents(), cell_style(cell)))
+                });
+                let spans = encode_styled_row(cells);
+                (!spans.is_empty()).then_some(StyledRow { row, spans })
+            })
+            .collect()
+    }
+
+    /// Detect UI elements (buttons, checkboxes, fields, menus, dialogs).
+    ///
+    /// Reverse-video cells count as highlighted, which is how dialog and
+    /// whiptail mark the focused button or selected menu entry.
+    pub fn elements(&self) -> Vec<Element> {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let grid: Vec<Vec<GridCell>> = (0..rows)
+            .map(|row| {
+                (0..cols)
+                    .map(|col| match screen.cell(row, col) {
+                        Some(cell) => GridCell {
+                            ch: if cell.is_wide_continuation() {
+                                WIDE_CONTINUATION
+                            } else {
+                                cell.contents().chars().next().unwrap_or(' ')
+                            },
+                            highlighted: cell.inverse(),
+                        },
+                        None => GridCell::BLANK,
+                    })
+                    .collect()
+            })
+            .collect();
+        detect_elements(&grid)
+    }
+
+    /// Get the text of every screen row along with its cell columns.
+    pub fn screen_lines(&self) -> Vec<ScreenLine> {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        (0..rows)
+            .map(|row| {
+                let mut line = ScreenLine {
+                    row,
+                    text: String::new(),
+                    starts: Vec::new(),
+                    cols,
+                };
+                for col in 0..cols {
+                    let Some(cell) = screen.cell(row, col) else {
+                        continue;
+                    };
+                    if cell.is_wide_continuation() {
+                        continue;
+                    }
+                    line.starts.push((line.text.len(), col));
+                    match cell.contents() {
+                        "" => line.text.push(' '),
+                        contents => line.text.push_str(contents),
+                    }
+                }
+                line
+            })
+            .collect()
+    }
+
     /// Get a cell at the given position.
     ///
     /// Returns None if position is out of bounds.
@@ -74,6 +471,78 @@ impl TerminalEmulator {
         self.parser.screen().contents()
     }
 
+    /// Get the plain text inside `region`, one line per region row.
+    ///
+    /// Trailing blanks are dropped from each line and trailing empty lines
+    /// from the end, matching [`Self::get_text`].
+    pub fn region_text(&self, region: &ScreenRegion) -> String {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let (rows, cols) = region.resolve(TerminalSize { cols, rows });
+        let lines: Vec<String> = screen
+            .rows(cols.start, cols.end - cols.start)
+            .skip(usize::from(rows.start))
+            .take(rows.len())
+            .map(|line| line.trim_end().to_owned())
+            .collect();
+        lines.join("\n").trim_end_matches('\n').to_owned()
+    }
+
+    /// Like [`Self::region_text`], with every cell inside `masks` blanked.
+    pub fn masked_text(&self, region: &ScreenRegion, masks: &[ScreenRegion]) -> String {
+        let screen = self.parser.screen();
+        let (rows, cols) = screen.size();
+        let size = TerminalSize { cols, rows };
+        let (rows, cols) = region.resolve(size);
+        let masks: Vec<_> = masks.iter().map(|mask| mask.resolve(size)).collect();
+        let lines: Vec<String> = rows
+            .map(|row| {
+                let mut line = String::new();
+                for col in cols.clone() {
+                    let Some(cell) = screen.cell(row, col) else {
+                        continue;
+                    };
+                    if cell.is_wide_continuation() {
+                        continue;
+                    }
+                    let masked = masks
+                        .iter()
+                        .any(|(rows, cols)| rows.contains(&row) && cols.contains(&col));
+                    match cell.contents() {
+                        contents if !masked && !contents.is_empty() => line.push_str(contents),
+                        _ => line.push(' '),
+                    }
+                }
+                line.trim_end().to_owned()
+            })
+            .collect();
+        lines.join("\n").trim_end_matches('\n').to_owned()
+    }
+
+    /// Up to `count` of the most recent lines that scrolled off the top of
+    /// the screen, oldest first.
+    pub fn scrollback(&mut self, count: usize) -> Vec<String> {
+        let screen = self.parser.screen_mut();
+        let (rows, cols) = screen.size();
+        // The view offset clamps to the lines actually kept.
+        screen.set_scrollback(count);
+        let mut offset = screen.scrollback();
+        let mut lines = Vec::with_capacity(offset);
+        while offset > 0 {
+            screen.set_scrollback(offset);
+            let visible = offset.min(usize::from(rows));
+            lines.extend(
+                screen
+                    .rows(0, cols)
+                    .take(visible)
+                    .map(|line| line.trim_end().to_owned()),
+            );
+            offset -= visible;
+        }
+        screen.set_scrollback(0);
+        lines
+    }
+
     /// Get the text content of a single row.
     ///
     /// Returns `None` if the row is out of bounds.
@@ -87,39 +556,123 @@ impl TerminalEmulator {
         self.parser.screen().rows(0, cols).nth(row as usize)
     }
 
+    /// Take the bells, title change, and query replies collected since the
+    /// last call.
+    pub fn take_signals(&mut self) -> TerminalSignals {
+        std::mem::take(&mut self.parser.callbacks_mut().signals)
+    }
+
+    /// Escape sequences that redraw the current screen on a blank terminal.
+    pub fn formatted_contents(&self) -> Vec<u8> {
+        self.parser.screen().contents_formatted()
+    }
+
     /// Resize the terminal.
     pub fn resize(&mut self, size: TermSize) {
         self.parser.screen_mut().set_size(size.rows, size.cols);
     }
 }
 
+/// One screen row's text, mapping byte offsets back to screen columns.
+///
+/// Unlike [`TerminalEmulator::get_text`], every cell contributes to the
+/// text (blanks included), so matches can be located on the grid even when
+/// the row contains wide characters.
+pub struct ScreenLine {
+    pub row: u16,
+    pub text: String,
+    /// `(byte offset in text, screen column)` for each cell's first byte.
+    starts: Vec<(usize, u16)>,
+    cols: u16,
+}
+
+impl ScreenLine {
+    /// Screen columns `(col, width)` covered by the byte range `start..end`.
+    pub fn columns(&self, start: usize, end: usize) -> (u16, u16) {
+        let column_at = |offset: usize| {
+            self.starts
+                .iter()
+                .find(|&&(byte, _)| byte >= offset)
+                .map_or(self.cols, |&(_, col)| col)
+        };
+        let first = self
+            .starts
+            .iter()
+            .rev()
+            .find(|&&(byte, _)| byte <= start)
+            .map_or(0, |&(_, col)| col);
+        (first, column_at(end).saturating_sub(first))
+    }
+}
+
+fn cell_style(cell: &vt100::Cell) -> CellStyle {
+    CellStyle {
+        fg: cell_color(cell.fgcolor()),
+        bg: cell_color(cell.bgcolor()),
+        bold: cell.bold(),
+        italic: cell.italic(),
+        underline: cell.underline(),
+        inverse: cell.inverse(),
+    }
+}
+
+fn cell_color(color: vt100::Color) -> Option<Color> {
+    match color {
+        vt100::Color::Default => None,
+        vt100::Color::Idx(index) => Some(Color::Idx(index)),
+        vt100::Color::Rgb(r, g, b) => Some(Color::Rgb(r, g, b)),
+    }
+}
+
 /// Replay retained terminal bytes into a readable bounded scrollback tail.
 pub(crate) fn render_retained_output(bytes: &[u8], size: TermSize) -> String {
-    let mut parser = vt100::Parser::new(size.rows, size.cols, READABLE_LOG_SCROLLBACK_ROWS);
-    parser.process(bytes);
-
-    let mut screen = parser.screen().clone();
-    screen.set_scrollback(usize::MAX);
-    let mut offset = screen.scrollback();
-    let mut lines = Vec::new();
-    while offset > 0 {
-        screen.set_scrollback(offset);
-        let count = offset.min(usize::from(size.rows));
-        lines.extend(
-            screen
-                .rows(0, size.cols)
-                .take(count)
-                .map(|line| line.trim_end().to_owned()),
-        );
-        offset = offset.saturating_sub(usize::from(size.rows));
-    }
-    screen.set_scrollback(0);
-    lines.extend(
-        screen
-            .rows(0, size.cols)
-            .map(|line| line.trim_end().to_owned()),
-    );
-    lines.join("\n").trim_end().to_owned()
+    let mut history = OutputHistory::new(size);
+    history.feed(bytes);
+    history.render()
+}
+
+/// Bounded scrollback tail of terminal output, for output that spans resizes.
+pub(crate) struct OutputHistory {
+    parser: vt100::Parser,
+}
+
+impl OutputHistory {
+    pub(crate) fn new(size: TermSize) -> Self {
+        Self {
+            parser: vt100::Parser::new(size.rows, size.cols, READABLE_LOG_SCROLLBACK_ROWS),
+        }
+    }
+
+    pub(crate) fn feed(&mut self, bytes: &[u8]) {
+        self.parser.process(bytes);
+    }
+
+    pub(crate) fn resize(&mut self, size: TermSize) {
+        self.parser.screen_mut().set_size(size.rows, size.cols);
+    }
+
+    /// Scrollback and screen as trimmed lines, oldest first.
+    pub(crate) fn render(&self) -> String {
+        let mut screen = self.parser.screen().clone();
+        let (rows, cols) = screen.size();
+        screen.set_scrollback(usize::MAX);
+        let mut offset = screen.scrollback();
+        let mut lines = Vec::new();
+        while offset > 0 {
+            screen.set_scrollback(offset);
+            let count = offset.min(usize::from(rows));
+            lines.extend(
+                screen
+                    .rows(0, cols)
+                    .take(count)
+                    .map(|line| line.trim_end().to_owned()),
+            );
+            offset = offset.saturating_sub(usize::from(rows));
+        }
+        screen.set_scrollback(0);
+        lines.extend(screen.rows(0, cols).map(|line| line.trim_end().to_owned()));
+        lines.join("\n").trim_end().to_owned()
+    }
 }
 
 #[cfg(test)]
@@ -336,6 +889,119 @@ mod tests {
         );
     }
 
+    #[test]
+    fn styled_rows_report_highlighted_runs() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 20, rows: 3 });
+
+        term.feed(
+            b"  \x1b[7m< OK >\x1b[0m \x1b[31;1mfail\x1b[0m\r\n\r\n\x1b[38;2;1;2;3m\xe6\x97\xa5x",
+        );
+
+        let rows = term.styled_rows();
+        assert_eq!(rows.len(), 2, "blank rows are omitted: {rows:?}");
+
+        let first = &rows[0];
+        assert_eq!(first.row, 0);
+        let texts: Vec<&str> = first.spans.iter().map(|span| span.text.as_str()).collect();
+        assert_eq!(texts, vec!["  ", "< OK >", " ", "fail"]);
+        assert!(first.spans[1].style.inverse);
+        assert_eq!(first.spans[1].col, 2);
+        assert_eq!(first.spans[3].style.fg, Some(Color::Idx(1)));
+        assert!(first.spans[3].style.bold);
+
+        let wide = &rows[1];
+        assert_eq!(wide.row, 2);
+        assert_eq!(wide.spans.len(), 1);
+        assert_eq!(wide.spans[0].text, "日x");
+        assert_eq!(wide.spans[0].style.fg, Some(Color::Rgb(1, 2, 3)));
+    }
+
+    #[test]
+    fn elements_use_reverse_video_for_focus() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 30, rows: 2 });
+
+        term.feed(b"Save changes?\r\n  \x1b[7m<Yes>\x1b[0m  <No>");
+
+        let elements = term.elements();
+        let summary: Vec<(&str, &str, bool)> = elements
+            .iter()
+            .map(|e| (e.id.as_str(), e.label.as_str(), e.focused))
+            .collect();
+        assert_eq!(summary, vec![("@e1", "Yes", true), ("@e2", "No", false)]);
+        assert_eq!((elements[1].row, elements[1].col), (1, 9));
+    }
+
+    #[test]
+    fn screen_lines_map_matches_to_columns_past_wide_chars() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 12, rows: 2 });
+
+        term.feed("日本 Save\r\n\x1b[2C<OK>".as_bytes());
+
+        let lines = term.screen_lines();
+        assert_eq!(lines[0].text, "日本 Save   ");
+        let start = lines[0].text.find("Save").unwrap();
+        assert_eq!(lines[0].columns(start, start + 4), (5, 4));
+        let start = lines[1].text.find("<OK>").unwrap();
+        assert_eq!(
+            (lines[1].row, lines[1].columns(start, start + 4)),
+            (1, (2, 4))
+        );
+    }
+
+    #[test]
+    fn region_text_covers_only_the_requested_cells() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 12, rows: 4 });
+
+        term.feed("12:00:01 top\r\n日本 Save\r\n\r\nready".as_bytes());
+
+        let region = |spec: &str| term.region_text(&spec.parse().unwrap());
+        assert_eq!(region("-1"), "ready");
+        assert_eq!(region("2"), "");
+        assert_eq!(region("1:2"), "日本 Save");
+        assert_eq!(region(":,9:"), "top");
+        assert_eq!(region("1,4:"), " Save");
+        assert_eq!(region("0:-2,0:7"), "12:00:01\n日本 Sav");
+        assert_eq!(region("2:"), "\nready");
+    }
+
+    #[test]
+    fn scrollback_returns_recent_off_screen_lines_oldest_first() {
+        let identity = Arc::default();
+        let mut term = TerminalEmulator::with_identity(TermSize { cols: 10, rows: 2 }, identity, 3);
+
+        term.feed(b"one\r\ntwo\r\nthree\r\nfour\r\nfive\r\nsix");
+
+        assert_eq!(term.scrollback(2), vec!["three", "four"]);
+        // Only three lines are kept, and reading leaves the live screen in view.
+        assert_eq!(term.scrollback(10), vec!["two", "three", "four"]);
+        assert_eq!(term.get_text(), "five\nsix");
+        assert!(TerminalEmulator::new(TermSize { cols: 10, rows: 2 })
+            .scrollback(5)
+            .is_empty());
+    }
+
+    #[test]
+    fn masked_text_blanks_cells_inside_masks() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 12, rows: 3 });
+
+        term.feed("12:00:01 top\r\n日本 Save\r\nready".as_bytes());
+
+        let parse = |spec: &str| spec.parse::<ScreenRegion>().unwrap();
+        let whole = ScreenRegion::default();
+        assert_eq!(
+            term.masked_text(&whole, &[parse("0,0:7")]),
+            "         top\n日本 Save\nready"
+        );
+        assert_eq!(
+            term.masked_text(&parse("1:"), &[parse("1,0:1"), parse("-1")]),
+            " 本 Save"
+        );
+        assert_eq!(
+            term.masked_text(&parse("2"), &[]),
+            term.region_text(&parse("2"))
+        );
+    }
+
     #[test]
     fn test_cursor_carriage_return() {
         let mut term = TerminalEmulator::new(TermSize { cols: 80, rows: 24 });
@@ -533,4 +1199,132 @@ mod tests {
             "Should be normal mode after ESC[?1l"
         );
     }
+
+    #[test]
+    fn modes_follow_private_mode_sequences() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 80, rows: 24 });
+        assert_eq!(term.modes(), TerminalModes::default());
+
+        // One sequence can set several modes, including ones vt100 ignores.
+        term.feed(b"\x1b[?1049h\x1b[?1002;1006;1004;2004h\x1b=");
+        assert_eq!(
+            term.modes(),
+            TerminalModes {
+                alternate_screen: true,
+                mouse_tracking: MouseTracking::ButtonMotion,
+                mouse_encoding: MouseEncoding::Sgr,
+                bracketed_paste: true,
+                focus_reporting: true,
+                application_keypad: true,
+                application_cursor: false,
+                kitty_keyboard: 0,
+                modify_other_keys: 0,
+            }
+        );
+
+        // urxvt encoding yields to SGR while both are on.
+        term.feed(b"\x1b[?1015h");
+        assert_eq!(term.modes().mouse_encoding, MouseEncoding::Sgr);
+        term.feed(b"\x1b[?1006l");
+        assert_eq!(term.modes().mouse_encoding, MouseEncoding::Urxvt);
+
+        term.feed(b"\x1b[?1004l\x1b[?1002l\x1b[?1049l\x1b>");
+        let modes = term.modes();
+        assert!(!modes.alternate_screen && !modes.focus_reporting && !modes.application_keypad);
+        assert_eq!(modes.mouse_tracking, MouseTracking::None);
+        assert!(term.take_signals().replies.is_empty());
+    }
+
+    #[test]
+    fn keyboard_protocols_follow_negotiation_per_screen() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 80, rows: 24 });
+
+        // Push, query, and adjust kitty flags on the main screen.
+        term.feed(b"\x1b[>1u\x1b[?u\x1b[=4;2u");
+        assert_eq!(term.modes().kitty_keyboard, 0b101);
+        // The alternate screen keeps its own stack.
+        term.feed(b"\x1b[?1049h\x1b[>31u\x1b[>8u");
+        assert_eq!(term.modes().kitty_keyboard, 8);
+        term.feed(b"\x1b[<u");
+        assert_eq!(term.modes().kitty_keyboard, 31);
+        term.feed(b"\x1b[<5u\x1b[?u\x1b[?1049l");
+        assert_eq!(term.modes().kitty_keyboard, 0b101);
+        term.feed(b"\x1b[=1;3u");
+        assert_eq!(term.modes().kitty_keyboard, 0b100);
+
+        term.feed(b"\x1b[>4;2m\x1b[?4m");
+        assert_eq!(term.modes().modify_other_keys, 2);
+        term.feed(b"\x1b[>4n");
+        assert_eq!(term.modes().modify_other_keys, 0);
+
+        let replies = String::from_utf8(term.take_signals().replies).unwrap();
+        assert_eq!(replies, "\x1b[?1u\x1b[?0u\x1b[>4;2m");
+    }
+
+    #[test]
+    fn full_reset_clears_modes_vt100_does_not_track() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 80, rows: 24 });
+        let enable = b"\x1b[?1000h\x1b[?1004h\x1b[?1015h\x1b[>1u\x1b[>4;2m";
+
+        term.feed(enable);
+        let modes = term.modes();
+        assert!(modes.focus_reporting);
+        assert_eq!(modes.mouse_encoding, MouseEncoding::Urxvt);
+        assert_eq!((modes.kitty_keyboard, modes.modify_other_keys), (1, 2));
+        // RIS split across chunks still resets.
+        term.feed(b"\x1b");
+        term.feed(b"c");
+        assert_eq!(term.modes(), TerminalModes::default());
+
+        // Modes set after a reset in the same chunk are kept.
+        term.feed(&[b"\x1bc".as_slice(), enable].concat());
+        assert!(term.modes().focus_reporting);
+        term.feed(&[enable.as_slice(), b"\x1bc"].concat());
+        assert!(!term.modes().focus_reporting);
+
+        // A `c` ending a CSI, string, or intermediate sequence is not RIS.
+        term.feed(enable);
+        term.feed(b"\x1b[c\x1b]2;c\x07\x1b]2;t\x1b\\c\x1b#c");
+        let modes = term.modes();
+        assert!(modes.focus_reporting);
+        assert_eq!(modes.kitty_keyboard, 1);
+        // ESC aborts a string, so `ESC c` inside one is still RIS, as in vt100.
+        term.feed(b"\x1b]2;\x1bc");
+        assert_eq!(term.modes(), TerminalModes::default());
+    }
+
+    #[test]
+    fn signals_collect_bells_and_latest_title_until_taken() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 20, rows: 2 });
+
+        term.feed(b"\x07ding\x1b]2;first\x07\x1b]2;second\x07\x07");
+        assert_eq!(
+            term.take_signals(),
+            TerminalSignals {
+                bells: 2,
+                title: Some("second".to_string()),
+                replies: Vec::new(),
+            }
+        );
+        assert_eq!(term.get_text().trim_end(), "ding");
+        assert_eq!(term.take_signals(), TerminalSignals::default());
+    }
+
+    #[test]
+    fn queries_are_answered_at_the_cursor_position_of_the_query() {
+        let mut term = TerminalEmulator::new(TermSize { cols: 10, rows: 4 });
+
+        // The second report reflects the cursor after "abc", and the pending
+        // wrap after filling the row reports the last column. OSC replies
+        // end with the terminator their query used.
+        term.feed(b"\x1b[6nabc\x1b[6n\r\n0123456789\x1b[6n\x1b[c\x1b]11;?\x07\x1b]10;?\x1b\\");
+        let signals = term.take_signals();
+        let replies = String::from_utf8(signals.replies).unwrap();
+        assert_eq!(
+            replies,
+            "\x1b[1;1R\x1b[1;4R\x1b[2;10R\x1b[?62;22c\x1b]11;rgb:0000/0000/0000\x07\x1b]10;rgb:e5e5/e5e5/e5e5\x1b\\"
+        );
+        assert_eq!(signals.bells, 0);
+        assert_eq!(term.get_text().trim_end(), "abc\n0123456789");
+    }
 }
@@ -12,6 +12,7 @@ use crate::daemon::session::SessionId;
 pub(crate) const TOMBSTONE_CAPACITY: usize = 100;
 pub(crate) const TOMBSTONE_TTL: Duration = Duration::from_secs(10 * 60);
 pub(crate) const TOMBSTONE_OUTPUT_BYTES: usize = 64 * 1024;
+pub(crate) const TOMBSTONE_SCROLLBACK_LINES: usize = 1_000;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) struct ExitMetadata {
@@ -47,6 +48,10 @@ pub(crate) struct Tombstone {
     pub(crate) exit: ExitMetadata,
     pub(crate) output_complete: bool,
     pub(crate) final_screen: ScreenState,
+    /// Escape sequences that redraw the final screen, for region captures.
+    pub(crate) final_contents: Vec<u8>,
+    /// Most recent off-screen lines at exit, oldest first.
+    pub(crate) final_scrollback: Vec<String>,
     pub(crate) output: RetentionSnapshot,
 }
 
@@ -152,6 +157,8 @@ mod tests {
             },
             output_complete: true,
             final_screen: ScreenState::empty(80, 24),
+            final_contents: Vec::new(),
+            final_scrollback: Vec::new(),
             output: RetentionSnapshot {
                 bytes: vec![],
                 total_bytes: 0,
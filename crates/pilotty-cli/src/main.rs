@@ -2,17 +2,22 @@
 
 mod args;
 mod daemon;
+mod mcp;
+mod replay;
 
-use clap::Parser;
+use anyhow::Context;
+use clap::{CommandFactory, Parser};
 use pilotty_core::error::ErrorCode;
 use pilotty_core::protocol::{
-    CaptureOutcome, Command, OutputFormat, Request, ResponseData, ScrollDirection, SnapshotFormat,
+    CaptureOutcome, ClickTarget, Command, MatchMode, MouseButton, MouseModifiers, OutputFormat,
+    ProcessCondition, Request, ResponseData, ScreenMask, ScrollDirection, SignalTarget,
+    SnapshotFormat,
 };
-use std::io::Write;
+use std::io::{IsTerminal, Read, Write};
 use tracing::{error, info};
 use uuid::Uuid;
 
-use crate::args::{Cli, Commands};
+use crate::args::{Cli, ClickPosition, Commands, MouseModifierArgs, PasteArgs, RecordAction};
 use crate::daemon::client::DaemonClient;
 use crate::daemon::server::DaemonServer;
 
@@ -31,22 +36,41 @@ impl CliExitCode {
 }
 
 fn main() -> std::process::ExitCode {
-    // Initialize tracing
+    let cli = Cli::parse();
+
+    // Initialize tracing. MCP owns stdout for protocol messages, so its logs
+    // go to stderr.
+    let mcp = matches!(cli.command, Commands::Mcp);
     tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive(tracing::Level::INFO.into()),
         )
+        .with_writer(move || -> Box<dyn Write> {
+            if mcp {
+                Box::new(std::io::stderr())
+            } else {
+                Box::new(std::io::stdout())
+            }
+        })
         .init();
 
-    let cli = Cli::parse();
-
     // Daemon command runs the server, all other commands are clients
     if let Commands::Daemon = cli.command {
         run_daemon();
         return std::process::ExitCode::SUCCESS;
     }
 
+    if mcp {
+        return match mcp::run() {
+            Ok(()) => std::process::ExitCode::SUCCESS,
+            Err(e) => {
+                error!("MCP server error: {:#}", e);
+                std::process::ExitCode::from(CliExitCode::GenericError.value())
+            }
+        };
+    }
+
     // All other commands talk to the daemon
     match run_client_command(cli) {
         Ok(CliExitCode::Success) => {}
@@ -68,8 +92,8 @@ fn main() -> std::process::ExitCode {
 /// Convert CLI args to a protocol Command.
 ///
 /// Returns None for commands that don't require daemon communication.
-fn cli_to_command(cli: &Cli) -> Option<Command> {
-    match &cli.command {
+fn cli_to_command(cli: &Cli) -> anyhow::Result<Option<Command>> {
+    Ok(match &cli.command {
         Commands::Spawn(args) => Some(Command::Spawn {
             command: args.command.clone(),
             session_name: args.name.clone(),
@@ -80,33 +104,97 @@ fn cli_to_command(cli: &Cli) -> Option<Command> {
                     .map(|p| p.to_string_lossy().into_owned())
             }),
             retain_bytes: args.retain_bytes,
+            record: args.record.as_deref().map(absolute_path),
+            record_input: args.record_input,
+            env: args.env.iter().cloned().collect(),
+            env_clear: args.env_clear,
+            term: args.term.clone(),
+            cols: args.cols,
+            rows: args.rows,
         }),
         Commands::Kill(args) => Some(Command::Kill {
             session: args.session.clone(),
         }),
         Commands::Snapshot(args) => Some(Command::Snapshot {
             session: args.session.clone(),
-            format: match args.format {
-                crate::args::SnapshotFormat::Full => SnapshotFormat::Full,
-                crate::args::SnapshotFormat::Compact => SnapshotFormat::Compact,
-                crate::args::SnapshotFormat::Text => SnapshotFormat::Text,
-            },
+            format: args.format.into(),
             await_change: args.await_change,
             settle_ms: args.settle,
             timeout_ms: args.timeout,
+            since: args.since,
+            region: args.region,
+            ignore: args
+                .ignore
+                .iter()
+                .copied()
+                .map(ScreenMask::Region)
+                .chain(args.ignore_regex.iter().cloned().map(ScreenMask::Regex))
+                .collect(),
+            scrollback: args.scrollback,
         }),
         Commands::Type(args) => Some(Command::Type {
             text: args.text.clone(),
             session: args.session.clone(),
         }),
+        Commands::Paste(args) => Some(Command::Paste {
+            text: paste_text(args)?,
+            session: args.session.clone(),
+        }),
         Commands::Key(args) => Some(Command::Key {
             key: args.key.clone(),
             delay_ms: args.delay,
             session: args.session.clone(),
         }),
-        Commands::Click(args) => Some(Command::Click {
-            row: args.row,
-            col: args.col,
+        Commands::Click(args) => {
+            let target = click_target(
+                args.row.as_ref(),
+                args.col,
+                args.text.as_deref(),
+                args.regex,
+                args.nth,
+            );
+            let button = mouse_button(args.button);
+            let modifiers = mouse_modifiers(&args.modifiers);
+            // Plain left clicks stay understandable to older daemons.
+            if button == MouseButton::Left
+                && args.count == 1
+                && modifiers == MouseModifiers::default()
+            {
+                Some(Command::Click {
+                    target,
+                    session: args.session.clone(),
+                })
+            } else {
+                Some(Command::MouseClick {
+                    target,
+                    button,
+                    count: args.count,
+                    modifiers,
+                    session: args.session.clone(),
+                })
+            }
+        }
+        Commands::Drag(args) => Some(Command::Drag {
+            from: ClickTarget::Cell {
+                row: args.from_row,
+                col: args.from_col,
+            },
+            to: ClickTarget::Cell {
+                row: args.to_row,
+                col: args.to_col,
+            },
+            button: mouse_button(args.button),
+            modifiers: mouse_modifiers(&args.modifiers),
+            session: args.session.clone(),
+        }),
+        Commands::Hover(args) => Some(Command::Hover {
+            target: click_target(
+                args.row.as_ref(),
+                args.col,
+                args.text.as_deref(),
+                args.regex,
+                args.nth,
+            ),
             session: args.session.clone(),
         }),
         Commands::Scroll(args) => Some(Command::Scroll {
@@ -130,18 +218,139 @@ fn cli_to_command(cli: &Cli) -> Option<Command> {
             rows: args.rows,
             session: args.session.clone(),
         }),
+        Commands::Signal(args) => Some(Command::Signal {
+            signal: args.signal,
+            target: match args.target {
+                crate::args::SignalTarget::Child => SignalTarget::Child,
+                crate::args::SignalTarget::Foreground => SignalTarget::Foreground,
+            },
+            session: args.session.clone(),
+        }),
+        Commands::Record(args) => Some(match &args.action {
+            RecordAction::Start(start) => Command::RecordStart {
+                path: absolute_path(&start.path),
+                input: start.input,
+                session: start.session.clone(),
+            },
+            RecordAction::Stop(stop) => Command::RecordStop {
+                session: stop.session.clone(),
+            },
+        }),
+        Commands::Subscribe(args) => Some(Command::Subscribe {
+            sessions: args.sessions.clone(),
+            output: args.output,
+        }),
         Commands::WaitFor(args) => Some(Command::WaitFor {
-            pattern: args.pattern.clone(),
+            pattern: args.pattern.clone().unwrap_or_default(),
+            patterns: args.patterns.clone(),
+            match_mode: if args.all {
+                MatchMode::All
+            } else {
+                MatchMode::Any
+            },
+            absent: args.absent,
             timeout_ms: Some(args.timeout),
             regex: Some(args.regex),
+            until: if args.exit {
+                Some(ProcessCondition::Exit)
+            } else if args.foreground_change {
+                Some(ProcessCondition::ForegroundChange)
+            } else {
+                args.idle.map(|idle_ms| ProcessCondition::Idle { idle_ms })
+            },
+            region: args.region,
+            scrollback: args.scrollback,
             session: args.session.clone(),
         }),
-        Commands::Daemon => unreachable!("Daemon command handled separately"),
-        Commands::Examples => None,
+        Commands::Daemon | Commands::Mcp => unreachable!("Server commands handled separately"),
+        Commands::Examples | Commands::Replay(_) => None,
         Commands::Stop => Some(Command::Shutdown),
+    })
+}
+
+/// Text for `paste`: the argument, the file, or else all of stdin.
+fn paste_text(args: &PasteArgs) -> anyhow::Result<String> {
+    if let Some(text) = &args.text {
+        return Ok(text.clone());
+    }
+    if let Some(path) = &args.file {
+        return std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read paste text from {path}"));
+    }
+    // Reading a terminal would block waiting for an EOF nobody means to send.
+    if std::io::stdin().is_terminal() {
+        usage_error("Nothing to paste: pass TEXT, --file, or pipe input".to_string());
+    }
+    let mut text = String::new();
+    std::io::stdin()
+        .read_to_string(&mut text)
+        .context("Failed to read paste text from stdin")?;
+    Ok(text)
+}
+
+fn mouse_button(button: crate::args::MouseButton) -> MouseButton {
+    match button {
+        crate::args::MouseButton::Left => MouseButton::Left,
+        crate::args::MouseButton::Middle => MouseButton::Middle,
+        crate::args::MouseButton::Right => MouseButton::Right,
+    }
+}
+
+fn mouse_modifiers(args: &MouseModifierArgs) -> MouseModifiers {
+    MouseModifiers {
+        shift: args.shift,
+        alt: args.alt,
+        ctrl: args.ctrl,
     }
 }
 
+/// Resolve a client-relative path, since the daemon runs in its own directory.
+pub(crate) fn absolute_path(path: &str) -> String {
+    std::env::current_dir()
+        .map(|dir| dir.join(path))
+        .unwrap_or_else(|_| path.into())
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Build a click or hover target from its positional arguments or --text.
+fn click_target(
+    position: Option<&ClickPosition>,
+    col: Option<u16>,
+    text: Option<&str>,
+    regex: bool,
+    nth: u32,
+) -> ClickTarget {
+    // clap requires either --text or a row or ref, and rejects both.
+    match (text, position, col) {
+        (Some(text), _, _) => ClickTarget::Text {
+            text: text.to_string(),
+            regex,
+            nth,
+        },
+        (None, Some(ClickPosition::Row(row)), Some(col)) => ClickTarget::Cell { row: *row, col },
+        (None, Some(ClickPosition::Ref(element_ref)), None) => ClickTarget::Ref {
+            r#ref: element_ref.clone(),
+        },
+        (None, Some(ClickPosition::Row(row)), None) => usage_error(format!(
+            "missing the column after row {row}; pass both, e.g. '{row} 10'"
+        )),
+        (None, Some(ClickPosition::Ref(element_ref)), Some(_)) => usage_error(format!(
+            "an element ref takes no column; pass just '{element_ref}'"
+        )),
+        (None, None, _) => {
+            usage_error("pass a row and column, an element ref, or --text".to_string())
+        }
+    }
+}
+
+/// Exit with a usage error for argument combinations clap cannot check.
+fn usage_error(message: String) -> ! {
+    Cli::command()
+        .error(clap::error::ErrorKind::ArgumentConflict, message)
+        .exit()
+}
+
 /// Run a client command by connecting to the daemon.
 fn run_client_command(cli: Cli) -> anyhow::Result<CliExitCode> {
     let strict = matches!(
@@ -151,17 +360,30 @@ fn run_client_command(cli: Cli) -> anyhow::Result<CliExitCode> {
     let targets_live_session = matches!(
         &cli.command,
         Commands::Type(_)
+            | Commands::Paste(_)
             | Commands::Key(_)
             | Commands::Click(_)
+            | Commands::Drag(_)
+            | Commands::Hover(_)
             | Commands::Scroll(_)
             | Commands::Resize(_)
+            | Commands::Signal(_)
+            | Commands::Record(_)
     );
 
     // Handle commands that don't need daemon communication
-    let Some(command) = cli_to_command(&cli) else {
-        // Examples command just prints and exits
-        if let Commands::Examples = cli.command {
-            println!("{}", crate::args::EXAMPLES_TEXT);
+    let Some(command) = cli_to_command(&cli)? else {
+        match &cli.command {
+            // Examples command just prints and exits
+            Commands::Examples => println!("{}", crate::args::EXAMPLES_TEXT),
+            // Replay runs offline against a file
+            Commands::Replay(args) => {
+                if let Err(error) = replay::run(args) {
+                    eprintln!("Error: {error:#}");
+                    return Ok(CliExitCode::GenericError);
+                }
+            }
+            _ => {}
         }
         return Ok(CliExitCode::Success);
     };
@@ -175,6 +397,10 @@ fn run_client_command(cli: Cli) -> anyhow::Result<CliExitCode> {
         // Build request
         let request = Request::new(Uuid::new_v4().to_string(), command);
 
+        if let Command::Subscribe { .. } = request.command {
+            return print_event_stream(client, request).await;
+        }
+
         // Send request and get response
         let response = client.request(request).await?;
 
@@ -231,6 +457,23 @@ fn run_client_command(cli: Cli) -> anyhow::Result<CliExitCode> {
     })
 }
 
+/// Print a subscription as JSON lines until the daemon ends it.
+async fn print_event_stream(client: DaemonClient, request: Request) -> anyhow::Result<CliExitCode> {
+    let mut stream = client.subscribe(request).await?;
+    while let Some(response) = stream.next().await? {
+        if let Some(err) = response.error {
+            eprintln!("Error: {}", err);
+            return Ok(CliExitCode::GenericError);
+        }
+        if let Some(data) = response.data {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{}", serde_json::to_string(&data)?)?;
+            stdout.flush()?;
+        }
+    }
+    Ok(CliExitCode::Success)
+}
+
 fn capture_exit_code(strict: bool, outcome: Option<CaptureOutcome>) -> CliExitCode {
     if !strict {
         return CliExitCode::Success;
@@ -322,10 +565,12 @@ async fn sigterm() {
 
 #[cfg(test)]
 mod tests {
+    use clap::Parser;
     use pilotty_core::error::ErrorCode;
-    use pilotty_core::protocol::CaptureOutcome;
+    use pilotty_core::protocol::{CaptureOutcome, Command, MouseButton, LEGACY_PROTOCOL_VERSION};
 
-    use crate::{api_error_exit_code, capture_exit_code, CliExitCode};
+    use crate::args::Cli;
+    use crate::{api_error_exit_code, capture_exit_code, cli_to_command, CliExitCode};
 
     #[test]
     fn strict_capture_exit_codes_are_categorical() {
@@ -362,4 +607,29 @@ mod tests {
             CliExitCode::GenericError
         );
     }
+
+    #[test]
+    fn only_plain_left_clicks_use_the_legacy_click_command() {
+        let command = |args: &[&str]| {
+            cli_to_command(&Cli::parse_from(args))
+                .expect("convert")
+                .expect("daemon command")
+        };
+
+        let plain = command(&["pilotty", "click", "3", "7"]);
+        assert!(matches!(plain, Command::Click { .. }));
+        assert_eq!(plain.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
+        assert!(matches!(
+            command(&["pilotty", "click", "--button", "right", "3", "7"]),
+            Command::MouseClick {
+                button: MouseButton::Right,
+                count: 1,
+                ..
+            }
+        ));
+        assert!(matches!(
+            command(&["pilotty", "click", "--alt", "3", "7"]),
+            Command::MouseClick { modifiers, .. } if modifiers.alt
+        ));
+    }
 }
@@ -0,0 +1,907 @@
+//! Model Context Protocol server over stdio.
+//!
+//! `pilotty mcp` reads newline-delimited JSON-RPC 2.0 messages from stdin and
+//! writes responses to stdout. Each tool maps onto one daemon command, so MCP
+//! hosts get the same sessions, snapshots, and structured errors as the CLI
+//! without spawning a process per step. Logs go to stderr; stdout carries
+//! protocol messages only.
+
+use anyhow::Result;
+use pilotty_core::error::ApiError;
+use pilotty_core::protocol::{
+    Command, OutputFormat, Request, Response, ResponseData, Signal, SnapshotFormat,
+};
+use serde_json::{json, Map, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+use crate::daemon::client::DaemonClient;
+
+/// MCP revisions this server speaks, newest first.
+const SUPPORTED_PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const SESSION_PROPERTY: &str = "Target session by name or ID. Defaults to the 'default' session.";
+
+/// Serve MCP on stdin/stdout until stdin closes.
+pub(crate) fn run() -> Result<()> {
+    let runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(serve())
+}
+
+async fn serve() -> Result<()> {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    let mut stdout = tokio::io::stdout();
+    let mut server = McpServer::default();
+
+    while let Some(line) = lines.next_line().await? {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(message) => server.handle(message).await,
+            Err(error) => Some(rpc_error(
+                Value::Null,
+                PARSE_ERROR,
+                format!("Parse error: {error}"),
+            )),
+        };
+        if let Some(reply) = reply {
+            stdout.write_all(format!("{reply}\n").as_bytes()).await?;
+            stdout.flush().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Per-connection MCP state: a lazily opened daemon connection.
+#[derive(Default)]
+struct McpServer {
+    client: Option<DaemonClient>,
+}
+
+impl McpServer {
+    /// Handle one JSON-RPC message, returning the reply for requests.
+    ///
+    /// Notifications (messages without an `id`) never get a reply.
+    async fn handle(&mut self, message: Value) -> Option<Value> {
+        let Some(id) = message.get("id").cloned() else {
+            let method = message.get("method").and_then(Value::as_str);
+            debug!("Ignoring MCP notification {:?}", method);
+            return None;
+        };
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            return Some(rpc_error(
+                id,
+                INVALID_REQUEST,
+                "Invalid request: missing method",
+            ));
+        };
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => initialize(&params),
+            "ping" => json!({}),
+            "tools/list" => json!({ "tools": tools() }),
+            "tools/call" => {
+                let name = params.get("name").and_then(Value::as_str).unwrap_or("");
+                let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+                if !TOOL_NAMES.contains(&name) {
+                    return Some(rpc_error(
+                        id,
+                        INVALID_PARAMS,
+                        format!("Unknown tool '{name}'"),
+                    ));
+                }
+                match tool_command(name, arguments) {
+                    Ok(command) => self.call(command).await,
+                    Err(error) => api_error_result(&error),
+                }
+            }
+            _ => {
+                return Some(rpc_error(
+                    id,
+                    METHOD_NOT_FOUND,
+                    format!("Method not found: {method}"),
+                ))
+            }
+        };
+        Some(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// Forward a command to the daemon and render the tool result.
+    async fn call(&mut self, command: Command) -> Value {
+        match self.request(command).await {
+            Ok(response) => tool_result(response),
+            Err(error) => {
+                // The daemon may have been stopped or restarted; reconnect on
+                // the next call instead of failing every call after this one.
+                warn!("Daemon request failed: {:#}", error);
+                self.client = None;
+                error_result(format!("Daemon request failed: {error:#}"))
+            }
+        }
+    }
+
+    async fn request(&mut self, command: Command) -> Result<Response> {
+        let client = match &mut self.client {
+            Some(client) => client,
+            None => self.client.insert(DaemonClient::connect().await?),
+        };
+        client
+            .request(Request::new(Uuid::new_v4().to_string(), command))
+            .await
+    }
+}
+
+fn initialize(params: &Value) -> Value {
+    let requested = params
+        .get("protocolVersion")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let version = SUPPORTED_PROTOCOL_VERSIONS
+        .iter()
+        .find(|version| **version == requested)
+        .unwrap_or(&SUPPORTED_PROTOCOL_VERSIONS[0]);
+    json!({
+        "protocolVersion": version,
+        "capabilities": { "tools": {} },
+        "serverInfo": { "name": "pilotty", "version": env!("CARGO_PKG_VERSION") },
+        "instructions": "Drive terminal applications in managed PTY sessions. \
+            Spawn a session, read it with snapshot, act with type/key/click/scroll, \
+            and synchronize with wait_for or snapshot's await_change/settle_ms.",
+    })
+}
+
+fn rpc_error(id: Value, code: i64, message: impl Into<String>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message.into() },
+    })
+}
+
+const TOOL_NAMES: &[&str] = &[
+    "spawn",
+    "snapshot",
+    "type",
+    "paste",
+    "key",
+    "click",
+    "mouse_click",
+    "drag",
+    "hover",
+    "scroll",
+    "wait_for",
+    "output",
+    "status",
+    "signal",
+    "kill",
+];
+
+/// Tool definitions for `tools/list`.
+///
+/// Argument names match the daemon protocol, so a call's arguments decode
+/// directly into the corresponding [`Command`].
+fn tools() -> Value {
+    let session = json!({ "type": "string", "description": SESSION_PROPERTY });
+    let span = json!({
+        "type": "array",
+        "items": { "type": "integer" },
+        "minItems": 2,
+        "maxItems": 2,
+        "description": "Inclusive 0-indexed [start, end]; negative counts from the end.",
+    });
+    let region = json!({
+        "type": "object",
+        "description": "Only consider this part of the screen. Omitted rows or cols cover the whole axis.",
+        "properties": { "rows": span, "cols": span },
+    });
+    let button = json!({
+        "type": "string",
+        "enum": ["left", "middle", "right"],
+        "description": "Mouse button. Defaults to left.",
+    });
+    let modifiers = json!({
+        "type": "object",
+        "description": "Modifier keys held during the action.",
+        "properties": {
+            "shift": { "type": "boolean" },
+            "ctrl": { "type": "boolean" },
+            "alt": { "type": "boolean" },
+        },
+    });
+    let target = json!({
+        "type": "object",
+        "description": "A 0-indexed {row, col} cell, {text, regex, nth} to use the center of visible text, or {ref} to use the center of an element from a compact snapshot.",
+        "properties": {
+            "row": { "type": "integer", "minimum": 0 },
+            "col": { "type": "integer", "minimum": 0 },
+            "text": { "type": "string" },
+            "regex": { "type": "boolean" },
+            "nth": { "type": "integer", "minimum": 1 },
+            "ref": { "type": "string" },
+        },
+    });
+    json!([
+        {
+            "name": "spawn",
+            "description": "Start a command in a new PTY session.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "command": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Program and arguments, e.g. [\"vim\", \"notes.txt\"].",
+                    },
+                    "session_name": { "type": "string", "description": "Human-readable session name." },
+                    "cwd": {
+                        "type": "string",
+                        "description": "Working directory. Defaults to the MCP server's directory.",
+                    },
+                    "retain_bytes": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Maximum raw output bytes retained for the session.",
+                    },
+                    "record": {
+                        "type": "string",
+                        "description": "Path of a new asciicast v2 file to record the session into.",
+                    },
+                    "record_input": { "type": "boolean", "description": "Also record input. Requires record." },
+                    "env": {
+                        "type": "object",
+                        "additionalProperties": { "type": "string" },
+                        "description": "Environment variables to set, e.g. {\"LANG\": \"C.UTF-8\"}.",
+                    },
+                    "env_clear": {
+                        "type": "boolean",
+                        "description": "Start from an empty environment (PATH and TERM are still set).",
+                    },
+                    "term": { "type": "string", "description": "TERM for the process. Defaults to xterm-256color." },
+                    "cols": { "type": "integer", "minimum": 1, "description": "Initial terminal width. Defaults to 80." },
+                    "rows": { "type": "integer", "minimum": 1, "description": "Initial terminal height. Defaults to 24." },
+                },
+                "required": ["command"],
+            },
+        },
+        {
+            "name": "snapshot",
+            "description": "Capture the terminal screen, optionally waiting for a change or for it to settle.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "session": session,
+                    "format": {
+                        "type": "string",
+                        "enum": ["full", "styled", "compact", "text"],
+                        "description": "full (default) includes text and content_hash; compact lists UI elements; text is plain screen text.",
+                    },
+                    "await_change": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Block until content_hash differs from this value.",
+                    },
+                    "settle_ms": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Wait until the screen is unchanged for this many ms.",
+                    },
+                    "timeout_ms": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Deadline for await_change/settle_ms. Defaults to 30000.",
+                    },
+                    "since": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Return only rows changed since this revision.",
+                    },
+                    "region": region,
+                    "ignore": {
+                        "type": "array",
+                        "description": "Leave clocks, spinners, or counters out of content_hash and settle detection. The text still includes them.",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "region": region,
+                                "regex": { "type": "string", "description": "Ignore text matching this regex." },
+                            },
+                        },
+                    },
+                    "scrollback": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "description": "Also return up to this many lines that scrolled off the top, with negative row offsets.",
+                    },
+                },
+            },
+        },
+        {
+            "name": "type",
+            "description": "Type text at the cursor.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string", "description": "Text to type." },
+                    "session": session,
+                },
+                "required": ["text"],
+            },
+        },
+        {
+            "name": "paste",
+            "description": "Paste text literally, in bracketed paste markers when the app enabled bracketed paste, so editors and REPLs do not auto-indent or run it line by line.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "text": { "type": "string", "description": "Text to paste. Newlines are sent as Enter." },
+                    "session": session,
+                },
+                "required": ["text"],
+            },
+        },
+        {
+            "name": "key",
+            "description": "Send a key, combo, or space-separated sequence, e.g. \"Enter\", \"Ctrl+C\", \"Escape : w q Enter\".",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "key": { "type": "string", "description": "Key, combo, or sequence." },
+                    "delay_ms": {
+                        "type": "integer",
+                        "minimum": 0,
+                        "maximum": 10000,
+                        "description": "Delay between keys in a sequence.",
+                    },
+                    "session": session,
+                },
+                "required": ["key"],
+            },
+        },
+        {
+            "name": "click",
+            "description": "Click a 0-indexed row/col cell, the center of visible text, or an element by its compact snapshot ref.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "row": { "type": "integer", "minimum": 0, "description": "Row to click. Requires col." },
+                    "col": { "type": "integer", "minimum": 0, "description": "Column to click. Requires row." },
+                    "text": { "type": "string", "description": "Visible text to click instead of a cell." },
+                    "regex": { "type": "boolean", "description": "Treat text as a regular expression." },
+                    "nth": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Which match to click, in reading order. Defaults to 1.",
+                    },
+                    "ref": {
+                        "type": "string",
+                        "description": "Element ref such as \"@e3\" from a compact snapshot, instead of a cell.",
+                    },
+                    "session": session,
+                },
+            },
+        },
+        {
+            "name": "mouse_click",
+            "description": "Click with the right or middle button, double or triple click, or hold Shift/Ctrl/Alt. Targets a row/col cell, visible text, or an element ref like click.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "row": { "type": "integer", "minimum": 0, "description": "Row to click. Requires col." },
+                    "col": { "type": "integer", "minimum": 0, "description": "Column to click. Requires row." },
+                    "text": { "type": "string", "description": "Visible text to click instead of a cell." },
+                    "regex": { "type": "boolean", "description": "Treat text as a regular expression." },
+                    "nth": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Which match to click, in reading order. Defaults to 1.",
+                    },
+                    "ref": {
+                        "type": "string",
+                        "description": "Element ref such as \"@e3\" from a compact snapshot, instead of a cell.",
+                    },
+                    "button": button,
+                    "count": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "maximum": 3,
+                        "description": "2 for a double click, 3 for a triple click. Defaults to 1.",
+                    },
+                    "modifiers": modifiers,
+                    "session": session,
+                },
+            },
+        },
+        {
+            "name": "drag",
+            "description": "Press a mouse button at one position, move to another, and release: select text, resize splits, drag scrollbars.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "from": target,
+                    "to": target,
+                    "button": button,
+                    "modifiers": modifiers,
+                    "session": session,
+                },
+                "required": ["from", "to"],
+            },
+        },
+        {
+            "name": "hover",
+            "description": "Move the mouse pointer over a row/col cell, visible text, or element ref without pressing a button. Needs an app with any-event mouse tracking.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "row": { "type": "integer", "minimum": 0, "description": "Row to hover over. Requires col." },
+                    "col": { "type": "integer", "minimum": 0, "description": "Column to hover over. Requires row." },
+                    "text": { "type": "string", "description": "Visible text to hover over instead of a cell." },
+                    "regex": { "type": "boolean", "description": "Treat text as a regular expression." },
+                    "nth": {
+                        "type": "integer",
+                        "minimum": 1,
+                        "description": "Which match to hover over, in reading order. Defaults to 1.",
+                    },
+                    "ref": {
+                        "type": "string",
+                        "description": "Element ref such as \"@e3\" from a compact snapshot, instead of a cell.",
+                    },
+                    "session": session,
+                },
+            },
+        },
+        {
+            "name": "scroll",
+            "description": "Scroll the terminal with mouse wheel events.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "direction": { "type": "string", "enum": ["up", "down"] },
+                    "amount": { "type": "integer", "minimum": 1, "description": "Lines to scroll." },
+                    "session": session,
+                },
+                "required": ["direction", "amount"],
+            },
+        },
+        {
+            "name": "wait_for",
+            "description": "Wait for text or a regex to appear on screen, or for the session's process to exit, go idle, or change its foreground job.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "pattern": { "type": "string", "description": "Text or regex to wait for." },
+                    "patterns": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "More patterns. The result's pattern_index says which one matched.",
+                    },
+                    "match_mode": { "type": "string", "enum": ["any", "all"], "description": "Whether one or every pattern must match. Defaults to any." },
+                    "absent": { "type": "boolean", "description": "Wait for the patterns to disappear instead." },
+                    "regex": { "type": "boolean", "description": "Treat pattern as a regular expression." },
+                    "until": {
+                        "type": "object",
+                        "description": "Wait for a process condition instead of any pattern.",
+                        "properties": {
+                            "condition": { "type": "string", "enum": ["exit", "idle", "foreground_change"] },
+                            "idle_ms": { "type": "integer", "minimum": 0, "description": "Quiet period for `idle`." },
+                        },
+                        "required": ["condition"],
+                    },
+                    "region": region,
+                    "scrollback": { "type": "integer", "minimum": 0, "description": "Also search up to this many lines that scrolled off the top." },
+                    "timeout_ms": { "type": "integer", "minimum": 0, "description": "Defaults to 30000." },
+                    "session": session,
+                },
+            },
+        },
+        {
+            "name": "output",
+            "description": "Read the session's retained output history, or its exact ANSI bytes.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "ansi": { "type": "boolean", "description": "Return raw ANSI/VT bytes instead of rendered text." },
+                    "session": session,
+                },
+            },
+        },
+        {
+            "name": "status",
+            "description": "Report whether a session is running or exited, with exit evidence.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "session": session },
+            },
+        },
+        {
+            "name": "signal",
+            "description": "Send a POSIX signal to the session's process, or to the job in the terminal foreground as a keyboard signal would.",
+            "inputSchema": {
+                "type": "object",
+                "properties": {
+                    "signal": {
+                        "type": "string",
+                        "enum": Signal::ALL.iter().map(|signal| signal.name()).collect::<Vec<_>>(),
+                    },
+                    "target": {
+                        "type": "string",
+                        "enum": ["child", "foreground"],
+                        "description": "The spawned process (default) or the PTY's foreground process group.",
+                    },
+                    "session": session,
+                },
+                "required": ["signal"],
+            },
+        },
+        {
+            "name": "kill",
+            "description": "Kill a session and its child process.",
+            "inputSchema": {
+                "type": "object",
+                "properties": { "session": session },
+            },
+        },
+    ])
+}
+
+/// Decode a tool call's arguments into the daemon command it maps onto.
+fn tool_command(name: &str, arguments: Value) -> Result<Command, ApiError> {
+    let mut arguments = match arguments {
+        Value::Object(arguments) => arguments,
+        Value::Null => Map::new(),
+        _ => {
+            return Err(ApiError::invalid_input(format!(
+                "Arguments for '{name}' must be an object"
+            )))
+        }
+    };
+    arguments.insert("action".to_string(), json!(name));
+    if name == "spawn" {
+        // Like the CLI: paths resolve against the caller, not the daemon.
+        if !arguments.contains_key("cwd") {
+            if let Ok(dir) = std::env::current_dir() {
+                arguments.insert("cwd".to_string(), json!(dir.to_string_lossy()));
+            }
+        }
+        for key in ["cwd", "record"] {
+            if let Some(Value::String(path)) = arguments.get_mut(key) {
+                *path = crate::absolute_path(path);
+            }
+        }
+    }
+
+    serde_json::from_value(Value::Object(arguments)).map_err(|error| {
+        ApiError::invalid_input_with_suggestion(
+            format!("Invalid arguments for '{name}': {error}"),
+            "Check the tool's inputSchema from tools/list.",
+        )
+    })
+}
+
+/// Render a daemon response as an MCP tool result.
+fn tool_result(response: Response) -> Value {
+    if !response.success {
+        return match response.error {
+            Some(error) => api_error_result(&error),
+            None => error_result("Daemon reported failure without an error"),
+        };
+    }
+
+    let content = match response.data {
+        Some(ResponseData::Snapshot {
+            format: SnapshotFormat::Text,
+            content,
+            ..
+        }) => vec![text_content(content)],
+        Some(ResponseData::Output {
+            format,
+            bytes,
+            total_bytes,
+            retained_bytes,
+            dropped_bytes,
+            truncated,
+        }) => {
+            let format = match format {
+                OutputFormat::Text => "text",
+                OutputFormat::Ansi => "ansi",
+            };
+            let retention = json!({
+                "format": format,
+                "total_bytes": total_bytes,
+                "retained_bytes": retained_bytes,
+                "dropped_bytes": dropped_bytes,
+                "truncated": truncated,
+            });
+            vec![
+                text_content(String::from_utf8_lossy(&bytes).into_owned()),
+                text_content(retention.to_string()),
+            ]
+        }
+        Some(data) => vec![text_content(
+            serde_json::to_string_pretty(&data).unwrap_or_default(),
+        )],
+        None => vec![text_content("OK")],
+    };
+    json!({ "content": content, "isError": false })
+}
+
+/// Tool result carrying an [`ApiError`] as JSON, so hosts keep its code and
+/// suggestion.
+fn api_error_result(error: &ApiError) -> Value {
+    error_result(serde_json::to_string_pretty(error).unwrap_or_else(|_| error.to_string()))
+}
+
+fn error_result(text: impl Into<String>) -> Value {
+    json!({ "content": [text_content(text)], "isError": true })
+}
+
+fn text_content(text: impl Into<String>) -> Value {
+    json!({ "type": "text", "text": text.into() })
+}
+
+#[cfg(test)]
+mod tests {
+    use pilotty_core::error::ErrorCode;
+    use pilotty_core::protocol::{
+        ClickTarget, MatchMode, MouseButton, MouseModifiers, ProcessCondition, ScreenRegion,
+        SignalTarget,
+    };
+
+    use super::*;
+
+    #[test]
+    fn every_tool_has_an_object_schema() {
+        let tools = tools();
+        let names: Vec<&str> = tools
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| {
+                assert_eq!(tool["inputSchema"]["type"], "object", "{tool}");
+                assert!(tool["description"].is_string(), "{tool}");
+                tool["name"].as_str().unwrap()
+            })
+            .collect();
+        assert_eq!(names, TOOL_NAMES);
+    }
+
+    #[test]
+    fn tool_arguments_decode_into_daemon_commands() {
+        let click = tool_command("click", json!({ "text": "OK", "session": "app" })).unwrap();
+        assert_eq!(
+            click,
+            Command::Click {
+                target: ClickTarget::Text {
+                    text: "OK".to_string(),
+                    regex: false,
+                    nth: 1,
+                },
+                session: Some("app".to_string()),
+            }
+        );
+
+        let drag = tool_command(
+            "drag",
+            json!({ "from": { "ref": "@e2" }, "to": { "text": "end" }, "modifiers": { "shift": true } }),
+        );
+        assert_eq!(
+            drag.unwrap(),
+            Command::Drag {
+                from: ClickTarget::Ref {
+                    r#ref: "@e2".to_string(),
+                },
+                to: ClickTarget::Text {
+                    text: "end".to_string(),
+                    regex: false,
+                    nth: 1,
+                },
+                button: MouseButton::Left,
+                modifiers: MouseModifiers {
+                    shift: true,
+                    ..MouseModifiers::default()
+                },
+                session: None,
+            }
+        );
+
+        let wait = tool_command(
+            "wait_for",
+            json!({
+                "patterns": ["Ready", "Error"],
+                "match_mode": "all",
+                "region": { "rows": [-1, -1] },
+                "timeout_ms": 500,
+            }),
+        );
+        assert_eq!(
+            wait.unwrap(),
+            Command::WaitFor {
+                pattern: String::new(),
+                patterns: vec!["Ready".to_string(), "Error".to_string()],
+                match_mode: MatchMode::All,
+                absent: false,
+                timeout_ms: Some(500),
+                regex: None,
+                until: None,
+                region: Some(ScreenRegion {
+                    rows: Some((-1, -1)),
+                    cols: None,
+                }),
+                scrollback: 0,
+                session: None,
+            }
+        );
+
+        let idle = tool_command(
+            "wait_for",
+            json!({ "until": { "condition": "idle", "idle_ms": 250 } }),
+        );
+        assert_eq!(
+            idle.unwrap(),
+            Command::WaitFor {
+                pattern: String::new(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
+                timeout_ms: None,
+                regex: None,
+                until: Some(ProcessCondition::Idle { idle_ms: 250 }),
+                region: None,
+                scrollback: 0,
+                session: None,
+            }
+        );
+
+        assert_eq!(
+            tool_command("status", Value::Null).unwrap(),
+            Command::Status { session: None }
+        );
+
+        assert_eq!(
+            tool_command(
+                "signal",
+                json!({ "signal": "SIGINT", "target": "foreground" })
+            )
+            .unwrap(),
+            Command::Signal {
+                signal: Signal::Sigint,
+                target: SignalTarget::Foreground,
+                session: None,
+            }
+        );
+    }
+
+    #[test]
+    fn spawn_resolves_paths_against_the_server() {
+        let spawn = tool_command(
+            "spawn",
+            json!({ "command": ["bash"], "record": "run.cast" }),
+        )
+        .unwrap();
+        let cwd = std::env::current_dir().unwrap();
+        let Command::Spawn {
+            cwd: Some(spawn_cwd),
+            record: Some(record),
+            ..
+        } = spawn
+        else {
+            panic!("expected spawn with cwd and record: {spawn:?}");
+        };
+        assert_eq!(spawn_cwd, cwd.to_string_lossy());
+        assert_eq!(record, cwd.join("run.cast").to_string_lossy());
+    }
+
+    #[test]
+    fn spawn_resolves_a_relative_cwd_against_the_server() {
+        let spawn =
+            tool_command("spawn", json!({ "command": ["bash"], "cwd": "sub/dir" })).unwrap();
+        let Command::Spawn {
+            cwd: Some(spawn_cwd),
+            ..
+        } = spawn
+        else {
+            panic!("expected spawn with cwd: {spawn:?}");
+        };
+        let expected = std::env::current_dir().unwrap().join("sub/dir");
+        assert_eq!(spawn_cwd, expected.to_string_lossy());
+    }
+
+    #[test]
+    fn bad_arguments_become_structured_tool_errors() {
+        let error = tool_command("type", json!({ "session": "app" })).unwrap_err();
+        assert_eq!(error.code, ErrorCode::InvalidInput);
+        assert!(error.message.contains("text"), "{}", error.message);
+
+        let result = api_error_result(&error);
+        assert_eq!(result["isError"], true);
+        let body: ApiError =
+            serde_json::from_str(result["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(body, error);
+    }
+
+    #[test]
+    fn output_results_carry_text_and_retention() {
+        let response = Response::success(
+            "id",
+            ResponseData::Output {
+                format: OutputFormat::Text,
+                bytes: b"hello\n".to_vec(),
+                total_bytes: 6,
+                retained_bytes: 6,
+                dropped_bytes: 0,
+                truncated: false,
+            },
+        );
+        let result = tool_result(response);
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["content"][0]["text"], "hello\n");
+        let retention: Value =
+            serde_json::from_str(result["content"][1]["text"].as_str().unwrap()).unwrap();
+        assert_eq!(retention["total_bytes"], 6);
+        assert_eq!(retention["truncated"], false);
+    }
+
+    #[tokio::test]
+    async fn handshake_negotiates_version_and_skips_notifications() {
+        let mut server = McpServer::default();
+        let reply = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": "initialize",
+                "params": { "protocolVersion": "2024-11-05", "capabilities": {} },
+            }))
+            .await
+            .unwrap();
+        assert_eq!(reply["id"], 1);
+        assert_eq!(reply["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(reply["result"]["serverInfo"]["name"], "pilotty");
+
+        let future = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": 2,
+                "method": "initialize",
+                "params": { "protocolVersion": "2099-01-01" },
+            }))
+            .await
+            .unwrap();
+        assert_eq!(
+            future["result"]["protocolVersion"],
+            SUPPORTED_PROTOCOL_VERSIONS[0]
+        );
+
+        let notification = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert_eq!(server.handle(notification).await, None);
+    }
+
+    #[tokio::test]
+    async fn unknown_methods_and_tools_are_rpc_errors() {
+        let mut server = McpServer::default();
+        let method = server
+            .handle(json!({ "jsonrpc": "2.0", "id": "a", "method": "resources/list" }))
+            .await
+            .unwrap();
+        assert_eq!(method["error"]["code"], METHOD_NOT_FOUND);
+
+        let tool = server
+            .handle(json!({
+                "jsonrpc": "2.0",
+                "id": "b",
+                "method": "tools/call",
+                "params": { "name": "shutdown", "arguments": {} },
+            }))
+            .await
+            .unwrap();
+        assert_eq!(tool["id"], "b");
+        assert_eq!(tool["error"]["code"], INVALID_PARAMS);
+        assert!(server.client.is_none());
+    }
+}
@@ -0,0 +1,455 @@
+//! Offline replay of recordings and raw ANSI dumps into screen snapshots.
+//!
+//! Accepts an asciicast v2 recording (as written by `spawn --record`) or the
+//! raw bytes saved by `output --ansi`, and feeds them through the same
+//! [`TerminalEmulator`] the daemon uses, so a failing interaction can be
+//! inspected screen by screen without re-running the application.
+
+use std::io::Write;
+
+use anyhow::{bail, Context};
+use pilotty_core::protocol::SnapshotFormat;
+use pilotty_core::snapshot::{compute_content_hash, CursorState, ScreenState, TerminalSize};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::args::ReplayArgs;
+use crate::daemon::pty::TermSize;
+use crate::daemon::server::format_text_snapshot;
+use crate::daemon::terminal::{OutputHistory, TerminalEmulator};
+
+/// One event from a recording, in file order.
+#[derive(Debug, Clone, PartialEq)]
+struct TimedEvent {
+    /// Seconds since the recording started.
+    time: f64,
+    event: ReplayEvent,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ReplayEvent {
+    Output(Vec<u8>),
+    Input(String),
+    Resize(TermSize),
+    Marker(String),
+}
+
+/// Parsed replay source: initial geometry plus timed events.
+#[derive(Debug)]
+struct Recording {
+    size: TermSize,
+    events: Vec<TimedEvent>,
+}
+
+impl Recording {
+    /// Parse an asciicast v2 file, or treat anything else as a raw dump.
+    ///
+    /// Raw dumps have no timing, so they become a single output event at
+    /// time zero on a terminal of `raw_size`.
+    fn parse(bytes: &[u8], raw_size: TermSize) -> anyhow::Result<Self> {
+        let mut lines = bytes.split(|&byte| byte == b'\n');
+        let header = lines
+            .next()
+            .and_then(|line| serde_json::from_slice::<Value>(line).ok())
+            .filter(|header| header.get("version").is_some());
+        let Some(header) = header else {
+            return Ok(Self {
+                size: raw_size,
+                events: vec![TimedEvent {
+                    time: 0.0,
+                    event: ReplayEvent::Output(bytes.to_vec()),
+                }],
+            });
+        };
+
+        if header["version"] != 2 {
+            bail!(
+                "Unsupported asciicast version {}; only version 2 can be replayed",
+                header["version"]
+            );
+        }
+        let dimension = |key: &str| {
+            header[key]
+                .as_u64()
+                .and_then(|value| u16::try_from(value).ok())
+                .filter(|&value| value > 0)
+                .with_context(|| format!("Recording header has no valid '{key}'"))
+        };
+        let size = TermSize {
+            cols: dimension("width")?,
+            rows: dimension("height")?,
+        };
+
+        let mut events = Vec::new();
+        for (index, line) in lines.enumerate() {
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+            // Line 1 is the header.
+            let line_number = index + 2;
+            let (time, code, data): (f64, String, String) = serde_json::from_slice(line)
+                .with_context(|| format!("Line {line_number} is not an asciicast event"))?;
+            let event = match code.as_str() {
+                "o" => ReplayEvent::Output(data.into_bytes()),
+                "i" => ReplayEvent::Input(data),
+                "r" => ReplayEvent::Resize(
+                    parse_resize(&data)
+                        .with_context(|| format!("Line {line_number} has bad resize '{data}'"))?,
+                ),
+                "m" => ReplayEvent::Marker(data),
+                // The format reserves unknown codes for extensions.
+                _ => continue,
+            };
+            events.push(TimedEvent { time, event });
+        }
+
+        Ok(Self { size, events })
+    }
+
+    /// Time of the last event, or zero for an empty recording.
+    fn duration(&self) -> f64 {
+        self.events.last().map_or(0.0, |event| event.time)
+    }
+}
+
+fn parse_resize(data: &str) -> Option<TermSize> {
+    let (cols, rows) = data.split_once('x')?;
+    let size = TermSize {
+        cols: cols.trim().parse().ok()?,
+        rows: rows.trim().parse().ok()?,
+    };
+    (size.cols > 0 && size.rows > 0).then_some(size)
+}
+
+/// Which screens a replay emits.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ReplayMode {
+    /// Only the screen after the last event.
+    Final,
+    /// The screen once every event up to this time has been applied.
+    At(f64),
+    /// The screen at every resize, input, and marker event.
+    Markers,
+    /// The screen after every output event.
+    Frames,
+}
+
+/// A replayed screen, serialized as a `ScreenState` JSON line with timing.
+#[derive(Debug, Serialize)]
+struct Frame {
+    /// Seconds since the recording started.
+    time: f64,
+    /// Event that produced this frame: `output`, `resize`, `input`, or `marker`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    event: Option<&'static str>,
+    /// Resize geometry, input text, or marker label.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<String>,
+    #[serde(flatten)]
+    screen: ScreenState,
+}
+
+/// Emulator state while stepping through a recording.
+struct Player {
+    emulator: TerminalEmulator,
+    size: TermSize,
+    /// Output events applied so far, like the daemon's per-batch revision.
+    revision: u64,
+}
+
+impl Player {
+    fn new(size: TermSize) -> Self {
+        Self {
+            emulator: TerminalEmulator::new(size),
+            size,
+            revision: 0,
+        }
+    }
+
+    fn apply(&mut self, event: &ReplayEvent) {
+        match event {
+            ReplayEvent::Output(bytes) => {
+                self.emulator.feed(bytes);
+                self.revision += 1;
+            }
+            ReplayEvent::Resize(size) => {
+                self.size = *size;
+                self.emulator.resize(*size);
+            }
+            ReplayEvent::Input(_) | ReplayEvent::Marker(_) => {}
+        }
+    }
+
+    fn screen(&self, format: SnapshotFormat) -> ScreenState {
+        let include_text = matches!(format, SnapshotFormat::Full | SnapshotFormat::Styled);
+        let text = self.emulator.get_text();
+        let (row, col) = self.emulator.cursor_position();
+        ScreenState {
+            size: TerminalSize {
+                cols: self.size.cols,
+                rows: self.size.rows,
+            },
+            cursor: CursorState {
+                row,
+                col,
+                visible: self.emulator.cursor_visible(),
+            },
+            content_hash: include_text.then(|| compute_content_hash(&text)),
+            text: (include_text || format == SnapshotFormat::Text).then_some(text),
+            styled: (format == SnapshotFormat::Styled).then(|| self.emulator.styled_rows()),
+            revision: Some(self.revision),
+            elements: (format == SnapshotFormat::Compact).then(|| self.emulator.elements()),
+            scrollback: None,
+            modes: Some(self.emulator.modes()),
+        }
+    }
+}
+
+/// Step through a recording, collecting the frames `mode` asks for.
+fn replay(recording: &Recording, mode: ReplayMode, format: SnapshotFormat) -> Vec<Frame> {
+    let mut player = Player::new(recording.size);
+    let mut frames = Vec::new();
+
+    for timed in &recording.events {
+        if let ReplayMode::At(at) = mode {
+            if timed.time > at {
+                break;
+            }
+        }
+        player.apply(&timed.event);
+
+        let (event, data) = match &timed.event {
+            ReplayEvent::Output(_) => ("output", None),
+            ReplayEvent::Resize(size) => ("resize", Some(format!("{}x{}", size.cols, size.rows))),
+            ReplayEvent::Input(text) => ("input", Some(text.clone())),
+            ReplayEvent::Marker(label) => ("marker", Some(label.clone())),
+        };
+        let wanted = match mode {
+            ReplayMode::Frames => event == "output",
+            ReplayMode::Markers => event != "output",
+            ReplayMode::Final | ReplayMode::At(_) => false,
+        };
+        if wanted {
+            frames.push(Frame {
+                time: timed.time,
+                event: Some(event),
+                data,
+                screen: player.screen(format),
+            });
+        }
+    }
+
+    match mode {
+        ReplayMode::Final => frames.push(Frame {
+            time: recording.duration(),
+            event: None,
+            data: None,
+            screen: player.screen(format),
+        }),
+        ReplayMode::At(at) => frames.push(Frame {
+            time: at,
+            event: None,
+            data: None,
+            screen: player.screen(format),
+        }),
+        ReplayMode::Markers | ReplayMode::Frames => {}
+    }
+    frames
+}
+
+/// Readable output history up to `at`, rendered like `pilotty output`.
+fn history(recording: &Recording, at: Option<f64>) -> String {
+    let mut history = OutputHistory::new(recording.size);
+    for timed in &recording.events {
+        if at.is_some_and(|at| timed.time > at) {
+            break;
+        }
+        match &timed.event {
+            ReplayEvent::Output(output) => history.feed(output),
+            ReplayEvent::Resize(size) => history.resize(*size),
+            ReplayEvent::Input(_) | ReplayEvent::Marker(_) => {}
+        }
+    }
+    history.render()
+}
+
+/// Run `pilotty replay`, printing frames to stdout.
+pub(crate) fn run(args: &ReplayArgs) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(&args.file).with_context(|| format!("Failed to read '{}'", args.file))?;
+    let raw_size = TermSize {
+        cols: args.cols,
+        rows: args.rows,
+    };
+    let recording = Recording::parse(&bytes, raw_size)
+        .with_context(|| format!("Failed to parse '{}'", args.file))?;
+
+    let mut stdout = std::io::stdout().lock();
+    if args.history {
+        writeln!(stdout, "{}", history(&recording, args.at))?;
+        return Ok(());
+    }
+
+    let mode = match (args.at, args.markers, args.frames) {
+        (Some(at), _, _) => ReplayMode::At(at),
+        (None, true, _) => ReplayMode::Markers,
+        (None, false, true) => ReplayMode::Frames,
+        (None, false, false) => ReplayMode::Final,
+    };
+    let format = SnapshotFormat::from(args.format);
+    for frame in replay(&recording, mode, format) {
+        if format == SnapshotFormat::Text {
+            let label = match (frame.event, &frame.data) {
+                (Some(event), Some(data)) => format!(" {event} {data:?}"),
+                (Some(event), None) => format!(" {event}"),
+                (None, _) => String::new(),
+            };
+            writeln!(stdout, "=== {:.3}s{} ===", frame.time, label)?;
+            let size = TermSize {
+                cols: frame.screen.size.cols,
+                rows: frame.screen.size.rows,
+            };
+            let text = frame.screen.text.unwrap_or_default();
+            let cursor = frame.screen.cursor;
+            writeln!(
+                stdout,
+                "{}",
+                format_text_snapshot(&text, cursor.row, cursor.col, size)
+            )?;
+        } else {
+            writeln!(stdout, "{}", serde_json::to_string(&frame)?)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAST: &str = r#"{"version": 2, "width": 20, "height": 3, "timestamp": 0}
+[0.1, "o", "hello"]
+[0.5, "i", "x"]
+[0.6, "o", "\r\nworld"]
+[1.0, "r", "10x2"]
+[1.5, "m", "checkpoint"]
+[2.0, "o", "!"]
+"#;
+
+    fn cast() -> Recording {
+        Recording::parse(CAST.as_bytes(), TermSize::default()).expect("parse cast")
+    }
+
+    #[test]
+    fn parses_asciicast_header_and_events() {
+        let recording = cast();
+
+        assert_eq!(recording.size, TermSize { cols: 20, rows: 3 });
+        assert_eq!(recording.events.len(), 6);
+        assert_eq!(
+            recording.events[3],
+            TimedEvent {
+                time: 1.0,
+                event: ReplayEvent::Resize(TermSize { cols: 10, rows: 2 }),
+            }
+        );
+        assert_eq!(recording.duration(), 2.0);
+    }
+
+    #[test]
+    fn raw_dump_replays_as_single_output_event() {
+        let raw = b"\x1b[1mbold\x1b[0m plain";
+        let recording = Recording::parse(raw, TermSize { cols: 30, rows: 4 }).expect("parse raw");
+
+        assert_eq!(recording.size, TermSize { cols: 30, rows: 4 });
+        let frames = replay(&recording, ReplayMode::Final, SnapshotFormat::Full);
+        assert_eq!(frames.len(), 1);
+        assert!(frames[0]
+            .screen
+            .text
+            .as_deref()
+            .unwrap()
+            .starts_with("bold plain"));
+    }
+
+    #[test]
+    fn rejects_other_asciicast_versions_and_bad_events() {
+        let v1 = br#"{"version": 1, "width": 80, "height": 24}"#;
+        let error = Recording::parse(v1, TermSize::default()).unwrap_err();
+        assert!(error.to_string().contains("version 1"), "got: {error}");
+
+        let bad = b"{\"version\": 2, \"width\": 80, \"height\": 24}\n[0.1, \"o\"]\n";
+        let error = Recording::parse(bad, TermSize::default()).unwrap_err();
+        assert!(error.to_string().contains("Line 2"), "got: {error}");
+    }
+
+    #[test]
+    fn screen_at_timestamp_applies_only_earlier_events() {
+        let frames = replay(&cast(), ReplayMode::At(0.55), SnapshotFormat::Full);
+
+        assert_eq!(frames.len(), 1);
+        let screen = &frames[0].screen;
+        assert_eq!(
+            screen.text.as_deref().unwrap().lines().next(),
+            Some("hello")
+        );
+        assert!(!screen.text.as_deref().unwrap().contains("world"));
+        assert_eq!(screen.revision, Some(1));
+        assert_eq!(frames[0].time, 0.55);
+    }
+
+    #[test]
+    fn markers_emit_screen_at_resize_input_and_marker_events() {
+        let frames = replay(&cast(), ReplayMode::Markers, SnapshotFormat::Compact);
+
+        let events: Vec<_> = frames
+            .iter()
+            .map(|frame| (frame.event, frame.data.as_deref()))
+            .collect();
+        assert_eq!(
+            events,
+            vec![
+                (Some("input"), Some("x")),
+                (Some("resize"), Some("10x2")),
+                (Some("marker"), Some("checkpoint")),
+            ]
+        );
+        assert_eq!(frames[1].screen.size, TerminalSize { cols: 10, rows: 2 });
+        assert!(frames[0].screen.text.is_none());
+        assert!(frames[0].screen.elements.is_some());
+    }
+
+    #[test]
+    fn frames_serialize_as_screen_state_json_lines() {
+        let frames = replay(&cast(), ReplayMode::Frames, SnapshotFormat::Full);
+
+        assert_eq!(frames.len(), 3);
+        let line = serde_json::to_string(&frames[2]).expect("serialize frame");
+        let screen: ScreenState = serde_json::from_str(&line).expect("frame is a ScreenState");
+        assert_eq!(screen.revision, Some(3));
+        assert!(screen.text.unwrap().contains("world!"));
+        assert!(line.contains("\"time\":2.0"), "got: {line}");
+        assert!(line.contains("\"event\":\"output\""), "got: {line}");
+    }
+
+    #[test]
+    fn history_renders_output_up_to_timestamp() {
+        let recording = cast();
+
+        assert_eq!(history(&recording, Some(0.7)), "hello\nworld");
+        assert_eq!(history(&recording, None), "hello\nworld!");
+    }
+
+    #[test]
+    fn history_applies_resizes_where_they_happen() {
+        // Wrapped at 5 columns before the resize, 10 after it.
+        let cast = r#"{"version": 2, "width": 5, "height": 4}
+[0.1, "o", "0123456789\r\n"]
+[0.2, "r", "10x4"]
+[0.3, "o", "abcdefghij"]
+"#;
+        let recording = Recording::parse(cast.as_bytes(), TermSize::default()).expect("parse");
+
+        assert_eq!(history(&recording, None), "01234\n56789\nabcdefghij");
+    }
+}
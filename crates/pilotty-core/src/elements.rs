@@ -0,0 +1,851 @@
+//! UI element detection for compact snapshots.
+//!
+//! TUI toolkits draw their widgets out of plain characters: `< OK >` and
+//! `[ Cancel ]` buttons, `[x]` checkboxes, `( )` radio buttons, `____` input
+//! fields, highlighted or pointer-marked menu items, and box-drawn dialogs.
+//! [`detect_elements`] recognizes these shapes on a screen grid and gives each
+//! one a short ref (`@e1`, `@e2`, ...) plus its bounds, so agents can talk
+//! about "the Cancel button" and click it by ref instead of by coordinates.
+//!
+//! # Refs
+//!
+//! Refs are assigned in reading order (top to bottom, left to right). They
+//! label elements within one snapshot only: the same screen produces the
+//! same refs, and toggling a checkbox or moving a highlight renumbers
+//! nothing, but an element appearing or disappearing earlier on the screen
+//! shifts every later ref. Click, drag, and hover accept a ref and resolve
+//! it against the screen at the moment they act, so take a fresh compact
+//! snapshot after the screen changes.
+//!
+//! Detection is heuristic: it favors the conventions of dialog, whiptail,
+//! ncurses forms, and prompt libraries, and may miss custom-drawn widgets.
+
+use serde::{Deserialize, Serialize};
+
+use crate::snapshot::is_false;
+
+/// Grid placeholder for the second cell of a double-width character.
+pub const WIDE_CONTINUATION: char = '\0';
+
+/// Longest button label, in cells, still treated as a button.
+const MAX_BUTTON_LABEL: usize = 24;
+
+/// Marks that fill a checked or unchecked checkbox (`[x]`, `[ ]`).
+const CHECKBOX_MARKS: &[char] = &[' ', 'x', 'X', '*', '✓', '✔', '■', '█'];
+
+/// Marks that fill a selected or unselected radio button (`(*)`, `( )`).
+const RADIO_MARKS: &[char] = &[' ', '*', 'o', 'O', 'x', 'X', '•', '●', '◉'];
+
+/// Cursor glyphs prompt libraries place before the focused menu item.
+const MENU_POINTERS: &[char] = &['>', '❯', '›', '▶', '▸', '➜', '→', '»'];
+
+const TOP_LEFT: &[char] = &['┌', '╭', '╔', '┏', '╒', '╓', '+'];
+const TOP_RIGHT: &[char] = &['┐', '╮', '╗', '┓', '╕', '╖', '+'];
+const BOTTOM_LEFT: &[char] = &['└', '╰', '╚', '┗', '╘', '╙', '+'];
+const BOTTOM_RIGHT: &[char] = &['┘', '╯', '╝', '┛', '╛', '╜', '+'];
+const LEFT_SIDE: &[char] = &['│', '║', '┃', '|', '├', '╟', '╠', '┣', '╞', '+'];
+const RIGHT_SIDE: &[char] = &['│', '║', '┃', '|', '┤', '╢', '╣', '┫', '╡', '+'];
+
+/// One screen cell as seen by element detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridCell {
+    /// First character of the cell, `' '` when empty, or
+    /// [`WIDE_CONTINUATION`] for the tail of a wide character.
+    pub ch: char,
+    /// Whether the cell is drawn highlighted (reverse video).
+    pub highlighted: bool,
+}
+
+impl GridCell {
+    pub const BLANK: Self = Self {
+        ch: ' ',
+        highlighted: false,
+    };
+}
+
+/// Kind of a detected UI element.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ElementKind {
+    /// A box-drawn frame; the label is its title, if any.
+    Dialog,
+    /// `< OK >`, `<Cancel>`, `[ Save ]`.
+    Button,
+    /// `[x] label` or `[ ] label`.
+    Checkbox,
+    /// `(*) label` or `( ) label`.
+    Radio,
+    /// An entry field: `Name: ______`, `[      ]`, or a one-line inner box.
+    Input,
+    /// A selectable line in a highlighted or pointer-marked list.
+    MenuItem,
+}
+
+/// A detected UI element with its snapshot ref and screen bounds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Element {
+    /// Short reference such as `@e3`.
+    #[serde(rename = "ref")]
+    pub id: String,
+    pub kind: ElementKind,
+    /// Top row of the element (0-indexed).
+    pub row: u16,
+    /// Left column of the element (0-indexed).
+    pub col: u16,
+    pub width: u16,
+    pub height: u16,
+    /// Visible label: button text, checkbox caption, field name, or title.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub label: String,
+    /// Checked state for checkboxes and radio buttons.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checked: Option<bool>,
+    /// Whether the element is highlighted or marked by a menu pointer.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub focused: bool,
+}
+
+/// Detect UI elements on a screen grid.
+///
+/// `rows` holds one [`GridCell`] per terminal cell. The result is sorted in
+/// reading order with refs `@e1..@eN` assigned in that order.
+#[must_use]
+pub fn detect_elements(rows: &[Vec<GridCell>]) -> Vec<Element> {
+    let mut detector = Detector::new(rows);
+    detector.detect_boxes();
+    for row in 0..rows.len() {
+        detector.detect_inline(row);
+    }
+    detector.detect_pointer_menus();
+    detector.detect_highlighted_menus();
+
+    let mut found = detector.found;
+    found.sort_by_key(|element| (element.rect.row, element.rect.col, element.kind));
+    found
+        .into_iter()
+        .enumerate()
+        .map(|(index, element)| Element {
+            id: format!("@e{}", index + 1),
+            kind: element.kind,
+            row: to_u16(element.rect.row),
+            col: to_u16(element.rect.col),
+            width: to_u16(element.rect.width),
+            height: to_u16(element.rect.height),
+            label: element.label,
+            checked: element.checked,
+            focused: element.focused,
+        })
+        .collect()
+}
+
+fn to_u16(value: usize) -> u16 {
+    u16::try_from(value).unwrap_or(u16::MAX)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    row: usize,
+    col: usize,
+    width: usize,
+    height: usize,
+}
+
+struct Found {
+    kind: ElementKind,
+    rect: Rect,
+    label: String,
+    checked: Option<bool>,
+    focused: bool,
+}
+
+struct Detector<'a> {
+    rows: &'a [Vec<GridCell>],
+    /// Cells already claimed by a non-dialog element.
+    taken: Vec<Vec<bool>>,
+    /// Interior rectangles of detected boxes.
+    interiors: Vec<Rect>,
+    found: Vec<Found>,
+}
+
+impl<'a> Detector<'a> {
+    fn new(rows: &'a [Vec<GridCell>]) -> Self {
+        Self {
+            rows,
+            taken: rows.iter().map(|row| vec![false; row.len()]).collect(),
+            interiors: Vec::new(),
+            found: Vec::new(),
+        }
+    }
+
+    fn ch(&self, row: usize, col: usize) -> char {
+        self.rows
+            .get(row)
+            .and_then(|cells| cells.get(col))
+            .map_or(' ', |cell| cell.ch)
+    }
+
+    fn width(&self, row: usize) -> usize {
+        self.rows.get(row).map_or(0, Vec::len)
+    }
+
+    fn text(&self, row: usize, start: usize, end: usize) -> String {
+        (start..end.min(self.width(row)))
+            .map(|col| self.ch(row, col))
+            .filter(|&ch| ch != WIDE_CONTINUATION)
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    fn is_highlighted(&self, rect: Rect) -> bool {
+        (rect.row..rect.row + rect.height).any(|row| {
+            (rect.col..rect.col + rect.width).any(|col| {
+                self.rows
+                    .get(row)
+                    .and_then(|cells| cells.get(col))
+                    .is_some_and(|cell| cell.highlighted)
+            })
+        })
+    }
+
+    fn is_free(&self, rect: Rect) -> bool {
+        (rect.row..rect.row + rect.height).all(|row| {
+            (rect.col..rect.col + rect.width).all(|col| {
+                !self
+                    .taken
+                    .get(row)
+                    .and_then(|r| r.get(col))
+                    .copied()
+                    .unwrap_or(false)
+            })
+        })
+    }
+
+    fn push(&mut self, kind: ElementKind, rect: Rect, label: String, checked: Option<bool>) {
+        if kind != ElementKind::Dialog {
+            for row in rect.row..rect.row + rect.height {
+                for col in rect.col..rect.col + rect.width {
+                    if let Some(cell) = self.taken.get_mut(row).and_then(|r| r.get_mut(col)) {
+                        *cell = true;
+                    }
+                }
+            }
+        }
+        // A dialog containing a highlighted widget is not itself focused.
+        let focused = kind != ElementKind::Dialog && self.is_highlighted(rect);
+        self.found.push(Found {
+            kind,
+            rect,
+            label,
+            checked,
+            focused,
+        });
+    }
+
+    /// Text starting at `start` up to a double space, border, or row end.
+    fn text_run(&self, row: usize, start: usize) -> (String, usize) {
+        let width = self.width(row);
+        let mut end = start;
+        while end < width {
+            let ch = self.ch(row, end);
+            if is_border(ch) || (ch == ' ' && self.ch(row, end + 1) == ' ') {
+                break;
+            }
+            end += 1;
+        }
+        while end > start && self.ch(row, end - 1) == ' ' {
+            end -= 1;
+        }
+        (self.text(row, start, end), end)
+    }
+
+    /// Field caption to the left of `col`, e.g. `Name:` in `Name: ____`.
+    fn label_before(&self, row: usize, col: usize) -> String {
+        let mut end = col;
+        while end > 0 && self.ch(row, end - 1) == ' ' {
+            end -= 1;
+            if col - end > 2 {
+                return String::new();
+            }
+        }
+        let mut start = end;
+        while start > 0 {
+            let ch = self.ch(row, start - 1);
+            if is_border(ch) || (ch == ' ' && start >= 2 && self.ch(row, start - 2) == ' ') {
+                break;
+            }
+            start -= 1;
+        }
+        self.text(row, start, end)
+            .trim_end_matches(':')
+            .trim_end()
+            .to_string()
+    }
+
+    /// Find box-drawn frames. One-line frames nested in another frame are
+    /// entry fields (dialog's inputbox); everything else is a dialog.
+    fn detect_boxes(&mut self) {
+        let mut boxes = Vec::new();
+        for row in 0..self.rows.len() {
+            for col in 0..self.width(row) {
+                if let Some(rect) = self.box_at(row, col) {
+                    boxes.push(rect);
+                }
+            }
+        }
+
+        for &rect in &boxes {
+            self.interiors.push(Rect {
+                row: rect.row + 1,
+                col: rect.col + 1,
+                width: rect.width - 2,
+                height: rect.height - 2,
+            });
+        }
+
+        for &rect in &boxes {
+            let nested = boxes.iter().any(|outer| {
+                outer.row < rect.row
+                    && outer.col < rect.col
+                    && outer.row + outer.height > rect.row + rect.height
+                    && outer.col + outer.width > rect.col + rect.width
+            });
+            let title = self.box_title(rect);
+            if nested && rect.height == 3 && title.is_empty() {
+                let value = self.text(rect.row + 1, rect.col + 1, rect.col + rect.width - 1);
+                self.push(ElementKind::Input, rect, value, None);
+            } else {
+                self.push(ElementKind::Dialog, rect, title, None);
+            }
+        }
+    }
+
+    fn box_at(&self, row: usize, col: usize) -> Option<Rect> {
+        let top_left = self.ch(row, col);
+        if !TOP_LEFT.contains(&top_left) {
+            return None;
+        }
+        let ascii = top_left == '+';
+        let corner = |ch: char, set: &[char]| set.contains(&ch) && (ch == '+') == ascii;
+        let horizontal = |ch: char| {
+            if ascii {
+                ch == '-'
+            } else {
+                ch != '+' && is_border(ch) && !TOP_LEFT.contains(&ch)
+            }
+        };
+
+        // Top edge; unicode frames may embed a title.
+        if !horizontal(self.ch(row, col + 1)) {
+            return None;
+        }
+        let width = self.width(row);
+        let mut right = col + 1;
+        while right < width && !corner(self.ch(row, right), TOP_RIGHT) {
+            if ascii && self.ch(row, right) != '-' {
+                return None;
+            }
+            right += 1;
+        }
+        if right >= width {
+            return None;
+        }
+
+        // Sides down to the bottom edge.
+        let mut bottom = row + 1;
+        loop {
+            if bottom >= self.rows.len() {
+                return None;
+            }
+            let left_ch = self.ch(bottom, col);
+            let right_ch = self.ch(bottom, right);
+            let bottom_edge = corner(left_ch, BOTTOM_LEFT)
+                && corner(right_ch, BOTTOM_RIGHT)
+                && horizontal(self.ch(bottom, col + 1))
+                && (!ascii || (col + 1..right).all(|c| self.ch(bottom, c) == '-'));
+            if bottom_edge {
+                break;
+            }
+            let side = |ch: char, set: &[char]| set.contains(&ch) && (ascii || ch != '+');
+            if !side(left_ch, LEFT_SIDE) || !side(right_ch, RIGHT_SIDE) {
+                return None;
+            }
+            bottom += 1;
+        }
+        if bottom < row + 2 {
+            return None;
+        }
+
+        Some(Rect {
+            row,
+            col,
+            width: right - col + 1,
+            height: bottom - row + 1,
+        })
+    }
+
+    fn box_title(&self, rect: Rect) -> String {
+        let title: String = (rect.col + 1..rect.col + rect.width - 1)
+            .map(|col| self.ch(rect.row, col))
+            .filter(|&ch| ch != WIDE_CONTINUATION)
+            .map(|ch| if is_border(ch) || ch == '-' { ' ' } else { ch })
+            .collect();
+        title.trim().to_string()
+    }
+
+    /// Find bracketed widgets and underscore fields on one row.
+    fn detect_inline(&mut self, row: usize) {
+        let width = self.width(row);
+        let mut col = 0;
+        while col < width {
+            let ch = self.ch(row, col);
+            if matches!(ch, '[' | '(' | '<') {
+                if let Some(end) = self.bracketed(row, col) {
+                    col = end;
+                    continue;
+                }
+            } else if ch == '_' {
+                let end = (col..width)
+                    .find(|&c| self.ch(row, c) != '_')
+                    .unwrap_or(width);
+                if end - col >= 3 {
+                    let rect = Rect {
+                        row,
+                        col,
+                        width: end - col,
+                        height: 1,
+                    };
+                    if self.is_free(rect) {
+                        let label = self.label_before(row, col);
+                        self.push(ElementKind::Input, rect, label, None);
+                    }
+                }
+                col = end;
+                continue;
+            }
+            col += 1;
+        }
+    }
+
+    /// Classify a bracketed token at `col`; returns the column after it.
+    fn bracketed(&mut self, row: usize, col: usize) -> Option<usize> {
+        let open = self.ch(row, col);
+        let close = match open {
+            '[' => ']',
+            '(' => ')',
+            _ => '>',
+        };
+        let limit = (col + MAX_BUTTON_LABEL + 3).min(self.width(row));
+        let close_col = (col + 1..limit)
+            .take_while(|&c| {
+                let ch = self.ch(row, c);
+                ch == close || !(is_border(ch) || "[]()<>".contains(ch))
+            })
+            .find(|&c| self.ch(row, c) == close)?;
+        let inner: Vec<char> = (col + 1..close_col).map(|c| self.ch(row, c)).collect();
+        let token = Rect {
+            row,
+            col,
+            width: close_col - col + 1,
+            height: 1,
+        };
+        if !self.is_free(token) {
+            return None;
+        }
+        let after = close_col + 1;
+        let boundary = |ch: char| ch == ' ' || is_border(ch);
+        let starts_cleanly = col == 0 || boundary(self.ch(row, col - 1));
+
+        if let [mark] = inner[..] {
+            let kind = match open {
+                _ if !starts_cleanly => None,
+                '[' if CHECKBOX_MARKS.contains(&mark) => Some(ElementKind::Checkbox),
+                '(' if RADIO_MARKS.contains(&mark) => Some(ElementKind::Radio),
+                _ => None,
+            };
+            if let Some(kind) = kind {
+                let label_start = if self.ch(row, after) == ' ' {
+                    after + 1
+                } else {
+                    after
+                };
+                let (label, label_end) = if self.ch(row, label_start) == ' ' {
+                    (String::new(), after)
+                } else {
+                    self.text_run(row, label_start)
+                };
+                let rect = Rect {
+                    width: label_end.max(after) - col,
+                    ..token
+                };
+                self.push(kind, rect, label, Some(mark != ' '));
+                return Some(label_end.max(after));
+            }
+        }
+
+        if open == '[' && inner.len() >= 3 && inner.iter().all(|ch| matches!(ch, ' ' | '_' | '.')) {
+            let label = self.label_before(row, col);
+            self.push(ElementKind::Input, token, label, None);
+            return Some(after);
+        }
+
+        let label = self.text(row, col + 1, close_col);
+        let ends_cleanly = after >= self.width(row) || boundary(self.ch(row, after));
+        let is_button = open != '('
+            && starts_cleanly
+            && ends_cleanly
+            && label.chars().next().is_some_and(char::is_alphanumeric)
+            && label.chars().count() <= MAX_BUTTON_LABEL
+            && label.split_whitespace().count() <= 3;
+        if is_button {
+            self.push(ElementKind::Button, token, label, None);
+            return Some(after);
+        }
+        None
+    }
+
+    /// Find lists whose focused entry is marked by a pointer glyph, as drawn
+    /// by prompt libraries (`❯ Option`), along with their unmarked siblings.
+    fn detect_pointer_menus(&mut self) {
+        for row in 0..self.rows.len() {
+            let Some(pointer) = self.segment_start(row, 0) else {
+                continue;
+            };
+            if !MENU_POINTERS.contains(&self.ch(row, pointer))
+                || self.ch(row, pointer + 1) != ' '
+                || !self.ch(row, pointer + 2).is_alphanumeric()
+            {
+                continue;
+            }
+            let text_col = pointer + 2;
+            let sibling = |detector: &Self, candidate: usize| {
+                (pointer..text_col).all(|c| detector.ch(candidate, c) == ' ')
+                    && detector.segment_start(candidate, 0) == Some(text_col)
+                    && detector.ch(candidate, text_col).is_alphanumeric()
+            };
+            let mut first = row;
+            while first > 0 && sibling(self, first - 1) {
+                first -= 1;
+            }
+            let mut last = row;
+            while last + 1 < self.rows.len() && sibling(self, last + 1) {
+                last += 1;
+            }
+            if first == last {
+                continue;
+            }
+            for item_row in first..=last {
+                let (label, end) = self.text_run(item_row, text_col);
+                let rect = Rect {
+                    row: item_row,
+                    col: pointer,
+                    width: end - pointer,
+                    height: 1,
+                };
+                if self.is_free(rect) {
+                    self.push(ElementKind::MenuItem, rect, label, None);
+                    if item_row == row {
+                        if let Some(found) = self.found.last_mut() {
+                            found.focused = true;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Find highlighted selection bars inside dialogs (dialog, whiptail)
+    /// and the unhighlighted entries aligned with them.
+    fn detect_highlighted_menus(&mut self) {
+        for interior in self.interiors.clone() {
+            let end = interior.col + interior.width;
+            for row in interior.row..interior.row + interior.height {
+                let mut col = interior.col;
+                while col < end {
+                    if !self.rows[row].get(col).is_some_and(|cell| cell.highlighted) {
+                        col += 1;
+                        continue;
+                    }
+                    let bar_end = (col..end)
+                        .find(|&c| !self.rows[row].get(c).is_some_and(|cell| cell.highlighted))
+                        .unwrap_or(end);
+                    self.highlighted_menu(interior, row, col, bar_end);
+                    col = bar_end;
+                }
+            }
+        }
+    }
+
+    fn highlighted_menu(&mut self, interior: Rect, row: usize, start: usize, end: usize) {
+        let bar = Rect {
+            row,
+            col: start,
+            width: end - start,
+            height: 1,
+        };
+        let label = self.text(row, start, end);
+        if !self.is_free(bar) || !label.chars().any(char::is_alphanumeric) {
+            return;
+        }
+        let Some(text_col) = (start..end).find(|&c| self.ch(row, c) != ' ') else {
+            return;
+        };
+        let sibling = |detector: &Self, candidate: usize| {
+            detector.ch(candidate, text_col).is_alphanumeric()
+                && (start..text_col).all(|c| detector.ch(candidate, c) == ' ')
+                && detector.is_free(Rect {
+                    row: candidate,
+                    ..bar
+                })
+        };
+        let mut first = row;
+        while first > interior.row && sibling(self, first - 1) {
+            first -= 1;
+        }
+        let mut last = row;
+        while last + 1 < interior.row + interior.height && sibling(self, last + 1) {
+            last += 1;
+        }
+        for item_row in first..=last {
+            let label = self.text(item_row, start, end);
+            self.push(
+                ElementKind::MenuItem,
+                Rect {
+                    row: item_row,
+                    ..bar
+                },
+                label,
+                None,
+            );
+        }
+    }
+
+    /// First non-blank column at or after `from`, skipping frame borders.
+    fn segment_start(&self, row: usize, from: usize) -> Option<usize> {
+        (from..self.width(row)).find(|&col| {
+            let ch = self.ch(row, col);
+            ch != ' ' && !is_border(ch)
+        })
+    }
+}
+
+/// Box-drawing characters and the ASCII vertical bar.
+fn is_border(ch: char) -> bool {
+    ('\u{2500}'..='\u{257f}').contains(&ch) || ch == '|'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid(lines: &[&str]) -> Vec<Vec<GridCell>> {
+        lines
+            .iter()
+            .map(|line| {
+                line.chars()
+                    .map(|ch| GridCell {
+                        ch,
+                        highlighted: false,
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn highlight(grid: &mut [Vec<GridCell>], row: usize, cols: std::ops::Range<usize>) {
+        for cell in &mut grid[row][cols] {
+            cell.highlighted = true;
+        }
+    }
+
+    fn summary(elements: &[Element]) -> Vec<(&str, ElementKind, &str)> {
+        elements
+            .iter()
+            .map(|element| (element.id.as_str(), element.kind, element.label.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn detects_dialog_with_buttons_and_focus() {
+        let mut rows = grid(&[
+            "┌──── Confirm ─────┐",
+            "│ Delete file?     │",
+            "│ < OK > <Cancel>  │",
+            "└──────────────────┘",
+        ]);
+        highlight(&mut rows, 2, 9..17);
+
+        let elements = detect_elements(&rows);
+        assert_eq!(
+            summary(&elements),
+            vec![
+                ("@e1", ElementKind::Dialog, "Confirm"),
+                ("@e2", ElementKind::Button, "OK"),
+                ("@e3", ElementKind::Button, "Cancel"),
+            ]
+        );
+        assert_eq!(
+            (
+                elements[0].row,
+                elements[0].col,
+                elements[0].width,
+                elements[0].height
+            ),
+            (0, 0, 20, 4)
+        );
+        assert_eq!(
+            (elements[1].row, elements[1].col, elements[1].width),
+            (2, 2, 6)
+        );
+        assert!(!elements[1].focused);
+        assert!(elements[2].focused);
+    }
+
+    #[test]
+    fn detects_checkboxes_radios_and_fields() {
+        let rows = grid(&[
+            "Options: [x] Enable  [ ] Debug",
+            "Mode: (*) Fast  ( ) Safe",
+            "Name: ________  Port: [      ]",
+            "See (optional) notes [ Save ]",
+        ]);
+
+        let elements = detect_elements(&rows);
+        assert_eq!(
+            summary(&elements),
+            vec![
+                ("@e1", ElementKind::Checkbox, "Enable"),
+                ("@e2", ElementKind::Checkbox, "Debug"),
+                ("@e3", ElementKind::Radio, "Fast"),
+                ("@e4", ElementKind::Radio, "Safe"),
+                ("@e5", ElementKind::Input, "Name"),
+                ("@e6", ElementKind::Input, "Port"),
+                ("@e7", ElementKind::Button, "Save"),
+            ]
+        );
+        let checked: Vec<Option<bool>> = elements[..4].iter().map(|e| e.checked).collect();
+        assert_eq!(
+            checked,
+            vec![Some(true), Some(false), Some(true), Some(false)]
+        );
+        assert_eq!((elements[0].col, elements[0].width), (9, 10));
+    }
+
+    #[test]
+    fn detects_pointer_menu_and_ignores_lone_prompt() {
+        let rows = grid(&[
+            "? Pick a flavor",
+            "  Vanilla",
+            "❯ Chocolate",
+            "  Strawberry",
+            "",
+            "> echo hi",
+        ]);
+
+        let elements = detect_elements(&rows);
+        assert_eq!(
+            summary(&elements),
+            vec![
+                ("@e1", ElementKind::MenuItem, "Vanilla"),
+                ("@e2", ElementKind::MenuItem, "Chocolate"),
+                ("@e3", ElementKind::MenuItem, "Strawberry"),
+            ]
+        );
+        assert!(elements[1].focused);
+        assert!(!elements[0].focused);
+    }
+
+    #[test]
+    fn detects_highlighted_menu_inside_dialog() {
+        let mut rows = grid(&[
+            "┌─────────────────┐",
+            "│ Choose one:     │",
+            "│                 │",
+            "│  1  Install     │",
+            "│  2  Upgrade     │",
+            "│  3  Remove      │",
+            "│                 │",
+            "│   <Ok>  <Exit>  │",
+            "└─────────────────┘",
+        ]);
+        highlight(&mut rows, 4, 2..14);
+        highlight(&mut rows, 7, 4..8);
+
+        let elements = detect_elements(&rows);
+        assert_eq!(
+            summary(&elements),
+            vec![
+                ("@e1", ElementKind::Dialog, ""),
+                ("@e2", ElementKind::MenuItem, "1  Install"),
+                ("@e3", ElementKind::MenuItem, "2  Upgrade"),
+                ("@e4", ElementKind::MenuItem, "3  Remove"),
+                ("@e5", ElementKind::Button, "Ok"),
+                ("@e6", ElementKind::Button, "Exit"),
+            ]
+        );
+        let focused: Vec<bool> = elements.iter().map(|e| e.focused).collect();
+        assert_eq!(focused, vec![false, false, true, false, true, false]);
+    }
+
+    #[test]
+    fn nested_one_line_box_is_an_input() {
+        let rows = grid(&[
+            "╔═ Login ══════════╗",
+            "║ User:            ║",
+            "║ ┌──────────────┐ ║",
+            "║ │alice         │ ║",
+            "║ └──────────────┘ ║",
+            "╚══════════════════╝",
+        ]);
+
+        let elements = detect_elements(&rows);
+        assert_eq!(
+            summary(&elements),
+            vec![
+                ("@e1", ElementKind::Dialog, "Login"),
+                ("@e2", ElementKind::Input, "alice"),
+            ]
+        );
+        assert_eq!((elements[1].row, elements[1].height), (2, 3));
+    }
+
+    #[test]
+    fn refs_do_not_change_when_only_state_changes() {
+        let before = detect_elements(&grid(&["[ ] Alpha  [ ] Beta  < OK >"]));
+        let after = detect_elements(&grid(&["[x] Alpha  [ ] Beta  < OK >"]));
+
+        let ids = |elements: &[Element]| -> Vec<(String, String)> {
+            elements
+                .iter()
+                .map(|e| (e.id.clone(), e.label.clone()))
+                .collect()
+        };
+        assert_eq!(ids(&before), ids(&after));
+        assert_eq!(after[0].checked, Some(true));
+    }
+
+    #[test]
+    fn ignores_plain_prose_and_key_hints_inside_words() {
+        let rows = grid(&["let x = a<b> + c[i];  f(x)", "---------------"]);
+        assert!(detect_elements(&rows).is_empty());
+    }
+
+    #[test]
+    fn element_serializes_compactly() {
+        let element = Element {
+            id: "@e2".to_string(),
+            kind: ElementKind::Checkbox,
+            row: 1,
+            col: 4,
+            width: 10,
+            height: 1,
+            label: "Enable".to_string(),
+            checked: Some(true),
+            focused: false,
+        };
+        assert_eq!(
+            serde_json::to_string(&element).unwrap(),
+            r#"{"ref":"@e2","kind":"checkbox","row":1,"col":4,"width":10,"height":1,"label":"Enable","checked":true}"#
+        );
+    }
+}
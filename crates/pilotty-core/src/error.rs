@@ -12,6 +12,7 @@ pub enum ErrorCode {
     CommandFailed,
     InvalidInput,
     InternalError,
+    MouseNotEnabled,
 }
 
 impl fmt::Display for ErrorCode {
@@ -22,6 +23,7 @@ impl fmt::Display for ErrorCode {
             ErrorCode::CommandFailed => write!(f, "COMMAND_FAILED"),
             ErrorCode::InvalidInput => write!(f, "INVALID_INPUT"),
             ErrorCode::InternalError => write!(f, "INTERNAL_ERROR"),
+            ErrorCode::MouseNotEnabled => write!(f, "MOUSE_NOT_ENABLED"),
         }
     }
 }
@@ -69,6 +71,21 @@ impl ApiError {
         }
     }
 
+    /// Create an error for mouse input to an application that reports no mouse events.
+    pub fn mouse_not_enabled(session_id: &str) -> Self {
+        Self {
+            code: ErrorCode::MouseNotEnabled,
+            message: format!(
+                "The application in session '{}' has not enabled mouse reporting",
+                session_id
+            ),
+            suggestion: Some(
+                "It would read mouse input as typed text. Navigate with 'pilotty key' instead; a snapshot's modes.mouse_tracking shows when the mouse is on."
+                    .into(),
+            ),
+        }
+    }
+
     pub fn command_failed(message: impl Into<String>) -> Self {
         Self {
             code: ErrorCode::CommandFailed,
@@ -375,6 +392,20 @@ mod tests {
             .is_some_and(|suggestion| suggestion.contains("pilotty status")));
     }
 
+    #[test]
+    fn mouse_not_enabled_error_is_versioned_and_actionable() {
+        let err = ApiError::mouse_not_enabled("editor");
+
+        assert_eq!(err.code, ErrorCode::MouseNotEnabled);
+        assert_eq!(err.code.to_string(), "MOUSE_NOT_ENABLED");
+        assert_eq!(err.minimum_protocol(), crate::protocol::PROTOCOL_V4);
+        assert!(err.message.contains("editor"));
+        assert!(err
+            .suggestion
+            .as_deref()
+            .is_some_and(|suggestion| suggestion.contains("pilotty key")));
+    }
+
     #[test]
     fn session_not_found_describes_expired_or_unknown_state() {
         let err = ApiError::session_not_found("editor");
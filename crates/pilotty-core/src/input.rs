@@ -2,7 +2,8 @@
 //!
 //! Handles conversion of text and key names to bytes for PTY input.
 
-use crate::protocol::ScrollDirection;
+use crate::protocol::{MouseButton, MouseModifiers, ScrollDirection};
+use crate::snapshot::{MouseEncoding, MouseTracking, TerminalModes};
 
 /// Encode text for PTY input, handling escape sequences.
 ///
@@ -66,129 +67,297 @@ pub fn encode_text(text: &str) -> Vec<u8> {
     result
 }
 
-/// Named keys and their byte sequences.
+/// Start marker of a bracketed paste (`?2004`).
+const PASTE_START: &str = "\x1b[200~";
+/// End marker of a bracketed paste.
+const PASTE_END: &str = "\x1b[201~";
+
+/// Encode text as a terminal pastes it.
 ///
-/// The `application_cursor` parameter affects arrow key encoding:
-/// - `false`: CSI sequences (`\x1b[A`, `\x1b[B`, etc.) for normal mode
-/// - `true`: SS3 sequences (`\x1bOA`, `\x1bOB`, etc.) for application mode
+/// Unlike [`encode_text`], the text is taken literally. Line breaks are sent
+/// as carriage returns, as the Enter key would send them, and any paste
+/// markers inside the text are dropped so it cannot end the paste early.
+/// With `bracketed`, the result is wrapped in `ESC[200~` / `ESC[201~`.
 ///
-/// TUI applications like dialog, vim, htop enable application cursor mode
-/// via DECCKM (`ESC[?1h`), and arrow keys must use SS3 encoding to work.
+/// # Examples
 ///
-/// Returns the escape sequence for a named key, or None if not recognized.
+/// ```
+/// use pilotty_core::input::encode_paste;
 ///
-/// Note: Internal function. Use `parse_key_sequence` for the public API.
-fn key_to_bytes(key: &str, application_cursor: bool) -> Option<Vec<u8>> {
-    // Normalize key name (case insensitive)
-    let key_lower = key.to_lowercase();
-    let key_str = key_lower.as_str();
-
-    // Arrow keys depend on application cursor mode
-    match key_str {
-        "up" | "arrowup" => {
-            return Some(if application_cursor {
-                b"\x1bOA".to_vec()
-            } else {
-                b"\x1b[A".to_vec()
-            });
-        }
-        "down" | "arrowdown" => {
-            return Some(if application_cursor {
-                b"\x1bOB".to_vec()
-            } else {
-                b"\x1b[B".to_vec()
-            });
-        }
-        "right" | "arrowright" => {
-            return Some(if application_cursor {
-                b"\x1bOC".to_vec()
-            } else {
-                b"\x1b[C".to_vec()
-            });
-        }
-        "left" | "arrowleft" => {
-            return Some(if application_cursor {
-                b"\x1bOD".to_vec()
-            } else {
-                b"\x1b[D".to_vec()
-            });
-        }
-        _ => {}
+/// assert_eq!(encode_paste("a\nb", false), b"a\rb");
+/// assert_eq!(encode_paste("a\r\nb", true), b"\x1b[200~a\rb\x1b[201~");
+/// ```
+pub fn encode_paste(text: &str, bracketed: bool) -> Vec<u8> {
+    let mut text = text.replace("\r\n", "\r").replace('\n', "\r");
+    // Removing one marker can join the pieces of another around it.
+    while text.contains(PASTE_START) || text.contains(PASTE_END) {
+        text = text.replace(PASTE_START, "").replace(PASTE_END, "");
     }
+    if bracketed {
+        format!("{PASTE_START}{text}{PASTE_END}").into_bytes()
+    } else {
+        text.into_bytes()
+    }
+}
+
+/// How a named key is encoded, which decides where xterm puts modifiers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NamedKey {
+    /// Arrow keys: `CSI X`, or `SS3 X` in application cursor mode.
+    Cursor(u8),
+    /// `CSI X` (Home, End).
+    Csi(u8),
+    /// `SS3 X` (F1-F4).
+    Ss3(u8),
+    /// `CSI n ~` (Insert, Delete, PageUp, PageDown, F5-F12, Menu).
+    Tilde(u8),
+    /// F13-F24, which xterm sends as Shift+F1 to Shift+F12 (index into
+    /// [`FUNCTION_KEYS`]).
+    ShiftedFunction(u8),
+    /// Keypad key: its text, or `SS3 X` in application keypad mode.
+    Keypad(&'static [u8], u8),
+    /// `CSI n u`, for keys xterm has no sequence for (Pause, PrintScreen,
+    /// ScrollLock); `n` is the key's kitty keyboard protocol code. Only
+    /// applications that enabled the kitty keyboard protocol can read it.
+    CsiU(u32),
+    /// Plain bytes that take no modifier parameter (Enter, Tab, Escape, ...).
+    Literal(&'static [u8]),
+}
+
+/// F1-F12 as xterm sends them.
+const FUNCTION_KEYS: [NamedKey; 12] = [
+    NamedKey::Ss3(b'P'),
+    NamedKey::Ss3(b'Q'),
+    NamedKey::Ss3(b'R'),
+    NamedKey::Ss3(b'S'),
+    NamedKey::Tilde(15),
+    NamedKey::Tilde(17),
+    NamedKey::Tilde(18),
+    NamedKey::Tilde(19),
+    NamedKey::Tilde(20),
+    NamedKey::Tilde(21),
+    NamedKey::Tilde(23),
+    NamedKey::Tilde(24),
+];
+
+/// Kitty keyboard protocol code of F13; F14-F24 follow it.
+const KITTY_F13: u32 = 57376;
+/// Kitty keyboard protocol code of keypad 0; keypad 1-9 follow it.
+const KITTY_KP_0: u32 = 57399;
+
+/// Modifier keys held with a key.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Modifiers {
+    shift: bool,
+    alt: bool,
+    ctrl: bool,
+}
+
+impl Modifiers {
+    /// xterm's modifier parameter: 1 plus Shift=1, Alt=2, Ctrl=4.
+    ///
+    /// `None` when no modifier is held, so the key keeps its short form.
+    fn xterm_param(self) -> Option<u8> {
+        let bits = u8::from(self.shift) | u8::from(self.alt) << 1 | u8::from(self.ctrl) << 2;
+        (bits != 0).then_some(bits + 1)
+    }
+}
+
+/// Look up a named key (case insensitive).
+fn named_key(key: &str) -> Option<NamedKey> {
+    use NamedKey::*;
+
+    Some(match key.to_lowercase().as_str() {
+        // Arrow keys depend on application cursor mode
+        "up" | "arrowup" => Cursor(b'A'),
+        "down" | "arrowdown" => Cursor(b'B'),
+        "right" | "arrowright" => Cursor(b'C'),
+        "left" | "arrowleft" => Cursor(b'D'),
 
-    let bytes: &[u8] = match key_str {
         // Basic keys
-        "enter" | "return" => b"\r",
-        "tab" => b"\t",
-        "escape" | "esc" => b"\x1b",
-        "backspace" => b"\x7f",
-        "delete" | "del" => b"\x1b[3~",
-        "space" => b" ",
-        "plus" => b"+", // Named alias for literal + (useful since + is combo separator)
+        "enter" | "return" => Literal(b"\r"),
+        "tab" => Literal(b"\t"),
+        "escape" | "esc" => Literal(b"\x1b"),
+        "backspace" => Literal(b"\x7f"),
+        "delete" | "del" => Tilde(3),
+        "space" => Literal(b" "),
+        "plus" => Literal(b"+"), // Named alias for literal + (useful since + is combo separator)
 
         // Navigation keys
-        "home" => b"\x1b[H",
-        "end" => b"\x1b[F",
-        "pageup" | "pgup" => b"\x1b[5~",
-        "pagedown" | "pgdn" => b"\x1b[6~",
-        "insert" | "ins" => b"\x1b[2~",
-
-        // Function keys (F1-F12)
-        "f1" => b"\x1bOP",
-        "f2" => b"\x1bOQ",
-        "f3" => b"\x1bOR",
-        "f4" => b"\x1bOS",
-        "f5" => b"\x1b[15~",
-        "f6" => b"\x1b[17~",
-        "f7" => b"\x1b[18~",
-        "f8" => b"\x1b[19~",
-        "f9" => b"\x1b[20~",
-        "f10" => b"\x1b[21~",
-        "f11" => b"\x1b[23~",
-        "f12" => b"\x1b[24~",
+        "home" => Csi(b'H'),
+        "end" => Csi(b'F'),
+        "pageup" | "pgup" => Tilde(5),
+        "pagedown" | "pgdn" => Tilde(6),
+        "insert" | "ins" => Tilde(2),
+
+        // System keys: Menu is the VT220 Do key, as in xterm and rxvt
+        "menu" => Tilde(29),
+        "pause" => CsiU(57362),
+        "printscreen" | "print" => CsiU(57361),
+        "scrolllock" => CsiU(57359),
+
+        // Keypad keys depend on application keypad mode
+        "kpenter" => Keypad(b"\r", b'M'),
+        "kpmultiply" => Keypad(b"*", b'j'),
+        "kpplus" => Keypad(b"+", b'k'),
+        "kpcomma" => Keypad(b",", b'l'),
+        "kpminus" => Keypad(b"-", b'm'),
+        "kpdecimal" | "kpperiod" => Keypad(b".", b'n'),
+        "kpdivide" => Keypad(b"/", b'o'),
+        "kpequal" => Keypad(b"=", b'X'),
+
+        // Keypad digits (KP0-KP9) and function keys (F1-F24)
+        name => return numbered_key(name),
+    })
+}
 
+/// Look up a lowercase keypad digit (`kp0`-`kp9`) or function key (`f1`-`f24`).
+fn numbered_key(name: &str) -> Option<NamedKey> {
+    const DIGITS: [&[u8]; 10] = [b"0", b"1", b"2", b"3", b"4", b"5", b"6", b"7", b"8", b"9"];
+
+    let (prefix, number) = name.split_at(name.find(|c: char| c.is_ascii_digit())?);
+    if number.len() > 1 && number.starts_with('0') {
+        return None;
+    }
+    Some(match (prefix, number.parse::<u8>().ok()?) {
+        ("kp", n @ 0..=9) => NamedKey::Keypad(DIGITS[usize::from(n)], b'p' + n),
+        ("f", n @ 1..=12) => FUNCTION_KEYS[usize::from(n - 1)],
+        ("f", n @ 13..=24) => NamedKey::ShiftedFunction(n - 13),
         _ => return None,
-    };
+    })
+}
+
+impl NamedKey {
+    /// Encode the key with `modifiers` the way xterm does.
+    ///
+    /// Modified keys use `CSI 1;<mod>X` or `CSI n;<mod>~`, even in
+    /// application cursor mode. Keys without a modifier parameter get an ESC
+    /// prefix for Alt, and Shift+Tab becomes back-tab (`CSI Z`).
+    ///
+    /// Returns `None` for a key the application's keyboard mode cannot
+    /// represent (see [`NamedKey::supported`]).
+    fn encode(self, modifiers: Modifiers, modes: TerminalModes) -> Option<Vec<u8>> {
+        if !self.supported(modes) {
+            return None;
+        }
+        let param = modifiers.xterm_param();
+        Some(match (self, param) {
+            (NamedKey::Cursor(c), None) if modes.application_cursor => vec![0x1b, b'O', c],
+            (NamedKey::Cursor(c) | NamedKey::Csi(c), None) => vec![0x1b, b'[', c],
+            (NamedKey::Ss3(c), None) => vec![0x1b, b'O', c],
+            (NamedKey::Cursor(c) | NamedKey::Csi(c) | NamedKey::Ss3(c), Some(m)) => {
+                format!("\x1b[1;{}{}", m, c as char).into_bytes()
+            }
+            (NamedKey::Tilde(n), None) => format!("\x1b[{}~", n).into_bytes(),
+            (NamedKey::Tilde(n), Some(m)) => format!("\x1b[{};{}~", n, m).into_bytes(),
+            (NamedKey::ShiftedFunction(index), _) => {
+                let shifted = Modifiers {
+                    shift: true,
+                    ..modifiers
+                };
+                return FUNCTION_KEYS[usize::from(index)].encode(shifted, modes);
+            }
+            (NamedKey::Keypad(_, c), _) if modes.application_keypad => {
+                return NamedKey::Ss3(c).encode(modifiers, modes);
+            }
+            (NamedKey::Keypad(text, _), _) => {
+                return NamedKey::Literal(text).encode(modifiers, modes);
+            }
+            (NamedKey::CsiU(code), None) => format!("\x1b[{}u", code).into_bytes(),
+            (NamedKey::CsiU(code), Some(m)) => format!("\x1b[{};{}u", code, m).into_bytes(),
+            (NamedKey::Literal(bytes), _) => {
+                let mut result = Vec::with_capacity(bytes.len() + 2);
+                if modifiers.alt {
+                    result.push(0x1b);
+                }
+                if modifiers.shift && bytes == b"\t" {
+                    result.extend_from_slice(b"\x1b[Z");
+                } else {
+                    result.extend_from_slice(bytes);
+                }
+                result
+            }
+        })
+    }
 
-    Some(bytes.to_vec())
+    /// Whether the application's keyboard mode has an encoding for the key.
+    ///
+    /// Pause, PrintScreen, and ScrollLock only exist as kitty keyboard
+    /// protocol codes; a legacy application would read them as garbage.
+    fn supported(self, modes: TerminalModes) -> bool {
+        !matches!(self, NamedKey::CsiU(_)) || modes.kitty_keyboard != 0
+    }
+
+    /// The key's code in the kitty keyboard protocol, for keys it reports
+    /// as `CSI code u` rather than in their xterm form.
+    fn kitty_code(self) -> Option<u32> {
+        match self {
+            NamedKey::ShiftedFunction(index) => Some(KITTY_F13 + u32::from(index)),
+            NamedKey::Keypad(_, c @ b'p'..=b'y') => Some(KITTY_KP_0 + u32::from(c - b'p')),
+            NamedKey::Keypad(_, c) => {
+                let offset = b"nojmkMXl".iter().position(|&final_byte| final_byte == c)?;
+                // KP_DECIMAL through KP_SEPARATOR follow KP_9.
+                Some(KITTY_KP_0 + 10 + offset as u32)
+            }
+            NamedKey::CsiU(code) => Some(code),
+            _ => None,
+        }
+    }
 }
 
-/// Parse a key combo like "Ctrl+C" or "Alt+F" and return the bytes.
+/// Named keys and their byte sequences.
 ///
-/// The `application_cursor` parameter affects arrow key encoding in combos.
+/// Application cursor mode in `modes` affects arrow key encoding:
+/// - off: CSI sequences (`\x1b[A`, `\x1b[B`, etc.) for normal mode
+/// - on: SS3 sequences (`\x1bOA`, `\x1bOB`, etc.) for application mode
 ///
-/// Supports:
-/// - Ctrl+<key>: Control character (Ctrl+A = 0x01, Ctrl+C = 0x03, etc.)
-/// - Alt+<key>: Escape prefix + key (Alt+F = ESC f)
-/// - Shift+<key>: Uppercase for letters, otherwise ignored
-/// - Combinations: Ctrl+Alt+<key>, etc.
+/// TUI applications like dialog, vim, htop enable application cursor mode
+/// via DECCKM (`ESC[?1h`), and arrow keys must use SS3 encoding to work.
+/// Application keypad mode (DECKPAM, `ESC =`) likewise switches keypad keys
+/// from their text to SS3 sequences.
+///
+/// Returns the escape sequence for a named key, or None if not recognized.
 ///
 /// Note: Internal function. Use `parse_key_sequence` for the public API.
-fn parse_key_combo(combo: &str, application_cursor: bool) -> Option<Vec<u8>> {
-    let parts: Vec<&str> = combo.split('+').collect();
-
-    if parts.is_empty() {
-        return None;
-    }
+fn key_to_bytes(key: &str, modes: TerminalModes) -> Option<Vec<u8>> {
+    named_key(key)?.encode(Modifiers::default(), modes)
+}
 
-    let mut ctrl = false;
-    let mut alt = false;
-    let mut shift = false;
+/// Split a combo like "Ctrl+Shift+X" into its key and modifiers.
+///
+/// Returns `None` when no key is named.
+fn split_combo(combo: &str) -> Option<(&str, Modifiers)> {
+    let mut modifiers = Modifiers::default();
     let mut key_part = "";
 
-    for part in &parts {
-        let lower = part.to_lowercase();
-        match lower.as_str() {
-            "ctrl" | "control" => ctrl = true,
-            "alt" | "meta" | "option" => alt = true,
-            "shift" => shift = true,
+    for part in combo.split('+') {
+        match part.to_lowercase().as_str() {
+            "ctrl" | "control" => modifiers.ctrl = true,
+            "alt" | "meta" | "option" => modifiers.alt = true,
+            "shift" => modifiers.shift = true,
             _ => key_part = part,
         }
     }
 
-    if key_part.is_empty() {
-        return None;
-    }
+    (!key_part.is_empty()).then_some((key_part, modifiers))
+}
+
+/// Parse a key combo like "Ctrl+C" or "Alt+F" and return the bytes.
+///
+/// Application cursor and keypad modes in `modes` affect named keys in combos.
+///
+/// Supports:
+/// - Ctrl+<key>: Control character (Ctrl+A = 0x01, Ctrl+C = 0x03, etc.)
+/// - Alt+<key>: Escape prefix + key (Alt+F = ESC f)
+/// - Shift+<key>: Uppercase for letters, otherwise ignored
+/// - Combinations: Ctrl+Alt+<key>, etc.
+/// - Modified named keys in xterm form: Ctrl+Right = `CSI 1;5C`,
+///   Shift+F5 = `CSI 15;2~`, Shift+Tab = `CSI Z`
+///
+/// Note: Internal function. Use `parse_key_sequence` for the public API.
+fn parse_key_combo(combo: &str, modes: TerminalModes) -> Option<Vec<u8>> {
+    let (key_part, modifiers) = split_combo(combo)?;
+    let Modifiers { shift, alt, ctrl } = modifiers;
 
     // Handle Ctrl+Space specially (produces NUL)
     if ctrl && key_part.to_lowercase() == "space" {
@@ -201,14 +370,8 @@ fn parse_key_combo(combo: &str, application_cursor: bool) -> Option<Vec<u8>> {
     }
 
     // Try as named key first
-    if let Some(bytes) = key_to_bytes(key_part, application_cursor) {
-        // For named keys, modifiers are typically not applied (except Alt prefix)
-        if alt {
-            let mut result = vec![0x1b];
-            result.extend(bytes);
-            return Some(result);
-        }
-        return Some(bytes);
+    if let Some(named) = named_key(key_part) {
+        return named.encode(modifiers, modes);
     }
 
     // Single character
@@ -263,30 +426,301 @@ fn parse_key_combo(combo: &str, application_cursor: bool) -> Option<Vec<u8>> {
     Some(c.to_string().into_bytes())
 }
 
-/// Generate mouse click escape sequences (SGR extended encoding).
+/// Kitty flag: report ambiguous keys (Escape, modified text keys) as `CSI u`.
+const KITTY_DISAMBIGUATE: u8 = 0b1;
+/// Kitty flag: add the shifted key as an alternate key code.
+const KITTY_ALTERNATE_KEYS: u8 = 0b100;
+/// Kitty flag: report every key, text included, as `CSI u`.
+const KITTY_ALL_KEYS: u8 = 0b1000;
+
+/// Encode a combo for an application that negotiated the kitty keyboard
+/// protocol or xterm's modifyOtherKeys.
 ///
-/// SGR mouse encoding is: `\x1b[<button;x;yM` for press, `\x1b[<button;x;ym` for release
-/// Coordinates are 1-indexed.
+/// Only keys legacy encoding sends as text or C0 controls are affected
+/// (characters, Enter, Tab, Backspace, Escape, Space); named keys keep their
+/// xterm form, which already carries modifiers. Returns `None` when the key
+/// should be sent the legacy way.
+fn parse_extended_key(combo: &str, modes: TerminalModes) -> Option<Vec<u8>> {
+    if modes.kitty_keyboard == 0 && modes.modify_other_keys == 0 {
+        return None;
+    }
+    let (key_part, mut modifiers) = split_combo(combo)?;
+    let key = match named_key(key_part) {
+        Some(NamedKey::Literal(&[byte])) => char::from(byte),
+        // Keys without a legacy text form keep it unless the kitty protocol
+        // has its own code for them.
+        Some(named) => {
+            let code = named.kitty_code()?;
+            let escaped = modes.kitty_keyboard & (KITTY_DISAMBIGUATE | KITTY_ALL_KEYS) != 0;
+            return escaped.then(|| kitty_sequence(code, None, modifiers));
+        }
+        None => {
+            let mut chars = key_part.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => c,
+                _ => return None,
+            }
+        }
+    };
+    // Letter case only matters on its own: "Ctrl+A" is Ctrl+a, but a bare
+    // "A" is typed with Shift.
+    if key.is_ascii_uppercase() && !modifiers.ctrl && !modifiers.alt {
+        modifiers.shift = true;
+    }
+    let key = key.to_ascii_lowercase();
+
+    if modes.kitty_keyboard != 0 {
+        kitty_key(key, modifiers, modes.kitty_keyboard)
+    } else {
+        modify_other_key(key, modifiers, modes.modify_other_keys)
+    }
+}
+
+/// Encode `key` as kitty's `CSI code[:shifted][;mod] u`, if `flags` call for it.
+fn kitty_key(key: char, modifiers: Modifiers, flags: u8) -> Option<Vec<u8>> {
+    let text = !key.is_control();
+    let escaped = flags & KITTY_ALL_KEYS != 0
+        || (flags & KITTY_DISAMBIGUATE != 0
+            && (key == '\x1b' || modifiers.ctrl || modifiers.alt || (modifiers.shift && !text)));
+    if !escaped {
+        return None;
+    }
+
+    let shifted =
+        (flags & KITTY_ALTERNATE_KEYS != 0 && modifiers.shift && key.is_ascii_lowercase())
+            .then(|| u32::from(key.to_ascii_uppercase()));
+    Some(kitty_sequence(u32::from(key), shifted, modifiers))
+}
+
+/// Kitty's `CSI code[:shifted][;mod] u`.
+fn kitty_sequence(code: u32, shifted: Option<u32>, modifiers: Modifiers) -> Vec<u8> {
+    let mut sequence = format!("\x1b[{}", code);
+    if let Some(shifted) = shifted {
+        sequence.push_str(&format!(":{}", shifted));
+    }
+    if let Some(param) = modifiers.xterm_param() {
+        sequence.push_str(&format!(";{}", param));
+    }
+    sequence.push('u');
+    sequence.into_bytes()
+}
+
+/// Encode `key` as xterm's `CSI 27;mod;code ~`, if modifyOtherKeys `level`
+/// calls for it.
+///
+/// Level 2 covers every modified key except shifted text. Level 1 covers
+/// only combos whose legacy bytes would drop a modifier, such as Ctrl+Enter,
+/// Ctrl+Shift+A, or Ctrl+1; the well-known ones (Ctrl+letter, Alt+key,
+/// Shift+Tab, Ctrl+Space) stay as they are.
+fn modify_other_key(key: char, modifiers: Modifiers, level: u8) -> Option<Vec<u8>> {
+    let param = modifiers.xterm_param()?;
+    let Modifiers { shift, alt, ctrl } = modifiers;
+    let shift_only = shift && !alt && !ctrl;
+    let escaped = match level {
+        0 => false,
+        1 => match key {
+            '\t' => ctrl,
+            ' ' => shift,
+            '\r' | '\x1b' | '\x7f' => ctrl || shift,
+            _ if key.is_ascii_lowercase() => ctrl && shift,
+            _ => ctrl && !matches!(key, '@' | '[' | '\\' | ']' | '^' | '_' | '?'),
+        },
+        _ => !(shift_only && key.is_ascii_graphic()),
+    };
+    if !escaped {
+        return None;
+    }
+
+    let code = if shift { key.to_ascii_uppercase() } else { key };
+    Some(format!("\x1b[27;{};{}~", param, u32::from(code)).into_bytes())
+}
+
+/// Button code that X10, UTF-8, and urxvt encodings use for any release, and
+/// every encoding uses for motion with no button held.
+const MOUSE_RELEASE: u8 = 3;
+/// Button code bit set on motion reports.
+const MOUSE_MOTION: u8 = 32;
+/// Offset that keeps X10 and UTF-8 encoded bytes printable.
+const MOUSE_BYTE_OFFSET: u32 = 32;
+
+/// Button code for `button` with `modifiers` held.
 ///
-/// Returns (press_sequence, release_sequence).
-pub fn encode_mouse_click(x: u16, y: u16) -> (Vec<u8>, Vec<u8>) {
-    // Convert to 1-indexed coordinates
-    let x1 = x.saturating_add(1);
-    let y1 = y.saturating_add(1);
+/// Shift, Alt, and Ctrl add 4, 8, and 16. X10 compatibility tracking (`?9`)
+/// reports no modifiers, so they are left out there.
+fn mouse_button_code(
+    button: MouseButton,
+    modifiers: MouseModifiers,
+    tracking: MouseTracking,
+) -> u8 {
+    let code = match button {
+        MouseButton::Left => 0,
+        MouseButton::Middle => 1,
+        MouseButton::Right => 2,
+    };
+    if tracking == MouseTracking::Press {
+        return code;
+    }
+    code | u8::from(modifiers.shift) << 2
+        | u8::from(modifiers.alt) << 3
+        | u8::from(modifiers.ctrl) << 4
+}
 
-    // Button 0 = left click
-    let press = format!("\x1b[<0;{};{}M", x1, y1);
-    let release = format!("\x1b[<0;{};{}m", x1, y1);
+/// Encode one mouse report at 0-indexed (`x`, `y`) in `encoding`.
+///
+/// `code` carries the button, modifier, and motion bits. Releases keep the
+/// button in SGR and replace it with 3 in the other encodings.
+///
+/// Returns `None` when the position is beyond what the encoding can express:
+/// column or row 223 for X10, and 2015 for UTF-8.
+fn encode_mouse_report(
+    code: u8,
+    release: bool,
+    x: u16,
+    y: u16,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    // Reports use 1-indexed coordinates.
+    let x1 = u32::from(x) + 1;
+    let y1 = u32::from(y) + 1;
+    let legacy_code = if release {
+        code & !0b11 | MOUSE_RELEASE
+    } else {
+        code
+    };
+    match encoding {
+        MouseEncoding::Sgr => {
+            let kind = if release { 'm' } else { 'M' };
+            Some(format!("\x1b[<{};{};{}{}", code, x1, y1, kind).into_bytes())
+        }
+        MouseEncoding::Urxvt => Some(
+            format!(
+                "\x1b[{};{};{}M",
+                u32::from(legacy_code) + MOUSE_BYTE_OFFSET,
+                x1,
+                y1
+            )
+            .into_bytes(),
+        ),
+        MouseEncoding::X10 => {
+            let byte = |value: u32| u8::try_from(value + MOUSE_BYTE_OFFSET).ok();
+            Some(vec![
+                0x1b,
+                b'[',
+                b'M',
+                byte(u32::from(legacy_code))?,
+                byte(x1)?,
+                byte(y1)?,
+            ])
+        }
+        MouseEncoding::Utf8 => {
+            let mut report = b"\x1b[M".to_vec();
+            for value in [u32::from(legacy_code), x1, y1] {
+                // Two-byte UTF-8 sequences top out at U+07FF.
+                let c =
+                    char::from_u32(value + MOUSE_BYTE_OFFSET).filter(|c| (*c as u32) < 0x800)?;
+                let mut buf = [0; 4];
+                report.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+            }
+            Some(report)
+        }
+    }
+}
 
-    (press.into_bytes(), release.into_bytes())
+/// Generate click press and release sequences for the application's mouse mode.
+///
+/// `x` and `y` are 0-indexed; reports are 1-indexed. In SGR encoding a left
+/// press is `\x1b[<0;x;yM` and its release `\x1b[<0;x;ym`. X10 compatibility
+/// tracking (`?9`) reports presses only, so its release sequence is empty.
+///
+/// Returns `None` when tracking is off or the position does not fit the encoding.
+pub fn encode_mouse_click(
+    x: u16,
+    y: u16,
+    button: MouseButton,
+    modifiers: MouseModifiers,
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+) -> Option<(Vec<u8>, Vec<u8>)> {
+    if tracking == MouseTracking::None {
+        return None;
+    }
+    let code = mouse_button_code(button, modifiers, tracking);
+    let press = encode_mouse_report(code, false, x, y, encoding)?;
+    let release = if tracking == MouseTracking::Press {
+        Vec::new()
+    } else {
+        encode_mouse_report(code, true, x, y, encoding)?
+    };
+    Some((press, release))
 }
 
 /// Generate a complete mouse click (press + release) as a single sequence.
-pub fn encode_mouse_click_combined(x: u16, y: u16) -> Vec<u8> {
-    let (press, release) = encode_mouse_click(x, y);
-    let mut result = press;
-    result.extend(release);
-    result
+pub fn encode_mouse_click_combined(
+    x: u16,
+    y: u16,
+    button: MouseButton,
+    modifiers: MouseModifiers,
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    let (mut press, release) = encode_mouse_click(x, y, button, modifiers, tracking, encoding)?;
+    press.extend(release);
+    Some(press)
+}
+
+/// Generate a drag: a press at `from`, motion to `to`, and a release there.
+///
+/// Positions are 0-indexed (`x`, `y`) pairs. The motion report is only sent
+/// when the application tracks motion (`?1002` or `?1003`); with plain
+/// press/release tracking the application sees the press and release alone.
+///
+/// Returns `None` when tracking cannot report a release (off or X10
+/// compatibility) or a position does not fit the encoding.
+pub fn encode_mouse_drag(
+    from: (u16, u16),
+    to: (u16, u16),
+    button: MouseButton,
+    modifiers: MouseModifiers,
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    if matches!(tracking, MouseTracking::None | MouseTracking::Press) {
+        return None;
+    }
+    let code = mouse_button_code(button, modifiers, tracking);
+    let mut drag = encode_mouse_report(code, false, from.0, from.1, encoding)?;
+    if matches!(
+        tracking,
+        MouseTracking::ButtonMotion | MouseTracking::AnyMotion
+    ) {
+        drag.extend(encode_mouse_report(
+            code | MOUSE_MOTION,
+            false,
+            to.0,
+            to.1,
+            encoding,
+        )?);
+    }
+    drag.extend(encode_mouse_report(code, true, to.0, to.1, encoding)?);
+    Some(drag)
+}
+
+/// Generate a motion report with no button held, as the pointer hovering
+/// over 0-indexed (`x`, `y`).
+///
+/// In SGR encoding this is `\x1b[<35;x;yM`. Only any-event tracking
+/// (`?1003`) reports such motion, so every other mode returns `None`, as does
+/// a position that does not fit the encoding.
+pub fn encode_mouse_hover(
+    x: u16,
+    y: u16,
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
+    if tracking != MouseTracking::AnyMotion {
+        return None;
+    }
+    encode_mouse_report(MOUSE_RELEASE | MOUSE_MOTION, false, x, y, encoding)
 }
 
 /// Parse a key sequence like "Ctrl+X m" into a list of byte sequences.
@@ -296,26 +730,37 @@ pub fn encode_mouse_click_combined(x: u16, y: u16) -> Vec<u8> {
 /// - A named key: `Enter`, `Escape`, `Tab`, `F1`, `Space`
 /// - A single character: `a`, `m`, `:`
 ///
-/// The `application_cursor` parameter affects arrow key encoding.
+/// `modes` are the application's terminal modes. Application cursor mode
+/// affects arrow key encoding, and once the application negotiates the kitty
+/// keyboard protocol or modifyOtherKeys, combos legacy encoding cannot tell
+/// apart (Ctrl+I and Tab, Ctrl+Enter and Enter) are sent in its `CSI` form.
 ///
 /// # Examples
 ///
 /// ```
 /// use pilotty_core::input::parse_key_sequence;
+/// use pilotty_core::snapshot::TerminalModes;
+///
+/// let modes = TerminalModes::default();
 ///
 /// // Emacs chord: Ctrl+X then m
-/// let seq = parse_key_sequence("Ctrl+X m", false).unwrap();
+/// let seq = parse_key_sequence("Ctrl+X m", modes).unwrap();
 /// assert_eq!(seq.len(), 2);
 ///
 /// // vim :wq
-/// let seq = parse_key_sequence("Escape : w q Enter", false).unwrap();
+/// let seq = parse_key_sequence("Escape : w q Enter", modes).unwrap();
 /// assert_eq!(seq.len(), 5);
 ///
 /// // Single key still works
-/// let seq = parse_key_sequence("Enter", false).unwrap();
+/// let seq = parse_key_sequence("Enter", modes).unwrap();
 /// assert_eq!(seq.len(), 1);
+///
+/// // Under the kitty keyboard protocol Ctrl+I is no longer Tab
+/// let kitty = TerminalModes { kitty_keyboard: 1, ..modes };
+/// let seq = parse_key_sequence("Ctrl+I Tab", kitty).unwrap();
+/// assert_eq!(seq, vec![b"\x1b[105;5u".to_vec(), b"\t".to_vec()]);
 /// ```
-pub fn parse_key_sequence(sequence: &str, application_cursor: bool) -> Option<Vec<Vec<u8>>> {
+pub fn parse_key_sequence(sequence: &str, modes: TerminalModes) -> Option<Vec<Vec<u8>>> {
     let parts: Vec<&str> = sequence.split_whitespace().collect();
 
     if parts.is_empty() {
@@ -324,9 +769,11 @@ pub fn parse_key_sequence(sequence: &str, application_cursor: bool) -> Option<Ve
 
     let mut result = Vec::with_capacity(parts.len());
     for part in parts {
-        // Try combo first (Ctrl+X), then named key (Enter), then single char
-        let bytes = parse_key_combo(part, application_cursor)
-            .or_else(|| key_to_bytes(part, application_cursor))
+        // Try the negotiated keyboard protocol, then combo (Ctrl+X), then
+        // named key (Enter), then single char
+        let bytes = parse_extended_key(part, modes)
+            .or_else(|| parse_key_combo(part, modes))
+            .or_else(|| key_to_bytes(part, modes))
             .or_else(|| {
                 // Single character fallback (avoids Vec allocation)
                 let mut chars = part.chars();
@@ -341,26 +788,54 @@ pub fn parse_key_sequence(sequence: &str, application_cursor: bool) -> Option<Ve
     Some(result)
 }
 
-/// Generate scroll wheel sequences.
+/// The first key in `sequence` that names a real key the application's
+/// keyboard mode has no encoding for, such as Pause before the application
+/// enables the kitty keyboard protocol.
 ///
-/// Scroll up = button 64 (0x40), scroll down = button 65 (0x41)
-/// Using SGR encoding: `\x1b[<button;x;yM`
-pub fn encode_scroll(direction: ScrollDirection, x: u16, y: u16) -> Vec<u8> {
-    let x1 = x.saturating_add(1);
-    let y1 = y.saturating_add(1);
+/// Lets callers tell such keys apart from names that are not keys at all
+/// when [`parse_key_sequence`] returns `None`.
+pub fn unsupported_key(sequence: &str, modes: TerminalModes) -> Option<&str> {
+    sequence.split_whitespace().find(|part| {
+        let key = split_combo(part).map_or(*part, |(key, _)| key);
+        named_key(key).is_some_and(|named| !named.supported(modes))
+    })
+}
 
+/// Generate a scroll wheel sequence for the application's mouse encoding.
+///
+/// Scroll up = button 64 (0x40), scroll down = button 65 (0x41); wheels send
+/// presses only. In SGR encoding this is `\x1b[<button;x;yM`.
+///
+/// Returns `None` when the position does not fit the encoding.
+pub fn encode_scroll(
+    direction: ScrollDirection,
+    x: u16,
+    y: u16,
+    encoding: MouseEncoding,
+) -> Option<Vec<u8>> {
     let button = match direction {
         ScrollDirection::Up => 64,
         ScrollDirection::Down => 65,
     };
 
-    format!("\x1b[<{};{};{}M", button, x1, y1).into_bytes()
+    encode_mouse_report(button, false, x, y, encoding)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn normal() -> TerminalModes {
+        TerminalModes::default()
+    }
+
+    fn application_cursor() -> TerminalModes {
+        TerminalModes {
+            application_cursor: true,
+            ..TerminalModes::default()
+        }
+    }
+
     #[test]
     fn test_encode_text_plain() {
         assert_eq!(encode_text("hello"), b"hello");
@@ -399,120 +874,294 @@ mod tests {
         assert_eq!(result, "hello 世界".as_bytes());
     }
 
+    #[test]
+    fn pastes_are_literal_and_cannot_close_the_bracket() {
+        assert_eq!(encode_paste("if x:\n    y\n", false), b"if x:\r    y\r");
+        assert_eq!(encode_paste("\\n stays", false), b"\\n stays");
+        assert_eq!(
+            encode_paste("a\x1b[201~rm -rf\n", true),
+            b"\x1b[200~arm -rf\r\x1b[201~"
+        );
+        assert_eq!(
+            encode_paste("\x1b[20\x1b[200~1~x", true),
+            b"\x1b[200~x\x1b[201~"
+        );
+        assert_eq!(encode_paste("", true), b"\x1b[200~\x1b[201~");
+    }
+
     #[test]
     fn test_key_to_bytes_enter() {
-        assert_eq!(key_to_bytes("Enter", false), Some(b"\r".to_vec()));
-        assert_eq!(key_to_bytes("ENTER", false), Some(b"\r".to_vec()));
-        assert_eq!(key_to_bytes("enter", false), Some(b"\r".to_vec()));
+        assert_eq!(key_to_bytes("Enter", normal()), Some(b"\r".to_vec()));
+        assert_eq!(key_to_bytes("ENTER", normal()), Some(b"\r".to_vec()));
+        assert_eq!(key_to_bytes("enter", normal()), Some(b"\r".to_vec()));
     }
 
     #[test]
     fn test_key_to_bytes_escape() {
-        assert_eq!(key_to_bytes("Escape", false), Some(vec![0x1b]));
-        assert_eq!(key_to_bytes("Esc", false), Some(vec![0x1b]));
+        assert_eq!(key_to_bytes("Escape", normal()), Some(vec![0x1b]));
+        assert_eq!(key_to_bytes("Esc", normal()), Some(vec![0x1b]));
     }
 
     #[test]
     fn test_key_to_bytes_arrows_normal_mode() {
         // Normal cursor mode: CSI sequences
-        assert_eq!(key_to_bytes("Up", false), Some(b"\x1b[A".to_vec()));
-        assert_eq!(key_to_bytes("Down", false), Some(b"\x1b[B".to_vec()));
-        assert_eq!(key_to_bytes("Right", false), Some(b"\x1b[C".to_vec()));
-        assert_eq!(key_to_bytes("Left", false), Some(b"\x1b[D".to_vec()));
+        assert_eq!(key_to_bytes("Up", normal()), Some(b"\x1b[A".to_vec()));
+        assert_eq!(key_to_bytes("Down", normal()), Some(b"\x1b[B".to_vec()));
+        assert_eq!(key_to_bytes("Right", normal()), Some(b"\x1b[C".to_vec()));
+        assert_eq!(key_to_bytes("Left", normal()), Some(b"\x1b[D".to_vec()));
     }
 
     #[test]
     fn test_key_to_bytes_arrows_application_mode() {
         // Application cursor mode: SS3 sequences (used by dialog, vim, htop, etc.)
-        assert_eq!(key_to_bytes("Up", true), Some(b"\x1bOA".to_vec()));
-        assert_eq!(key_to_bytes("Down", true), Some(b"\x1bOB".to_vec()));
-        assert_eq!(key_to_bytes("Right", true), Some(b"\x1bOC".to_vec()));
-        assert_eq!(key_to_bytes("Left", true), Some(b"\x1bOD".to_vec()));
+        assert_eq!(
+            key_to_bytes("Up", application_cursor()),
+            Some(b"\x1bOA".to_vec())
+        );
+        assert_eq!(
+            key_to_bytes("Down", application_cursor()),
+            Some(b"\x1bOB".to_vec())
+        );
+        assert_eq!(
+            key_to_bytes("Right", application_cursor()),
+            Some(b"\x1bOC".to_vec())
+        );
+        assert_eq!(
+            key_to_bytes("Left", application_cursor()),
+            Some(b"\x1bOD".to_vec())
+        );
     }
 
     #[test]
     fn test_key_to_bytes_function_keys() {
-        assert_eq!(key_to_bytes("F1", false), Some(b"\x1bOP".to_vec()));
-        assert_eq!(key_to_bytes("F5", false), Some(b"\x1b[15~".to_vec()));
-        assert_eq!(key_to_bytes("F12", false), Some(b"\x1b[24~".to_vec()));
+        assert_eq!(key_to_bytes("F1", normal()), Some(b"\x1bOP".to_vec()));
+        assert_eq!(key_to_bytes("F5", normal()), Some(b"\x1b[15~".to_vec()));
+        assert_eq!(key_to_bytes("F12", normal()), Some(b"\x1b[24~".to_vec()));
     }
 
     #[test]
     fn test_key_to_bytes_unknown() {
-        assert_eq!(key_to_bytes("NotAKey", false), None);
+        assert_eq!(key_to_bytes("NotAKey", normal()), None);
     }
 
     #[test]
     fn test_key_to_bytes_plus() {
         // "plus" is a named alias for the literal + character
-        assert_eq!(key_to_bytes("plus", false), Some(b"+".to_vec()));
-        assert_eq!(key_to_bytes("Plus", false), Some(b"+".to_vec()));
-        assert_eq!(key_to_bytes("PLUS", false), Some(b"+".to_vec()));
+        assert_eq!(key_to_bytes("plus", normal()), Some(b"+".to_vec()));
+        assert_eq!(key_to_bytes("Plus", normal()), Some(b"+".to_vec()));
+        assert_eq!(key_to_bytes("PLUS", normal()), Some(b"+".to_vec()));
     }
 
     #[test]
     fn test_parse_key_combo_ctrl_c() {
-        assert_eq!(parse_key_combo("Ctrl+C", false), Some(vec![0x03]));
-        assert_eq!(parse_key_combo("ctrl+c", false), Some(vec![0x03]));
+        assert_eq!(parse_key_combo("Ctrl+C", normal()), Some(vec![0x03]));
+        assert_eq!(parse_key_combo("ctrl+c", normal()), Some(vec![0x03]));
     }
 
     #[test]
     fn test_parse_key_combo_ctrl_letters() {
-        assert_eq!(parse_key_combo("Ctrl+A", false), Some(vec![0x01]));
-        assert_eq!(parse_key_combo("Ctrl+Z", false), Some(vec![0x1a]));
-        assert_eq!(parse_key_combo("Ctrl+S", false), Some(vec![0x13])); // XOFF
-        assert_eq!(parse_key_combo("Ctrl+Q", false), Some(vec![0x11])); // XON
+        assert_eq!(parse_key_combo("Ctrl+A", normal()), Some(vec![0x01]));
+        assert_eq!(parse_key_combo("Ctrl+Z", normal()), Some(vec![0x1a]));
+        assert_eq!(parse_key_combo("Ctrl+S", normal()), Some(vec![0x13])); // XOFF
+        assert_eq!(parse_key_combo("Ctrl+Q", normal()), Some(vec![0x11])); // XON
     }
 
     #[test]
     fn test_parse_key_combo_alt_letter() {
         // Alt+F should be ESC followed by 'f'
-        assert_eq!(parse_key_combo("Alt+f", false), Some(vec![0x1b, b'f']));
-        assert_eq!(parse_key_combo("Alt+F", false), Some(vec![0x1b, b'F']));
+        assert_eq!(parse_key_combo("Alt+f", normal()), Some(vec![0x1b, b'f']));
+        assert_eq!(parse_key_combo("Alt+F", normal()), Some(vec![0x1b, b'F']));
     }
 
     #[test]
     fn test_parse_key_combo_ctrl_alt() {
         // Ctrl+Alt+C = ESC followed by Ctrl+C
-        assert_eq!(parse_key_combo("Ctrl+Alt+C", false), Some(vec![0x1b, 0x03]));
+        assert_eq!(
+            parse_key_combo("Ctrl+Alt+C", normal()),
+            Some(vec![0x1b, 0x03])
+        );
     }
 
     #[test]
     fn test_parse_key_combo_named_key() {
-        assert_eq!(parse_key_combo("Enter", false), Some(b"\r".to_vec()));
-        assert_eq!(parse_key_combo("Tab", false), Some(b"\t".to_vec()));
+        assert_eq!(parse_key_combo("Enter", normal()), Some(b"\r".to_vec()));
+        assert_eq!(parse_key_combo("Tab", normal()), Some(b"\t".to_vec()));
     }
 
     #[test]
     fn test_parse_key_combo_alt_named_key() {
         // Alt+Enter = ESC followed by CR
-        let result = parse_key_combo("Alt+Enter", false);
+        let result = parse_key_combo("Alt+Enter", normal());
         assert_eq!(result, Some(vec![0x1b, b'\r']));
     }
 
     #[test]
     fn test_parse_key_combo_shift() {
         // Shift+a = A
-        assert_eq!(parse_key_combo("Shift+a", false), Some(b"A".to_vec()));
+        assert_eq!(parse_key_combo("Shift+a", normal()), Some(b"A".to_vec()));
     }
 
     #[test]
     fn test_parse_key_combo_ctrl_special() {
-        assert_eq!(parse_key_combo("Ctrl+[", false), Some(vec![0x1b])); // Escape
-        assert_eq!(parse_key_combo("Ctrl+Space", false), Some(vec![0x00])); // NUL
+        assert_eq!(parse_key_combo("Ctrl+[", normal()), Some(vec![0x1b])); // Escape
+        assert_eq!(parse_key_combo("Ctrl+Space", normal()), Some(vec![0x00])); // NUL
     }
 
     #[test]
     fn test_parse_key_combo_alt_arrow_application_mode() {
-        // Alt+Up in application cursor mode = ESC followed by SS3 sequence
-        let result = parse_key_combo("Alt+Up", true);
-        assert_eq!(result, Some(vec![0x1b, 0x1b, b'O', b'A']));
+        // Modified arrows use the CSI form even in application cursor mode
+        let result = parse_key_combo("Alt+Up", application_cursor());
+        assert_eq!(result, Some(b"\x1b[1;3A".to_vec()));
+    }
+
+    #[test]
+    fn modified_named_keys_use_xterm_parameters() {
+        let combo = |combo| parse_key_combo(combo, normal()).expect(combo);
+
+        assert_eq!(combo("Ctrl+Right"), b"\x1b[1;5C");
+        assert_eq!(combo("Shift+Up"), b"\x1b[1;2A");
+        assert_eq!(combo("Ctrl+Shift+Left"), b"\x1b[1;6D");
+        assert_eq!(combo("Ctrl+Alt+Shift+Down"), b"\x1b[1;8B");
+        assert_eq!(combo("Ctrl+Home"), b"\x1b[1;5H");
+        assert_eq!(combo("Shift+End"), b"\x1b[1;2F");
+        assert_eq!(combo("Shift+F1"), b"\x1b[1;2P");
+        assert_eq!(combo("Shift+F5"), b"\x1b[15;2~");
+        assert_eq!(combo("Ctrl+PageDown"), b"\x1b[6;5~");
+        assert_eq!(combo("Alt+Delete"), b"\x1b[3;3~");
+        assert_eq!(combo("Shift+Tab"), b"\x1b[Z");
+        assert_eq!(combo("Alt+Shift+Tab"), b"\x1b\x1b[Z");
+        assert_eq!(combo("Alt+Escape"), b"\x1b\x1b");
+    }
+
+    #[test]
+    fn extended_function_keypad_and_system_keys() {
+        let normal = TerminalModes::default();
+        let keypad = TerminalModes {
+            application_keypad: true,
+            ..normal
+        };
+        let key = |key, modes| parse_key_sequence(key, modes).expect(key).remove(0);
+
+        // F13-F24 are xterm's shifted F1-F12.
+        assert_eq!(key("F13", normal), b"\x1b[1;2P");
+        assert_eq!(key("F17", normal), b"\x1b[15;2~");
+        assert_eq!(key("f24", normal), b"\x1b[24;2~");
+        assert_eq!(key("Ctrl+F13", normal), b"\x1b[1;6P");
+        assert!(parse_key_sequence("F25", normal).is_none());
+        assert!(parse_key_sequence("F01", normal).is_none());
+        // Keypad keys send their text, or SS3 in application keypad mode.
+        assert_eq!(key("KP7", normal), b"7");
+        assert_eq!(key("KPEnter", normal), b"\r");
+        assert_eq!(key("Alt+KP0", normal), b"\x1b0");
+        assert_eq!(key("KP7", keypad), b"\x1bOw");
+        assert_eq!(key("KPEnter", keypad), b"\x1bOM");
+        assert_eq!(key("KPMinus", keypad), b"\x1bOm");
+        assert_eq!(key("Shift+KPPlus", keypad), b"\x1b[1;2k");
+        assert!(parse_key_sequence("KP10", normal).is_none());
+        // System keys. Pause, PrintScreen, and ScrollLock only have kitty
+        // codes, so legacy applications cannot be sent them.
+        assert_eq!(key("Menu", normal), b"\x1b[29~");
+        for name in ["Pause", "Ctrl+PrintScreen", "ScrollLock"] {
+            assert!(parse_key_sequence(name, normal).is_none(), "{name}");
+        }
+        assert_eq!(
+            unsupported_key("a Ctrl+Pause b", normal),
+            Some("Ctrl+Pause")
+        );
+        assert_eq!(unsupported_key("Menu NotAKey", normal), None);
+        // The kitty protocol has its own codes for keypad keys and F13-F24.
+        let kitty = TerminalModes {
+            kitty_keyboard: 1,
+            ..keypad
+        };
+        assert_eq!(key("Pause", kitty), b"\x1b[57362u");
+        assert_eq!(key("Ctrl+PrintScreen", kitty), b"\x1b[57361;5u");
+        assert_eq!(key("ScrollLock", kitty), b"\x1b[57359u");
+        assert_eq!(unsupported_key("Pause", kitty), None);
+        assert_eq!(key("KP7", kitty), b"\x1b[57406u");
+        assert_eq!(key("KPEnter", kitty), b"\x1b[57414u");
+        assert_eq!(key("KPComma", kitty), b"\x1b[57416u");
+        assert_eq!(key("Shift+F14", kitty), b"\x1b[57377;2u");
+        assert_eq!(key("F1", kitty), b"\x1bOP");
+    }
+
+    #[test]
+    fn kitty_keyboard_flags_select_csi_u_encodings() {
+        let key = |key, kitty_keyboard| {
+            let modes = TerminalModes {
+                kitty_keyboard,
+                ..TerminalModes::default()
+            };
+            parse_key_sequence(key, modes).expect(key).remove(0)
+        };
+
+        // Disambiguate: modified and ambiguous keys only.
+        assert_eq!(key("Ctrl+I", 1), b"\x1b[105;5u");
+        assert_eq!(key("Tab", 1), b"\t");
+        assert_eq!(key("Ctrl+Enter", 1), b"\x1b[13;5u");
+        assert_eq!(key("Shift+Tab", 1), b"\x1b[9;2u");
+        assert_eq!(key("Escape", 1), b"\x1b[27u");
+        assert_eq!(key("Alt+Escape", 1), b"\x1b[27;3u");
+        assert_eq!(key("Ctrl+Shift+A", 1), b"\x1b[97;6u");
+        assert_eq!(key("Ctrl+Space", 1), b"\x1b[32;5u");
+        assert_eq!(key("Shift+a", 1), b"A");
+        assert_eq!(key("Ctrl+Right", 1), b"\x1b[1;5C");
+        // Alternate keys add the shifted code; all-keys mode escapes text too.
+        assert_eq!(key("Ctrl+Shift+a", 0b101), b"\x1b[97:65;6u");
+        assert_eq!(key("a", 0b1000), b"\x1b[97u");
+        assert_eq!(key("A", 0b1000), b"\x1b[97;2u");
+        assert_eq!(key("Enter", 0b1000), b"\x1b[13u");
+        // Event-type reporting alone leaves key presses alone.
+        assert_eq!(key("Ctrl+I", 0b10), b"\t");
+    }
+
+    #[test]
+    fn modify_other_keys_levels_escape_modified_keys() {
+        let key = |key, modify_other_keys| {
+            let modes = TerminalModes {
+                modify_other_keys,
+                ..TerminalModes::default()
+            };
+            parse_key_sequence(key, modes).expect(key).remove(0)
+        };
+
+        // Level 1 only escapes combos legacy bytes cannot express.
+        assert_eq!(key("Ctrl+Enter", 1), b"\x1b[27;5;13~");
+        assert_eq!(key("Ctrl+Shift+A", 1), b"\x1b[27;6;65~");
+        assert_eq!(key("Ctrl+1", 1), b"\x1b[27;5;49~");
+        assert_eq!(key("Ctrl+I", 1), vec![0x09]);
+        assert_eq!(key("Alt+x", 1), b"\x1bx");
+        assert_eq!(key("Shift+Tab", 1), b"\x1b[Z");
+        // Level 2 escapes every modified key but shifted text.
+        assert_eq!(key("Ctrl+I", 2), b"\x1b[27;5;105~");
+        assert_eq!(key("Alt+x", 2), b"\x1b[27;3;120~");
+        assert_eq!(key("Shift+Space", 2), b"\x1b[27;2;32~");
+        assert_eq!(key("Shift+a", 2), b"A");
+        assert_eq!(key("Tab", 2), b"\t");
+        // The kitty protocol wins when both are negotiated.
+        let both = TerminalModes {
+            kitty_keyboard: 1,
+            modify_other_keys: 2,
+            ..TerminalModes::default()
+        };
+        assert_eq!(
+            parse_key_sequence("Ctrl+I", both).unwrap(),
+            vec![b"\x1b[105;5u".to_vec()]
+        );
     }
 
     #[test]
     fn test_encode_mouse_click() {
         // Click at (0, 0) should produce 1-indexed coordinates (1, 1)
-        let (press, release) = encode_mouse_click(0, 0);
+        let (press, release) = encode_mouse_click(
+            0,
+            0,
+            MouseButton::Left,
+            MouseModifiers::default(),
+            MouseTracking::PressRelease,
+            MouseEncoding::Sgr,
+        )
+        .unwrap();
         assert_eq!(press, b"\x1b[<0;1;1M");
         assert_eq!(release, b"\x1b[<0;1;1m");
     }
@@ -520,29 +1169,183 @@ mod tests {
     #[test]
     fn test_encode_mouse_click_position() {
         // Click at (10, 5) should produce (11, 6)
-        let (press, release) = encode_mouse_click(10, 5);
+        let (press, release) = encode_mouse_click(
+            10,
+            5,
+            MouseButton::Left,
+            MouseModifiers::default(),
+            MouseTracking::PressRelease,
+            MouseEncoding::Sgr,
+        )
+        .unwrap();
         assert_eq!(press, b"\x1b[<0;11;6M");
         assert_eq!(release, b"\x1b[<0;11;6m");
     }
 
     #[test]
     fn test_encode_mouse_click_combined() {
-        let combined = encode_mouse_click_combined(5, 3);
+        let combined = encode_mouse_click_combined(
+            5,
+            3,
+            MouseButton::Left,
+            MouseModifiers::default(),
+            MouseTracking::PressRelease,
+            MouseEncoding::Sgr,
+        )
+        .unwrap();
         // Should contain both press and release
         assert!(combined.starts_with(b"\x1b[<0;6;4M"));
         assert!(combined.ends_with(b"\x1b[<0;6;4m"));
     }
 
+    #[test]
+    fn mouse_clicks_follow_the_requested_encoding() {
+        let click = |tracking, encoding| {
+            encode_mouse_click_combined(
+                10,
+                5,
+                MouseButton::Left,
+                MouseModifiers::default(),
+                tracking,
+                encoding,
+            )
+        };
+
+        // X10 and UTF-8 offset every value by 32 and release with button 3.
+        assert_eq!(
+            click(MouseTracking::PressRelease, MouseEncoding::X10).unwrap(),
+            b"\x1b[M +&\x1b[M#+&"
+        );
+        assert_eq!(
+            click(MouseTracking::PressRelease, MouseEncoding::Urxvt).unwrap(),
+            b"\x1b[32;11;6M\x1b[35;11;6M"
+        );
+        // X10 compatibility tracking reports the press only.
+        assert_eq!(
+            click(MouseTracking::Press, MouseEncoding::Sgr).unwrap(),
+            b"\x1b[<0;11;6M"
+        );
+        assert_eq!(click(MouseTracking::None, MouseEncoding::Sgr), None);
+    }
+
+    #[test]
+    fn mouse_buttons_and_modifiers_set_the_button_code() {
+        let ctrl_shift = MouseModifiers {
+            shift: true,
+            ctrl: true,
+            ..MouseModifiers::default()
+        };
+        let click = |button, modifiers, tracking, encoding| {
+            encode_mouse_click_combined(10, 5, button, modifiers, tracking, encoding).unwrap()
+        };
+
+        assert_eq!(
+            click(
+                MouseButton::Right,
+                MouseModifiers::default(),
+                MouseTracking::PressRelease,
+                MouseEncoding::Sgr
+            ),
+            b"\x1b[<2;11;6M\x1b[<2;11;6m"
+        );
+        // Shift (4) + Ctrl (16) on the middle button (1).
+        assert_eq!(
+            click(
+                MouseButton::Middle,
+                ctrl_shift,
+                MouseTracking::PressRelease,
+                MouseEncoding::Sgr
+            ),
+            b"\x1b[<21;11;6M\x1b[<21;11;6m"
+        );
+        // Legacy releases keep the modifiers but not the button.
+        assert_eq!(
+            click(
+                MouseButton::Middle,
+                ctrl_shift,
+                MouseTracking::PressRelease,
+                MouseEncoding::Urxvt
+            ),
+            b"\x1b[53;11;6M\x1b[55;11;6M"
+        );
+        // X10 compatibility tracking reports no modifiers.
+        assert_eq!(
+            click(
+                MouseButton::Right,
+                ctrl_shift,
+                MouseTracking::Press,
+                MouseEncoding::Sgr
+            ),
+            b"\x1b[<2;11;6M"
+        );
+    }
+
+    #[test]
+    fn drags_report_motion_only_when_the_application_tracks_it() {
+        let drag = |tracking| {
+            encode_mouse_drag(
+                (0, 0),
+                (4, 2),
+                MouseButton::Left,
+                MouseModifiers::default(),
+                tracking,
+                MouseEncoding::Sgr,
+            )
+        };
+
+        assert_eq!(
+            drag(MouseTracking::ButtonMotion).unwrap(),
+            b"\x1b[<0;1;1M\x1b[<32;5;3M\x1b[<0;5;3m"
+        );
+        assert_eq!(
+            drag(MouseTracking::PressRelease).unwrap(),
+            b"\x1b[<0;1;1M\x1b[<0;5;3m"
+        );
+        assert_eq!(drag(MouseTracking::Press), None);
+        assert_eq!(drag(MouseTracking::None), None);
+    }
+
+    #[test]
+    fn hovers_need_any_event_tracking() {
+        assert_eq!(
+            encode_mouse_hover(4, 2, MouseTracking::AnyMotion, MouseEncoding::Sgr).unwrap(),
+            b"\x1b[<35;5;3M"
+        );
+        assert_eq!(
+            encode_mouse_hover(4, 2, MouseTracking::AnyMotion, MouseEncoding::X10).unwrap(),
+            b"\x1b[MC%#"
+        );
+        assert_eq!(
+            encode_mouse_hover(4, 2, MouseTracking::ButtonMotion, MouseEncoding::Sgr),
+            None
+        );
+    }
+
+    #[test]
+    fn mouse_positions_beyond_the_encoding_are_rejected() {
+        let far = |x, encoding| encode_scroll(ScrollDirection::Up, x, 0, encoding);
+
+        assert_eq!(far(222, MouseEncoding::X10).unwrap(), b"\x1b[M`\xff!");
+        assert_eq!(far(223, MouseEncoding::X10), None);
+        // UTF-8 switches to two-byte characters past column 95.
+        assert_eq!(
+            far(200, MouseEncoding::Utf8).unwrap(),
+            [b"\x1b[M`".as_slice(), "\u{e9}".as_bytes(), b"!"].concat()
+        );
+        assert_eq!(far(2015, MouseEncoding::Utf8), None);
+        assert!(far(5000, MouseEncoding::Sgr).is_some());
+    }
+
     #[test]
     fn test_encode_scroll_up() {
-        let scroll = encode_scroll(ScrollDirection::Up, 10, 5);
+        let scroll = encode_scroll(ScrollDirection::Up, 10, 5, MouseEncoding::Sgr).unwrap();
         // Button 64 for scroll up
         assert_eq!(scroll, b"\x1b[<64;11;6M");
     }
 
     #[test]
     fn test_encode_scroll_down() {
-        let scroll = encode_scroll(ScrollDirection::Down, 10, 5);
+        let scroll = encode_scroll(ScrollDirection::Down, 10, 5, MouseEncoding::Sgr).unwrap();
         // Button 65 for scroll down
         assert_eq!(scroll, b"\x1b[<65;11;6M");
     }
@@ -550,14 +1353,14 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_single_key() {
         // Single key should work (backward compatible)
-        let seq = parse_key_sequence("Enter", false).unwrap();
+        let seq = parse_key_sequence("Enter", normal()).unwrap();
         assert_eq!(seq.len(), 1);
         assert_eq!(seq[0], b"\r".to_vec());
     }
 
     #[test]
     fn test_parse_key_sequence_single_combo() {
-        let seq = parse_key_sequence("Ctrl+C", false).unwrap();
+        let seq = parse_key_sequence("Ctrl+C", normal()).unwrap();
         assert_eq!(seq.len(), 1);
         assert_eq!(seq[0], vec![0x03]);
     }
@@ -565,7 +1368,7 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_emacs_chord() {
         // Ctrl+X then m (emacs-style chord)
-        let seq = parse_key_sequence("Ctrl+X m", false).unwrap();
+        let seq = parse_key_sequence("Ctrl+X m", normal()).unwrap();
         assert_eq!(seq.len(), 2);
         assert_eq!(seq[0], vec![0x18]); // Ctrl+X
         assert_eq!(seq[1], b"m".to_vec());
@@ -574,7 +1377,7 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_vim_wq() {
         // vim :wq sequence
-        let seq = parse_key_sequence("Escape : w q Enter", false).unwrap();
+        let seq = parse_key_sequence("Escape : w q Enter", normal()).unwrap();
         assert_eq!(seq.len(), 5);
         assert_eq!(seq[0], vec![0x1b]); // Escape
         assert_eq!(seq[1], b":".to_vec());
@@ -586,7 +1389,7 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_emacs_save() {
         // Ctrl+X Ctrl+S (emacs save)
-        let seq = parse_key_sequence("Ctrl+X Ctrl+S", false).unwrap();
+        let seq = parse_key_sequence("Ctrl+X Ctrl+S", normal()).unwrap();
         assert_eq!(seq.len(), 2);
         assert_eq!(seq[0], vec![0x18]); // Ctrl+X
         assert_eq!(seq[1], vec![0x13]); // Ctrl+S
@@ -595,7 +1398,7 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_with_space_key() {
         // "a Space b" should send 'a', then space, then 'b'
-        let seq = parse_key_sequence("a Space b", false).unwrap();
+        let seq = parse_key_sequence("a Space b", normal()).unwrap();
         assert_eq!(seq.len(), 3);
         assert_eq!(seq[0], b"a".to_vec());
         assert_eq!(seq[1], b" ".to_vec()); // Space is a named key
@@ -605,7 +1408,7 @@ mod tests {
     #[test]
     fn test_parse_key_sequence_handles_extra_whitespace() {
         // Multiple spaces between keys should be handled
-        let seq = parse_key_sequence("Ctrl+X   m", false).unwrap();
+        let seq = parse_key_sequence("Ctrl+X   m", normal()).unwrap();
         assert_eq!(seq.len(), 2);
         assert_eq!(seq[0], vec![0x18]);
         assert_eq!(seq[1], b"m".to_vec());
@@ -613,20 +1416,20 @@ mod tests {
 
     #[test]
     fn test_parse_key_sequence_empty_returns_none() {
-        assert!(parse_key_sequence("", false).is_none());
-        assert!(parse_key_sequence("   ", false).is_none());
+        assert!(parse_key_sequence("", normal()).is_none());
+        assert!(parse_key_sequence("   ", normal()).is_none());
     }
 
     #[test]
     fn test_parse_key_sequence_invalid_key_returns_none() {
         // "NotAKey" is not a valid single char or named key
-        assert!(parse_key_sequence("Ctrl+X NotAKey", false).is_none());
+        assert!(parse_key_sequence("Ctrl+X NotAKey", normal()).is_none());
     }
 
     #[test]
     fn test_parse_key_sequence_application_cursor_mode() {
         // Arrow keys in application cursor mode
-        let seq = parse_key_sequence("Up Down", true).unwrap();
+        let seq = parse_key_sequence("Up Down", application_cursor()).unwrap();
         assert_eq!(seq.len(), 2);
         assert_eq!(seq[0], b"\x1bOA".to_vec()); // SS3 sequence
         assert_eq!(seq[1], b"\x1bOB".to_vec());
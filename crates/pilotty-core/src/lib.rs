@@ -5,11 +5,13 @@
 //!
 //! # Modules
 //!
+//! - [`elements`]: UI element detection with refs for compact snapshots
 //! - [`error`]: API error types with actionable suggestions for AI consumers
 //! - [`input`]: Terminal input encoding (keys, mouse, modifiers)
 //! - [`protocol`]: JSON-line request/response protocol
 //! - [`snapshot`]: Screen state capture and change detection
 
+pub mod elements;
 pub mod error;
 pub mod input;
 pub mod protocol;
@@ -1,9 +1,11 @@
 //! Protocol types for CLI-daemon communication.
 
+use std::collections::BTreeMap;
+
 use serde::{Deserialize, Serialize};
 
 use crate::error::{ApiError, ErrorCode};
-use crate::snapshot::{ScreenState, TerminalSize};
+use crate::snapshot::{ScreenDiff, ScreenState, TerminalSize};
 
 /// Default timeout for snapshot await_change/settle operations (30 seconds).
 fn default_snapshot_timeout() -> u64 {
@@ -22,11 +24,23 @@ pub const PROTOCOL_V2: u32 = 2;
 /// Minimal text-and-cursor screen snapshots.
 pub const PROTOCOL_V3: u32 = 3;
 
+/// Structured screen observation and richer session control:
+///
+/// - styled spans, revision diffs, and pushed event streams
+/// - region-scoped, masked, and scrollback snapshots
+/// - process-level, absent-text, multi-pattern, region-scoped, and scrollback waits
+/// - spawn environment, TERM, and initial size control; asciicast recordings
+/// - process signals and bracketed pastes
+/// - click by text or element ref; button- and modifier-aware clicks, drags,
+///   and hovers
+/// - the mouse-not-enabled error
+pub const PROTOCOL_V4: u32 = 4;
+
 /// Current daemon protocol advertised on every request and response.
 ///
 /// Historical minimum-version mappings below must use the stable version
 /// constants, not this moving alias.
-pub const PROTOCOL_VERSION: u32 = PROTOCOL_V3;
+pub const PROTOCOL_VERSION: u32 = PROTOCOL_V4;
 
 /// Whether an observed peer protocol satisfies a wire variant's requirement.
 pub fn supports_protocol(observed: u32, required: u32) -> bool {
@@ -45,6 +59,7 @@ impl ApiError {
             | ErrorCode::InvalidInput
             | ErrorCode::InternalError => LEGACY_PROTOCOL_VERSION,
             ErrorCode::SessionExited => PROTOCOL_V2,
+            ErrorCode::MouseNotEnabled => PROTOCOL_V4,
         }
     }
 }
@@ -87,6 +102,27 @@ pub enum Command {
         /// Uses the daemon default when omitted.
         #[serde(default)]
         retain_bytes: Option<u64>,
+        /// Absolute path of an asciicast v2 file to record the session into.
+        #[serde(default)]
+        record: Option<String>,
+        /// Also record input written to the session. Requires `record`.
+        #[serde(default)]
+        record_input: bool,
+        /// Environment variables to set for the process.
+        #[serde(default)]
+        env: BTreeMap<String, String>,
+        /// Start from an empty environment instead of the daemon's.
+        #[serde(default)]
+        env_clear: bool,
+        /// TERM for the process. Uses the daemon default when omitted.
+        #[serde(default)]
+        term: Option<String>,
+        /// Initial terminal columns. Uses the daemon default when omitted.
+        #[serde(default)]
+        cols: Option<u16>,
+        /// Initial terminal rows. Uses the daemon default when omitted.
+        #[serde(default)]
+        rows: Option<u16>,
     },
     /// Kill a session.
     Kill { session: Option<String> },
@@ -107,12 +143,34 @@ pub enum Command {
         /// Timeout in ms for await_change/settle operations.
         #[serde(default = "default_snapshot_timeout")]
         timeout_ms: u64,
+        /// If set, return only what changed since this revision.
+        ///
+        /// Falls back to a full snapshot when the daemon no longer remembers
+        /// the baseline.
+        #[serde(default)]
+        since: Option<u64>,
+        /// Limit the text, content hash, and settle detection to this region.
+        #[serde(default)]
+        region: Option<ScreenRegion>,
+        /// Content left out of the content hash, so it cannot hold off
+        /// settle or trigger await_change. The text still includes it.
+        #[serde(default)]
+        ignore: Vec<ScreenMask>,
+        /// Also return up to this many lines that scrolled off the screen.
+        #[serde(default)]
+        scrollback: u32,
     },
     /// Type text at cursor.
     Type {
         text: String,
         session: Option<String>,
     },
+    /// Paste text, wrapped in bracketed paste markers when the application
+    /// enabled bracketed paste.
+    Paste {
+        text: String,
+        session: Option<String>,
+    },
     /// Send a key, key combo, or key sequence.
     ///
     /// For sequences (space-separated keys like "Ctrl+X m"), `delay_ms` specifies
@@ -124,10 +182,44 @@ pub enum Command {
         delay_ms: u32,
         session: Option<String>,
     },
-    /// Click at a specific row/column coordinate.
+    /// Click at a row/column coordinate, on matching on-screen text, or on a
+    /// detected element.
     Click {
-        row: u16,
-        col: u16,
+        #[serde(flatten)]
+        target: ClickTarget,
+        session: Option<String>,
+    },
+    /// Click with any button, several times in a row, or with modifier keys
+    /// held.
+    ///
+    /// Plain left clicks keep using `Click`, which older daemons understand.
+    MouseClick {
+        #[serde(flatten)]
+        target: ClickTarget,
+        #[serde(default)]
+        button: MouseButton,
+        /// Clicks in a row: 2 for a double click, 3 for a triple click.
+        #[serde(default = "default_click_count")]
+        count: u8,
+        #[serde(default)]
+        modifiers: MouseModifiers,
+        session: Option<String>,
+    },
+    /// Press a mouse button at `from`, move to `to`, and release it there.
+    Drag {
+        from: ClickTarget,
+        to: ClickTarget,
+        #[serde(default)]
+        button: MouseButton,
+        #[serde(default)]
+        modifiers: MouseModifiers,
+        session: Option<String>,
+    },
+    /// Move the mouse pointer over a cell, text, or element without pressing a
+    /// button.
+    Hover {
+        #[serde(flatten)]
+        target: ClickTarget,
         session: Option<String>,
     },
     /// Scroll the terminal.
@@ -146,19 +238,70 @@ pub enum Command {
     },
     /// Get live or finalized lifecycle status for a session.
     Status { session: Option<String> },
+    /// Start recording a running session to a new asciicast v2 file.
+    RecordStart {
+        /// Absolute path of the file to create.
+        path: String,
+        /// Also record input written to the session.
+        #[serde(default)]
+        input: bool,
+        session: Option<String>,
+    },
+    /// Stop a session's active recording.
+    RecordStop { session: Option<String> },
     /// Resize the terminal.
     Resize {
         cols: u16,
         rows: u16,
         session: Option<String>,
     },
-    /// Wait for text to appear.
+    /// Wait for text to appear, or for a process-level condition.
     WaitFor {
+        /// Text to wait for. Must be empty when `until` is set.
+        #[serde(default)]
         pattern: String,
+        /// Further patterns, matched after `pattern` when it is non-empty.
+        #[serde(default)]
+        patterns: Vec<String>,
+        /// Whether one pattern or every pattern must satisfy the wait.
+        #[serde(default)]
+        match_mode: MatchMode,
+        /// Wait for the patterns to be gone from the screen instead.
+        #[serde(default)]
+        absent: bool,
         timeout_ms: Option<u64>,
         regex: Option<bool>,
+        /// Wait for this process condition instead of screen text.
+        #[serde(default)]
+        until: Option<ProcessCondition>,
+        /// Match only text inside this region of the screen.
+        #[serde(default)]
+        region: Option<ScreenRegion>,
+        /// Also search up to this many lines that scrolled off the screen.
+        #[serde(default)]
+        scrollback: u32,
+        session: Option<String>,
+    },
+    /// Send a POSIX signal to a session's process or foreground process group.
+    Signal {
+        signal: Signal,
+        #[serde(default)]
+        target: SignalTarget,
         session: Option<String>,
     },
+    /// Stream session events on this connection until the client disconnects.
+    ///
+    /// The daemon answers with one `subscribed` response, then one `event`
+    /// response per line. The connection accepts no further requests.
+    Subscribe {
+        /// Sessions to follow by name or ID. Empty follows every session,
+        /// including ones spawned after subscribing.
+        #[serde(default)]
+        sessions: Vec<String>,
+        /// Also stream raw output chunks.
+        #[serde(default)]
+        output: bool,
+    },
     /// Shutdown the daemon gracefully.
     Shutdown,
 }
@@ -170,6 +313,52 @@ impl Command {
     /// compatibility before it can compile.
     pub fn minimum_protocol(&self) -> u32 {
         match self {
+            Self::Click {
+                target: ClickTarget::Text { .. } | ClickTarget::Ref { .. },
+                ..
+            }
+            | Self::Snapshot {
+                format: SnapshotFormat::Styled,
+                ..
+            }
+            | Self::Snapshot { since: Some(_), .. }
+            | Self::Snapshot {
+                region: Some(_), ..
+            }
+            | Self::Spawn {
+                record: Some(_), ..
+            }
+            | Self::Spawn {
+                env_clear: true, ..
+            }
+            | Self::Spawn { term: Some(_), .. }
+            | Self::Spawn { cols: Some(_), .. }
+            | Self::Spawn { rows: Some(_), .. }
+            | Self::RecordStart { .. }
+            | Self::RecordStop { .. }
+            | Self::Signal { .. }
+            | Self::Subscribe { .. }
+            | Self::Paste { .. }
+            | Self::MouseClick { .. }
+            | Self::Drag { .. }
+            | Self::Hover { .. }
+            | Self::WaitFor { until: Some(_), .. }
+            | Self::WaitFor { absent: true, .. }
+            | Self::WaitFor {
+                region: Some(_), ..
+            }
+            | Self::WaitFor {
+                match_mode: MatchMode::All,
+                ..
+            } => PROTOCOL_V4,
+            Self::Spawn { env, .. } if !env.is_empty() => PROTOCOL_V4,
+            Self::WaitFor { patterns, .. } if !patterns.is_empty() => PROTOCOL_V4,
+            Self::Snapshot { ignore, .. } if !ignore.is_empty() => PROTOCOL_V4,
+            Self::Snapshot { scrollback, .. } | Self::WaitFor { scrollback, .. }
+                if *scrollback > 0 =>
+            {
+                PROTOCOL_V4
+            }
             Self::Spawn {
                 retain_bytes: Some(_),
                 ..
@@ -183,11 +372,14 @@ impl Command {
             | Self::Kill { .. }
             | Self::Type { .. }
             | Self::Key { .. }
-            | Self::Click { .. }
+            | Self::Click {
+                target: ClickTarget::Cell { .. },
+                ..
+            }
             | Self::Scroll { .. }
             | Self::ListSessions
             | Self::Resize { .. }
-            | Self::WaitFor { .. }
+            | Self::WaitFor { until: None, .. }
             | Self::Shutdown => LEGACY_PROTOCOL_VERSION,
         }
     }
@@ -200,7 +392,10 @@ pub enum SnapshotFormat {
     /// Full JSON with screen text and content hash.
     #[default]
     Full,
-    /// Compact format: omits text and content hash, just metadata.
+    /// Full format plus run-length-encoded styled spans per row.
+    Styled,
+    /// Compact format: omits text and content hash, reports detected UI
+    /// elements and their bounds instead.
     Compact,
     /// Plain text only (no JSON structure).
     Text,
@@ -247,6 +442,72 @@ pub struct ScreenCapture {
     pub note: Option<String>,
 }
 
+/// A screen diff with the same wait and lifecycle evidence as a capture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DiffCapture {
+    #[serde(flatten)]
+    pub diff: ScreenDiff,
+    pub outcome: CaptureOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit: Option<CaptureExit>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Where a click lands.
+///
+/// Serialized inline with the click command, so coordinate clicks keep the
+/// legacy `{"row": .., "col": ..}` wire shape.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ClickTarget {
+    /// A screen cell (0-indexed).
+    Cell { row: u16, col: u16 },
+    /// The center of the `nth` match of `text` on the current screen.
+    Text {
+        text: String,
+        /// Treat `text` as a regular expression.
+        #[serde(default)]
+        regex: bool,
+        /// 1-based index of the match to click, in reading order.
+        #[serde(default = "default_click_nth")]
+        nth: u32,
+    },
+    /// The center of the element a compact snapshot labeled `ref` (`@e3`).
+    ///
+    /// Resolved against the screen at the moment of the action.
+    Ref { r#ref: String },
+}
+
+fn default_click_nth() -> u32 {
+    1
+}
+
+fn default_click_count() -> u8 {
+    1
+}
+
+/// Mouse button for clicks and drags.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Middle,
+    Right,
+}
+
+/// Modifier keys held during a mouse action.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MouseModifiers {
+    #[serde(default)]
+    pub shift: bool,
+    #[serde(default)]
+    pub alt: bool,
+    #[serde(default)]
+    pub ctrl: bool,
+}
+
 /// Scroll direction.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -255,6 +516,244 @@ pub enum ScrollDirection {
     Down,
 }
 
+/// How `wait_for` combines several patterns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchMode {
+    /// Any one pattern satisfies the wait.
+    #[default]
+    Any,
+    /// Every pattern must satisfy the wait at the same time.
+    All,
+}
+
+/// A rectangle of the screen that snapshot and `wait_for` text is taken from.
+///
+/// Ranges are inclusive and 0-indexed. Negative indices count from the end,
+/// so `(-1, -1)` is the last row. A missing range covers the whole axis, and
+/// ranges are clamped to the current screen size.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenRegion {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<(i32, i32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<(i32, i32)>,
+}
+
+impl ScreenRegion {
+    /// Row and column ranges this region covers on a screen of `size`.
+    ///
+    /// Either range is empty when the region lies entirely off screen.
+    pub fn resolve(&self, size: TerminalSize) -> (std::ops::Range<u16>, std::ops::Range<u16>) {
+        (
+            resolve_span(self.rows, size.rows),
+            resolve_span(self.cols, size.cols),
+        )
+    }
+}
+
+fn resolve_span(span: Option<(i32, i32)>, len: u16) -> std::ops::Range<u16> {
+    let Some((start, end)) = span else {
+        return 0..len;
+    };
+    // i64 so that `end + 1` and `len + i` cannot overflow at the i32 extremes.
+    let len = i64::from(len);
+    let index = |i: i32| {
+        if i < 0 {
+            len + i64::from(i)
+        } else {
+            i64::from(i)
+        }
+    };
+    let start = index(start).clamp(0, len);
+    let end = (index(end) + 1).clamp(start, len);
+    // Both bounds lie within 0..=len, which fits in u16.
+    start as u16..end as u16
+}
+
+/// Parses `ROWS[,COLS]`, where each range is `N` or `START:END` and either
+/// end of a range may be left open: `-1` is the last row, `2:,0:39` is row 2
+/// onward in the first 40 columns, and `:,-20:` is the last 20 columns.
+impl std::str::FromStr for ScreenRegion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rows, cols) = match s.split_once(',') {
+            Some((rows, cols)) => (rows, Some(cols)),
+            None => (s, None),
+        };
+        let rows = parse_span(rows)
+            .map_err(|e| format!("invalid region '{s}': rows {e}, expected ROWS[,COLS]"))?;
+        let cols = cols
+            .map(parse_span)
+            .transpose()
+            .map_err(|e| format!("invalid region '{s}': columns {e}, expected ROWS[,COLS]"))?
+            .flatten();
+        Ok(Self { rows, cols })
+    }
+}
+
+fn parse_span(span: &str) -> Result<Option<(i32, i32)>, String> {
+    let bound = |text: &str, open: i32| -> Result<i32, String> {
+        let text = text.trim();
+        if text.is_empty() {
+            return Ok(open);
+        }
+        text.parse()
+            .map_err(|_| format!("'{text}' is not an integer"))
+    };
+    match span.split_once(':') {
+        None if span.trim().is_empty() => Ok(None),
+        None => {
+            let index = bound(span, 0)?;
+            Ok(Some((index, index)))
+        }
+        Some(("", "")) => Ok(None),
+        Some((start, end)) => Ok(Some((bound(start, 0)?, bound(end, -1)?))),
+    }
+}
+
+impl std::fmt::Display for ScreenRegion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let span = |span: Option<(i32, i32)>| match span {
+            None => ":".to_string(),
+            Some((start, end)) if start == end => start.to_string(),
+            Some((start, end)) => format!("{start}:{end}"),
+        };
+        write!(f, "rows {}", span(self.rows))?;
+        if self.cols.is_some() {
+            write!(f, ", columns {}", span(self.cols))?;
+        }
+        Ok(())
+    }
+}
+
+/// Screen content a snapshot leaves out of its content hash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScreenMask {
+    /// Cells inside this rectangle, such as a clock in a status bar.
+    Region(ScreenRegion),
+    /// Text matching this regex, such as an elapsed-time counter.
+    Regex(String),
+}
+
+/// A process-level condition `wait_for` can block on instead of screen text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "condition", rename_all = "snake_case")]
+pub enum ProcessCondition {
+    /// The session's spawned process has exited.
+    Exit,
+    /// The session has produced no output for `idle_ms` milliseconds.
+    Idle { idle_ms: u64 },
+    /// The PTY's foreground process group differs from when the wait began.
+    ForegroundChange,
+}
+
+/// Signals the daemon delivers to session processes.
+///
+/// Serialized as the uppercase `SIG*` name. Parsing also accepts lowercase
+/// names and names without the `SIG` prefix, such as `int` or `sigterm`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "UPPERCASE", try_from = "String")]
+pub enum Signal {
+    Sighup,
+    Sigint,
+    Sigquit,
+    Sigkill,
+    Sigusr1,
+    Sigusr2,
+    Sigalrm,
+    Sigterm,
+    Sigcont,
+    Sigstop,
+    Sigtstp,
+    Sigttin,
+    Sigttou,
+    Sigwinch,
+}
+
+impl Signal {
+    /// Every supported signal, in conventional number order.
+    pub const ALL: [Signal; 14] = [
+        Self::Sighup,
+        Self::Sigint,
+        Self::Sigquit,
+        Self::Sigkill,
+        Self::Sigusr1,
+        Self::Sigusr2,
+        Self::Sigalrm,
+        Self::Sigterm,
+        Self::Sigcont,
+        Self::Sigstop,
+        Self::Sigtstp,
+        Self::Sigttin,
+        Self::Sigttou,
+        Self::Sigwinch,
+    ];
+
+    /// The conventional name, such as `SIGINT`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sighup => "SIGHUP",
+            Self::Sigint => "SIGINT",
+            Self::Sigquit => "SIGQUIT",
+            Self::Sigkill => "SIGKILL",
+            Self::Sigusr1 => "SIGUSR1",
+            Self::Sigusr2 => "SIGUSR2",
+            Self::Sigalrm => "SIGALRM",
+            Self::Sigterm => "SIGTERM",
+            Self::Sigcont => "SIGCONT",
+            Self::Sigstop => "SIGSTOP",
+            Self::Sigtstp => "SIGTSTP",
+            Self::Sigttin => "SIGTTIN",
+            Self::Sigttou => "SIGTTOU",
+            Self::Sigwinch => "SIGWINCH",
+        }
+    }
+}
+
+impl std::fmt::Display for Signal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Signal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.trim().to_ascii_uppercase();
+        let name = upper.strip_prefix("SIG").unwrap_or(&upper);
+        Self::ALL
+            .into_iter()
+            .find(|signal| &signal.name()[3..] == name)
+            .ok_or_else(|| {
+                let names: Vec<_> = Self::ALL.iter().map(|signal| signal.name()).collect();
+                format!("unknown signal '{s}', expected one of {}", names.join(", "))
+            })
+    }
+}
+
+impl TryFrom<String> for Signal {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+/// Which processes a signal is delivered to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalTarget {
+    /// The process the session spawned.
+    #[default]
+    Child,
+    /// The PTY's foreground process group, like a signal from the keyboard.
+    Foreground,
+}
+
 /// A response from daemon to CLI.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Response {
@@ -331,9 +830,29 @@ pub enum ResponseData {
         found: bool,
         matched_text: Option<String>,
         elapsed_ms: u64,
+        /// Position of the pattern that satisfied an `any` wait, counting
+        /// `pattern` first when it is set.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        pattern_index: Option<usize>,
+        /// How the process ended, for `exit` waits.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exit: Option<CaptureExit>,
+        /// The new foreground process, for `foreground_change` waits. Omitted
+        /// when nothing holds the terminal any more.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        foreground: Option<ProcessInfo>,
     },
     /// Generic success message.
     Ok { message: String },
+    /// Text written by a paste command.
+    Pasted {
+        /// Bytes written to the PTY: the text after newline normalization,
+        /// plus the bracketed paste markers when used.
+        bytes: usize,
+        /// Whether the text was wrapped in bracketed paste markers; false
+        /// when the application had bracketed paste off and it was typed.
+        bracketed: bool,
+    },
     /// Readable or exact output derived from a session's bounded retention window.
     Output {
         format: OutputFormat,
@@ -345,6 +864,16 @@ pub enum ResponseData {
     },
     /// Live or finalized lifecycle status for a session.
     Status(SessionStatus),
+    /// Rows that changed since a baseline revision.
+    ScreenDiff(DiffCapture),
+    /// A subscription stream opened; events follow on the same connection.
+    Subscribed { sessions: Vec<SessionInfo> },
+    /// One event on a subscription stream.
+    Event(SessionEvent),
+    /// The subscriber fell behind and this many events were dropped.
+    ///
+    /// Later revision events still diff against the last screen delivered.
+    Lagged { skipped: u64 },
 }
 
 impl ResponseData {
@@ -354,6 +883,12 @@ impl ResponseData {
     /// older client.
     pub fn minimum_protocol(&self) -> u32 {
         match self {
+            Self::ScreenState(capture) if capture.screen.styled.is_some() => PROTOCOL_V4,
+            Self::ScreenDiff(_)
+            | Self::Subscribed { .. }
+            | Self::Event(_)
+            | Self::Lagged { .. }
+            | Self::Pasted { .. } => PROTOCOL_V4,
             Self::ScreenState(_) | Self::Snapshot { .. } => PROTOCOL_V3,
             Self::Output { .. } | Self::Status(_) => PROTOCOL_V2,
             Self::SessionCreated { .. }
@@ -367,12 +902,43 @@ impl ResponseData {
     pub fn capture_outcome(&self) -> Option<CaptureOutcome> {
         match self {
             Self::ScreenState(capture) => Some(capture.outcome),
+            Self::ScreenDiff(capture) => Some(capture.outcome),
             Self::Snapshot { outcome, .. } => Some(*outcome),
             _ => None,
         }
     }
 }
 
+/// Something that happened to a subscribed session.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    pub session_id: String,
+    pub session_name: Option<String>,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+}
+
+/// What happened, tagged as `event` on the wire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEventKind {
+    /// A session matching the subscription was spawned.
+    Spawned { command: Vec<String> },
+    /// The screen revision advanced; rows changed since the previous
+    /// revision event for this session (or since a blank screen).
+    Revision(ScreenDiff),
+    /// Raw output bytes, streamed only when requested.
+    Output { bytes: Vec<u8> },
+    /// The terminal was resized.
+    Resize { size: TerminalSize },
+    /// The application rang the bell.
+    Bell,
+    /// The application set its window title.
+    Title { title: String },
+    /// The session exited and was finalized; no events follow for it.
+    Exited { exit: CaptureExit },
+}
+
 /// Information about an active session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct SessionInfo {
@@ -391,6 +957,25 @@ pub struct RetentionAccounting {
     pub truncated: bool,
 }
 
+/// A process running under a session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// Short command name, as the kernel reports it.
+    pub name: String,
+    /// Full argument vector. Empty for processes that have already exited.
+    pub argv: Vec<String>,
+}
+
+/// A process and its child processes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessNode {
+    #[serde(flatten)]
+    pub process: ProcessInfo,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<ProcessNode>,
+}
+
 /// Lifecycle status and available evidence for a session.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(tag = "state", rename_all = "snake_case")]
@@ -404,6 +989,17 @@ pub enum SessionStatus {
         size: TerminalSize,
         idle_ms: u64,
         retention: RetentionAccounting,
+        /// Leader of the PTY's foreground process group, i.e. what currently
+        /// owns the terminal. Omitted where the daemon cannot inspect processes.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        foreground: Option<ProcessInfo>,
+        /// The spawned process and all of its descendants.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        processes: Option<ProcessNode>,
+        /// Working directory the spawned process is in now, which may differ
+        /// from the `cwd` it started in.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        current_cwd: Option<String>,
     },
     Exited {
         id: String,
@@ -430,7 +1026,7 @@ mod tests {
     fn request_serializes_with_protocol_version() {
         let request = Request::new("req-1", Command::ListSessions);
         let json = serde_json::to_string(&request).unwrap();
-        assert!(json.contains("\"protocol\":3"), "got: {json}");
+        assert!(json.contains("\"protocol\":4"), "got: {json}");
     }
 
     #[test]
@@ -506,6 +1102,8 @@ mod tests {
         assert!(supports_protocol(PROTOCOL_V3, PROTOCOL_V2));
         assert!(supports_protocol(PROTOCOL_V3, PROTOCOL_V3));
         assert!(!supports_protocol(PROTOCOL_V2, PROTOCOL_V3));
+        assert!(supports_protocol(PROTOCOL_V4, PROTOCOL_V3));
+        assert!(!supports_protocol(PROTOCOL_V3, PROTOCOL_V4));
     }
 
     #[test]
@@ -529,12 +1127,26 @@ mod tests {
             session_name: None,
             cwd: None,
             retain_bytes: None,
+            record: None,
+            record_input: false,
+            env: Default::default(),
+            env_clear: false,
+            term: None,
+            cols: None,
+            rows: None,
         };
         let configured_spawn = Command::Spawn {
             command: vec!["sh".to_string()],
             session_name: None,
             cwd: None,
             retain_bytes: Some(1024),
+            record: None,
+            record_input: false,
+            env: Default::default(),
+            env_clear: false,
+            term: None,
+            cols: None,
+            rows: None,
         };
         let status = SessionStatus::Running {
             id: "session-1".to_string(),
@@ -553,6 +1165,9 @@ mod tests {
                 dropped_bytes: 2,
                 truncated: true,
             },
+            foreground: None,
+            processes: None,
+            current_cwd: None,
         };
         let outcome_snapshot = Command::Snapshot {
             session: None,
@@ -560,6 +1175,10 @@ mod tests {
             await_change: None,
             settle_ms: 0,
             timeout_ms: 30_000,
+            since: None,
+            region: None,
+            ignore: Vec::new(),
+            scrollback: 0,
         };
 
         assert_eq!(plain_spawn.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
@@ -600,6 +1219,528 @@ mod tests {
         assert_eq!(decoded, response);
     }
 
+    #[test]
+    fn styled_snapshots_require_protocol_v4() {
+        let styled_snapshot = Command::Snapshot {
+            session: None,
+            format: SnapshotFormat::Styled,
+            await_change: None,
+            settle_ms: 0,
+            timeout_ms: 30_000,
+            since: None,
+            region: None,
+            ignore: Vec::new(),
+            scrollback: 0,
+        };
+        let mut screen = ScreenState::empty(80, 24);
+        screen.styled = Some(vec![]);
+        let response = ResponseData::ScreenState(ScreenCapture {
+            screen,
+            outcome: CaptureOutcome::Immediate,
+            exit: None,
+            note: None,
+        });
+
+        assert_eq!(styled_snapshot.minimum_protocol(), PROTOCOL_V4);
+        assert_eq!(response.minimum_protocol(), PROTOCOL_V4);
+        let json = serde_json::to_string(&response).expect("serialize styled capture");
+        assert!(json.contains("\"styled\":[]"), "got: {json}");
+    }
+
+    #[test]
+    fn recordings_require_protocol_v4() {
+        let recorded_spawn = Command::Spawn {
+            command: vec!["sh".to_string()],
+            session_name: None,
+            cwd: None,
+            retain_bytes: Some(1024),
+            record: Some("/tmp/run.cast".to_string()),
+            record_input: true,
+            env: Default::default(),
+            env_clear: false,
+            term: None,
+            cols: None,
+            rows: None,
+        };
+        let start = Command::RecordStart {
+            path: "/tmp/run.cast".to_string(),
+            input: false,
+            session: None,
+        };
+
+        assert_eq!(recorded_spawn.minimum_protocol(), PROTOCOL_V4);
+        assert_eq!(start.minimum_protocol(), PROTOCOL_V4);
+        assert_eq!(
+            Command::RecordStop { session: None }.minimum_protocol(),
+            PROTOCOL_V4
+        );
+
+        let legacy_spawn = r#"{"action":"spawn","command":["sh"],"session_name":null,"cwd":null}"#;
+        let decoded: Command = serde_json::from_str(legacy_spawn).expect("decode legacy spawn");
+        assert_eq!(decoded.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn spawn_environment_and_size_require_protocol_v4() {
+        let spawn = |json: &str| -> Command { serde_json::from_str(json).expect("decode spawn") };
+        let base = r#""action":"spawn","command":["sh"],"session_name":null,"cwd":null"#;
+
+        assert_eq!(
+            spawn(&format!(r#"{{{base},"env":{{}}}}"#)).minimum_protocol(),
+            LEGACY_PROTOCOL_VERSION
+        );
+        for configured in [
+            r#""env":{"LANG":"C"}"#,
+            r#""env_clear":true"#,
+            r#""term":"vt100""#,
+            r#""cols":120"#,
+            r#""rows":40"#,
+        ] {
+            let command = spawn(&format!("{{{base},{configured}}}"));
+            assert_eq!(command.minimum_protocol(), PROTOCOL_V4, "{configured}");
+        }
+    }
+
+    #[test]
+    fn running_status_process_fields_are_optional_and_flattened() {
+        let legacy = r#"{"state":"running","id":"s","name":null,"command":["sh"],"cwd":null,
+            "created_at":"2026-07-11T12:00:00Z","size":{"cols":80,"rows":24},"idle_ms":0,
+            "retention":{"total_bytes":0,"retained_bytes":0,"dropped_bytes":0,"truncated":false}}"#;
+        let mut status: SessionStatus = serde_json::from_str(legacy).expect("decode status");
+        let SessionStatus::Running {
+            foreground,
+            processes,
+            current_cwd,
+            ..
+        } = &mut status
+        else {
+            panic!("expected running status");
+        };
+        assert_eq!(
+            (&*foreground, &*processes, &*current_cwd),
+            (&None, &None, &None)
+        );
+
+        let process = |pid: u32, argv: &[&str]| ProcessInfo {
+            pid,
+            name: argv[0].to_string(),
+            argv: argv.iter().map(|arg| arg.to_string()).collect(),
+        };
+        *foreground = Some(process(11, &["vim", "notes.txt"]));
+        *processes = Some(ProcessNode {
+            process: process(10, &["bash"]),
+            children: vec![ProcessNode {
+                process: process(11, &["vim", "notes.txt"]),
+                children: vec![],
+            }],
+        });
+        let json = serde_json::to_value(&status).expect("encode status");
+        assert_eq!(json["foreground"]["argv"][1], "notes.txt");
+        assert_eq!(json["processes"]["pid"], 10);
+        assert_eq!(json["processes"]["children"][0]["name"], "vim");
+        assert!(json["processes"]["children"][0].get("children").is_none());
+        assert!(json.get("current_cwd").is_none());
+        assert_eq!(
+            serde_json::from_value::<SessionStatus>(json).expect("round trip"),
+            status
+        );
+    }
+
+    #[test]
+    fn process_wait_conditions_require_protocol_v4() {
+        let wait = |json: &str| -> Command { serde_json::from_str(json).expect("decode wait") };
+
+        let text = wait(
+            r#"{"action":"wait_for","pattern":"Ready","timeout_ms":null,"regex":null,"session":null}"#,
+        );
+        assert_eq!(text.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
+
+        let idle = wait(r#"{"action":"wait_for","until":{"condition":"idle","idle_ms":500}}"#);
+        assert_eq!(
+            idle,
+            Command::WaitFor {
+                pattern: String::new(),
+                patterns: vec![],
+                match_mode: MatchMode::Any,
+                absent: false,
+                timeout_ms: None,
+                regex: None,
+                until: Some(ProcessCondition::Idle { idle_ms: 500 }),
+                region: None,
+                scrollback: 0,
+                session: None,
+            }
+        );
+        assert_eq!(idle.minimum_protocol(), PROTOCOL_V4);
+        let exit = wait(r#"{"action":"wait_for","until":{"condition":"exit"}}"#);
+        assert_eq!(exit.minimum_protocol(), PROTOCOL_V4);
+
+        let result = serde_json::to_value(ResponseData::WaitForResult {
+            found: true,
+            matched_text: Some("Ready".to_string()),
+            elapsed_ms: 5,
+            pattern_index: None,
+            exit: None,
+            foreground: None,
+        })
+        .expect("encode result");
+        assert!(result.get("exit").is_none() && result.get("foreground").is_none());
+    }
+
+    #[test]
+    fn pattern_set_waits_require_protocol_v4() {
+        let base = r#""action":"wait_for","pattern":"Ready","timeout_ms":null,"regex":null,"session":null"#;
+        for extra in [
+            r#""patterns":["Error"]"#,
+            r#""match_mode":"all""#,
+            r#""absent":true"#,
+        ] {
+            let command: Command =
+                serde_json::from_str(&format!("{{{base},{extra}}}")).expect("decode wait");
+            assert_eq!(command.minimum_protocol(), PROTOCOL_V4, "{extra}");
+        }
+        let any: Command =
+            serde_json::from_str(&format!(r#"{{{base},"patterns":[],"match_mode":"any"}}"#))
+                .expect("decode wait");
+        assert_eq!(any.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
+    }
+
+    #[test]
+    fn screen_regions_parse_resolve_and_require_protocol_v4() {
+        let size = TerminalSize { cols: 80, rows: 24 };
+        let region = |spec: &str| spec.parse::<ScreenRegion>().expect(spec);
+        assert_eq!(region("-1").resolve(size), (23..24, 0..80));
+        assert_eq!(region("2:,0:39").resolve(size), (2..24, 0..40));
+        assert_eq!(region(":,-20:").resolve(size), (0..24, 60..80));
+        assert_eq!(region("0:-2").resolve(size), (0..23, 0..80));
+        // Off-screen and inverted ranges clamp to nothing.
+        assert_eq!(region("30:40").resolve(size), (24..24, 0..80));
+        assert_eq!(region("5:2").resolve(size), (5..5, 0..80));
+        // Spans at the i32 extremes clamp instead of overflowing.
+        let span = |rows| ScreenRegion {
+            rows: Some(rows),
+            cols: None,
+        };
+        assert_eq!(span((0, i32::MAX)).resolve(size), (0..24, 0..80));
+        assert_eq!(span((i32::MAX, i32::MAX)).resolve(size), (24..24, 0..80));
+        assert_eq!(span((i32::MIN, -1)).resolve(size), (0..24, 0..80));
+        assert_eq!(span((i32::MIN, i32::MIN)).resolve(size), (0..0, 0..80));
+        assert_eq!(span((i32::MAX, i32::MIN)).resolve(size), (24..24, 0..80));
+        assert_eq!(span((-1, i32::MIN)).resolve(size), (23..23, 0..80));
+        assert_eq!(region("-1").to_string(), "rows -1");
+        assert_eq!(region("2:,0:39").to_string(), "rows 2:-1, columns 0:39");
+        assert!("a:b".parse::<ScreenRegion>().is_err());
+        assert!("1,x".parse::<ScreenRegion>().is_err());
+
+        let snapshot: Command = serde_json::from_str(
+            r#"{"action":"snapshot","session":null,"region":{"rows":[-1,-1]}}"#,
+        )
+        .expect("decode snapshot");
+        let Command::Snapshot { region, .. } = &snapshot else {
+            panic!("expected snapshot, got {snapshot:?}");
+        };
+        assert_eq!(region.and_then(|r| r.rows), Some((-1, -1)));
+        assert_eq!(snapshot.minimum_protocol(), PROTOCOL_V4);
+
+        let wait: Command = serde_json::from_str(
+            r#"{"action":"wait_for","pattern":"Saved","timeout_ms":null,"regex":null,"session":null,"region":{"cols":[0,9]}}"#,
+        )
+        .expect("decode wait");
+        assert_eq!(wait.minimum_protocol(), PROTOCOL_V4);
+    }
+
+    #[test]
+    fn snapshot_masks_are_tagged_and_require_protocol_v4() {
+        let snapshot: Command = serde_json::from_str(
+            r#"{"action":"snapshot","session":null,"ignore":[{"region":{"rows":[0,0]}},{"regex":"\\d+s"}]}"#,
+        )
+        .expect("decode snapshot");
+        let Command::Snapshot { ignore, .. } = &snapshot else {
+            panic!("expected snapshot, got {snapshot:?}");
+        };
+        assert_eq!(
+            ignore,
+            &vec![
+                ScreenMask::Region(ScreenRegion {
+                    rows: Some((0, 0)),
+                    cols: None,
+                }),
+                ScreenMask::Regex(r"\d+s".to_string()),
+            ]
+        );
+        assert_eq!(snapshot.minimum_protocol(), PROTOCOL_V4);
+
+        let unmasked: Command =
+            serde_json::from_str(r#"{"action":"snapshot","session":null,"ignore":[]}"#)
+                .expect("decode snapshot");
+        assert_eq!(unmasked.minimum_protocol(), PROTOCOL_V3);
+    }
+
+    #[test]
+    fn scrollback_requests_require_protocol_v4() {
+        for json in [
+            r#"{"action":"snapshot","session":null,"scrollback":50}"#,
+            r#"{"action":"wait_for","pattern":"PASS","scrollback":50}"#,
+        ] {
+            let command: Command = serde_json::from_str(json).expect("decode command");
+            assert_eq!(command.minimum_protocol(), PROTOCOL_V4, "{json}");
+        }
+
+        let without: Command = serde_json::from_str(r#"{"action":"wait_for","pattern":"PASS"}"#)
+            .expect("decode wait_for");
+        assert_eq!(without.minimum_protocol(), 0);
+    }
+
+    #[test]
+    fn pastes_require_protocol_v4() {
+        let command: Command =
+            serde_json::from_str(r#"{"action":"paste","text":"a\nb","session":"repl"}"#)
+                .expect("decode paste");
+        assert_eq!(command.minimum_protocol(), PROTOCOL_V4);
+
+        let pasted = ResponseData::Pasted {
+            bytes: 3,
+            bracketed: true,
+        };
+        let json = serde_json::to_value(&pasted).expect("encode pasted");
+        assert_eq!(json["type"], "pasted");
+        assert_eq!(json["bracketed"], true);
+        assert_eq!(pasted.minimum_protocol(), PROTOCOL_V4);
+    }
+
+    #[test]
+    fn signal_names_parse_leniently_and_require_protocol_v4() {
+        for name in ["SIGINT", "sigint", "INT", "int", " Int "] {
+            assert_eq!(name.parse::<Signal>(), Ok(Signal::Sigint), "{name}");
+        }
+        assert!("SIGFOO".parse::<Signal>().unwrap_err().contains("SIGWINCH"));
+        assert!("".parse::<Signal>().is_err());
+        assert!("SIG".parse::<Signal>().is_err());
+
+        let decoded: Command =
+            serde_json::from_str(r#"{"action":"signal","signal":"tstp","session":"app"}"#)
+                .expect("decode signal");
+        assert_eq!(
+            decoded,
+            Command::Signal {
+                signal: Signal::Sigtstp,
+                target: SignalTarget::Child,
+                session: Some("app".to_string()),
+            }
+        );
+        assert_eq!(decoded.minimum_protocol(), PROTOCOL_V4);
+
+        let encoded = serde_json::to_value(Command::Signal {
+            signal: Signal::Sigusr1,
+            target: SignalTarget::Foreground,
+            session: None,
+        })
+        .expect("encode signal");
+        assert_eq!(encoded["signal"], "SIGUSR1");
+        assert_eq!(encoded["target"], "foreground");
+        assert!(serde_json::from_str::<Signal>(r#""SIGNOPE""#).is_err());
+    }
+
+    #[test]
+    fn session_events_are_tagged_and_require_protocol_v4() {
+        let decoded: Command =
+            serde_json::from_str(r#"{"action":"subscribe"}"#).expect("decode bare subscribe");
+        assert_eq!(
+            decoded,
+            Command::Subscribe {
+                sessions: vec![],
+                output: false,
+            }
+        );
+        assert_eq!(decoded.minimum_protocol(), PROTOCOL_V4);
+
+        let event = ResponseData::Event(SessionEvent {
+            session_id: "abc".to_string(),
+            session_name: Some("app".to_string()),
+            kind: SessionEventKind::Title {
+                title: "Build".to_string(),
+            },
+        });
+        assert_eq!(event.minimum_protocol(), PROTOCOL_V4);
+        let json = serde_json::to_value(&event).expect("encode event");
+        assert_eq!(json["type"], "event");
+        assert_eq!(json["event"], "title");
+        assert_eq!(json["session_name"], "app");
+        assert_eq!(json["title"], "Build");
+        assert_eq!(
+            ResponseData::Lagged { skipped: 3 }.minimum_protocol(),
+            PROTOCOL_V4
+        );
+    }
+
+    #[test]
+    fn coordinate_click_keeps_legacy_wire_shape_and_ref_and_text_clicks_need_v4() {
+        let legacy = r#"{"action":"click","row":3,"col":7,"session":null}"#;
+        let decoded: Command = serde_json::from_str(legacy).expect("decode legacy click");
+        assert_eq!(
+            decoded,
+            Command::Click {
+                target: ClickTarget::Cell { row: 3, col: 7 },
+                session: None,
+            }
+        );
+        assert_eq!(decoded.minimum_protocol(), LEGACY_PROTOCOL_VERSION);
+        let reencoded = serde_json::to_value(&decoded).expect("encode click");
+        assert_eq!(
+            reencoded,
+            serde_json::from_str::<serde_json::Value>(legacy).unwrap()
+        );
+
+        let by_ref: Command =
+            serde_json::from_str(r#"{"action":"click","ref":"@e3","session":null}"#)
+                .expect("decode ref click");
+        assert_eq!(
+            by_ref,
+            Command::Click {
+                target: ClickTarget::Ref {
+                    r#ref: "@e3".to_string(),
+                },
+                session: None,
+            }
+        );
+        assert_eq!(by_ref.minimum_protocol(), PROTOCOL_V4);
+
+        let by_text: Command =
+            serde_json::from_str(r#"{"action":"click","text":"Save","session":"app"}"#)
+                .expect("decode text click");
+        assert_eq!(
+            by_text,
+            Command::Click {
+                target: ClickTarget::Text {
+                    text: "Save".to_string(),
+                    regex: false,
+                    nth: 1,
+                },
+                session: Some("app".to_string()),
+            }
+        );
+        assert_eq!(by_text.minimum_protocol(), PROTOCOL_V4);
+    }
+
+    #[test]
+    fn mouse_clicks_drags_and_hovers_require_protocol_v4() {
+        let click: Command = serde_json::from_str(
+            r#"{"action":"mouse_click","row":3,"col":7,"button":"right","count":2,"modifiers":{"ctrl":true},"session":null}"#,
+        )
+        .expect("decode mouse click");
+        assert_eq!(
+            click,
+            Command::MouseClick {
+                target: ClickTarget::Cell { row: 3, col: 7 },
+                button: MouseButton::Right,
+                count: 2,
+                modifiers: MouseModifiers {
+                    ctrl: true,
+                    ..MouseModifiers::default()
+                },
+                session: None,
+            }
+        );
+        assert_eq!(click.minimum_protocol(), PROTOCOL_V4);
+
+        let plain: Command =
+            serde_json::from_str(r#"{"action":"mouse_click","text":"OK","session":null}"#)
+                .expect("decode defaulted mouse click");
+        assert!(matches!(
+            plain,
+            Command::MouseClick {
+                button: MouseButton::Left,
+                count: 1,
+                ..
+            }
+        ));
+
+        let drag: Command = serde_json::from_str(
+            r#"{"action":"drag","from":{"row":0,"col":0},"to":{"text":"end"},"session":"app"}"#,
+        )
+        .expect("decode drag");
+        assert_eq!(
+            drag,
+            Command::Drag {
+                from: ClickTarget::Cell { row: 0, col: 0 },
+                to: ClickTarget::Text {
+                    text: "end".to_string(),
+                    regex: false,
+                    nth: 1,
+                },
+                button: MouseButton::Left,
+                modifiers: MouseModifiers::default(),
+                session: Some("app".to_string()),
+            }
+        );
+        assert_eq!(drag.minimum_protocol(), PROTOCOL_V4);
+
+        let hover = Command::Hover {
+            target: ClickTarget::Cell { row: 1, col: 2 },
+            session: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&hover).unwrap(),
+            serde_json::json!({"action": "hover", "row": 1, "col": 2, "session": null})
+        );
+        assert_eq!(hover.minimum_protocol(), PROTOCOL_V4);
+    }
+
+    #[test]
+    fn snapshot_diffs_require_protocol_v4() {
+        let since = Command::Snapshot {
+            session: None,
+            format: SnapshotFormat::Full,
+            await_change: None,
+            settle_ms: 0,
+            timeout_ms: 30_000,
+            since: Some(7),
+            region: None,
+            ignore: Vec::new(),
+            scrollback: 0,
+        };
+        let response = ResponseData::ScreenDiff(DiffCapture {
+            diff: ScreenDiff {
+                since: 7,
+                revision: 9,
+                content_hash: 1,
+                size: None,
+                cursor: None,
+                rows: vec![],
+            },
+            outcome: CaptureOutcome::Changed,
+            exit: None,
+            note: None,
+        });
+
+        assert_eq!(since.minimum_protocol(), PROTOCOL_V4);
+        assert_eq!(response.minimum_protocol(), PROTOCOL_V4);
+        assert_eq!(response.capture_outcome(), Some(CaptureOutcome::Changed));
+        let json = serde_json::to_string(&response).expect("serialize diff");
+        assert_eq!(
+            json,
+            r#"{"type":"screen_diff","since":7,"revision":9,"content_hash":1,"rows":[],"outcome":"changed"}"#
+        );
+    }
+
+    #[test]
+    fn compact_elements_stay_readable_by_v3_clients() {
+        // Elements are an additive field on an existing format, so older
+        // clients simply ignore them instead of needing an upgrade.
+        let mut screen = ScreenState::empty(80, 24);
+        screen.elements = Some(vec![]);
+        let response = ResponseData::ScreenState(ScreenCapture {
+            screen,
+            outcome: CaptureOutcome::Immediate,
+            exit: None,
+            note: None,
+        });
+
+        assert_eq!(response.minimum_protocol(), PROTOCOL_V3);
+        let json = serde_json::to_string(&response).expect("serialize compact capture");
+        assert!(json.contains("\"elements\":[]"), "got: {json}");
+    }
+
     #[test]
     fn output_response_requires_protocol_v2_and_preserves_raw_bytes() {
         let response = ResponseData::Output {
@@ -660,6 +1801,13 @@ mod tests {
                 session_name: None,
                 cwd: None,
                 retain_bytes: None,
+                record: None,
+                record_input: false,
+                env: Default::default(),
+                env_clear: false,
+                term: None,
+                cols: None,
+                rows: None,
             }
         );
     }
@@ -5,12 +5,13 @@
 //!
 //! # Snapshot Formats
 //!
-//! The daemon supports two snapshot formats:
+//! The daemon supports these JSON snapshot formats:
 //!
 //! | Format | Content | Use Case |
 //! |--------|---------|----------|
 //! | **Full** | text + hash | Complete screen state |
-//! | **Compact** | metadata only | Quick status checks |
+//! | **Styled** | text + hash + styled spans | Highlights, selection, error colors |
+//! | **Compact** | metadata + detected UI elements | Acting on buttons, fields, menus by ref |
 //!
 //! # Change Detection
 //!
@@ -22,9 +23,17 @@
 //!     // Screen changed, inspect the new text
 //! }
 //! ```
+//!
+//! # Incremental Diffs
+//!
+//! Live snapshots also carry the session's `revision`. Passing it back as a
+//! baseline yields a [`ScreenDiff`] with only the rows that changed, plus the
+//! cursor and size when those moved.
 
 use serde::{Deserialize, Serialize};
 
+use crate::elements::Element;
+
 /// Terminal dimensions.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct TerminalSize {
@@ -55,6 +64,111 @@ pub struct ScreenState {
     /// to detect screen changes without comparing the full text.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub content_hash: Option<u64>,
+    /// Run-length-encoded styled spans for each non-blank row.
+    ///
+    /// Present in styled snapshots only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub styled: Option<Vec<StyledRow>>,
+    /// Revision this screen was captured at; usable as a diff baseline.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revision: Option<u64>,
+    /// Detected UI elements, with refs (`@e1`, ...) in reading order.
+    ///
+    /// Present in compact snapshots only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elements: Option<Vec<Element>>,
+    /// Lines that scrolled off the top of the screen, oldest first.
+    ///
+    /// Present only when a snapshot asks for scrollback.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scrollback: Option<Vec<ScrollbackLine>>,
+    /// Input and display modes the application has switched on.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modes: Option<TerminalModes>,
+}
+
+/// Terminal modes set by the application through DEC private mode sequences.
+///
+/// Tells an agent whether it is inside a full-screen app, and whether mouse
+/// input, pastes, and focus changes will be understood.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TerminalModes {
+    /// The alternate screen is active (`?1049`, `?47`), as in full-screen apps.
+    pub alternate_screen: bool,
+    /// Which mouse events the application asked to receive.
+    pub mouse_tracking: MouseTracking,
+    /// How the application wants mouse events encoded.
+    pub mouse_encoding: MouseEncoding,
+    /// Pasted text is wrapped in `ESC[200~` / `ESC[201~` (`?2004`).
+    pub bracketed_paste: bool,
+    /// The application is told when the terminal gains or loses focus (`?1004`).
+    pub focus_reporting: bool,
+    /// Keypad keys send application sequences (DECKPAM) instead of digits.
+    pub application_keypad: bool,
+    /// Arrow keys send SS3 sequences instead of CSI (DECCKM, `?1`).
+    pub application_cursor: bool,
+    /// Kitty keyboard enhancement flags pushed with `CSI > flags u`; 0 when off.
+    #[serde(default)]
+    pub kitty_keyboard: u8,
+    /// xterm modifyOtherKeys level set with `CSI > 4 ; level m`; 0 when off.
+    #[serde(default)]
+    pub modify_other_keys: u8,
+}
+
+/// Mouse events an application has requested.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseTracking {
+    /// No mouse reporting; clicks reach the application as nothing at all.
+    #[default]
+    None,
+    /// Button presses only (X10, `?9`).
+    Press,
+    /// Presses and releases (`?1000`).
+    PressRelease,
+    /// Presses, releases, and motion while a button is held (`?1002`).
+    ButtonMotion,
+    /// Presses, releases, and all motion (`?1003`).
+    AnyMotion,
+}
+
+/// Wire encoding for reported mouse events.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseEncoding {
+    /// Single bytes offset by 32, limited to 223 rows and columns.
+    #[default]
+    X10,
+    /// UTF-8 encoded coordinates (`?1005`).
+    Utf8,
+    /// Decimal `ESC[<b;x;yM` sequences (`?1006`).
+    Sgr,
+    /// Decimal `ESC[b;x;yM` sequences (`?1015`).
+    Urxvt,
+}
+
+/// A line above the visible screen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrollbackLine {
+    /// Row relative to the top of the screen: -1 is the line just above row 0.
+    pub row: i32,
+    pub text: String,
+}
+
+impl ScrollbackLine {
+    /// Number `lines` (oldest first) so the last one sits at row -1.
+    pub fn number(lines: Vec<String>) -> Vec<Self> {
+        let count = lines.len();
+        lines
+            .into_iter()
+            .enumerate()
+            .map(|(i, text)| Self {
+                // Scrollback is bounded far below i32::MAX lines.
+                row: i as i32 - count as i32,
+                text,
+            })
+            .collect()
+    }
 }
 
 impl ScreenState {
@@ -68,10 +182,163 @@ impl ScreenState {
             },
             text: None,
             content_hash: None,
+            styled: None,
+            revision: None,
+            elements: None,
+            scrollback: None,
+            modes: None,
         }
     }
 }
 
+/// A terminal color as reported by the emulator.
+///
+/// The terminal's default color is represented by omitting the color field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Color {
+    /// One of the 256 indexed palette colors (0-7 normal, 8-15 bright).
+    Idx(u8),
+    /// A 24-bit true color.
+    Rgb(u8, u8, u8),
+}
+
+/// Visual attributes shared by every cell in a styled span.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellStyle {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fg: Option<Color>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bg: Option<Color>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub bold: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub italic: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub underline: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub inverse: bool,
+}
+
+impl CellStyle {
+    /// Whether this style is indistinguishable from the terminal default.
+    pub fn is_plain(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+pub(crate) fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A run of adjacent cells on one row that share the same style.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyledSpan {
+    /// Column of the first cell in the span (0-indexed).
+    pub col: u16,
+    pub text: String,
+    #[serde(flatten)]
+    pub style: CellStyle,
+}
+
+/// Styled spans for a single screen row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StyledRow {
+    /// Screen row (0-indexed).
+    pub row: u16,
+    pub spans: Vec<StyledSpan>,
+}
+
+/// Run-length encode one row of styled cells into spans.
+///
+/// Cells are `(col, text, style)` in column order; wide-character
+/// continuation cells should be omitted by the caller. Adjacent cells with
+/// the same style are merged, and trailing blank cells with the default
+/// style are dropped so agents only see meaningful runs.
+#[must_use]
+pub fn encode_styled_row<'a>(
+    cells: impl IntoIterator<Item = (u16, &'a str, CellStyle)>,
+) -> Vec<StyledSpan> {
+    let mut spans: Vec<StyledSpan> = Vec::new();
+    for (col, text, style) in cells {
+        let text = if text.is_empty() { " " } else { text };
+        match spans.last_mut() {
+            Some(span) if span.style == style => span.text.push_str(text),
+            _ => spans.push(StyledSpan {
+                col,
+                text: text.to_string(),
+                style,
+            }),
+        }
+    }
+
+    while let Some(span) = spans.last_mut() {
+        if !span.style.is_plain() {
+            break;
+        }
+        let trimmed_len = span.text.trim_end_matches(' ').len();
+        if trimmed_len == 0 {
+            spans.pop();
+        } else {
+            span.text.truncate(trimmed_len);
+            break;
+        }
+    }
+    spans
+}
+
+/// New content of a screen row that changed since a baseline.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RowChange {
+    /// Screen row (0-indexed).
+    pub row: u16,
+    /// Full text of the row, with trailing blanks trimmed.
+    pub text: String,
+}
+
+/// The changes between a baseline revision and the current screen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScreenDiff {
+    /// Baseline revision this diff applies to.
+    pub since: u64,
+    /// Revision of the current screen; use it as the next baseline.
+    pub revision: u64,
+    /// Hash of the current screen text, as in a full snapshot.
+    pub content_hash: u64,
+    /// New terminal size; present only when it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<TerminalSize>,
+    /// New cursor state; present only when it changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor: Option<CursorState>,
+    /// Rows whose text differs from the baseline, in row order.
+    pub rows: Vec<RowChange>,
+}
+
+/// Compare two screen texts row by row.
+///
+/// Rows present on only one side compare against an empty row, so rows
+/// cleared at the bottom of the screen are reported with empty text.
+#[must_use]
+pub fn changed_rows(before: &str, after: &str) -> Vec<RowChange> {
+    let mut before = before.split('\n');
+    let mut after = after.split('\n');
+    let mut changes = Vec::new();
+    for row in 0..=u16::MAX {
+        let (old, new) = match (before.next(), after.next()) {
+            (None, None) => break,
+            (old, new) => (old.unwrap_or_default(), new.unwrap_or_default()),
+        };
+        if old.trim_end() != new.trim_end() {
+            changes.push(RowChange {
+                row,
+                text: new.trim_end().to_string(),
+            });
+        }
+    }
+    changes
+}
+
 /// Compute a content hash from screen text.
 ///
 /// Uses FNV-1a, a fast non-cryptographic hash suitable for change detection.
@@ -93,6 +360,27 @@ pub fn compute_content_hash(text: &str) -> u64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn changed_rows_reports_edits_and_cleared_rows() {
+        let before = "$ ls\nfoo  bar\nold tail";
+        let after = "$ ls\nfoo  baz   \n";
+
+        assert_eq!(
+            changed_rows(before, after),
+            vec![
+                RowChange {
+                    row: 1,
+                    text: "foo  baz".to_string(),
+                },
+                RowChange {
+                    row: 2,
+                    text: String::new(),
+                },
+            ]
+        );
+        assert!(changed_rows(after, "$ ls\nfoo  baz").is_empty());
+    }
+
     #[test]
     fn content_hash_deterministic() {
         let text = "Hello, World!";
@@ -123,6 +411,73 @@ mod tests {
         assert_ne!(hash1, hash2);
     }
 
+    #[test]
+    fn styled_row_merges_runs_and_drops_plain_trailing_blanks() {
+        let inverse = CellStyle {
+            inverse: true,
+            ..CellStyle::default()
+        };
+        let cells = [
+            (0, "a", CellStyle::default()),
+            (1, "", CellStyle::default()),
+            (2, "O", inverse),
+            (3, "K", inverse),
+            (4, " ", CellStyle::default()),
+            (5, "", CellStyle::default()),
+        ];
+
+        let spans = encode_styled_row(cells);
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].col, 0);
+        assert_eq!(spans[0].text, "a ");
+        assert_eq!(spans[1].col, 2);
+        assert_eq!(spans[1].text, "OK");
+        assert!(spans[1].style.inverse);
+    }
+
+    #[test]
+    fn styled_span_serializes_only_non_default_attributes() {
+        let span = StyledSpan {
+            col: 4,
+            text: "Error".to_string(),
+            style: CellStyle {
+                fg: Some(Color::Idx(1)),
+                bold: true,
+                ..CellStyle::default()
+            },
+        };
+
+        let json = serde_json::to_string(&span).expect("serialize span");
+        assert_eq!(
+            json,
+            r#"{"col":4,"text":"Error","fg":{"idx":1},"bold":true}"#
+        );
+
+        let decoded: StyledSpan = serde_json::from_str(&json).expect("deserialize span");
+        assert_eq!(decoded, span);
+    }
+
+    #[test]
+    fn terminal_modes_serialize_as_snake_case() {
+        let modes = TerminalModes {
+            alternate_screen: true,
+            mouse_tracking: MouseTracking::ButtonMotion,
+            mouse_encoding: MouseEncoding::Sgr,
+            bracketed_paste: true,
+            kitty_keyboard: 1,
+            ..TerminalModes::default()
+        };
+
+        let json = serde_json::to_value(modes).expect("serialize modes");
+        assert_eq!(json["mouse_tracking"], "button_motion");
+        assert_eq!(json["mouse_encoding"], "sgr");
+        assert_eq!(json["focus_reporting"], false);
+        assert_eq!(json["kitty_keyboard"], 1);
+        let decoded: TerminalModes = serde_json::from_value(json).expect("deserialize modes");
+        assert_eq!(decoded, modes);
+    }
+
     #[test]
     fn content_hash_unicode() {
         // Unicode text should hash consistently